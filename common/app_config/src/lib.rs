@@ -0,0 +1,486 @@
+// Every runtime setting an adaptor reads, loaded once at startup and handed to each adaptor.
+// Settings come from the environment, over a JSON file of the same names when APP_CONFIG_FILE
+// points at one, e.g. {"DYNAMO_TABLE_NAME": "store", "CART_EXPIRY_SECONDS": 3600}
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+pub const APP_CONFIG_FILE: &str = "APP_CONFIG_FILE";
+
+pub const DYNAMO_TABLE_NAME: &str = "DYNAMO_TABLE_NAME";
+pub const DYNAMO_GSI1_NAME: &str = "DYNAMO_GSI1_NAME";
+pub const DYNAMO_GSI2_NAME: &str = "DYNAMO_GSI2_NAME";
+pub const CURSOR_SIGNING_KEY: &str = "CURSOR_SIGNING_KEY";
+pub const DATABASE_URL: &str = "DATABASE_URL";
+pub const EVENT_BUS_NAME: &str = "EVENT_BUS_NAME";
+pub const EVENT_SOURCE: &str = "EVENT_SOURCE";
+pub const CART_EXPIRY_SECONDS: &str = "CART_EXPIRY_SECONDS";
+pub const DELETE_RETENTION_SECONDS: &str = "DELETE_RETENTION_SECONDS";
+pub const TENANTS: &str = "TENANTS";
+pub const PRODUCT_CACHE_TTL_SECONDS: &str = "PRODUCT_CACHE_TTL_SECONDS";
+pub const PRODUCT_CACHE_CAPACITY: &str = "PRODUCT_CACHE_CAPACITY";
+pub const METRICS_NAMESPACE: &str = "METRICS_NAMESPACE";
+
+const SETTINGS: [&str; 13] = [
+    DYNAMO_TABLE_NAME,
+    DYNAMO_GSI1_NAME,
+    DYNAMO_GSI2_NAME,
+    CURSOR_SIGNING_KEY,
+    DATABASE_URL,
+    EVENT_BUS_NAME,
+    EVENT_SOURCE,
+    CART_EXPIRY_SECONDS,
+    DELETE_RETENTION_SECONDS,
+    TENANTS,
+    PRODUCT_CACHE_TTL_SECONDS,
+    PRODUCT_CACHE_CAPACITY,
+    METRICS_NAMESPACE,
+];
+
+pub const DEFAULT_GSI1_NAME: &str = "GSI1";
+pub const DEFAULT_GSI2_NAME: &str = "GSI2";
+pub const DEFAULT_EVENT_SOURCE: &str = "RUSTHEXAGONALSTOREFRONT";
+pub const DEFAULT_METRICS_NAMESPACE: &str = "RustHexagonalStorefront";
+pub const DEFAULT_CART_EXPIRY_SECONDS: u64 = 60 * 60 * 24 * 30;
+pub const DEFAULT_DELETE_RETENTION_SECONDS: u64 = 60 * 60 * 24 * 30;
+pub const DEFAULT_PRODUCT_CACHE_TTL_SECONDS: u64 = 60;
+pub const DEFAULT_PRODUCT_CACHE_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableConfig {
+    pub name: Option<String>,
+    pub gsi1_name: String,
+    pub gsi2_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventingConfig {
+    pub bus_name: Option<String>,
+    pub source: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitsConfig {
+    pub cart_expiry: Duration,
+    // how long a deleted user or product can be restored, the restore endpoints and purge job
+    // must agree on it
+    pub delete_retention: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    // how long an entry is served without going back to the table
+    pub ttl: Duration,
+    // entries kept before the least recently used is dropped, 0 turns the cache off
+    pub capacity: usize,
+}
+
+// Settings only some functions need are optional here, the adaptors that need them ask for them
+// with `require` so a function fails at startup rather than on its first request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppConfig {
+    pub table: TableConfig,
+    pub cursor_signing_key: Option<String>,
+    pub database_url: Option<String>,
+    pub eventing: EventingConfig,
+    // CloudWatch namespace the functions' metrics are published under
+    pub metrics_namespace: String,
+    pub limits: LimitsConfig,
+    pub product_cache: CacheConfig,
+    // Comma separated ids of the tenants scheduled jobs work through, besides the default tenant
+    // which is always included. Requests name their own tenant
+    pub tenants: Vec<String>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            table: TableConfig {
+                name: None,
+                gsi1_name: DEFAULT_GSI1_NAME.to_string(),
+                gsi2_name: DEFAULT_GSI2_NAME.to_string(),
+            },
+            cursor_signing_key: None,
+            database_url: None,
+            eventing: EventingConfig {
+                bus_name: None,
+                source: DEFAULT_EVENT_SOURCE.to_string(),
+            },
+            metrics_namespace: DEFAULT_METRICS_NAMESPACE.to_string(),
+            limits: LimitsConfig {
+                cart_expiry: Duration::from_secs(DEFAULT_CART_EXPIRY_SECONDS),
+                delete_retention: Duration::from_secs(DEFAULT_DELETE_RETENTION_SECONDS),
+            },
+            product_cache: CacheConfig {
+                ttl: Duration::from_secs(DEFAULT_PRODUCT_CACHE_TTL_SECONDS),
+                capacity: DEFAULT_PRODUCT_CACHE_CAPACITY,
+            },
+            tenants: Vec::new(),
+        }
+    }
+}
+
+impl AppConfig {
+    pub fn load() -> Result<AppConfig, ConfigError> {
+        let mut values = match std::env::var(APP_CONFIG_FILE) {
+            Ok(path) => read_file(&path)?,
+            Err(_) => HashMap::new(),
+        };
+        for setting in SETTINGS {
+            if let Ok(value) = std::env::var(setting) {
+                values.insert(setting.to_string(), value);
+            }
+        }
+        AppConfig::from_values(&values)
+    }
+
+    // Every problem with the values is reported together, not just the first
+    pub fn from_values(values: &HashMap<String, String>) -> Result<AppConfig, ConfigError> {
+        let mut reader = Reader {
+            values,
+            problems: Vec::new(),
+        };
+        let config = AppConfig {
+            table: TableConfig {
+                name: reader.optional(DYNAMO_TABLE_NAME),
+                gsi1_name: reader.text(DYNAMO_GSI1_NAME, DEFAULT_GSI1_NAME),
+                gsi2_name: reader.text(DYNAMO_GSI2_NAME, DEFAULT_GSI2_NAME),
+            },
+            cursor_signing_key: reader.optional(CURSOR_SIGNING_KEY),
+            database_url: reader.optional(DATABASE_URL),
+            eventing: EventingConfig {
+                bus_name: reader.optional(EVENT_BUS_NAME),
+                source: reader.text(EVENT_SOURCE, DEFAULT_EVENT_SOURCE),
+            },
+            metrics_namespace: reader.text(METRICS_NAMESPACE, DEFAULT_METRICS_NAMESPACE),
+            limits: LimitsConfig {
+                cart_expiry: reader.seconds(CART_EXPIRY_SECONDS, DEFAULT_CART_EXPIRY_SECONDS),
+                delete_retention: reader
+                    .seconds(DELETE_RETENTION_SECONDS, DEFAULT_DELETE_RETENTION_SECONDS),
+            },
+            product_cache: CacheConfig {
+                ttl: reader.seconds(PRODUCT_CACHE_TTL_SECONDS, DEFAULT_PRODUCT_CACHE_TTL_SECONDS),
+                capacity: reader.count(PRODUCT_CACHE_CAPACITY, DEFAULT_PRODUCT_CACHE_CAPACITY),
+            },
+            tenants: reader.list(TENANTS),
+        };
+        if config.table.gsi1_name == config.table.gsi2_name {
+            reader.problems.push(ConfigProblem::Invalid {
+                setting: DYNAMO_GSI2_NAME.to_string(),
+                value: config.table.gsi2_name.clone(),
+                reason: "must differ from DYNAMO_GSI1_NAME",
+            });
+        }
+        match reader.problems.is_empty() {
+            true => Ok(config),
+            false => Err(ConfigError {
+                problems: reader.problems,
+            }),
+        }
+    }
+
+    pub fn with_table_name(self, table_name: &str) -> AppConfig {
+        AppConfig {
+            table: TableConfig {
+                name: Some(table_name.to_string()),
+                ..self.table
+            },
+            ..self
+        }
+    }
+
+    // The optional settings an adaptor can't run without, in the order asked for
+    // let [table_name, cursor_signing_key] = config.require([DYNAMO_TABLE_NAME, CURSOR_SIGNING_KEY])?;
+    pub fn require<const N: usize>(
+        &self,
+        settings: [&'static str; N],
+    ) -> Result<[String; N], ConfigError> {
+        let problems: Vec<ConfigProblem> = settings
+            .iter()
+            .filter(|setting| self.optional(setting).is_none())
+            .map(|setting| ConfigProblem::Missing(setting))
+            .collect();
+        match problems.is_empty() {
+            true => Ok(settings.map(|setting| self.optional(setting).unwrap_or_default())),
+            false => Err(ConfigError { problems }),
+        }
+    }
+
+    fn optional(&self, setting: &str) -> Option<String> {
+        match setting {
+            DYNAMO_TABLE_NAME => self.table.name.clone(),
+            CURSOR_SIGNING_KEY => self.cursor_signing_key.clone(),
+            DATABASE_URL => self.database_url.clone(),
+            EVENT_BUS_NAME => self.eventing.bus_name.clone(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigProblem {
+    Missing(&'static str),
+    Invalid {
+        setting: String,
+        value: String,
+        reason: &'static str,
+    },
+    Unreadable {
+        path: String,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigProblem::Missing(setting) => write!(
+                f,
+                "{} is not set, set it in the environment or in the file {} names",
+                setting, APP_CONFIG_FILE
+            ),
+            ConfigProblem::Invalid {
+                setting,
+                value,
+                reason,
+            } => write!(f, "{} is {:?}, it {}", setting, value, reason),
+            ConfigProblem::Unreadable { path, reason } => {
+                write!(f, "unable to read {}: {}", path, reason)
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub problems: Vec<ConfigProblem>,
+}
+
+// Errors returned from main are printed with Debug, which should read the same as Display
+impl fmt::Debug for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid configuration")?;
+        for problem in &self.problems {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+struct Reader<'a> {
+    values: &'a HashMap<String, String>,
+    problems: Vec<ConfigProblem>,
+}
+
+impl<'a> Reader<'a> {
+    // Set but empty is almost always a templating mistake, so it's reported rather than ignored
+    fn optional(&mut self, setting: &str) -> Option<String> {
+        let value = self.values.get(setting)?.trim();
+        if value.is_empty() {
+            self.invalid(setting, value, "must not be empty");
+            return None;
+        }
+        Some(value.to_string())
+    }
+
+    fn text(&mut self, setting: &str, default: &str) -> String {
+        self.optional(setting)
+            .unwrap_or_else(|| default.to_string())
+    }
+
+    fn seconds(&mut self, setting: &str, default: u64) -> Duration {
+        let seconds = match self.optional(setting) {
+            Some(value) => match value.parse() {
+                Ok(seconds) => seconds,
+                Err(_) => {
+                    self.invalid(setting, &value, "must be a whole number of seconds");
+                    default
+                }
+            },
+            None => default,
+        };
+        Duration::from_secs(seconds)
+    }
+
+    fn count(&mut self, setting: &str, default: usize) -> usize {
+        match self.optional(setting) {
+            Some(value) => match value.parse() {
+                Ok(count) => count,
+                Err(_) => {
+                    self.invalid(setting, &value, "must be a whole number");
+                    default
+                }
+            },
+            None => default,
+        }
+    }
+
+    fn list(&mut self, setting: &str) -> Vec<String> {
+        let value = match self.optional(setting) {
+            Some(value) => value,
+            None => return Vec::new(),
+        };
+        let items: Vec<String> = value
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .collect();
+        if items.iter().any(|item| item.is_empty()) {
+            self.invalid(setting, &value, "must not have empty entries");
+        }
+        items
+    }
+
+    fn invalid(&mut self, setting: &str, value: &str, reason: &'static str) {
+        self.problems.push(ConfigProblem::Invalid {
+            setting: setting.to_string(),
+            value: value.to_string(),
+            reason,
+        });
+    }
+}
+
+// A JSON object of settings, strings or numbers. Anything else, or a name that isn't a
+// setting, is reported so a typo doesn't silently fall back to a default
+fn read_file(path: &str) -> Result<HashMap<String, String>, ConfigError> {
+    let unreadable = |reason: String| ConfigError {
+        problems: vec![ConfigProblem::Unreadable {
+            path: path.to_string(),
+            reason,
+        }],
+    };
+    let contents = std::fs::read_to_string(path).map_err(|e| unreadable(e.to_string()))?;
+    let object = match serde_json::from_str(&contents) {
+        Ok(serde_json::Value::Object(object)) => object,
+        Ok(_) => return Err(unreadable("expected a JSON object of settings".to_string())),
+        Err(e) => return Err(unreadable(e.to_string())),
+    };
+
+    let mut values = HashMap::new();
+    let mut problems = Vec::new();
+    for (setting, value) in object {
+        let value = match value {
+            serde_json::Value::String(value) => value,
+            serde_json::Value::Number(value) => value.to_string(),
+            other => {
+                problems.push(ConfigProblem::Invalid {
+                    setting,
+                    value: other.to_string(),
+                    reason: "must be a string or a number",
+                });
+                continue;
+            }
+        };
+        if !SETTINGS.contains(&setting.as_str()) {
+            problems.push(ConfigProblem::Invalid {
+                setting,
+                value,
+                reason: "is not a setting",
+            });
+            continue;
+        }
+        values.insert(setting, value);
+    }
+    match problems.is_empty() {
+        true => Ok(values),
+        false => Err(ConfigError { problems }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(setting, value)| (setting.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_values_defaults_and_require() {
+        let config = AppConfig::from_values(&values(&[
+            (DYNAMO_TABLE_NAME, "store"),
+            (CART_EXPIRY_SECONDS, "60"),
+            (TENANTS, "acme, globex"),
+            (PRODUCT_CACHE_CAPACITY, "10"),
+        ]))
+        .unwrap();
+        assert_eq!(config.product_cache.capacity, 10);
+        assert_eq!(
+            config.product_cache.ttl,
+            Duration::from_secs(DEFAULT_PRODUCT_CACHE_TTL_SECONDS)
+        );
+        assert_eq!(config.table.gsi1_name, "GSI1");
+        assert_eq!(config.tenants, vec!["acme", "globex"]);
+        assert_eq!(config.eventing.source, DEFAULT_EVENT_SOURCE);
+        assert_eq!(config.limits.cart_expiry, Duration::from_secs(60));
+        assert_eq!(
+            config.require([DYNAMO_TABLE_NAME]).unwrap(),
+            ["store".to_string()]
+        );
+
+        let err = config
+            .require([DYNAMO_TABLE_NAME, CURSOR_SIGNING_KEY, EVENT_BUS_NAME])
+            .unwrap_err();
+        assert_eq!(
+            err.problems,
+            vec![
+                ConfigProblem::Missing(CURSOR_SIGNING_KEY),
+                ConfigProblem::Missing(EVENT_BUS_NAME)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_values_reports_every_problem() {
+        let err = AppConfig::from_values(&values(&[
+            (EVENT_BUS_NAME, " "),
+            (DELETE_RETENTION_SECONDS, "a month"),
+            (DYNAMO_GSI2_NAME, "GSI1"),
+        ]))
+        .unwrap_err();
+        assert_eq!(err.problems.len(), 3);
+        assert_eq!(
+            err.to_string(),
+            "Invalid configuration\n  \
+             - EVENT_BUS_NAME is \"\", it must not be empty\n  \
+             - DELETE_RETENTION_SECONDS is \"a month\", it must be a whole number of seconds\n  \
+             - DYNAMO_GSI2_NAME is \"GSI1\", it must differ from DYNAMO_GSI1_NAME"
+        );
+    }
+
+    #[test]
+    fn test_read_file() {
+        let path = std::env::temp_dir().join(format!("app_config_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        std::fs::write(
+            path,
+            r#"{"DYNAMO_TABLE_NAME": "store", "CART_EXPIRY_SECONDS": 3600}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            read_file(path).unwrap(),
+            values(&[(DYNAMO_TABLE_NAME, "store"), (CART_EXPIRY_SECONDS, "3600")])
+        );
+
+        std::fs::write(path, r#"{"DYNAMO_TABLE": "store", "EVENT_SOURCE": true}"#).unwrap();
+        assert_eq!(read_file(path).unwrap_err().problems.len(), 2);
+
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(
+            read_file(path).unwrap_err().problems[0],
+            ConfigProblem::Unreadable { .. }
+        ));
+    }
+}
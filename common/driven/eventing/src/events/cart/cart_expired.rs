@@ -0,0 +1,66 @@
+use persistance_repository::Tenant;
+use serde::{Deserialize, Serialize};
+
+use super::SOURCE;
+use crate::events::event_emmiter::SerialisableEvent;
+use crate::events::event_envelope::EventEnvelope;
+use crate::events::event_registry::VersionedEvent;
+
+const EVENT_TYPE: &str = "cart_expired";
+
+// Items removed from a user's cart by the table's TTL after going untouched
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EventCartExpiredV1 {
+    pub version: u32,
+    pub event_type: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
+    pub user_id: String,
+    pub cart_items: Vec<models::models::cart::CartItem>,
+    // emitted straight from the table stream rather than the outbox, so it names its tenant itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+}
+
+impl EventCartExpiredV1 {
+    pub fn new(user_id: String, cart_items: Vec<models::models::cart::CartItem>) -> Self {
+        Self {
+            version: Self::VERSION,
+            event_type: EVENT_TYPE.to_string(),
+            envelope: EventEnvelope::new(SOURCE, user_id.clone()),
+            user_id,
+            cart_items,
+            tenant: None,
+        }
+    }
+
+    pub fn with_tenant(self, tenant: &Tenant) -> Self {
+        Self {
+            tenant: tenant.id().map(|id| id.to_string()),
+            ..self
+        }
+    }
+}
+
+impl VersionedEvent for EventCartExpiredV1 {
+    const EVENT_TYPE: &'static str = EVENT_TYPE;
+    const VERSION: u32 = 1;
+}
+
+impl SerialisableEvent for EventCartExpiredV1 {
+    fn get_event_type(&self) -> &String {
+        &self.event_type
+    }
+
+    fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    fn get_envelope(&self) -> EventEnvelope {
+        self.envelope.clone()
+    }
+
+    fn serialise(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 
+use super::SOURCE;
 use crate::events::event_emmiter::SerialisableEvent;
+use crate::events::event_envelope::EventEnvelope;
+use crate::events::event_registry::VersionedEvent;
 
 const EVENT_TYPE: &str = "cart_item_added";
 
@@ -8,19 +11,27 @@ const EVENT_TYPE: &str = "cart_item_added";
 pub struct EventCartItemAddedV1 {
     pub version: u32,
     pub event_type: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
     pub cart_item: models::models::cart::CartItem,
 }
 
 impl EventCartItemAddedV1 {
     pub fn new(cart_item: models::models::cart::CartItem) -> Self {
         Self {
-            version: 1,
+            version: Self::VERSION,
             event_type: EVENT_TYPE.to_string(),
+            envelope: EventEnvelope::new(SOURCE, cart_item.user_id.clone()),
             cart_item,
         }
     }
 }
 
+impl VersionedEvent for EventCartItemAddedV1 {
+    const EVENT_TYPE: &'static str = EVENT_TYPE;
+    const VERSION: u32 = 1;
+}
+
 impl SerialisableEvent for EventCartItemAddedV1 {
     fn get_event_type(&self) -> &String {
         &self.event_type
@@ -30,6 +41,10 @@ impl SerialisableEvent for EventCartItemAddedV1 {
         self.version
     }
 
+    fn get_envelope(&self) -> EventEnvelope {
+        self.envelope.clone()
+    }
+
     fn serialise(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 
+use super::SOURCE;
 use crate::events::event_emmiter::SerialisableEvent;
+use crate::events::event_envelope::EventEnvelope;
+use crate::events::event_registry::VersionedEvent;
 
 const EVENT_TYPE: &str = "cart_items_removed";
 
@@ -8,19 +11,34 @@ const EVENT_TYPE: &str = "cart_items_removed";
 pub struct EventCartItemsRemovedV1 {
     pub version: u32,
     pub event_type: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
     pub cart_items: Vec<models::models::cart::CartItem>,
 }
 
 impl EventCartItemsRemovedV1 {
     pub fn new(cart_items: Vec<models::models::cart::CartItem>) -> Self {
         Self {
-            version: 1,
+            version: Self::VERSION,
             event_type: EVENT_TYPE.to_string(),
+            // the items all come from the one cart
+            envelope: EventEnvelope::new(
+                SOURCE,
+                cart_items
+                    .first()
+                    .map(|cart_item| cart_item.user_id.clone())
+                    .unwrap_or_default(),
+            ),
             cart_items,
         }
     }
 }
 
+impl VersionedEvent for EventCartItemsRemovedV1 {
+    const EVENT_TYPE: &'static str = EVENT_TYPE;
+    const VERSION: u32 = 1;
+}
+
 impl SerialisableEvent for EventCartItemsRemovedV1 {
     fn get_event_type(&self) -> &String {
         &self.event_type
@@ -30,6 +48,10 @@ impl SerialisableEvent for EventCartItemsRemovedV1 {
         self.version
     }
 
+    fn get_envelope(&self) -> EventEnvelope {
+        self.envelope.clone()
+    }
+
     fn serialise(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
@@ -1,2 +1,6 @@
+pub mod cart_expired;
 pub mod cart_item_added;
 pub mod cart_items_removed;
+
+// The service these events are produced by, see EventEnvelope
+pub const SOURCE: &str = "cart";
@@ -1,3 +1,8 @@
+use models::models::outbox::OutboxItem;
+
+use crate::events::event_envelope::EventEnvelope;
+use crate::events::event_wrapper::with_tenant;
+
 #[async_trait::async_trait]
 pub trait EventEmmiter {
     async fn emit<T: SerialisableEvent>(event: T);
@@ -6,7 +11,38 @@ pub trait EventEmmiter {
 pub trait SerialisableEvent {
     fn get_event_type(&self) -> &String;
     fn get_version(&self) -> u32;
+    fn get_envelope(&self) -> EventEnvelope;
     fn serialise(&self) -> String;
+
+    // The outbox item written alongside the entity change, published later by the outbox relay
+    fn to_outbox_item(&self) -> OutboxItem {
+        OutboxItem::new(
+            self.get_event_type().clone(),
+            self.get_version(),
+            self.serialise(),
+        )
+    }
+}
+
+// Outbox items carry an already serialised event so they are published as is, naming the tenant
+// the change was made in unless that was the default one
+impl SerialisableEvent for OutboxItem {
+    fn get_event_type(&self) -> &String {
+        &self.event_type
+    }
+    fn get_version(&self) -> u32 {
+        self.version
+    }
+    // read back from the detail, events written before the envelope have an empty one
+    fn get_envelope(&self) -> EventEnvelope {
+        serde_json::from_str(&self.detail).unwrap_or_default()
+    }
+    fn serialise(&self) -> String {
+        match self.tenant.as_str() {
+            "" => self.detail.clone(),
+            tenant => with_tenant(&self.detail, tenant),
+        }
+    }
 }
 
 pub struct EventingRepository {}
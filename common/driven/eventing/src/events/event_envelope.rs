@@ -0,0 +1,92 @@
+use models::{default_time, new_uuid};
+use persistance_repository::Correlation;
+use serde::{Deserialize, Serialize};
+
+// Bumped when the envelope itself changes, events published before it had one read as 0
+pub const ENVELOPE_SCHEMA_VERSION: u32 = 1;
+
+// The fields every event carries next to its own, flattened into it so the detail stays one
+// object, e.g.
+// {"event_id": "..", "occurred_at": "..", "source": "product", "aggregate_id": "<product id>",
+//  "correlation_id": "..", "schema_version": 1, "version": 1, "event_type": "product_created", ..}
+//
+// event_id is what consumers deduplicate on, EventBridge delivers at least once
+// source is the service that produced the event, published as <EVENT_SOURCE>.<source>
+// aggregate_id is the entity the event is about, events for one aggregate can be ordered by
+// occurred_at
+// correlation_id and causation_id are added by the repository writing the event, see Correlation
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EventEnvelope {
+    #[serde(default)]
+    pub event_id: String,
+    #[serde(default)]
+    pub occurred_at: String,
+    #[serde(default)]
+    pub source: String,
+    #[serde(default)]
+    pub aggregate_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub causation_id: Option<String>,
+    #[serde(default)]
+    pub schema_version: u32,
+}
+
+impl EventEnvelope {
+    pub fn new(source: &str, aggregate_id: String) -> Self {
+        Self {
+            event_id: new_uuid(),
+            occurred_at: default_time(),
+            source: source.to_string(),
+            aggregate_id,
+            correlation_id: None,
+            causation_id: None,
+            schema_version: ENVELOPE_SCHEMA_VERSION,
+        }
+    }
+
+    // For the writes this event triggers. They join its chain, which starts at this event when
+    // it wasn't part of one
+    pub fn caused(&self) -> Correlation {
+        let event_id = match self.event_id.is_empty() {
+            true => None,
+            false => Some(self.event_id.clone()),
+        };
+        Correlation::new(
+            self.correlation_id.clone().or_else(|| event_id.clone()),
+            event_id,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caused() {
+        let envelope = EventEnvelope::new("users", "jdoe".to_string());
+        assert_eq!(
+            envelope.caused(),
+            Correlation::new(
+                Some(envelope.event_id.clone()),
+                Some(envelope.event_id.clone())
+            )
+        );
+
+        let envelope = EventEnvelope {
+            correlation_id: Some("request-1".to_string()),
+            ..envelope
+        };
+        assert_eq!(
+            envelope.caused(),
+            Correlation::new(
+                Some("request-1".to_string()),
+                Some(envelope.event_id.clone())
+            )
+        );
+
+        assert_eq!(EventEnvelope::default().caused(), Correlation::default());
+    }
+}
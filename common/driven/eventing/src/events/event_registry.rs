@@ -0,0 +1,292 @@
+// Event versions
+// Consumers are built against the current version of each event. Events published at an older
+// version, e.g. sitting in a DLQ or replayed from an archive, are lifted one version at a time by
+// the registered upcasters before they are read, so a consumer only ever sees the current shape.
+//
+// Changing an event means adding the new version's struct, registering it in place of the old one
+// and registering an upcaster from the old version, e.g.
+//
+// EventRegistry::new()
+//     .with_event::<EventUserDeletedV2>()
+//     .with_upcaster("user_deleted", 1, user_deleted_v1_to_v2)
+
+use std::collections::HashMap;
+
+use error::HexagonalError;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::events::cart::{
+    cart_expired::EventCartExpiredV1, cart_item_added::EventCartItemAddedV1,
+    cart_items_removed::EventCartItemsRemovedV1,
+};
+use crate::events::event_wrapper::{EventWrapper, TenantEvent};
+use crate::events::product::{
+    product_created::EventProductCreatedV1, product_deleted::EventProductDeletedV1,
+    product_restored::EventProductRestoredV1, product_soft_deleted::EventProductSoftDeletedV1,
+    product_updated::EventProductUpdatedV1,
+};
+use crate::events::user::{
+    user_created::EventUserCreatedV1, user_deleted::EventUserDeletedV1,
+    user_restored::EventUserRestoredV1, user_soft_deleted::EventUserSoftDeletedV1,
+    user_updated::EventUserUpdatedV1, username_updated::EventEmailUpdatedV1,
+};
+
+// The current version of an event, what consumers decode into
+pub trait VersionedEvent: DeserializeOwned {
+    const EVENT_TYPE: &'static str;
+    const VERSION: u32;
+}
+
+// Lifts an event's detail from its version to the next. The version field is set by the registry
+pub type Upcaster = fn(Value) -> Result<Value, String>;
+
+#[derive(Default, Clone)]
+pub struct EventRegistry {
+    // the current version of each event type
+    versions: HashMap<String, u32>,
+    // keyed by the version they lift from
+    upcasters: HashMap<(String, u32), Upcaster>,
+}
+
+impl EventRegistry {
+    pub fn new() -> EventRegistry {
+        EventRegistry::default()
+    }
+
+    // Every event in eventing::events at its current version
+    pub fn storefront() -> EventRegistry {
+        EventRegistry::new()
+            .with_event::<EventCartExpiredV1>()
+            .with_event::<EventCartItemAddedV1>()
+            .with_event::<EventCartItemsRemovedV1>()
+            .with_event::<EventProductCreatedV1>()
+            .with_event::<EventProductDeletedV1>()
+            .with_event::<EventProductRestoredV1>()
+            .with_event::<EventProductSoftDeletedV1>()
+            .with_event::<EventProductUpdatedV1>()
+            .with_event::<EventUserCreatedV1>()
+            .with_event::<EventUserDeletedV1>()
+            .with_event::<EventUserRestoredV1>()
+            .with_event::<EventUserSoftDeletedV1>()
+            .with_event::<EventUserUpdatedV1>()
+            .with_event::<EventEmailUpdatedV1>()
+    }
+
+    pub fn with_event<T: VersionedEvent>(mut self) -> EventRegistry {
+        self.versions.insert(T::EVENT_TYPE.to_string(), T::VERSION);
+        self
+    }
+
+    pub fn with_upcaster(
+        mut self,
+        event_type: &str,
+        from_version: u32,
+        upcaster: Upcaster,
+    ) -> EventRegistry {
+        self.upcasters
+            .insert((event_type.to_string(), from_version), upcaster);
+        self
+    }
+
+    // The current version of an event type, None when it isn't registered
+    pub fn version(&self, event_type: &str) -> Option<u32> {
+        self.versions.get(event_type).copied()
+    }
+
+    // The detail lifted to the current version of its event type
+    pub fn upcast(&self, detail: Value) -> Result<Value, HexagonalError> {
+        let wrapper: EventWrapper = serde_json::from_value(detail.clone())
+            .map_err(|err| bad_event("Event has no event_type or version", err.to_string()))?;
+        let current = self.version(&wrapper.event_type).ok_or_else(|| {
+            bad_event(
+                "Unknown event type",
+                format!("{} is not registered", wrapper.event_type),
+            )
+        })?;
+        if wrapper.version > current {
+            return Err(bad_event(
+                "Unknown event version",
+                format!(
+                    "{} v{} is newer than the current v{}",
+                    wrapper.event_type, wrapper.version, current
+                ),
+            ));
+        }
+
+        let mut detail = detail;
+        for version in wrapper.version..current {
+            let upcaster = self
+                .upcasters
+                .get(&(wrapper.event_type.clone(), version))
+                .ok_or_else(|| {
+                    bad_event(
+                        "Unknown event version",
+                        format!(
+                            "no upcaster from {} v{} to v{}",
+                            wrapper.event_type,
+                            version,
+                            version + 1
+                        ),
+                    )
+                })?;
+            detail = upcaster(detail).map_err(|trace| {
+                bad_event(
+                    &format!(
+                        "Unable to upcast {} v{} to v{}",
+                        wrapper.event_type,
+                        version,
+                        version + 1
+                    ),
+                    trace,
+                )
+            })?;
+            if let Value::Object(fields) = &mut detail {
+                fields.insert("version".to_string(), Value::from(version + 1));
+            }
+        }
+        Ok(detail)
+    }
+
+    // The published detail read as the current version of T, with the tenant it was raised in
+    pub fn decode<T: VersionedEvent>(
+        &self,
+        detail: Value,
+    ) -> Result<TenantEvent<T>, HexagonalError> {
+        let wrapper: EventWrapper = serde_json::from_value(detail.clone())
+            .map_err(|err| bad_event("Event has no event_type or version", err.to_string()))?;
+        if wrapper.event_type != T::EVENT_TYPE {
+            return Err(bad_event(
+                "Unexpected event type",
+                format!("expected {}, got {}", T::EVENT_TYPE, wrapper.event_type),
+            ));
+        }
+        // a consumer built against an older version than the registry's would be handed a shape
+        // it doesn't know
+        if self.version(T::EVENT_TYPE) != Some(T::VERSION) {
+            return Err(bad_event(
+                "Event is not registered at this version",
+                format!("{} v{}", T::EVENT_TYPE, T::VERSION),
+            ));
+        }
+        serde_json::from_value(self.upcast(detail)?)
+            .map_err(|err| bad_event("Unable to read event", err.to_string()))
+    }
+}
+
+fn bad_event(message: &str, trace: String) -> HexagonalError {
+    HexagonalError {
+        error: error::HexagonalErrorCode::BadInput,
+        message: message.to_string(),
+        trace,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::events::event_emmiter::SerialisableEvent;
+
+    #[derive(Deserialize)]
+    struct EventRenamedV3 {
+        full_name: String,
+    }
+
+    impl VersionedEvent for EventRenamedV3 {
+        const EVENT_TYPE: &'static str = "renamed";
+        const VERSION: u32 = 3;
+    }
+
+    fn registry() -> EventRegistry {
+        EventRegistry::new()
+            .with_event::<EventRenamedV3>()
+            .with_upcaster("renamed", 1, |mut detail| {
+                let name = format!(
+                    "{} {}",
+                    detail["first"].as_str().unwrap_or_default(),
+                    detail["last"].as_str().unwrap_or_default()
+                );
+                detail["name"] = json!(name);
+                Ok(detail)
+            })
+            .with_upcaster("renamed", 2, |mut detail| match detail["name"].take() {
+                Value::String(name) => {
+                    detail["full_name"] = json!(name);
+                    Ok(detail)
+                }
+                _ => Err("name is missing".to_string()),
+            })
+    }
+
+    #[test]
+    fn test_decode_upcasts_to_current_version() {
+        let detail = json!({"version": 1, "event_type": "renamed", "first": "Jo", "last": "Doe"});
+        let result = registry().decode::<EventRenamedV3>(detail).unwrap();
+        assert_eq!(result.event.full_name, "Jo Doe");
+        assert_eq!(result.tenant, None);
+
+        let detail =
+            json!({"version": 3, "event_type": "renamed", "full_name": "Jo Doe", "tenant": "acme"});
+        let result = registry().decode::<EventRenamedV3>(detail).unwrap();
+        assert_eq!(result.event.full_name, "Jo Doe");
+        assert_eq!(result.tenant, Some("acme".to_string()));
+
+        assert_eq!(
+            registry()
+                .upcast(json!({"version": 2, "event_type": "renamed", "name": "Jo Doe"}))
+                .unwrap()["version"],
+            3
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_unknown_versions() {
+        let decode = |detail: Value| {
+            registry()
+                .decode::<EventRenamedV3>(detail)
+                .map(|_| ())
+                .unwrap_err()
+        };
+        assert_eq!(
+            decode(json!({"version": 4, "event_type": "renamed"})).message,
+            "Unknown event version"
+        );
+        assert_eq!(
+            decode(json!({"version": 0, "event_type": "renamed"})).message,
+            "Unknown event version"
+        );
+        assert_eq!(
+            decode(json!({"version": 2, "event_type": "renamed"})).message,
+            "Unable to upcast renamed v2 to v3"
+        );
+        assert_eq!(
+            decode(json!({"version": 3, "event_type": "other"})).message,
+            "Unexpected event type"
+        );
+        assert_eq!(
+            decode(json!({"version": 3})).error,
+            error::HexagonalErrorCode::BadInput
+        );
+        assert_eq!(
+            EventRegistry::new()
+                .upcast(json!({"version": 1, "event_type": "renamed"}))
+                .unwrap_err()
+                .message,
+            "Unknown event type"
+        );
+    }
+
+    #[test]
+    fn test_storefront_registry_reads_published_events() {
+        let event = EventEmailUpdatedV1::new("jdoe".to_string(), "jdoe@example.com".to_string());
+        let detail = serde_json::from_str(&event.to_outbox_item().serialise()).unwrap();
+        let result = EventRegistry::storefront()
+            .decode::<EventEmailUpdatedV1>(detail)
+            .unwrap();
+        assert_eq!(result.event.username, "jdoe");
+        assert_eq!(result.event.envelope, event.envelope);
+    }
+}
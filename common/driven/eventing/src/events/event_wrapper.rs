@@ -1,9 +1,18 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::events::event_envelope::EventEnvelope;
 
 #[derive(Deserialize)]
 pub struct EventWrapper {
     pub version: u32,
     pub event_type: String,
+    // the storefront the event happened in, None for the default tenant
+    #[serde(default)]
+    pub tenant: Option<String>,
+    // empty for events published before the envelope
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
 }
 
 impl EventWrapper {
@@ -11,13 +20,38 @@ impl EventWrapper {
         Self {
             version,
             event_type,
+            tenant: None,
+            envelope: EventEnvelope::default(),
+        }
+    }
+}
+
+// An event as published, with the tenant the outbox relay adds alongside its own fields, e.g.
+// CloudWatchEvent<TenantEvent<EventUserDeletedV1>>
+#[derive(Serialize, Deserialize)]
+pub struct TenantEvent<T> {
+    #[serde(default)]
+    pub tenant: Option<String>,
+    #[serde(flatten)]
+    pub event: T,
+}
+
+// The serialised event with the tenant added, events that aren't objects are left as they are
+pub fn with_tenant(detail: &str, tenant: &str) -> String {
+    match serde_json::from_str::<Value>(detail) {
+        Ok(Value::Object(mut fields)) => {
+            fields.insert("tenant".to_string(), Value::String(tenant.to_string()));
+            Value::Object(fields).to_string()
         }
+        _ => detail.to_string(),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::events::event_emmiter::SerialisableEvent;
+    use crate::events::event_envelope::ENVELOPE_SCHEMA_VERSION;
     use serde_json;
 
     #[test]
@@ -47,6 +81,43 @@ mod tests {
         assert_eq!(result_value.event_type, "test");
     }
 
+    #[test]
+    fn test_tenant_event_reads_tenant_beside_event() {
+        let detail = with_tenant(r#"{"version": 1, "event_type": "test"}"#, "acme");
+        let result: TenantEvent<EventWrapper> = serde_json::from_str(&detail).unwrap();
+        assert_eq!(result.tenant, Some("acme".to_string()));
+        assert_eq!(result.event.event_type, "test");
+
+        let result: TenantEvent<EventWrapper> =
+            serde_json::from_str(r#"{"version": 1, "event_type": "test"}"#).unwrap();
+        assert_eq!(result.tenant, None);
+    }
+
+    #[test]
+    fn test_event_wrapper_reads_envelope() {
+        let event = crate::events::user::username_updated::EventEmailUpdatedV1::new(
+            "jdoe".to_string(),
+            "jdoe@example.com".to_string(),
+        );
+        let detail = persistance_repository::Correlation::new(Some("request-1".to_string()), None)
+            .stamp(&event.serialise());
+        let result: EventWrapper = serde_json::from_str(&detail).unwrap();
+        assert_eq!(result.event_type, "user_email_updated");
+        assert_eq!(result.envelope.event_id, event.envelope.event_id);
+        assert_eq!(result.envelope.source, "users");
+        assert_eq!(result.envelope.aggregate_id, "jdoe");
+        assert_eq!(
+            result.envelope.correlation_id,
+            Some("request-1".to_string())
+        );
+        assert_eq!(result.envelope.schema_version, ENVELOPE_SCHEMA_VERSION);
+
+        // published before the envelope
+        let result: EventWrapper =
+            serde_json::from_str(r#"{"version": 1, "event_type": "test"}"#).unwrap();
+        assert_eq!(result.envelope, EventEnvelope::default());
+    }
+
     #[test]
     fn test_event_wrapper_missing_fields() {
         let input = r#"{
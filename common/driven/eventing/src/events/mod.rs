@@ -1,5 +1,7 @@
 pub mod cart;
 pub mod event_emmiter;
+pub mod event_envelope;
+pub mod event_registry;
 pub mod event_wrapper;
 pub mod product;
 pub mod user;
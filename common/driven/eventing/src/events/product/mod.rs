@@ -1,3 +1,8 @@
 pub mod product_created;
 pub mod product_deleted;
+pub mod product_restored;
+pub mod product_soft_deleted;
 pub mod product_updated;
+
+// The service these events are produced by, see EventEnvelope
+pub const SOURCE: &str = "product";
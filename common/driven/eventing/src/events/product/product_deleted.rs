@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 
+use super::SOURCE;
 use crate::events::event_emmiter::SerialisableEvent;
+use crate::events::event_envelope::EventEnvelope;
+use crate::events::event_registry::VersionedEvent;
 
 const EVENT_TYPE: &str = "product_deleted";
 
@@ -8,19 +11,27 @@ const EVENT_TYPE: &str = "product_deleted";
 pub struct EventProductDeletedV1 {
     pub version: u32,
     pub event_type: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
     pub product: models::models::product::Product,
 }
 
 impl EventProductDeletedV1 {
     pub fn new(product: models::models::product::Product) -> Self {
         Self {
-            version: 1,
+            version: Self::VERSION,
             event_type: EVENT_TYPE.to_string(),
+            envelope: EventEnvelope::new(SOURCE, product.id.clone()),
             product,
         }
     }
 }
 
+impl VersionedEvent for EventProductDeletedV1 {
+    const EVENT_TYPE: &'static str = EVENT_TYPE;
+    const VERSION: u32 = 1;
+}
+
 impl SerialisableEvent for EventProductDeletedV1 {
     fn get_event_type(&self) -> &String {
         &self.event_type
@@ -30,6 +41,10 @@ impl SerialisableEvent for EventProductDeletedV1 {
         self.version
     }
 
+    fn get_envelope(&self) -> EventEnvelope {
+        self.envelope.clone()
+    }
+
     fn serialise(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
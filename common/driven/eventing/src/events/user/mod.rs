@@ -1,4 +1,9 @@
 pub mod user_created;
 pub mod user_deleted;
+pub mod user_restored;
+pub mod user_soft_deleted;
 pub mod user_updated;
 pub mod username_updated;
+
+// The service these events are produced by, see EventEnvelope
+pub const SOURCE: &str = "users";
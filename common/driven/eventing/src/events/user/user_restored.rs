@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+use super::SOURCE;
+use crate::events::event_emmiter::SerialisableEvent;
+use crate::events::event_envelope::EventEnvelope;
+use crate::events::event_registry::VersionedEvent;
+
+const EVENT_TYPE: &str = "user_restored";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EventUserRestoredV1 {
+    pub version: u32,
+    pub event_type: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
+    pub user: models::models::user::User,
+}
+
+impl EventUserRestoredV1 {
+    pub fn new(user: models::models::user::User) -> Self {
+        Self {
+            version: Self::VERSION,
+            event_type: EVENT_TYPE.to_string(),
+            envelope: EventEnvelope::new(SOURCE, user.username.clone()),
+            user,
+        }
+    }
+}
+
+impl VersionedEvent for EventUserRestoredV1 {
+    const EVENT_TYPE: &'static str = EVENT_TYPE;
+    const VERSION: u32 = 1;
+}
+
+impl SerialisableEvent for EventUserRestoredV1 {
+    fn get_event_type(&self) -> &String {
+        &self.event_type
+    }
+
+    fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    fn get_envelope(&self) -> EventEnvelope {
+        self.envelope.clone()
+    }
+
+    fn serialise(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
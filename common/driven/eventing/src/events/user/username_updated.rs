@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 
+use super::SOURCE;
 use crate::events::event_emmiter::SerialisableEvent;
+use crate::events::event_envelope::EventEnvelope;
+use crate::events::event_registry::VersionedEvent;
 
 const EVENT_TYPE: &str = "user_email_updated";
 
@@ -8,6 +11,8 @@ const EVENT_TYPE: &str = "user_email_updated";
 pub struct EventEmailUpdatedV1 {
     pub version: u32,
     pub event_type: String,
+    #[serde(flatten)]
+    pub envelope: EventEnvelope,
     pub new_email: String,
     pub username: String,
 }
@@ -15,14 +20,20 @@ pub struct EventEmailUpdatedV1 {
 impl EventEmailUpdatedV1 {
     pub fn new(username: String, new_email: String) -> Self {
         Self {
-            version: 1,
+            version: Self::VERSION,
             event_type: EVENT_TYPE.to_string(),
+            envelope: EventEnvelope::new(SOURCE, username.clone()),
             username,
             new_email,
         }
     }
 }
 
+impl VersionedEvent for EventEmailUpdatedV1 {
+    const EVENT_TYPE: &'static str = EVENT_TYPE;
+    const VERSION: u32 = 1;
+}
+
 impl SerialisableEvent for EventEmailUpdatedV1 {
     fn get_event_type(&self) -> &String {
         &self.event_type
@@ -32,6 +43,10 @@ impl SerialisableEvent for EventEmailUpdatedV1 {
         self.version
     }
 
+    fn get_envelope(&self) -> EventEnvelope {
+        self.envelope.clone()
+    }
+
     fn serialise(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
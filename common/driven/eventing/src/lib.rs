@@ -1,6 +1,10 @@
 pub mod events;
 
+use app_config::{AppConfig, ConfigError, EVENT_BUS_NAME};
+use std::time::Duration;
+
 use async_trait::async_trait;
+use aws_sdk_eventbridge::types::PutEventsRequestEntry;
 use aws_sdk_eventbridge::Client;
 
 use error::HexagonalError;
@@ -11,49 +15,415 @@ use sdk_credential_meta_repository::SdkCredentialsMetaRepository;
 pub struct EventingRepository {
     pub client: Client,
     pub bus_name: String,
+    pub source: String,
 }
 
 #[automock]
 #[async_trait]
-pub trait EventingPort {
+pub trait EventingPort: Sync {
     async fn emit<T: SerialisableEvent + Sync + 'static>(
         &self,
         event: &T,
     ) -> Result<(), HexagonalError>;
+
+    // One result per event, in the order given. Events that failed can be emitted again, the
+    // rest went through
+    async fn emit_batch<T: SerialisableEvent + Sync + 'static>(
+        &self,
+        events: &[T],
+    ) -> Vec<Result<(), HexagonalError>> {
+        let mut results = Vec::with_capacity(events.len());
+        for event in events {
+            results.push(self.emit(event).await);
+        }
+        results
+    }
 }
 
+// The most entries a PutEvents call takes
+pub const PUT_EVENTS_BATCH_SIZE: usize = 10;
+// The most a PutEvents call can carry, counted as EventBridge does, see put_events_entry_size
+pub const PUT_EVENTS_MAX_REQUEST_SIZE: usize = 256 * 1024;
+// includes the first call, so 1 means never retry
+const PUT_EVENTS_MAX_ATTEMPTS: u32 = 3;
+const PUT_EVENTS_BASE_DELAY: Duration = Duration::from_millis(50);
+
 impl EventingRepository {
     pub fn new(
         sdk_credential_meta_repository: &SdkCredentialsMetaRepository,
-    ) -> EventingRepository {
-        EventingRepository {
+        config: &AppConfig,
+    ) -> Result<EventingRepository, ConfigError> {
+        let [bus_name] = config.require([EVENT_BUS_NAME])?;
+        Ok(EventingRepository {
             client: Client::new(&sdk_credential_meta_repository.sdk_config),
-            bus_name: std::env::var("EVENT_BUS_NAME")
-                .expect("EVENT_BUS_NAME environment variable not set"),
+            bus_name,
+            source: config.eventing.source.clone(),
+        })
+    }
+}
+
+// <EVENT_SOURCE>.<service>, so rules can match one service or, by prefix, the whole storefront.
+// Events without an envelope are published under EVENT_SOURCE alone as they always were
+pub fn event_source<T: SerialisableEvent>(source: &str, event: &T) -> String {
+    match event.get_envelope().source.as_str() {
+        "" => source.to_string(),
+        service => format!("{}.{}", source, service),
+    }
+}
+
+// An entry's size as EventBridge counts it against the request limit
+// https://docs.aws.amazon.com/eventbridge/latest/userguide/eb-putevent-size.html
+fn put_events_entry_size(entry: &PutEventsRequestEntry) -> usize {
+    let time = match entry.time() {
+        Some(_) => 14,
+        None => 0,
+    };
+    time + entry.source().map_or(0, str::len)
+        + entry.detail_type().map_or(0, str::len)
+        + entry.detail().map_or(0, str::len)
+        + entry.resources().iter().map(String::len).sum::<usize>()
+}
+
+// Splits entries into PutEvents calls, in order, each within both the entry and the size limit.
+// An entry over the size limit on its own still gets a call, for EventBridge to fail it
+fn put_events_chunks(entries: Vec<PutEventsRequestEntry>) -> Vec<Vec<PutEventsRequestEntry>> {
+    let mut chunks: Vec<Vec<PutEventsRequestEntry>> = Vec::new();
+    let mut chunk_size = 0;
+    for entry in entries {
+        let entry_size = put_events_entry_size(&entry);
+        match chunks.last_mut() {
+            Some(chunk)
+                if chunk.len() < PUT_EVENTS_BATCH_SIZE
+                    && chunk_size + entry_size <= PUT_EVENTS_MAX_REQUEST_SIZE =>
+            {
+                chunk_size += entry_size;
+                chunk.push(entry);
+            }
+            _ => {
+                chunk_size = entry_size;
+                chunks.push(vec![entry]);
+            }
         }
     }
+    chunks
 }
 
-#[async_trait::async_trait]
-impl EventingPort for EventingRepository {
-    async fn emit<T: SerialisableEvent + Sync>(&self, event: &T) -> Result<(), HexagonalError> {
-        let put_events_request = aws_sdk_eventbridge::types::PutEventsRequestEntry::builder()
+impl EventingRepository {
+    fn entry<T: SerialisableEvent>(&self, event: &T) -> PutEventsRequestEntry {
+        PutEventsRequestEntry::builder()
             .set_event_bus_name(Some(self.bus_name.clone()))
             .set_detail_type(Some(event.get_event_type().clone()))
-            .set_source(Some("RUSTHEXAGONALSTOREFRONT".to_string()))
+            .set_source(Some(event_source(&self.source, event)))
             .set_detail(Some(event.serialise()))
-            .build();
-
-        self.client
-            .put_events()
-            .entries(put_events_request)
-            .send()
-            .await
-            .map_err(|err| HexagonalError {
-                message: format!("Unable to emit event: {}", event.get_event_type()),
-                error: error::HexagonalErrorCode::AdaptorError,
-                trace: err.to_string(),
+            .build()
+    }
+
+    // Sends one PutEvents call of entries from put_events_chunks, retrying only the entries
+    // that failed. Entries are failed individually, e.g. when throttled, even when the call works
+    async fn put_events_chunk(&self, entries: Vec<PutEventsRequestEntry>) -> Vec<Option<String>> {
+        // None once an entry has gone through, otherwise why it last failed
+        let mut failures: Vec<Option<String>> = vec![Some("not sent".to_string()); entries.len()];
+        let mut attempt = 1;
+        loop {
+            let pending: Vec<usize> = (0..entries.len())
+                .filter(|index| failures[*index].is_some())
+                .collect();
+            let result = self
+                .client
+                .put_events()
+                .set_entries(Some(
+                    pending
+                        .iter()
+                        .map(|index| entries[*index].clone())
+                        .collect(),
+                ))
+                .send()
+                .await;
+            match result {
+                // result entries line up with the request entries
+                Ok(output) => {
+                    for (index, result_entry) in pending.iter().zip(output.entries()) {
+                        failures[*index] = result_entry.error_code().map(|code| {
+                            format!("{}: {}", code, result_entry.error_message().unwrap_or(""))
+                        });
+                    }
+                }
+                Err(err) => {
+                    let err = err.into_service_error().to_string();
+                    for index in &pending {
+                        failures[*index] = Some(err.clone());
+                    }
+                }
+            }
+
+            if failures.iter().all(Option::is_none) || attempt >= PUT_EVENTS_MAX_ATTEMPTS {
+                return failures;
+            }
+            tokio::time::sleep(PUT_EVENTS_BASE_DELAY * 2u32.pow(attempt - 1)).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EventingPort for EventingRepository {
+    async fn emit<T: SerialisableEvent + Sync + 'static>(
+        &self,
+        event: &T,
+    ) -> Result<(), HexagonalError> {
+        self.emit_batch(std::slice::from_ref(event)).await.remove(0)
+    }
+
+    async fn emit_batch<T: SerialisableEvent + Sync + 'static>(
+        &self,
+        events: &[T],
+    ) -> Vec<Result<(), HexagonalError>> {
+        let entries = events.iter().map(|event| self.entry(event)).collect();
+        // chunks keep the order of the events, so the failures line up with them
+        let mut failures = Vec::with_capacity(events.len());
+        for chunk in put_events_chunks(entries) {
+            failures.extend(self.put_events_chunk(chunk).await);
+        }
+        events
+            .iter()
+            .zip(failures)
+            .map(|(event, failure)| match failure {
+                None => Ok(()),
+                Some(trace) => Err(HexagonalError {
+                    message: format!("Unable to emit event: {}", event.get_event_type()),
+                    error: error::HexagonalErrorCode::AdaptorError,
+                    trace,
+                }),
             })
-            .map(|value| (println!("{:?}", value)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use aws_sdk_eventbridge::config::{BehaviorVersion, Credentials, Region};
+    use aws_smithy_runtime_api::client::http::{
+        HttpClient, HttpConnector, HttpConnectorFuture, HttpConnectorSettings, SharedHttpClient,
+        SharedHttpConnector,
+    };
+    use aws_smithy_runtime_api::client::orchestrator::{HttpRequest, HttpResponse};
+    use aws_smithy_runtime_api::client::runtime_components::RuntimeComponents;
+    use aws_smithy_runtime_api::http::StatusCode;
+    use aws_smithy_types::body::SdkBody;
+    use models::models::outbox::OutboxItem;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use events::user::username_updated::EventEmailUpdatedV1;
+
+    // Answers PutEvents in place of EventBridge. fails is asked about each entry's detail along
+    // with how many times it has been sent, counting this one
+    #[derive(Clone, Debug)]
+    struct StubPutEvents {
+        fails: fn(&str, u32) -> bool,
+        // the details each call carried, in the order they were made
+        calls: Arc<Mutex<Vec<Vec<String>>>>,
+        sent: Arc<Mutex<HashMap<String, u32>>>,
+    }
+
+    impl StubPutEvents {
+        fn new(fails: fn(&str, u32) -> bool) -> StubPutEvents {
+            StubPutEvents {
+                fails,
+                calls: Arc::default(),
+                sent: Arc::default(),
+            }
+        }
+
+        fn repository(&self) -> EventingRepository {
+            let config = aws_sdk_eventbridge::Config::builder()
+                .behavior_version(BehaviorVersion::latest())
+                .region(Region::new("local"))
+                .credentials_provider(Credentials::new("local", "local", None, None, "stub"))
+                .endpoint_url("http://eventbridge.localhost")
+                .http_client(SharedHttpClient::new(self.clone()))
+                .build();
+            EventingRepository {
+                client: Client::from_conf(config),
+                bus_name: "bus".to_string(),
+                source: "RUSTHEXAGONALSTOREFRONT".to_string(),
+            }
+        }
+
+        fn calls(&self) -> Vec<Vec<String>> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        fn respond(&self, request: &HttpRequest) -> Value {
+            let body: Value = serde_json::from_slice(request.body().bytes().unwrap()).unwrap();
+            let details: Vec<String> = body["Entries"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| entry["Detail"].as_str().unwrap().to_string())
+                .collect();
+            self.calls.lock().unwrap().push(details.clone());
+
+            let mut sent = self.sent.lock().unwrap();
+            let entries: Vec<Value> = details
+                .iter()
+                .map(|detail| {
+                    let times = sent.entry(detail.clone()).or_default();
+                    *times += 1;
+                    match (self.fails)(detail, *times) {
+                        true => json!({
+                            "ErrorCode": "ThrottlingException",
+                            "ErrorMessage": "Rate exceeded",
+                        }),
+                        false => json!({"EventId": detail}),
+                    }
+                })
+                .collect();
+            let failed = entries
+                .iter()
+                .filter(|entry| entry.get("ErrorCode").is_some())
+                .count();
+            json!({"FailedEntryCount": failed, "Entries": entries})
+        }
+    }
+
+    impl HttpConnector for StubPutEvents {
+        fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+            let mut response = HttpResponse::new(
+                StatusCode::try_from(200).unwrap(),
+                SdkBody::from(self.respond(&request).to_string()),
+            );
+            response
+                .headers_mut()
+                .insert("content-type", "application/x-amz-json-1.1");
+            HttpConnectorFuture::ready(Ok(response))
+        }
+    }
+
+    impl HttpClient for StubPutEvents {
+        fn http_connector(
+            &self,
+            _settings: &HttpConnectorSettings,
+            _components: &RuntimeComponents,
+        ) -> SharedHttpConnector {
+            SharedHttpConnector::new(self.clone())
+        }
+    }
+
+    fn test_events(count: usize) -> Vec<OutboxItem> {
+        (0..count)
+            .map(|index| OutboxItem::new("Test".to_string(), 1, format!("event-{}", index)))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_emit_batch_retries_failed_entries() {
+        let stub = StubPutEvents::new(|detail, times| detail == "event-1" && times == 1);
+        let results = stub.repository().emit_batch(&test_events(3)).await;
+
+        assert!(results.iter().all(Result::is_ok));
+        // only the entry that failed is sent again
+        assert_eq!(
+            stub.calls(),
+            vec![
+                vec![
+                    "event-0".to_string(),
+                    "event-1".to_string(),
+                    "event-2".to_string()
+                ],
+                vec!["event-1".to_string()],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_emit_batch_gives_up_after_max_attempts() {
+        let stub = StubPutEvents::new(|detail, _| detail == "event-1");
+        let results = stub.repository().emit_batch(&test_events(3)).await;
+
+        assert!(results[0].is_ok());
+        assert!(results[2].is_ok());
+        let err = results[1].as_ref().unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::AdaptorError);
+        assert_eq!(err.message, "Unable to emit event: Test");
+        assert_eq!(err.trace, "ThrottlingException: Rate exceeded");
+        assert_eq!(stub.calls().len(), PUT_EVENTS_MAX_ATTEMPTS as usize);
+    }
+
+    #[tokio::test]
+    async fn test_emit_batch_results_line_up_across_chunks() {
+        let stub = StubPutEvents::new(|detail, _| detail == "event-3" || detail == "event-12");
+        let results = stub.repository().emit_batch(&test_events(23)).await;
+
+        assert_eq!(results.len(), 23);
+        for (index, result) in results.iter().enumerate() {
+            assert_eq!(
+                result.is_err(),
+                index == 3 || index == 12,
+                "event {}",
+                index
+            );
+        }
+        // a chunk's failures are retried before the next chunk goes
+        let first_sends: Vec<usize> = stub.calls().iter().take(3).map(|call| call.len()).collect();
+        assert_eq!(first_sends, vec![10, 1, 1]);
+    }
+
+    #[test]
+    fn test_put_events_chunks_stay_within_request_size() {
+        let entry = |detail_size: usize| {
+            PutEventsRequestEntry::builder()
+                .source("source")
+                .detail_type("Test")
+                .detail("x".repeat(detail_size))
+                .build()
+        };
+        let sizes = |chunks: Vec<Vec<PutEventsRequestEntry>>| -> Vec<usize> {
+            chunks.iter().map(Vec::len).collect()
+        };
+
+        assert_eq!(
+            sizes(put_events_chunks((0..25).map(|_| entry(10)).collect())),
+            vec![10, 10, 5]
+        );
+        // three 100 KB entries can't share one call
+        assert_eq!(
+            sizes(put_events_chunks(
+                (0..3).map(|_| entry(100 * 1024)).collect()
+            )),
+            vec![2, 1]
+        );
+        // one too large for any call is still sent on its own
+        assert_eq!(
+            sizes(put_events_chunks(vec![
+                entry(10),
+                entry(PUT_EVENTS_MAX_REQUEST_SIZE),
+                entry(10)
+            ])),
+            vec![1, 1, 1]
+        );
+    }
+
+    #[test]
+    fn test_event_source_names_the_service() {
+        let event = EventEmailUpdatedV1::new("jdoe".to_string(), "jdoe@example.com".to_string());
+        assert_eq!(
+            event_source("RUSTHEXAGONALSTOREFRONT", &event),
+            "RUSTHEXAGONALSTOREFRONT.users"
+        );
+        // the relay publishes what the outbox holds
+        assert_eq!(
+            event_source("RUSTHEXAGONALSTOREFRONT", &event.to_outbox_item()),
+            "RUSTHEXAGONALSTOREFRONT.users"
+        );
+
+        let legacy = OutboxItem::new("test".to_string(), 1, "{}".to_string());
+        assert_eq!(
+            event_source("RUSTHEXAGONALSTOREFRONT", &legacy),
+            "RUSTHEXAGONALSTOREFRONT"
+        );
     }
 }
@@ -0,0 +1,152 @@
+// Attribute values are kept in their DynamoDB wire format, e.g. {"S": "abc"} or {"N": "12"},
+// so items can be returned to the SDK exactly as they were written.
+
+use std::cmp::Ordering;
+
+use serde_json::{Map, Value};
+
+pub type Item = Map<String, Value>;
+
+pub fn type_of(value: &Value) -> Option<&str> {
+    value
+        .as_object()
+        .and_then(|object| object.keys().next())
+        .map(|key| key.as_str())
+}
+
+pub fn as_s(value: &Value) -> Option<&str> {
+    value.get("S").and_then(|s| s.as_str())
+}
+
+pub fn as_n(value: &Value) -> Option<&str> {
+    value.get("N").and_then(|n| n.as_str())
+}
+
+pub fn n(value: &str) -> Value {
+    serde_json::json!({ "N": value })
+}
+
+// DynamoDB stores numbers without leading or trailing zeros, so "01.50" reads back as "1.5"
+pub fn normalise_number(number: &str) -> Option<String> {
+    let trimmed = number.trim();
+    if let Ok(integer) = trimmed.parse::<i128>() {
+        return Some(integer.to_string());
+    }
+    let float = trimmed.parse::<f64>().ok()?;
+    if !float.is_finite() {
+        return None;
+    }
+    if float.fract() == 0.0 && float.abs() < 1e15 {
+        return Some(format!("{}", float as i128));
+    }
+    Some(float.to_string())
+}
+
+pub fn normalise(value: &Value) -> Result<Value, String> {
+    match type_of(value) {
+        Some("N") => {
+            let number = as_n(value).ok_or("N attribute must be a string")?;
+            normalise_number(number)
+                .map(|number| n(&number))
+                .ok_or(format!(
+                    "The parameter cannot be converted to a numeric value: {}",
+                    number
+                ))
+        }
+        Some("NS") => {
+            let numbers = value["NS"]
+                .as_array()
+                .ok_or("NS attribute must be a list")?;
+            let mut normalised = Vec::new();
+            for number in numbers {
+                let number = number.as_str().ok_or("NS members must be strings")?;
+                normalised.push(Value::String(normalise_number(number).ok_or(format!(
+                    "The parameter cannot be converted to a numeric value: {}",
+                    number
+                ))?));
+            }
+            Ok(serde_json::json!({ "NS": normalised }))
+        }
+        Some("M") => {
+            let map = value["M"].as_object().ok_or("M attribute must be a map")?;
+            let mut normalised = Map::new();
+            for (key, value) in map {
+                normalised.insert(key.clone(), normalise(value)?);
+            }
+            Ok(serde_json::json!({ "M": normalised }))
+        }
+        Some("L") => {
+            let list = value["L"].as_array().ok_or("L attribute must be a list")?;
+            let normalised = list.iter().map(normalise).collect::<Result<Vec<_>, _>>()?;
+            Ok(serde_json::json!({ "L": normalised }))
+        }
+        Some("S" | "B" | "BOOL" | "NULL" | "SS" | "BS") => Ok(value.clone()),
+        _ => Err(format!(
+            "Supplied AttributeValue is empty or invalid: {}",
+            value
+        )),
+    }
+}
+
+pub fn normalise_item(item: &Item) -> Result<Item, String> {
+    let mut normalised = Map::new();
+    for (key, value) in item {
+        normalised.insert(key.clone(), normalise(value)?);
+    }
+    Ok(normalised)
+}
+
+fn compare_numbers(left: &str, right: &str) -> Option<Ordering> {
+    if let (Ok(left), Ok(right)) = (left.parse::<i128>(), right.parse::<i128>()) {
+        return Some(left.cmp(&right));
+    }
+    let left = left.parse::<f64>().ok()?;
+    let right = right.parse::<f64>().ok()?;
+    left.partial_cmp(&right)
+}
+
+// Ordering is only defined between two scalars of the same type, mirroring the comparators
+// DynamoDB accepts in key conditions and condition expressions
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (type_of(left)?, type_of(right)?) {
+        ("S", "S") => Some(as_s(left)?.as_bytes().cmp(as_s(right)?.as_bytes())),
+        ("N", "N") => compare_numbers(as_n(left)?, as_n(right)?),
+        ("B", "B") => Some(left["B"].as_str()?.cmp(right["B"].as_str()?)),
+        _ => None,
+    }
+}
+
+fn sorted_set(value: &Value) -> Option<Vec<String>> {
+    let mut members = value
+        .as_array()?
+        .iter()
+        .map(|member| member.as_str().map(|member| member.to_string()))
+        .collect::<Option<Vec<String>>>()?;
+    members.sort();
+    Some(members)
+}
+
+pub fn equals(left: &Value, right: &Value) -> bool {
+    match (type_of(left), type_of(right)) {
+        (Some("N"), Some("N")) => compare(left, right) == Some(Ordering::Equal),
+        (Some(set @ ("SS" | "NS" | "BS")), Some(other)) if set == other => {
+            sorted_set(&left[set]) == sorted_set(&right[set])
+        }
+        _ => left == right,
+    }
+}
+
+pub fn size(value: &Value) -> Option<usize> {
+    match type_of(value)? {
+        "S" => Some(as_s(value)?.chars().count()),
+        "B" => Some(value["B"].as_str()?.len() * 3 / 4),
+        "SS" | "NS" | "BS" | "L" => value
+            .as_object()?
+            .values()
+            .next()?
+            .as_array()
+            .map(|v| v.len()),
+        "M" => value["M"].as_object().map(|m| m.len()),
+        _ => None,
+    }
+}
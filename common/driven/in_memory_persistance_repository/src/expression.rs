@@ -0,0 +1,1016 @@
+// A small interpreter for the DynamoDB expression language covering key conditions, condition and
+// filter expressions, update expressions and projections.
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use serde_json::{Map, Value};
+
+use crate::attribute::{self, Item};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Name(String),
+    Value(String),
+    Index(usize),
+    Comparator(&'static str),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Dot,
+    Plus,
+    Minus,
+}
+
+fn tokenise(expression: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let single = match c {
+            '(' => Some(Token::LParen),
+            ')' => Some(Token::RParen),
+            '[' => Some(Token::LBracket),
+            ']' => Some(Token::RBracket),
+            ',' => Some(Token::Comma),
+            '.' => Some(Token::Dot),
+            '+' => Some(Token::Plus),
+            '-' => Some(Token::Minus),
+            '=' => Some(Token::Comparator("=")),
+            _ => None,
+        };
+        if let Some(token) = single {
+            tokens.push(token);
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '<' || c == '>' {
+            let next = chars.get(i + 1).copied();
+            let (comparator, width) = match (c, next) {
+                ('<', Some('>')) => ("<>", 2),
+                ('<', Some('=')) => ("<=", 2),
+                ('>', Some('=')) => (">=", 2),
+                ('<', _) => ("<", 1),
+                _ => (">", 1),
+            };
+            tokens.push(Token::Comparator(comparator));
+            i += width;
+            continue;
+        }
+        let word_start = if c == '#' || c == ':' { i + 1 } else { i };
+        let mut end = word_start;
+        while end < chars.len() && (chars[end].is_alphanumeric() || chars[end] == '_') {
+            end += 1;
+        }
+        if end == word_start {
+            return Err(format!(
+                "Invalid expression: Syntax error; token: \"{}\", near: \"{}\"",
+                c, expression
+            ));
+        }
+        let word: String = chars[i..end].iter().collect();
+        tokens.push(match c {
+            '#' => Token::Name(word),
+            ':' => Token::Value(word),
+            _ if word.chars().all(|c| c.is_ascii_digit()) => {
+                Token::Index(word.parse().map_err(|_| "Invalid list index".to_string())?)
+            }
+            _ => Token::Ident(word),
+        });
+        i = end;
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathElement {
+    Attribute(String),
+    Index(usize),
+}
+
+pub type Path = Vec<PathElement>;
+
+#[derive(Debug, Clone)]
+pub enum Operand {
+    Path(Path),
+    Value(Value),
+    Size(Path),
+}
+
+#[derive(Debug, Clone)]
+pub enum Condition {
+    Compare(Operand, &'static str, Operand),
+    Between(Operand, Operand, Operand),
+    In(Operand, Vec<Operand>),
+    AttributeExists(Path),
+    AttributeNotExists(Path),
+    AttributeType(Path, Operand),
+    BeginsWith(Operand, Operand),
+    Contains(Operand, Operand),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+#[derive(Debug, Clone)]
+pub enum SetValue {
+    Operand(Operand),
+    IfNotExists(Path, Box<SetValue>),
+    ListAppend(Box<SetValue>, Box<SetValue>),
+    Plus(Box<SetValue>, Box<SetValue>),
+    Minus(Box<SetValue>, Box<SetValue>),
+}
+
+#[derive(Debug, Clone)]
+pub enum UpdateAction {
+    Set(Path, SetValue),
+    Remove(Path),
+    Add(Path, Value),
+    Delete(Path, Value),
+}
+
+// Expression attribute names and values are shared between all the expressions of one request,
+// and DynamoDB rejects requests that define placeholders no expression uses
+pub struct Placeholders<'a> {
+    names: Option<&'a Map<String, Value>>,
+    values: Option<&'a Map<String, Value>>,
+    used_names: RefCell<HashSet<String>>,
+    used_values: RefCell<HashSet<String>>,
+}
+
+impl<'a> Placeholders<'a> {
+    pub fn new(names: Option<&'a Value>, values: Option<&'a Value>) -> Result<Self, String> {
+        let names = names.map(|n| {
+            n.as_object()
+                .ok_or("ExpressionAttributeNames must be a map")
+        });
+        let values = values.map(|v| {
+            v.as_object()
+                .ok_or("ExpressionAttributeValues must be a map")
+        });
+        let placeholders = Placeholders {
+            names: names.transpose()?,
+            values: values.transpose()?,
+            used_names: RefCell::new(HashSet::new()),
+            used_values: RefCell::new(HashSet::new()),
+        };
+        if let Some(values) = placeholders.values {
+            for key in values.keys() {
+                if !key.starts_with(':') {
+                    return Err(format!(
+                        "ExpressionAttributeValues contains invalid key: Syntax error; key: \"{}\"",
+                        key
+                    ));
+                }
+            }
+        }
+        if let Some(names) = placeholders.names {
+            for key in names.keys() {
+                if !key.starts_with('#') {
+                    return Err(format!(
+                        "ExpressionAttributeNames contains invalid key: Syntax error; key: \"{}\"",
+                        key
+                    ));
+                }
+            }
+        }
+        Ok(placeholders)
+    }
+
+    fn name(&self, placeholder: &str) -> Result<String, String> {
+        self.used_names.borrow_mut().insert(placeholder.to_string());
+        self.names
+            .and_then(|names| names.get(placeholder))
+            .and_then(|name| name.as_str())
+            .map(|name| name.to_string())
+            .ok_or(format!(
+                "An expression attribute name used in the document path is not defined; attribute name: {}",
+                placeholder
+            ))
+    }
+
+    fn value(&self, placeholder: &str) -> Result<Value, String> {
+        self.used_values
+            .borrow_mut()
+            .insert(placeholder.to_string());
+        let value = self
+            .values
+            .and_then(|values| values.get(placeholder))
+            .ok_or(format!(
+            "An expression attribute value used in expression is not defined; attribute value: {}",
+            placeholder
+        ))?;
+        attribute::normalise(value)
+    }
+
+    pub fn check_all_used(&self) -> Result<(), String> {
+        if let Some(values) = self.values {
+            let used = self.used_values.borrow();
+            let unused: Vec<&String> = values.keys().filter(|k| !used.contains(*k)).collect();
+            if !unused.is_empty() {
+                return Err(format!(
+                    "Value provided in ExpressionAttributeValues unused in expressions: keys: {{{}}}",
+                    unused.iter().map(|k| k.as_str()).collect::<Vec<_>>().join(", ")
+                ));
+            }
+        }
+        if let Some(names) = self.names {
+            let used = self.used_names.borrow();
+            let unused: Vec<&String> = names.keys().filter(|k| !used.contains(*k)).collect();
+            if !unused.is_empty() {
+                return Err(format!(
+                    "Value provided in ExpressionAttributeNames unused in expressions: keys: {{{}}}",
+                    unused.iter().map(|k| k.as_str()).collect::<Vec<_>>().join(", ")
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Parser<'a, 'b> {
+    tokens: Vec<Token>,
+    position: usize,
+    placeholders: &'b Placeholders<'a>,
+}
+
+impl<'a, 'b> Parser<'a, 'b> {
+    fn new(expression: &str, placeholders: &'b Placeholders<'a>) -> Result<Self, String> {
+        Ok(Parser {
+            tokens: tokenise(expression)?,
+            position: 0,
+            placeholders,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            other => Err(format!(
+                "Invalid expression: Syntax error; expected {:?} but found {:?}",
+                expected, other
+            )),
+        }
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn peek_function(&self) -> Option<String> {
+        match (
+            self.tokens.get(self.position),
+            self.tokens.get(self.position + 1),
+        ) {
+            (Some(Token::Ident(word)), Some(Token::LParen)) => Some(word.to_ascii_lowercase()),
+            _ => None,
+        }
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(format!(
+                "Invalid expression: Syntax error; unexpected token {:?}",
+                token
+            )),
+        }
+    }
+
+    fn path(&mut self) -> Result<Path, String> {
+        let mut path = vec![self.path_attribute()?];
+        loop {
+            match self.peek() {
+                Some(Token::Dot) => {
+                    self.next();
+                    path.push(self.path_attribute()?);
+                }
+                Some(Token::LBracket) => {
+                    self.next();
+                    match self.next() {
+                        Some(Token::Index(index)) => path.push(PathElement::Index(index)),
+                        other => return Err(format!("Invalid list index {:?}", other)),
+                    }
+                    self.expect(Token::RBracket)?;
+                }
+                _ => return Ok(path),
+            }
+        }
+    }
+
+    fn path_attribute(&mut self) -> Result<PathElement, String> {
+        match self.next() {
+            Some(Token::Name(name)) => Ok(PathElement::Attribute(self.placeholders.name(&name)?)),
+            Some(Token::Ident(name)) if !is_reserved(&name) => Ok(PathElement::Attribute(name)),
+            Some(Token::Ident(name)) => Err(format!(
+                "Invalid expression: Attribute name is a reserved keyword; reserved keyword: {}",
+                name
+            )),
+            other => Err(format!(
+                "Invalid expression: Syntax error; expected an attribute but found {:?}",
+                other
+            )),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        if self.peek_function().as_deref() == Some("size") {
+            self.next();
+            self.expect(Token::LParen)?;
+            let path = self.path()?;
+            self.expect(Token::RParen)?;
+            return Ok(Operand::Size(path));
+        }
+        match self.peek() {
+            Some(Token::Value(_)) => match self.next() {
+                Some(Token::Value(value)) => Ok(Operand::Value(self.placeholders.value(&value)?)),
+                _ => unreachable!(),
+            },
+            _ => Ok(Operand::Path(self.path()?)),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let mut left = self.and_condition()?;
+        while self.peek_keyword("OR") {
+            self.next();
+            let right = self.and_condition()?;
+            left = Condition::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn and_condition(&mut self) -> Result<Condition, String> {
+        let mut left = self.not_condition()?;
+        while self.peek_keyword("AND") {
+            self.next();
+            let right = self.not_condition()?;
+            left = Condition::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn not_condition(&mut self) -> Result<Condition, String> {
+        if self.peek_keyword("NOT") {
+            self.next();
+            return Ok(Condition::Not(Box::new(self.not_condition()?)));
+        }
+        self.primary_condition()
+    }
+
+    fn primary_condition(&mut self) -> Result<Condition, String> {
+        if self.peek() == Some(&Token::LParen) {
+            self.next();
+            let condition = self.condition()?;
+            self.expect(Token::RParen)?;
+            return Ok(condition);
+        }
+        if let Some(function) = self.peek_function() {
+            if function != "size" {
+                self.next();
+                self.expect(Token::LParen)?;
+                let condition = match function.as_str() {
+                    "attribute_exists" => Condition::AttributeExists(self.path()?),
+                    "attribute_not_exists" => Condition::AttributeNotExists(self.path()?),
+                    "attribute_type" => {
+                        let path = self.path()?;
+                        self.expect(Token::Comma)?;
+                        Condition::AttributeType(path, self.operand()?)
+                    }
+                    "begins_with" | "contains" => {
+                        let left = self.operand()?;
+                        self.expect(Token::Comma)?;
+                        let right = self.operand()?;
+                        if function == "begins_with" {
+                            Condition::BeginsWith(left, right)
+                        } else {
+                            Condition::Contains(left, right)
+                        }
+                    }
+                    other => {
+                        return Err(format!(
+                            "Invalid expression: Invalid function name; function: {}",
+                            other
+                        ))
+                    }
+                };
+                self.expect(Token::RParen)?;
+                return Ok(condition);
+            }
+        }
+        let left = self.operand()?;
+        if self.peek_keyword("BETWEEN") {
+            self.next();
+            let low = self.operand()?;
+            if !self.peek_keyword("AND") {
+                return Err("Invalid expression: BETWEEN requires AND".to_string());
+            }
+            self.next();
+            let high = self.operand()?;
+            return Ok(Condition::Between(left, low, high));
+        }
+        if self.peek_keyword("IN") {
+            self.next();
+            self.expect(Token::LParen)?;
+            let mut options = vec![self.operand()?];
+            while self.peek() == Some(&Token::Comma) {
+                self.next();
+                options.push(self.operand()?);
+            }
+            self.expect(Token::RParen)?;
+            return Ok(Condition::In(left, options));
+        }
+        match self.next() {
+            Some(Token::Comparator(comparator)) => {
+                Ok(Condition::Compare(left, comparator, self.operand()?))
+            }
+            other => Err(format!(
+                "Invalid expression: Syntax error; expected a comparator but found {:?}",
+                other
+            )),
+        }
+    }
+
+    fn set_value(&mut self) -> Result<SetValue, String> {
+        let left = self.set_value_term()?;
+        match self.peek() {
+            Some(Token::Plus) => {
+                self.next();
+                Ok(SetValue::Plus(
+                    Box::new(left),
+                    Box::new(self.set_value_term()?),
+                ))
+            }
+            Some(Token::Minus) => {
+                self.next();
+                Ok(SetValue::Minus(
+                    Box::new(left),
+                    Box::new(self.set_value_term()?),
+                ))
+            }
+            _ => Ok(left),
+        }
+    }
+
+    fn set_value_term(&mut self) -> Result<SetValue, String> {
+        match self.peek_function().as_deref() {
+            Some("if_not_exists") => {
+                self.next();
+                self.expect(Token::LParen)?;
+                let path = self.path()?;
+                self.expect(Token::Comma)?;
+                let fallback = self.set_value_term()?;
+                self.expect(Token::RParen)?;
+                Ok(SetValue::IfNotExists(path, Box::new(fallback)))
+            }
+            Some("list_append") => {
+                self.next();
+                self.expect(Token::LParen)?;
+                let left = self.set_value_term()?;
+                self.expect(Token::Comma)?;
+                let right = self.set_value_term()?;
+                self.expect(Token::RParen)?;
+                Ok(SetValue::ListAppend(Box::new(left), Box::new(right)))
+            }
+            _ => Ok(SetValue::Operand(self.operand()?)),
+        }
+    }
+
+    fn value_placeholder(&mut self) -> Result<Value, String> {
+        match self.next() {
+            Some(Token::Value(value)) => self.placeholders.value(&value),
+            other => Err(format!(
+                "Invalid expression: Syntax error; expected a value but found {:?}",
+                other
+            )),
+        }
+    }
+
+    fn update(&mut self) -> Result<Vec<UpdateAction>, String> {
+        let mut actions = Vec::new();
+        let mut seen_clauses = HashSet::new();
+        while let Some(token) = self.next() {
+            let clause = match token {
+                Token::Ident(word) => word.to_ascii_uppercase(),
+                other => {
+                    return Err(format!(
+                        "Invalid UpdateExpression: Syntax error; unexpected token {:?}",
+                        other
+                    ))
+                }
+            };
+            if !seen_clauses.insert(clause.clone()) {
+                return Err(format!(
+                    "Invalid UpdateExpression: The \"{}\" section can only be used once in an update expression",
+                    clause
+                ));
+            }
+            loop {
+                let action = match clause.as_str() {
+                    "SET" => {
+                        let path = self.path()?;
+                        self.expect(Token::Comparator("="))?;
+                        UpdateAction::Set(path, self.set_value()?)
+                    }
+                    "REMOVE" => UpdateAction::Remove(self.path()?),
+                    "ADD" => UpdateAction::Add(self.path()?, self.value_placeholder()?),
+                    "DELETE" => UpdateAction::Delete(self.path()?, self.value_placeholder()?),
+                    other => {
+                        return Err(format!(
+                            "Invalid UpdateExpression: Syntax error; token: \"{}\"",
+                            other
+                        ))
+                    }
+                };
+                actions.push(action);
+                if self.peek() == Some(&Token::Comma) {
+                    self.next();
+                } else {
+                    break;
+                }
+            }
+        }
+        if actions.is_empty() {
+            return Err("Invalid UpdateExpression: The expression can not be empty".to_string());
+        }
+        Ok(actions)
+    }
+}
+
+fn is_reserved(word: &str) -> bool {
+    const RESERVED: [&str; 12] = [
+        "AND", "OR", "NOT", "BETWEEN", "IN", "SET", "REMOVE", "ADD", "DELETE", "SIZE", "NAME",
+        "KEY",
+    ];
+    RESERVED.iter().any(|r| r.eq_ignore_ascii_case(word))
+}
+
+pub fn parse_condition(expression: &str, placeholders: &Placeholders) -> Result<Condition, String> {
+    let mut parser = Parser::new(expression, placeholders)?;
+    let condition = parser.condition()?;
+    parser.finish()?;
+    Ok(condition)
+}
+
+pub fn parse_update(
+    expression: &str,
+    placeholders: &Placeholders,
+) -> Result<Vec<UpdateAction>, String> {
+    let mut parser = Parser::new(expression, placeholders)?;
+    parser.update()
+}
+
+pub fn parse_projection(
+    expression: &str,
+    placeholders: &Placeholders,
+) -> Result<Vec<Path>, String> {
+    let mut parser = Parser::new(expression, placeholders)?;
+    let mut paths = vec![parser.path()?];
+    while parser.peek() == Some(&Token::Comma) {
+        parser.next();
+        paths.push(parser.path()?);
+    }
+    parser.finish()?;
+    Ok(paths)
+}
+
+pub fn resolve<'v>(item: &'v Item, path: &Path) -> Option<&'v Value> {
+    let mut elements = path.iter();
+    let mut current = match elements.next()? {
+        PathElement::Attribute(name) => item.get(name)?,
+        PathElement::Index(_) => return None,
+    };
+    for element in elements {
+        current = match element {
+            PathElement::Attribute(name) => current.get("M")?.get(name)?,
+            PathElement::Index(index) => current.get("L")?.get(*index)?,
+        };
+    }
+    Some(current)
+}
+
+fn operand_value(item: &Item, operand: &Operand) -> Option<Value> {
+    match operand {
+        Operand::Path(path) => resolve(item, path).cloned(),
+        Operand::Value(value) => Some(value.clone()),
+        Operand::Size(path) => resolve(item, path)
+            .and_then(attribute::size)
+            .map(|size| attribute::n(&size.to_string())),
+    }
+}
+
+pub fn evaluate(condition: &Condition, item: &Item) -> bool {
+    match condition {
+        Condition::Compare(left, comparator, right) => {
+            let left = operand_value(item, left);
+            let right = operand_value(item, right);
+            match (left, right) {
+                (Some(left), Some(right)) => match *comparator {
+                    "=" => attribute::equals(&left, &right),
+                    "<>" => !attribute::equals(&left, &right),
+                    comparator => match attribute::compare(&left, &right) {
+                        Some(ordering) => match comparator {
+                            "<" => ordering == Ordering::Less,
+                            "<=" => ordering != Ordering::Greater,
+                            ">" => ordering == Ordering::Greater,
+                            _ => ordering != Ordering::Less,
+                        },
+                        None => false,
+                    },
+                },
+                // a missing attribute is never equal to anything
+                (None, Some(_)) | (Some(_), None) => *comparator == "<>",
+                (None, None) => false,
+            }
+        }
+        Condition::Between(value, low, high) => {
+            match (
+                operand_value(item, value),
+                operand_value(item, low),
+                operand_value(item, high),
+            ) {
+                (Some(value), Some(low), Some(high)) => {
+                    attribute::compare(&value, &low).is_some_and(|o| o != Ordering::Less)
+                        && attribute::compare(&value, &high).is_some_and(|o| o != Ordering::Greater)
+                }
+                _ => false,
+            }
+        }
+        Condition::In(value, options) => match operand_value(item, value) {
+            Some(value) => options.iter().any(|option| {
+                operand_value(item, option).is_some_and(|option| attribute::equals(&value, &option))
+            }),
+            None => false,
+        },
+        Condition::AttributeExists(path) => resolve(item, path).is_some(),
+        Condition::AttributeNotExists(path) => resolve(item, path).is_none(),
+        Condition::AttributeType(path, expected) => {
+            match (resolve(item, path), operand_value(item, expected)) {
+                (Some(value), Some(expected)) => {
+                    attribute::as_s(&expected) == attribute::type_of(value)
+                }
+                _ => false,
+            }
+        }
+        Condition::BeginsWith(value, prefix) => {
+            match (operand_value(item, value), operand_value(item, prefix)) {
+                (Some(value), Some(prefix)) => {
+                    match (attribute::as_s(&value), attribute::as_s(&prefix)) {
+                        (Some(value), Some(prefix)) => value.starts_with(prefix),
+                        _ => match (value.get("B"), prefix.get("B")) {
+                            (Some(Value::String(value)), Some(Value::String(prefix))) => {
+                                value.starts_with(prefix.as_str())
+                            }
+                            _ => false,
+                        },
+                    }
+                }
+                _ => false,
+            }
+        }
+        Condition::Contains(value, needle) => {
+            match (operand_value(item, value), operand_value(item, needle)) {
+                (Some(value), Some(needle)) => contains(&value, &needle),
+                _ => false,
+            }
+        }
+        Condition::And(left, right) => evaluate(left, item) && evaluate(right, item),
+        Condition::Or(left, right) => evaluate(left, item) || evaluate(right, item),
+        Condition::Not(condition) => !evaluate(condition, item),
+    }
+}
+
+fn contains(value: &Value, needle: &Value) -> bool {
+    match attribute::type_of(value) {
+        Some("S") => match (attribute::as_s(value), attribute::as_s(needle)) {
+            (Some(value), Some(needle)) => value.contains(needle),
+            _ => false,
+        },
+        Some(set @ ("SS" | "NS" | "BS")) => {
+            let member_type = &set[..1];
+            match (value[set].as_array(), needle.get(member_type)) {
+                (Some(members), Some(needle)) => members.iter().any(|member| member == needle),
+                _ => false,
+            }
+        }
+        Some("L") => value["L"]
+            .as_array()
+            .is_some_and(|list| list.iter().any(|member| attribute::equals(member, needle))),
+        _ => false,
+    }
+}
+
+// Used to pull the partition key value out of a key condition, which must be an equality
+pub fn find_equality(condition: &Condition, attribute_name: &str) -> Option<Value> {
+    match condition {
+        Condition::Compare(Operand::Path(path), "=", Operand::Value(value))
+        | Condition::Compare(Operand::Value(value), "=", Operand::Path(path)) => {
+            match path.as_slice() {
+                [PathElement::Attribute(name)] if name == attribute_name => Some(value.clone()),
+                _ => None,
+            }
+        }
+        Condition::And(left, right) => {
+            find_equality(left, attribute_name).or_else(|| find_equality(right, attribute_name))
+        }
+        _ => None,
+    }
+}
+
+fn set_value(item: &Item, value: &SetValue) -> Result<Value, String> {
+    match value {
+        SetValue::Operand(operand) => operand_value(item, operand).ok_or(
+            "The provided expression refers to an attribute that does not exist in the item"
+                .to_string(),
+        ),
+        SetValue::IfNotExists(path, fallback) => match resolve(item, path) {
+            Some(existing) => Ok(existing.clone()),
+            None => set_value(item, fallback),
+        },
+        SetValue::ListAppend(left, right) => {
+            let left = set_value(item, left)?;
+            let right = set_value(item, right)?;
+            match (left["L"].as_array(), right["L"].as_array()) {
+                (Some(left), Some(right)) => {
+                    let mut joined = left.clone();
+                    joined.extend(right.iter().cloned());
+                    Ok(serde_json::json!({ "L": joined }))
+                }
+                _ => Err(
+                    "An operand in the update expression has an incorrect data type".to_string(),
+                ),
+            }
+        }
+        SetValue::Plus(left, right) | SetValue::Minus(left, right) => {
+            let left = set_value(item, left)?;
+            let right = set_value(item, right)?;
+            let negate = matches!(value, SetValue::Minus(_, _));
+            match (attribute::as_n(&left), attribute::as_n(&right)) {
+                (Some(left), Some(right)) => add_numbers(left, right, negate),
+                _ => Err(
+                    "An operand in the update expression has an incorrect data type".to_string(),
+                ),
+            }
+        }
+    }
+}
+
+fn add_numbers(left: &str, right: &str, negate: bool) -> Result<Value, String> {
+    let sign = if negate { -1 } else { 1 };
+    if let (Ok(left), Ok(right)) = (left.parse::<i128>(), right.parse::<i128>()) {
+        return Ok(attribute::n(&(left + sign * right).to_string()));
+    }
+    match (left.parse::<f64>(), right.parse::<f64>()) {
+        (Ok(left), Ok(right)) => {
+            let sum = left + sign as f64 * right;
+            attribute::normalise_number(&sum.to_string())
+                .map(|number| attribute::n(&number))
+                .ok_or("Number overflow".to_string())
+        }
+        _ => Err("An operand in the update expression has an incorrect data type".to_string()),
+    }
+}
+
+fn assign(item: &mut Item, path: &Path, value: Option<Value>) -> Result<(), String> {
+    let (last, parents) = path
+        .split_last()
+        .ok_or("Empty attribute path".to_string())?;
+    let mut elements = parents.iter();
+    let first = match elements.next() {
+        None => {
+            return match last {
+                PathElement::Attribute(name) => {
+                    match value {
+                        Some(value) => item.insert(name.clone(), value),
+                        None => item.remove(name),
+                    };
+                    Ok(())
+                }
+                PathElement::Index(_) => Err("Invalid document path".to_string()),
+            }
+        }
+        Some(first) => first,
+    };
+    let invalid =
+        || "The document path provided in the update expression is invalid for update".to_string();
+    let mut current = match first {
+        PathElement::Attribute(name) => item.get_mut(name).ok_or_else(invalid)?,
+        PathElement::Index(_) => return Err(invalid()),
+    };
+    for element in elements {
+        current = match element {
+            PathElement::Attribute(name) => current
+                .get_mut("M")
+                .and_then(|m| m.get_mut(name))
+                .ok_or_else(invalid)?,
+            PathElement::Index(index) => current
+                .get_mut("L")
+                .and_then(|l| l.get_mut(*index))
+                .ok_or_else(invalid)?,
+        };
+    }
+    match last {
+        PathElement::Attribute(name) => {
+            let map = current
+                .get_mut("M")
+                .and_then(|m| m.as_object_mut())
+                .ok_or_else(invalid)?;
+            match value {
+                Some(value) => map.insert(name.clone(), value),
+                None => map.remove(name),
+            };
+        }
+        PathElement::Index(index) => {
+            let list = current
+                .get_mut("L")
+                .and_then(|l| l.as_array_mut())
+                .ok_or_else(invalid)?;
+            match value {
+                Some(value) if *index < list.len() => list[*index] = value,
+                Some(value) => list.push(value),
+                None if *index < list.len() => {
+                    list.remove(*index);
+                }
+                None => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+fn merge_set(existing: &Value, change: &Value, add: bool) -> Result<Value, String> {
+    let set = attribute::type_of(change).unwrap_or_default().to_string();
+    if attribute::type_of(existing) != Some(set.as_str()) {
+        return Err("An operand in the update expression has an incorrect data type".to_string());
+    }
+    let mut members: Vec<Value> = existing[&set].as_array().cloned().unwrap_or_default();
+    let changes = change[&set].as_array().cloned().unwrap_or_default();
+    if add {
+        for member in changes {
+            if !members.contains(&member) {
+                members.push(member);
+            }
+        }
+    } else {
+        members.retain(|member| !changes.contains(member));
+    }
+    Ok(serde_json::json!({ set: members }))
+}
+
+// Returns the top level attributes touched so UPDATED_OLD / UPDATED_NEW can be answered
+pub fn apply_update(item: &mut Item, actions: &[UpdateAction]) -> Result<Vec<String>, String> {
+    // all values are computed against the item as it was before the update
+    let original = item.clone();
+    let mut touched = Vec::new();
+    for action in actions {
+        let path = match action {
+            UpdateAction::Set(path, value) => {
+                assign(item, path, Some(set_value(&original, value)?))?;
+                path
+            }
+            UpdateAction::Remove(path) => {
+                assign(item, path, None)?;
+                path
+            }
+            UpdateAction::Add(path, value) => {
+                let updated = match (resolve(&original, path), attribute::type_of(value)) {
+                    (None, _) => value.clone(),
+                    (Some(existing), Some("N")) => {
+                        match attribute::as_n(existing) {
+                            Some(existing) => {
+                                add_numbers(existing, attribute::as_n(value).unwrap_or("0"), false)?
+                            }
+                            None => return Err(
+                                "An operand in the update expression has an incorrect data type"
+                                    .to_string(),
+                            ),
+                        }
+                    }
+                    (Some(existing), Some("SS" | "NS" | "BS")) => merge_set(existing, value, true)?,
+                    _ => {
+                        return Err(
+                            "Incorrect operand type for operator or function; operator: ADD"
+                                .to_string(),
+                        )
+                    }
+                };
+                assign(item, path, Some(updated))?;
+                path
+            }
+            UpdateAction::Delete(path, value) => {
+                if let Some(existing) = resolve(&original, path) {
+                    let remaining = merge_set(existing, value, false)?;
+                    let empty = attribute::size(&remaining) == Some(0);
+                    assign(item, path, if empty { None } else { Some(remaining) })?;
+                }
+                path
+            }
+        };
+        if let Some(PathElement::Attribute(name)) = path.first() {
+            if !touched.contains(name) {
+                touched.push(name.clone());
+            }
+        }
+    }
+    Ok(touched)
+}
+
+pub fn project(item: &Item, paths: &[Path]) -> Item {
+    // nested paths project their whole top level attribute, which is enough for our access patterns
+    let mut projected = Map::new();
+    for path in paths {
+        if let Some(PathElement::Attribute(name)) = path.first() {
+            if resolve(item, path).is_some() {
+                projected.insert(name.clone(), item[name].clone());
+            }
+        }
+    }
+    projected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn item() -> Item {
+        json!({
+            "Pkey": { "S": "USER#test" },
+            "Skey": { "S": "-" },
+            "quantity": { "N": "3" },
+            "tags": { "SS": ["a", "b"] },
+            "address": { "M": { "city": { "S": "Brisbane" } } }
+        })
+        .as_object()
+        .unwrap()
+        .clone()
+    }
+
+    #[test]
+    fn test_condition_functions_and_comparators() {
+        let values = json!({ ":pk": { "S": "USER#test" }, ":min": { "N": "2" }, ":prefix": { "S": "USER#" } });
+        let placeholders = Placeholders::new(None, Some(&values)).unwrap();
+        let condition = parse_condition(
+            "Pkey = :pk AND quantity > :min AND begins_with(Pkey, :prefix) AND attribute_not_exists(missing)",
+            &placeholders,
+        )
+        .unwrap();
+        assert!(evaluate(&condition, &item()));
+        assert!(placeholders.check_all_used().is_ok());
+    }
+
+    #[test]
+    fn test_condition_undefined_value_is_rejected() {
+        let values = json!({ "Pkey": { "S": "USER#test" } });
+        assert!(Placeholders::new(None, Some(&values)).is_err());
+        let placeholders = Placeholders::new(None, None).unwrap();
+        assert!(parse_condition("Pkey = :p_key", &placeholders).is_err());
+    }
+
+    #[test]
+    fn test_condition_unused_value_is_rejected() {
+        let values = json!({ ":pk": { "S": "USER#test" }, ":unused": { "S": "x" } });
+        let placeholders = Placeholders::new(None, Some(&values)).unwrap();
+        parse_condition("Pkey = :pk", &placeholders).unwrap();
+        assert!(placeholders.check_all_used().is_err());
+    }
+
+    #[test]
+    fn test_update_set_add_remove() {
+        let names = json!({ "#q": "quantity" });
+        let values =
+            json!({ ":one": { "N": "1" }, ":city": { "S": "Sydney" }, ":tag": { "SS": ["c"] } });
+        let placeholders = Placeholders::new(Some(&names), Some(&values)).unwrap();
+        let actions = parse_update(
+            "SET #q = #q + :one, address.city = :city REMOVE Skey ADD tags :tag",
+            &placeholders,
+        )
+        .unwrap();
+        let mut updated = item();
+        let touched = apply_update(&mut updated, &actions).unwrap();
+        assert_eq!(updated["quantity"], json!({ "N": "4" }));
+        assert_eq!(updated["address"]["M"]["city"], json!({ "S": "Sydney" }));
+        assert_eq!(updated["tags"], json!({ "SS": ["a", "b", "c"] }));
+        assert!(updated.get("Skey").is_none());
+        assert_eq!(touched, vec!["quantity", "address", "Skey", "tags"]);
+    }
+}
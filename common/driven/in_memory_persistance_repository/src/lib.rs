@@ -0,0 +1,463 @@
+mod attribute;
+mod expression;
+mod operations;
+mod table;
+
+use std::sync::{Arc, Mutex};
+
+use aws_sdk_dynamodb::config::{BehaviorVersion, Credentials, Region};
+use aws_sdk_dynamodb::Client;
+use aws_smithy_runtime_api::client::http::{
+    HttpClient, HttpConnector, HttpConnectorFuture, HttpConnectorSettings, SharedHttpClient,
+    SharedHttpConnector,
+};
+use aws_smithy_runtime_api::client::orchestrator::{HttpRequest, HttpResponse};
+use aws_smithy_runtime_api::client::runtime_components::RuntimeComponents;
+use aws_smithy_runtime_api::http::StatusCode;
+use aws_smithy_types::body::SdkBody;
+use persistance_repository::DynamoDBSingleTableRepository;
+use serde_json::Value;
+
+use operations::{OperationError, Tables};
+
+const TARGET_PREFIX: &str = "DynamoDB_20120810.";
+
+// An in process stand in for the single table. It sits underneath the AWS SDK as its HTTP client
+// and answers the DynamoDB JSON protocol, so DynamoDBSingleTableRepository and every adaptor built
+// on it run unchanged against it. Clones share the same tables.
+#[derive(Clone, Debug, Default)]
+pub struct InMemorySingleTable {
+    tables: Arc<Mutex<Tables>>,
+    batch_capacity: Option<usize>,
+}
+
+impl InMemorySingleTable {
+    pub fn new() -> InMemorySingleTable {
+        InMemorySingleTable::default()
+    }
+
+    // Batch calls only process this many entries each and return the rest as unprocessed, for
+    // exercising retries as if the table were being throttled
+    pub fn with_batch_capacity(mut self, batch_capacity: usize) -> InMemorySingleTable {
+        self.batch_capacity = Some(batch_capacity);
+        self
+    }
+
+    pub fn client(&self) -> Client {
+        let config = aws_sdk_dynamodb::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("local"))
+            .credentials_provider(Credentials::new("local", "local", None, None, "in_memory"))
+            .endpoint_url("http://in-memory.localhost")
+            .http_client(SharedHttpClient::new(self.clone()))
+            .build();
+        Client::from_conf(config)
+    }
+
+    pub fn repository(&self, table_name: &str) -> DynamoDBSingleTableRepository {
+        DynamoDBSingleTableRepository::new_with_client(self.client(), table_name.to_string())
+    }
+
+    fn handle(&self, request: &HttpRequest) -> Result<Value, OperationError> {
+        let operation = request
+            .headers()
+            .get("x-amz-target")
+            .and_then(|target| target.strip_prefix(TARGET_PREFIX))
+            .ok_or(OperationError::validation(
+                "Missing or unknown x-amz-target",
+            ))?
+            .to_string();
+        let body = request.body().bytes().unwrap_or_default();
+        let body: Value = if body.is_empty() {
+            Value::Object(Default::default())
+        } else {
+            serde_json::from_slice(body)
+                .map_err(|e| OperationError::validation(format!("Malformed request body: {}", e)))?
+        };
+        // a poisoned lock only means another test panicked mid request, the data is still usable
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        operations::dispatch(&mut tables, &operation, &body, self.batch_capacity)
+    }
+}
+
+impl HttpConnector for InMemorySingleTable {
+    fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+        let (status, body) = match self.handle(&request) {
+            Ok(body) => (200, body),
+            Err(err) => (400, err.into_body()),
+        };
+        let mut response = HttpResponse::new(
+            StatusCode::try_from(status).expect("status codes are valid"),
+            SdkBody::from(body.to_string()),
+        );
+        response
+            .headers_mut()
+            .insert("content-type", "application/x-amz-json-1.0");
+        HttpConnectorFuture::ready(Ok(response))
+    }
+}
+
+impl HttpClient for InMemorySingleTable {
+    fn http_connector(
+        &self,
+        _settings: &HttpConnectorSettings,
+        _components: &RuntimeComponents,
+    ) -> SharedHttpConnector {
+        SharedHttpConnector::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use aws_sdk_dynamodb::types::{
+        AttributeValue, DeleteRequest, Put, PutRequest, TransactWriteItem, WriteRequest,
+    };
+
+    use metrics::InMemoryMetricsAdaptor;
+    use persistance_repository::{
+        BatchPolicy, GSIs, IndexQuery, Page, PageRequest, SortKeyCondition,
+    };
+
+    use super::*;
+
+    fn s(value: &str) -> AttributeValue {
+        AttributeValue::S(value.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_consumed_capacity_is_recorded_per_operation() {
+        let metrics = Arc::new(InMemoryMetricsAdaptor::new());
+        let repository = InMemorySingleTable::new()
+            .repository("table")
+            .with_metrics(metrics.clone())
+            .with_operation("product_create");
+        let item = |id: &str| {
+            HashMap::from([
+                ("Pkey".to_string(), s(&format!("PRODUCT#{}", id))),
+                ("Skey".to_string(), s("-")),
+            ])
+        };
+        repository.put_new_item(item("1")).await.unwrap();
+        repository
+            .get_item_primary("PRODUCT#1".to_string(), "-".to_string())
+            .await
+            .unwrap();
+        repository
+            .batch_get(vec![item("1"), item("2")])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            metrics.operations(),
+            vec![
+                "product_create.put",
+                "product_create.get",
+                "product_create.batch_get"
+            ]
+        );
+        let units: Vec<(f64, f64)> = metrics
+            .records()
+            .iter()
+            .map(|record| (record.read_capacity_units, record.write_capacity_units))
+            .collect();
+        assert_eq!(units, vec![(0.0, 1.0), (0.5, 0.0), (1.0, 0.0)]);
+    }
+
+    #[tokio::test]
+    async fn test_put_then_get() {
+        let repository = InMemorySingleTable::new().repository("table");
+        repository
+            .put_new_item(HashMap::from([
+                ("Pkey".to_string(), s("PRODUCT#1")),
+                ("Skey".to_string(), s("-")),
+                ("price".to_string(), AttributeValue::N("10.50".to_string())),
+            ]))
+            .await
+            .unwrap();
+
+        let item = repository
+            .get_item_primary("PRODUCT#1".to_string(), "-".to_string())
+            .await
+            .unwrap()
+            .item
+            .unwrap();
+        assert_eq!(
+            item.get("price"),
+            Some(&AttributeValue::N("10.5".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_put_new_item_conflicts() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let item = HashMap::from([
+            ("Pkey".to_string(), s("USER#a")),
+            ("Skey".to_string(), s("-")),
+        ]);
+        repository.put_new_item(item.clone()).await.unwrap();
+        let err = repository.put_new_item(item).await.unwrap_err();
+        assert!(err.is_conditional_check_failed_exception());
+    }
+
+    #[tokio::test]
+    async fn test_query_begins_with_on_index_with_pagination() {
+        let store = InMemorySingleTable::new();
+        let client = store.client();
+        for product in ["1", "2", "3"] {
+            client
+                .put_item()
+                .table_name("table")
+                .item("Pkey", s("CART#USER#a"))
+                .item("Skey", s(&format!("CART#PRODUCT#{}", product)))
+                .item("GSI1Pkey", s(&format!("CART#PRODUCT#{}", product)))
+                .item("GSI1Skey", s("CART#USER#a"))
+                .send()
+                .await
+                .unwrap();
+        }
+
+        let first = client
+            .query()
+            .table_name("table")
+            .key_condition_expression("Pkey = :pkey and begins_with(Skey, :skey)")
+            .expression_attribute_values(":pkey", s("CART#USER#a"))
+            .expression_attribute_values(":skey", s("CART#PRODUCT#"))
+            .limit(2)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(first.count, 2);
+        let rest = client
+            .query()
+            .table_name("table")
+            .key_condition_expression("Pkey = :pkey and begins_with(Skey, :skey)")
+            .expression_attribute_values(":pkey", s("CART#USER#a"))
+            .expression_attribute_values(":skey", s("CART#PRODUCT#"))
+            .set_exclusive_start_key(first.last_evaluated_key)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(rest.count, 1);
+        assert!(rest.last_evaluated_key.is_none());
+
+        let by_product = client
+            .query()
+            .table_name("table")
+            .index_name("GSI1")
+            .key_condition_expression("GSI1Pkey = :pkey")
+            .expression_attribute_values(":pkey", s("CART#PRODUCT#2"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(by_product.count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_index_query_sort_key_filter_and_projection() {
+        let repository = InMemorySingleTable::new().repository("table");
+        for (placed_at, status) in [("100", "PLACED"), ("200", "SHIPPED"), ("300", "PLACED")] {
+            repository
+                .put_new_item(HashMap::from([
+                    ("Pkey".to_string(), s(&format!("ORDER#{}", placed_at))),
+                    ("Skey".to_string(), s("-")),
+                    ("GSI2Pkey".to_string(), s("ORDER#USER#a")),
+                    ("GSI2Skey".to_string(), s(placed_at)),
+                    ("status".to_string(), s(status)),
+                ]))
+                .await
+                .unwrap();
+        }
+        let placed_at = |page: Page<HashMap<String, AttributeValue>>| -> Vec<String> {
+            page.items
+                .iter()
+                .map(|item| item["GSI2Skey"].as_s().unwrap().clone())
+                .collect()
+        };
+        let query = IndexQuery::gsi(GSIs::GSI2, "ORDER#USER#a".to_string());
+
+        let page = repository
+            .query_index(
+                &query
+                    .clone()
+                    .sort_key(SortKeyCondition::GreaterThanOrEqual(s("200"))),
+                &PageRequest::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(placed_at(page), vec!["200", "300"]);
+
+        let page = repository
+            .query_index(
+                &query
+                    .clone()
+                    .sort_key(SortKeyCondition::Between(s("100"), s("200")))
+                    .descending(),
+                &PageRequest::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(placed_at(page), vec!["200", "100"]);
+
+        // status is a reserved word so it has to go through a name placeholder
+        let page = repository
+            .query_index(
+                &query
+                    .clone()
+                    .filter("#status = :status")
+                    .name("#status", "status")
+                    .value(":status", s("PLACED"))
+                    .project(&["GSI2Skey", "status"]),
+                &PageRequest::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(
+            page.items[0],
+            HashMap::from([
+                ("GSI2Skey".to_string(), s("100")),
+                ("status".to_string(), s("PLACED")),
+            ])
+        );
+
+        let found = repository
+            .get_item_index("ORDER#USER#a".to_string(), "300".to_string(), GSIs::GSI2)
+            .await
+            .unwrap();
+        assert_eq!(found.count, 1);
+    }
+
+    fn key(pkey: &str) -> HashMap<String, AttributeValue> {
+        HashMap::from([("Pkey".to_string(), s(pkey)), ("Skey".to_string(), s("-"))])
+    }
+
+    fn quick_retries(attempts: u32) -> BatchPolicy {
+        BatchPolicy {
+            max_attempts: attempts,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(2),
+            concurrency: 3,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_retries_unprocessed_entries() {
+        let mut repository = InMemorySingleTable::new()
+            .with_batch_capacity(7)
+            .repository("table");
+        repository.batch_policy = quick_retries(20);
+        let keys: Vec<_> = (0..130).map(|i| key(&format!("ITEM#{}", i))).collect();
+        let puts = keys
+            .iter()
+            .map(|key| {
+                WriteRequest::builder()
+                    .put_request(
+                        PutRequest::builder()
+                            .set_item(Some(key.clone()))
+                            .build()
+                            .unwrap(),
+                    )
+                    .build()
+            })
+            .collect();
+
+        repository.batch_write(puts).await.unwrap();
+        let items = repository.batch_get(keys).await.unwrap();
+        assert_eq!(items.len(), 130);
+    }
+
+    #[tokio::test]
+    async fn test_batch_reports_entries_left_unprocessed() {
+        let mut repository = InMemorySingleTable::new()
+            .with_batch_capacity(20)
+            .repository("table");
+        repository.batch_policy = quick_retries(1);
+        let keys: Vec<_> = (0..25).map(|i| key(&format!("ITEM#{}", i))).collect();
+        let deletes = keys
+            .iter()
+            .map(|key| {
+                WriteRequest::builder()
+                    .delete_request(
+                        DeleteRequest::builder()
+                            .set_key(Some(key.clone()))
+                            .build()
+                            .unwrap(),
+                    )
+                    .build()
+            })
+            .collect();
+
+        let err = repository.batch_write(deletes).await.unwrap_err();
+        let failed: Vec<_> = err
+            .failed
+            .iter()
+            .map(|write| write.delete_request().unwrap().key().clone())
+            .collect();
+        assert_eq!(failed, keys[20..].to_vec());
+        assert!(err.errors.is_empty());
+
+        let err = repository.batch_get(keys).await.unwrap_err();
+        assert_eq!(err.failed.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_cancelled_writes_nothing() {
+        let store = InMemorySingleTable::new();
+        let client = store.client();
+        client
+            .put_item()
+            .table_name("table")
+            .item("Pkey", s("USER#EMAIL#a@b.c"))
+            .item("Skey", s("-"))
+            .send()
+            .await
+            .unwrap();
+
+        let put = |pkey: &str| {
+            TransactWriteItem::builder()
+                .put(
+                    Put::builder()
+                        .table_name("table")
+                        .item("Pkey", s(pkey))
+                        .item("Skey", s("-"))
+                        .condition_expression("attribute_not_exists(Pkey)")
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+        };
+        let err = client
+            .transact_write_items()
+            .transact_items(put("USER#a"))
+            .transact_items(put("USER#EMAIL#a@b.c"))
+            .send()
+            .await
+            .unwrap_err()
+            .into_service_error();
+        match err {
+            aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError::TransactionCanceledException(cancelled) => {
+                let codes: Vec<Option<&str>> = cancelled
+                    .cancellation_reasons()
+                    .iter()
+                    .map(|reason| reason.code())
+                    .collect();
+                assert_eq!(codes, vec![Some("None"), Some("ConditionalCheckFailed")]);
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+
+        let user = client
+            .get_item()
+            .table_name("table")
+            .key("Pkey", s("USER#a"))
+            .key("Skey", s("-"))
+            .send()
+            .await
+            .unwrap();
+        assert!(user.item.is_none());
+    }
+}
@@ -0,0 +1,790 @@
+// Request handlers for the DynamoDB JSON protocol operations the repositories use. Each handler
+// takes the decoded request body and returns the response body, or an error in the shape the SDK
+// expects so that service errors (ConditionalCheckFailedException etc) surface exactly as they
+// would against DynamoDB.
+
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{json, Map, Value};
+
+use crate::attribute::{self, Item};
+use crate::expression::{self, Placeholders};
+use crate::table::{PrimaryKey, Table};
+
+const BATCH_WRITE_LIMIT: usize = 25;
+const BATCH_GET_LIMIT: usize = 100;
+const TRANSACTION_LIMIT: usize = 100;
+
+#[derive(Debug)]
+pub struct OperationError {
+    pub error_type: &'static str,
+    pub message: String,
+    pub extra: Map<String, Value>,
+}
+
+impl OperationError {
+    pub fn validation(message: impl Into<String>) -> Self {
+        OperationError {
+            error_type: "ValidationException",
+            message: message.into(),
+            extra: Map::new(),
+        }
+    }
+
+    fn resource_not_found() -> Self {
+        OperationError {
+            error_type: "ResourceNotFoundException",
+            message: "Requested resource not found".to_string(),
+            extra: Map::new(),
+        }
+    }
+
+    fn conditional_check_failed(item: Option<&Item>, return_old: bool) -> Self {
+        let mut extra = Map::new();
+        if let (Some(item), true) = (item, return_old) {
+            extra.insert("Item".to_string(), Value::Object(item.clone()));
+        }
+        OperationError {
+            error_type: "ConditionalCheckFailedException",
+            message: "The conditional request failed".to_string(),
+            extra,
+        }
+    }
+
+    pub fn into_body(self) -> Value {
+        let mut body = self.extra;
+        body.insert(
+            "__type".to_string(),
+            Value::String(format!(
+                "com.amazonaws.dynamodb.v20120810#{}",
+                self.error_type
+            )),
+        );
+        body.insert("message".to_string(), Value::String(self.message));
+        Value::Object(body)
+    }
+}
+
+impl From<String> for OperationError {
+    fn from(message: String) -> Self {
+        OperationError::validation(message)
+    }
+}
+
+pub type Tables = HashMap<String, Table>;
+
+type OperationResult = Result<Value, OperationError>;
+
+// `batch_capacity` caps how many entries a batch call processes, the rest are handed back as
+// unprocessed the way DynamoDB does when throttled
+pub fn dispatch(
+    tables: &mut Tables,
+    operation: &str,
+    request: &Value,
+    batch_capacity: Option<usize>,
+) -> OperationResult {
+    let mut response = match operation {
+        "GetItem" => get_item(tables, request),
+        "PutItem" => put_item(tables, request),
+        "UpdateItem" => update_item(tables, request),
+        "DeleteItem" => delete_item(tables, request),
+        "Query" => query(tables, request),
+        "Scan" => scan(tables, request),
+        "BatchGetItem" => batch_get_item(tables, request, batch_capacity),
+        "BatchWriteItem" => batch_write_item(tables, request, batch_capacity),
+        "TransactGetItems" => transact_get_items(tables, request),
+        "TransactWriteItems" => transact_write_items(tables, request),
+        other => Err(OperationError {
+            error_type: "UnknownOperationException",
+            message: format!(
+                "Operation {} is not supported by the in memory table",
+                other
+            ),
+            extra: Map::new(),
+        }),
+    }?;
+    if let Some(consumed) = consumed_capacity(operation, request, &response) {
+        response["ConsumedCapacity"] = consumed;
+    }
+    Ok(response)
+}
+
+// Only for requests asking with ReturnConsumedCapacity. DynamoDB prices by item size, here every
+// item is taken to fit in one unit, with reads eventually consistent and transactions double
+fn consumed_capacity(operation: &str, request: &Value, response: &Value) -> Option<Value> {
+    if !matches!(
+        string_field(request, "ReturnConsumedCapacity"),
+        Some("TOTAL") | Some("INDEXES")
+    ) {
+        return None;
+    }
+    let table_name = string_field(request, "TableName").unwrap_or_default();
+    let entry_count = |entries: &Value| entries.as_array().map_or(0, |entries| entries.len());
+    let mut units: HashMap<String, f64> = HashMap::new();
+    match operation {
+        "GetItem" => return Some(total(table_name, 0.5)),
+        "Query" | "Scan" => {
+            let scanned = response["ScannedCount"].as_u64().unwrap_or_default().max(1);
+            return Some(total(table_name, 0.5 * scanned as f64));
+        }
+        "PutItem" | "UpdateItem" | "DeleteItem" => return Some(total(table_name, 1.0)),
+        // batches are charged for what was processed, not what was handed back
+        "BatchGetItem" => {
+            for (table_name, keys) in request["RequestItems"].as_object()? {
+                let unprocessed = entry_count(&response["UnprocessedKeys"][table_name]["Keys"]);
+                let processed = entry_count(&keys["Keys"]) - unprocessed;
+                units.insert(table_name.clone(), 0.5 * processed as f64);
+            }
+        }
+        "BatchWriteItem" => {
+            for (table_name, writes) in request["RequestItems"].as_object()? {
+                let unprocessed = entry_count(&response["UnprocessedItems"][table_name]);
+                let processed = entry_count(writes) - unprocessed;
+                units.insert(table_name.clone(), processed as f64);
+            }
+        }
+        "TransactWriteItems" => {
+            for transact_item in request["TransactItems"].as_array()? {
+                let table_name = transact_item
+                    .as_object()
+                    .and_then(|operation| operation.values().next())
+                    .and_then(|operation| string_field(operation, "TableName"))
+                    .unwrap_or_default();
+                *units.entry(table_name.to_string()).or_default() += 2.0;
+            }
+        }
+        _ => return None,
+    }
+    let mut units: Vec<(String, f64)> = units.into_iter().collect();
+    units.sort_by(|a, b| a.0.cmp(&b.0));
+    Some(Value::Array(
+        units
+            .into_iter()
+            .map(|(table_name, units)| total(&table_name, units))
+            .collect(),
+    ))
+}
+
+fn total(table_name: &str, units: f64) -> Value {
+    json!({ "TableName": table_name, "CapacityUnits": units })
+}
+
+fn string_field<'a>(request: &'a Value, field: &str) -> Option<&'a str> {
+    request.get(field).and_then(|value| value.as_str())
+}
+
+fn required_string<'a>(request: &'a Value, field: &str) -> Result<&'a str, OperationError> {
+    string_field(request, field).ok_or(OperationError::validation(format!(
+        "1 validation error detected: Value null at '{}' failed to satisfy constraint: Member must not be null",
+        field
+    )))
+}
+
+fn map_field(request: &Value, field: &str) -> Result<Item, OperationError> {
+    request
+        .get(field)
+        .and_then(|value| value.as_object())
+        .cloned()
+        .ok_or(OperationError::validation(format!(
+            "1 validation error detected: Value null at '{}' failed to satisfy constraint: Member must not be null",
+            field
+        )))
+}
+
+// Tables are created on first use so the store needs no provisioning step
+fn table_mut<'a>(tables: &'a mut Tables, request: &Value) -> Result<&'a mut Table, OperationError> {
+    let name = required_string(request, "TableName")?;
+    Ok(tables.entry(name.to_string()).or_default())
+}
+
+fn lookup<'a>(
+    tables: &'a Tables,
+    request: &Value,
+) -> Result<(&'a Table, PrimaryKey), OperationError> {
+    let name = required_string(request, "TableName")?;
+    let key = map_field(request, "Key")?;
+    match tables.get(name) {
+        Some(table) => {
+            let primary_key = table.key_from_map(&key)?;
+            Ok((table, primary_key))
+        }
+        None => {
+            // validate the key against the default schema even for a table with no items yet
+            let empty = Table::default();
+            empty.key_from_map(&key)?;
+            Err(OperationError::resource_not_found())
+        }
+    }
+}
+
+fn condition_holds(
+    condition: Option<&str>,
+    placeholders: &Placeholders,
+    existing: Option<&Item>,
+) -> Result<bool, OperationError> {
+    match condition {
+        Some(condition) => {
+            let condition = expression::parse_condition(condition, placeholders)?;
+            Ok(expression::evaluate(
+                &condition,
+                existing.unwrap_or(&Map::new()),
+            ))
+        }
+        None => Ok(true),
+    }
+}
+
+fn placeholders(request: &Value) -> Result<Placeholders<'_>, OperationError> {
+    Ok(Placeholders::new(
+        request.get("ExpressionAttributeNames"),
+        request.get("ExpressionAttributeValues"),
+    )?)
+}
+
+fn projection(
+    request: &Value,
+    placeholders: &Placeholders,
+) -> Result<Option<Vec<expression::Path>>, OperationError> {
+    match string_field(request, "ProjectionExpression") {
+        Some(projection) => Ok(Some(expression::parse_projection(
+            projection,
+            placeholders,
+        )?)),
+        None => Ok(None),
+    }
+}
+
+fn return_old_on_failure(request: &Value) -> bool {
+    string_field(request, "ReturnValuesOnConditionCheckFailure") == Some("ALL_OLD")
+}
+
+fn get_item(tables: &mut Tables, request: &Value) -> OperationResult {
+    let placeholders = placeholders(request)?;
+    let projection = projection(request, &placeholders)?;
+    placeholders.check_all_used()?;
+    let (table, key) = match lookup(tables, request) {
+        Ok(found) => found,
+        Err(err) if err.error_type == "ResourceNotFoundException" => return Ok(json!({})),
+        Err(err) => return Err(err),
+    };
+    Ok(match table.items.get(&key) {
+        Some(item) => {
+            let item = match &projection {
+                Some(paths) => expression::project(item, paths),
+                None => item.clone(),
+            };
+            json!({ "Item": item })
+        }
+        None => json!({}),
+    })
+}
+
+struct PreparedPut {
+    key: PrimaryKey,
+    item: Item,
+}
+
+fn prepare_put(table: &Table, request: &Value, field: &str) -> Result<PreparedPut, OperationError> {
+    let item = attribute::normalise_item(&map_field(request, field)?)?;
+    let key = table.validate_item(&item)?;
+    Ok(PreparedPut { key, item })
+}
+
+fn put_item(tables: &mut Tables, request: &Value) -> OperationResult {
+    let placeholders = placeholders(request)?;
+    let table = table_mut(tables, request)?;
+    let put = prepare_put(table, request, "Item")?;
+    let existing = table.items.get(&put.key);
+    if !condition_holds(
+        string_field(request, "ConditionExpression"),
+        &placeholders,
+        existing,
+    )? {
+        return Err(OperationError::conditional_check_failed(
+            existing,
+            return_old_on_failure(request),
+        ));
+    }
+    placeholders.check_all_used()?;
+    let old = table.items.insert(put.key, put.item);
+    Ok(match (string_field(request, "ReturnValues"), old) {
+        (Some("ALL_OLD"), Some(old)) => json!({ "Attributes": old }),
+        _ => json!({}),
+    })
+}
+
+fn delete_item(tables: &mut Tables, request: &Value) -> OperationResult {
+    let placeholders = placeholders(request)?;
+    let table = table_mut(tables, request)?;
+    let key = table.key_from_map(&map_field(request, "Key")?)?;
+    let existing = table.items.get(&key);
+    if !condition_holds(
+        string_field(request, "ConditionExpression"),
+        &placeholders,
+        existing,
+    )? {
+        return Err(OperationError::conditional_check_failed(
+            existing,
+            return_old_on_failure(request),
+        ));
+    }
+    placeholders.check_all_used()?;
+    let old = table.items.remove(&key);
+    Ok(match (string_field(request, "ReturnValues"), old) {
+        (Some("ALL_OLD"), Some(old)) => json!({ "Attributes": old }),
+        _ => json!({}),
+    })
+}
+
+struct PreparedUpdate {
+    key: PrimaryKey,
+    old: Option<Item>,
+    new: Item,
+    touched: Vec<String>,
+}
+
+fn prepare_update(
+    table: &Table,
+    request: &Value,
+    placeholders: &Placeholders,
+) -> Result<Result<PreparedUpdate, OperationError>, OperationError> {
+    let key_map = map_field(request, "Key")?;
+    let key = table.key_from_map(&key_map)?;
+    let existing = table.items.get(&key);
+    let actions = match string_field(request, "UpdateExpression") {
+        Some(update) => expression::parse_update(update, placeholders)?,
+        None => Vec::new(),
+    };
+    if !condition_holds(
+        string_field(request, "ConditionExpression"),
+        placeholders,
+        existing,
+    )? {
+        return Ok(Err(OperationError::conditional_check_failed(
+            existing,
+            return_old_on_failure(request),
+        )));
+    }
+    placeholders.check_all_used()?;
+    // updating a missing item creates it from its key, like DynamoDB's upsert behaviour
+    let mut new = existing.cloned().unwrap_or(key_map);
+    let touched = expression::apply_update(&mut new, &actions)?;
+    let primary = &table.schema.primary;
+    if touched.contains(&primary.partition_key) || touched.contains(&primary.sort_key) {
+        return Err(OperationError::validation(
+            "One or more parameter values were invalid: Cannot update attribute Pkey. This attribute is part of the key",
+        ));
+    }
+    table.validate_item(&new)?;
+    Ok(Ok(PreparedUpdate {
+        key,
+        old: existing.cloned(),
+        new,
+        touched,
+    }))
+}
+
+fn only(item: &Item, attributes: &[String]) -> Item {
+    item.iter()
+        .filter(|(name, _)| attributes.contains(name))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+fn update_item(tables: &mut Tables, request: &Value) -> OperationResult {
+    let placeholders = placeholders(request)?;
+    let table = table_mut(tables, request)?;
+    let update = prepare_update(table, request, &placeholders)??;
+    table.items.insert(update.key, update.new.clone());
+    let attributes = match string_field(request, "ReturnValues") {
+        Some("ALL_NEW") => Some(update.new),
+        Some("UPDATED_NEW") => Some(only(&update.new, &update.touched)),
+        Some("ALL_OLD") => update.old,
+        Some("UPDATED_OLD") => update.old.map(|old| only(&old, &update.touched)),
+        _ => None,
+    };
+    Ok(match attributes {
+        Some(attributes) => json!({ "Attributes": attributes }),
+        None => json!({}),
+    })
+}
+
+fn limit(request: &Value) -> Result<Option<usize>, OperationError> {
+    match request.get("Limit") {
+        Some(limit) => match limit.as_u64() {
+            Some(limit) if limit > 0 => Ok(Some(limit as usize)),
+            _ => Err(OperationError::validation(
+                "1 validation error detected: Value at 'limit' failed to satisfy constraint: Member must have value greater than or equal to 1",
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
+fn page_response(
+    items: Vec<Item>,
+    scanned_count: usize,
+    last_evaluated_key: Option<Item>,
+    filter: Option<&expression::Condition>,
+    projection: Option<&Vec<expression::Path>>,
+) -> Value {
+    let items: Vec<Item> = items
+        .into_iter()
+        .filter(|item| filter.is_none_or(|filter| expression::evaluate(filter, item)))
+        .map(|item| match projection {
+            Some(paths) => expression::project(&item, paths),
+            None => item,
+        })
+        .collect();
+    let mut response = json!({
+        "Items": items,
+        "Count": items.len(),
+        "ScannedCount": scanned_count,
+    });
+    if let Some(key) = last_evaluated_key {
+        response["LastEvaluatedKey"] = Value::Object(key);
+    }
+    response
+}
+
+fn query(tables: &mut Tables, request: &Value) -> OperationResult {
+    let placeholders = placeholders(request)?;
+    let index_name = string_field(request, "IndexName");
+    let key_condition = expression::parse_condition(
+        required_string(request, "KeyConditionExpression")?,
+        &placeholders,
+    )?;
+    let filter = match string_field(request, "FilterExpression") {
+        Some(filter) => Some(expression::parse_condition(filter, &placeholders)?),
+        None => None,
+    };
+    let projection = projection(request, &placeholders)?;
+    placeholders.check_all_used()?;
+    let table = table_mut(tables, request)?;
+    let key_schema = table.schema.key_schema(index_name)?;
+    let partition_value = expression::find_equality(&key_condition, &key_schema.partition_key)
+        .ok_or(OperationError::validation(format!(
+            "Query condition missed key schema element: {}",
+            key_schema.partition_key
+        )))?;
+    let exclusive_start_key = request
+        .get("ExclusiveStartKey")
+        .and_then(|key| key.as_object());
+    let forward = request
+        .get("ScanIndexForward")
+        .and_then(|forward| forward.as_bool())
+        .unwrap_or(true);
+    let page = table.query(
+        index_name,
+        &partition_value,
+        forward,
+        exclusive_start_key,
+        limit(request)?,
+        |item| expression::evaluate(&key_condition, item),
+    )?;
+    Ok(page_response(
+        page.items,
+        page.scanned_count,
+        page.last_evaluated_key,
+        filter.as_ref(),
+        projection.as_ref(),
+    ))
+}
+
+fn scan(tables: &mut Tables, request: &Value) -> OperationResult {
+    let placeholders = placeholders(request)?;
+    let filter = match string_field(request, "FilterExpression") {
+        Some(filter) => Some(expression::parse_condition(filter, &placeholders)?),
+        None => None,
+    };
+    let projection = projection(request, &placeholders)?;
+    placeholders.check_all_used()?;
+    let segment = match (
+        request.get("Segment").and_then(|s| s.as_u64()),
+        request.get("TotalSegments").and_then(|s| s.as_u64()),
+    ) {
+        (Some(segment), Some(total)) if segment < total => Some((segment as usize, total as usize)),
+        (None, None) => None,
+        _ => {
+            return Err(OperationError::validation(
+                "The Segment parameter is required but was not present in the request when parameter TotalSegments is present",
+            ))
+        }
+    };
+    let table = table_mut(tables, request)?;
+    let exclusive_start_key = request
+        .get("ExclusiveStartKey")
+        .and_then(|key| key.as_object());
+    let page = table.scan(
+        string_field(request, "IndexName"),
+        segment,
+        exclusive_start_key,
+        limit(request)?,
+    )?;
+    Ok(page_response(
+        page.items,
+        page.scanned_count,
+        page.last_evaluated_key,
+        filter.as_ref(),
+        projection.as_ref(),
+    ))
+}
+
+fn batch_get_item(
+    tables: &mut Tables,
+    request: &Value,
+    batch_capacity: Option<usize>,
+) -> OperationResult {
+    let request_items = map_field(request, "RequestItems")?;
+    let key_count: usize = request_items
+        .values()
+        .map(|keys| keys["Keys"].as_array().map_or(0, |keys| keys.len()))
+        .sum();
+    if key_count > BATCH_GET_LIMIT {
+        return Err(OperationError::validation(
+            "Too many items requested for the BatchGetItem call",
+        ));
+    }
+    let mut capacity = batch_capacity.unwrap_or(usize::MAX);
+    let mut responses = Map::new();
+    let mut unprocessed_keys = Map::new();
+    for (table_name, keys_and_attributes) in request_items {
+        let placeholders = placeholders(&keys_and_attributes)?;
+        let projection = projection(&keys_and_attributes, &placeholders)?;
+        placeholders.check_all_used()?;
+        let table = tables.get(&table_name);
+        let mut seen = HashSet::new();
+        let mut items = Vec::new();
+        let mut unprocessed = Vec::new();
+        for key in keys_and_attributes["Keys"].as_array().into_iter().flatten() {
+            let key = key
+                .as_object()
+                .ok_or(OperationError::validation("Keys must be a list of maps"))?;
+            let primary_key = Table::default().key_from_map(key)?;
+            if !seen.insert(primary_key.clone()) {
+                return Err(OperationError::validation(
+                    "Provided list of item keys contains duplicates",
+                ));
+            }
+            if capacity == 0 {
+                unprocessed.push(Value::Object(key.clone()));
+                continue;
+            }
+            capacity -= 1;
+            if let Some(item) = table.and_then(|table| table.items.get(&primary_key)) {
+                items.push(Value::Object(match &projection {
+                    Some(paths) => expression::project(item, paths),
+                    None => item.clone(),
+                }));
+            }
+        }
+        if !unprocessed.is_empty() {
+            let mut retry = keys_and_attributes.clone();
+            retry["Keys"] = Value::Array(unprocessed);
+            unprocessed_keys.insert(table_name.clone(), retry);
+        }
+        responses.insert(table_name, Value::Array(items));
+    }
+    Ok(json!({ "Responses": responses, "UnprocessedKeys": unprocessed_keys }))
+}
+
+fn batch_write_item(
+    tables: &mut Tables,
+    request: &Value,
+    batch_capacity: Option<usize>,
+) -> OperationResult {
+    let request_items = map_field(request, "RequestItems")?;
+    let request_count: usize = request_items
+        .values()
+        .map(|requests| requests.as_array().map_or(0, |requests| requests.len()))
+        .sum();
+    if request_count > BATCH_WRITE_LIMIT {
+        return Err(OperationError::validation(
+            "1 validation error detected: Value at 'requestItems' failed to satisfy constraint: Map value must satisfy constraint: [Member must have length less than or equal to 25, Member must have length greater than or equal to 1]",
+        ));
+    }
+    // validate everything before applying anything, the batch is rejected as a whole on bad input
+    let mut writes: Vec<(String, PrimaryKey, Option<Item>, &Value)> = Vec::new();
+    let mut seen = HashSet::new();
+    for (table_name, requests) in &request_items {
+        let schema_table = Table::default();
+        for write in requests.as_array().into_iter().flatten() {
+            let (key, item) = if let Some(put) = write.get("PutRequest") {
+                let put = prepare_put(&schema_table, put, "Item")?;
+                (put.key, Some(put.item))
+            } else if let Some(delete) = write.get("DeleteRequest") {
+                (schema_table.key_from_map(&map_field(delete, "Key")?)?, None)
+            } else {
+                return Err(OperationError::validation(
+                    "Supplied write request must contain either a PutRequest or a DeleteRequest",
+                ));
+            };
+            if !seen.insert((table_name.clone(), key.clone())) {
+                return Err(OperationError::validation(
+                    "Provided list of item keys contains duplicates",
+                ));
+            }
+            writes.push((table_name.clone(), key, item, write));
+        }
+    }
+    let capacity = batch_capacity.unwrap_or(usize::MAX);
+    let mut unprocessed_items = Map::new();
+    for (table_name, _, _, write) in writes.iter().skip(capacity) {
+        unprocessed_items
+            .entry(table_name.clone())
+            .or_insert_with(|| Value::Array(Vec::new()))
+            .as_array_mut()
+            .expect("only arrays are inserted")
+            .push((*write).clone());
+    }
+    for (table_name, key, item, _) in writes.into_iter().take(capacity) {
+        let table = tables.entry(table_name).or_default();
+        match item {
+            Some(item) => table.items.insert(key, item),
+            None => table.items.remove(&key),
+        };
+    }
+    Ok(json!({ "UnprocessedItems": unprocessed_items }))
+}
+
+fn transact_get_items(tables: &mut Tables, request: &Value) -> OperationResult {
+    let transact_items = request["TransactItems"]
+        .as_array()
+        .ok_or(OperationError::validation("TransactItems must be a list"))?;
+    let mut responses = Vec::new();
+    for transact_item in transact_items {
+        let get = transact_item.get("Get").ok_or(OperationError::validation(
+            "TransactGetItem must contain a Get",
+        ))?;
+        let response = get_item(tables, get)?;
+        responses.push(match response.get("Item") {
+            Some(item) => json!({ "Item": item }),
+            None => json!({}),
+        });
+    }
+    Ok(json!({ "Responses": responses }))
+}
+
+enum TransactWrite {
+    Check,
+    Put(PrimaryKey, Item),
+    Delete(PrimaryKey),
+    Update(PrimaryKey, Item),
+}
+
+fn transact_write_items(tables: &mut Tables, request: &Value) -> OperationResult {
+    let transact_items = request["TransactItems"]
+        .as_array()
+        .ok_or(OperationError::validation("TransactItems must be a list"))?;
+    if transact_items.is_empty() || transact_items.len() > TRANSACTION_LIMIT {
+        return Err(OperationError::validation(format!(
+            "Member must have length less than or equal to {}",
+            TRANSACTION_LIMIT
+        )));
+    }
+
+    let mut seen = HashSet::new();
+    let mut writes = Vec::new();
+    let mut reasons = Vec::new();
+    let mut cancelled = false;
+
+    for transact_item in transact_items {
+        let (action, operation) = ["ConditionCheck", "Put", "Delete", "Update"]
+            .iter()
+            .find_map(|action| {
+                transact_item
+                    .get(*action)
+                    .map(|operation| (*action, operation))
+            })
+            .ok_or(OperationError::validation(
+                "TransactWriteItem must contain one of ConditionCheck, Put, Delete or Update",
+            ))?;
+        let table_name = required_string(operation, "TableName")?.to_string();
+        let table = tables.entry(table_name.clone()).or_default();
+        let placeholders = placeholders(operation)?;
+
+        let key = match action {
+            "Put" => prepare_put(table, operation, "Item")?.key,
+            _ => table.key_from_map(&map_field(operation, "Key")?)?,
+        };
+        if !seen.insert((table_name.clone(), key.clone())) {
+            return Err(OperationError::validation(
+                "Transaction request cannot include multiple operations on one item",
+            ));
+        }
+
+        let existing = table.items.get(&key);
+        let (holds, write) = match action {
+            "Update" => match prepare_update(table, operation, &placeholders)? {
+                Ok(update) => (true, TransactWrite::Update(update.key, update.new)),
+                Err(_) => (false, TransactWrite::Check),
+            },
+            _ => {
+                let holds = condition_holds(
+                    string_field(operation, "ConditionExpression"),
+                    &placeholders,
+                    existing,
+                )?;
+                placeholders.check_all_used()?;
+                let write = match action {
+                    "Put" => {
+                        let put = prepare_put(table, operation, "Item")?;
+                        TransactWrite::Put(put.key, put.item)
+                    }
+                    "Delete" => TransactWrite::Delete(key.clone()),
+                    _ => TransactWrite::Check,
+                };
+                (holds, write)
+            }
+        };
+
+        if holds {
+            reasons.push(json!({ "Code": "None" }));
+        } else {
+            cancelled = true;
+            let mut reason = json!({
+                "Code": "ConditionalCheckFailed",
+                "Message": "The conditional request failed",
+            });
+            if let (true, Some(existing)) = (return_old_on_failure(operation), existing) {
+                reason["Item"] = Value::Object(existing.clone());
+            }
+            reasons.push(reason);
+        }
+        writes.push((table_name, write));
+    }
+
+    if cancelled {
+        let codes: Vec<&str> = reasons
+            .iter()
+            .map(|reason| reason["Code"].as_str().unwrap_or_default())
+            .collect();
+        let mut extra = Map::new();
+        extra.insert(
+            "CancellationReasons".to_string(),
+            Value::Array(reasons.clone()),
+        );
+        return Err(OperationError {
+            error_type: "TransactionCanceledException",
+            message: format!(
+                "Transaction cancelled, please refer cancellation reasons for specific reasons [{}]",
+                codes.join(", ")
+            ),
+            extra,
+        });
+    }
+
+    for (table_name, write) in writes {
+        let table = tables.entry(table_name).or_default();
+        match write {
+            TransactWrite::Check => {}
+            TransactWrite::Put(key, item) | TransactWrite::Update(key, item) => {
+                table.items.insert(key, item);
+            }
+            TransactWrite::Delete(key) => {
+                table.items.remove(&key);
+            }
+        }
+    }
+    Ok(json!({}))
+}
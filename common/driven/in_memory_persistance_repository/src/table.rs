@@ -0,0 +1,301 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+use crate::attribute::{self, Item};
+
+// Mirrors infra/dynamodb_single_table.tf
+#[derive(Clone, Debug)]
+pub struct KeySchema {
+    pub name: Option<String>,
+    pub partition_key: String,
+    pub sort_key: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct TableSchema {
+    pub primary: KeySchema,
+    pub indexes: Vec<KeySchema>,
+}
+
+impl Default for TableSchema {
+    fn default() -> Self {
+        TableSchema {
+            primary: KeySchema {
+                name: None,
+                partition_key: "Pkey".to_string(),
+                sort_key: "Skey".to_string(),
+            },
+            indexes: ["GSI1", "GSI2"]
+                .iter()
+                .map(|index| KeySchema {
+                    name: Some(index.to_string()),
+                    partition_key: format!("{}Pkey", index),
+                    sort_key: format!("{}Skey", index),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TableSchema {
+    pub fn key_schema(&self, index_name: Option<&str>) -> Result<&KeySchema, String> {
+        match index_name {
+            None => Ok(&self.primary),
+            Some(index_name) => self
+                .indexes
+                .iter()
+                .find(|index| index.name.as_deref() == Some(index_name))
+                .ok_or(format!(
+                    "The table does not have the specified index: {}",
+                    index_name
+                )),
+        }
+    }
+}
+
+pub type PrimaryKey = (String, String);
+
+#[derive(Debug, Default)]
+pub struct Table {
+    pub schema: TableSchema,
+    pub items: BTreeMap<PrimaryKey, Item>,
+}
+
+pub struct Page {
+    pub items: Vec<Item>,
+    pub scanned_count: usize,
+    pub last_evaluated_key: Option<Item>,
+}
+
+impl Table {
+    pub fn primary_key(&self, key: &Item) -> Result<PrimaryKey, String> {
+        let schema = &self.schema.primary;
+        let extract = |attribute_name: &str| {
+            key.get(attribute_name)
+                .and_then(attribute::as_s)
+                .map(|value| value.to_string())
+                .ok_or(format!(
+                    "One or more parameter values were invalid: Missing the key {} in the item",
+                    attribute_name
+                ))
+        };
+        Ok((extract(&schema.partition_key)?, extract(&schema.sort_key)?))
+    }
+
+    // Key maps passed to GetItem/DeleteItem/UpdateItem must contain exactly the key attributes
+    pub fn key_from_map(&self, key: &Item) -> Result<PrimaryKey, String> {
+        if key.len() != 2 {
+            return Err("The provided key element does not match the schema".to_string());
+        }
+        self.primary_key(key)
+    }
+
+    pub fn validate_item(&self, item: &Item) -> Result<PrimaryKey, String> {
+        for index in &self.schema.indexes {
+            for key_attribute in [&index.partition_key, &index.sort_key] {
+                if let Some(value) = item.get(key_attribute) {
+                    match attribute::as_s(value) {
+                        Some("") => {
+                            return Err(format!(
+                                "One or more parameter values are not valid. A value specified for a secondary index key is not supported. The AttributeValue for a key attribute cannot contain an empty string value. IndexName: {}, IndexKey: {}",
+                                index.name.clone().unwrap_or_default(),
+                                key_attribute
+                            ))
+                        }
+                        Some(_) => {}
+                        None => {
+                            return Err(format!(
+                                "One or more parameter values were invalid: Type mismatch for Index Key {} Expected: S",
+                                key_attribute
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+        let key = self.primary_key(item)?;
+        if key.0.is_empty() || key.1.is_empty() {
+            return Err(
+                "One or more parameter values are not valid. The AttributeValue for a key attribute cannot contain an empty string value."
+                    .to_string(),
+            );
+        }
+        Ok(key)
+    }
+
+    pub fn key_attributes(&self, item: &Item, index_name: Option<&str>) -> Item {
+        let mut key = Map::new();
+        let mut names = vec![
+            &self.schema.primary.partition_key,
+            &self.schema.primary.sort_key,
+        ];
+        if let Ok(index) = self.schema.key_schema(index_name) {
+            names.push(&index.partition_key);
+            names.push(&index.sort_key);
+        }
+        for name in names {
+            if let Some(value) = item.get(name) {
+                key.insert(name.clone(), value.clone());
+            }
+        }
+        key
+    }
+
+    // Items are ordered by the index sort key, with the table key as a tie breaker so that
+    // pagination over a non unique index sort key is still deterministic
+    fn ordering(&self, schema: &KeySchema, left: &Item, right: &Item) -> Ordering {
+        let sort = attribute::compare(&left[&schema.sort_key], &right[&schema.sort_key])
+            .unwrap_or(Ordering::Equal);
+        sort.then_with(|| {
+            let primary = &self.schema.primary;
+            attribute::compare(
+                &left[&primary.partition_key],
+                &right[&primary.partition_key],
+            )
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| {
+                attribute::compare(&left[&primary.sort_key], &right[&primary.sort_key])
+                    .unwrap_or(Ordering::Equal)
+            })
+        })
+    }
+
+    fn in_index(schema: &KeySchema, item: &Item) -> bool {
+        item.contains_key(&schema.partition_key) && item.contains_key(&schema.sort_key)
+    }
+
+    // Everything after the exclusive start key, up to the limit, with a LastEvaluatedKey whenever
+    // the limit cut the page short
+    fn paginate(
+        &self,
+        index_name: Option<&str>,
+        candidates: Vec<&Item>,
+        is_after_start: impl Fn(&Item) -> bool,
+        has_start: bool,
+        limit: Option<usize>,
+    ) -> Page {
+        let start = if has_start {
+            candidates
+                .iter()
+                .position(|item| is_after_start(item))
+                .unwrap_or(candidates.len())
+        } else {
+            0
+        };
+        let remaining = &candidates[start..];
+        let take = limit.unwrap_or(usize::MAX).min(remaining.len());
+        let items: Vec<Item> = remaining[..take]
+            .iter()
+            .map(|item| (*item).clone())
+            .collect();
+        let last_evaluated_key = if take < remaining.len() {
+            items
+                .last()
+                .map(|item| self.key_attributes(item, index_name))
+        } else {
+            None
+        };
+        Page {
+            scanned_count: items.len(),
+            items,
+            last_evaluated_key,
+        }
+    }
+
+    pub fn query(
+        &self,
+        index_name: Option<&str>,
+        partition_value: &Value,
+        forward: bool,
+        exclusive_start_key: Option<&Item>,
+        limit: Option<usize>,
+        key_condition: impl Fn(&Item) -> bool,
+    ) -> Result<Page, String> {
+        let schema = self.schema.key_schema(index_name)?;
+        let mut candidates: Vec<&Item> = self
+            .items
+            .values()
+            .filter(|item| Self::in_index(schema, item))
+            .filter(|item| item.get(&schema.partition_key) == Some(partition_value))
+            .filter(|item| key_condition(item))
+            .collect();
+        candidates.sort_by(|left, right| self.ordering(schema, left, right));
+        let expected = if forward {
+            Ordering::Greater
+        } else {
+            candidates.reverse();
+            Ordering::Less
+        };
+        Ok(self.paginate(
+            index_name,
+            candidates,
+            |item| match exclusive_start_key {
+                Some(start) => self.ordering(schema, item, start) == expected,
+                None => true,
+            },
+            exclusive_start_key.is_some(),
+            limit,
+        ))
+    }
+
+    pub fn scan(
+        &self,
+        index_name: Option<&str>,
+        segment: Option<(usize, usize)>,
+        exclusive_start_key: Option<&Item>,
+        limit: Option<usize>,
+    ) -> Result<Page, String> {
+        let schema = self.schema.key_schema(index_name)?;
+        let mut candidates: Vec<&Item> = self
+            .items
+            .values()
+            .filter(|item| Self::in_index(schema, item))
+            .filter(|item| match segment {
+                Some((segment, total)) => {
+                    let partition = item[&schema.partition_key].to_string();
+                    segment_of(&partition, total) == segment
+                }
+                None => true,
+            })
+            .collect();
+        // scans walk partitions in a stable order, then each partition in sort key order
+        candidates.sort_by(|left, right| {
+            left[&schema.partition_key]
+                .to_string()
+                .cmp(&right[&schema.partition_key].to_string())
+                .then_with(|| self.ordering(schema, left, right))
+        });
+        let partition_of = |item: &Item| {
+            item.get(&schema.partition_key)
+                .map(|value| value.to_string())
+                .unwrap_or_default()
+        };
+        Ok(self.paginate(
+            index_name,
+            candidates,
+            |item| match exclusive_start_key {
+                Some(start) => {
+                    partition_of(item)
+                        .cmp(&partition_of(start))
+                        .then_with(|| self.ordering(schema, item, start))
+                        == Ordering::Greater
+                }
+                None => true,
+            },
+            exclusive_start_key.is_some(),
+            limit,
+        ))
+    }
+}
+
+// FNV-1a, so parallel scan segments are stable between runs
+fn segment_of(partition: &str, total_segments: usize) -> usize {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in partition.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    (hash % total_segments as u64) as usize
+}
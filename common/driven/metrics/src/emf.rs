@@ -0,0 +1,126 @@
+// CloudWatch Embedded Metric Format
+// Lambda ships stdout to CloudWatch Logs, which turns each EMF line into metrics, so publishing
+// needs no client and adds no latency to the call being measured.
+// https://docs.aws.amazon.com/AmazonCloudWatch/latest/monitoring/CloudWatch_Embedded_Metric_Format_Specification.html
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use app_config::AppConfig;
+use serde_json::json;
+
+use crate::{MetricsPort, OperationMetrics};
+
+pub struct EmfMetricsAdaptor {
+    namespace: String,
+}
+
+impl EmfMetricsAdaptor {
+    pub fn new(config: &AppConfig) -> EmfMetricsAdaptor {
+        EmfMetricsAdaptor {
+            namespace: config.metrics_namespace.clone(),
+        }
+    }
+
+    // One log line, dimensioned by operation
+    pub fn render(&self, metrics: &OperationMetrics, timestamp_millis: u128) -> String {
+        json!({
+            "_aws": {
+                "Timestamp": timestamp_millis as u64,
+                "CloudWatchMetrics": [{
+                    "Namespace": self.namespace,
+                    "Dimensions": [["Operation"]],
+                    "Metrics": [
+                        {"Name": "ReadCapacityUnits", "Unit": "Count"},
+                        {"Name": "WriteCapacityUnits", "Unit": "Count"},
+                        {"Name": "Latency", "Unit": "Milliseconds"},
+                    ],
+                }],
+            },
+            "Operation": metrics.operation,
+            "ReadCapacityUnits": metrics.read_capacity_units,
+            "WriteCapacityUnits": metrics.write_capacity_units,
+            "Latency": metrics.latency.as_secs_f64() * 1000.0,
+        })
+        .to_string()
+    }
+
+    // A count of one, summed per operation by CloudWatch
+    pub fn render_skipped_item(&self, operation: &str, timestamp_millis: u128) -> String {
+        json!({
+            "_aws": {
+                "Timestamp": timestamp_millis as u64,
+                "CloudWatchMetrics": [{
+                    "Namespace": self.namespace,
+                    "Dimensions": [["Operation"]],
+                    "Metrics": [{"Name": "SkippedItems", "Unit": "Count"}],
+                }],
+            },
+            "Operation": operation,
+            "SkippedItems": 1,
+        })
+        .to_string()
+    }
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis())
+        .unwrap_or_default()
+}
+
+impl MetricsPort for EmfMetricsAdaptor {
+    fn record(&self, metrics: &OperationMetrics) {
+        println!("{}", self.render(metrics, now_millis()));
+    }
+
+    fn record_skipped_item(&self, operation: &str) {
+        println!("{}", self.render_skipped_item(operation, now_millis()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_render_is_one_emf_line() {
+        let adaptor = EmfMetricsAdaptor::new(&AppConfig::default());
+        let line = adaptor.render(
+            &OperationMetrics {
+                operation: "cart_clear.query".to_string(),
+                read_capacity_units: 0.5,
+                write_capacity_units: 0.0,
+                latency: Duration::from_micros(12_500),
+            },
+            1_700_000_000_000,
+        );
+        assert!(!line.contains('\n'));
+
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["_aws"]["Timestamp"], 1_700_000_000_000u64);
+        assert_eq!(
+            value["_aws"]["CloudWatchMetrics"][0]["Namespace"],
+            app_config::DEFAULT_METRICS_NAMESPACE
+        );
+        assert_eq!(value["Operation"], "cart_clear.query");
+        assert_eq!(value["ReadCapacityUnits"], 0.5);
+        assert_eq!(value["Latency"], 12.5);
+    }
+
+    #[test]
+    fn test_render_skipped_item() {
+        let adaptor = EmfMetricsAdaptor::new(&AppConfig::default());
+        let line = adaptor.render_skipped_item("cart_get.decode", 1_700_000_000_000);
+
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            value["_aws"]["CloudWatchMetrics"][0]["Metrics"][0]["Name"],
+            "SkippedItems"
+        );
+        assert_eq!(value["Operation"], "cart_get.decode");
+        assert_eq!(value["SkippedItems"], 1);
+    }
+}
@@ -0,0 +1,47 @@
+// Keeps every record so tests can assert on what an adaptor cost
+use std::sync::Mutex;
+
+use crate::{MetricsPort, OperationMetrics};
+
+#[derive(Default)]
+pub struct InMemoryMetricsAdaptor {
+    records: Mutex<Vec<OperationMetrics>>,
+    skipped_items: Mutex<Vec<String>>,
+}
+
+impl InMemoryMetricsAdaptor {
+    pub fn new() -> InMemoryMetricsAdaptor {
+        InMemoryMetricsAdaptor::default()
+    }
+
+    // In the order they were recorded
+    pub fn records(&self) -> Vec<OperationMetrics> {
+        self.records.lock().unwrap().clone()
+    }
+
+    // The operation names recorded, e.g. ["user_create.transact"]
+    pub fn operations(&self) -> Vec<String> {
+        self.records()
+            .into_iter()
+            .map(|metrics| metrics.operation)
+            .collect()
+    }
+
+    // The operation of each item a read left out
+    pub fn skipped_items(&self) -> Vec<String> {
+        self.skipped_items.lock().unwrap().clone()
+    }
+}
+
+impl MetricsPort for InMemoryMetricsAdaptor {
+    fn record(&self, metrics: &OperationMetrics) {
+        self.records.lock().unwrap().push(metrics.clone());
+    }
+
+    fn record_skipped_item(&self, operation: &str) {
+        self.skipped_items
+            .lock()
+            .unwrap()
+            .push(operation.to_string());
+    }
+}
@@ -0,0 +1,27 @@
+pub mod emf;
+pub mod in_memory;
+
+use std::time::Duration;
+
+use mockall::automock;
+
+pub use emf::EmfMetricsAdaptor;
+pub use in_memory::InMemoryMetricsAdaptor;
+
+// What one call to the table cost. Named for the logical operation that made it, e.g.
+// cart_clear.query or user_create.transact, so each access pattern can be priced on its own
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationMetrics {
+    pub operation: String,
+    pub read_capacity_units: f64,
+    pub write_capacity_units: f64,
+    pub latency: Duration,
+}
+
+// Recording happens on the request path, so adaptors must not block on anything slow
+#[automock]
+pub trait MetricsPort: Send + Sync {
+    fn record(&self, metrics: &OperationMetrics);
+    // An item a read had to leave out because it could not be decoded
+    fn record_skipped_item(&self, operation: &str);
+}
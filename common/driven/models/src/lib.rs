@@ -1,7 +1,17 @@
 pub mod models;
+pub mod soft_delete;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::types::{
+    AttributeValue, Delete, Put, ReturnValuesOnConditionCheckFailure, TransactWriteItem,
+};
+use error::HexagonalError;
+use persistance_repository::{Condition, DynamoDBSingleTableRepository};
+// the derive shares the trait's name, so `use crate::DynamoDbModel` brings in both
+use models_derive::DynamoDbModel;
 
 pub fn default_time() -> String {
     let start = SystemTime::now();
@@ -15,8 +25,361 @@ pub fn new_uuid() -> String {
     uuid::Uuid::new_v4().to_string()
 }
 
+// Models are written at version 1 and every write bumps it. Items stored before versioning read
+// as version 0
+pub fn initial_version() -> u64 {
+    1
+}
+
 trait DynamoDbModel {
     fn into_attr_map(&self) -> std::collections::HashMap<String, AttributeValue>;
 
-    fn from_attr_map(attr_map: std::collections::HashMap<String, AttributeValue>) -> Self;
+    fn from_attr_map(
+        attr_map: std::collections::HashMap<String, AttributeValue>,
+    ) -> Result<Self, DecodeError>
+    where
+        Self: Sized;
+}
+
+// Why a stored item could not be read back into a model, naming the attribute and the item's key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    Missing {
+        attribute: String,
+        key: String,
+    },
+    WrongType {
+        attribute: String,
+        expected: &'static str,
+        key: String,
+    },
+    Unparsable {
+        attribute: String,
+        value: String,
+        key: String,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Missing { attribute, key } => {
+                write!(f, "Item {} is missing attribute {}", key, attribute)
+            }
+            DecodeError::WrongType {
+                attribute,
+                expected,
+                key,
+            } => write!(
+                f,
+                "Item {} has attribute {} which is not of type {}",
+                key, attribute, expected
+            ),
+            DecodeError::Unparsable {
+                attribute,
+                value,
+                key,
+            } => write!(
+                f,
+                "Item {} has attribute {} with unparsable value {}",
+                key, attribute, value
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl DecodeError {
+    pub fn key(&self) -> &str {
+        match self {
+            DecodeError::Missing { key, .. }
+            | DecodeError::WrongType { key, .. }
+            | DecodeError::Unparsable { key, .. } => key,
+        }
+    }
+}
+
+impl From<DecodeError> for HexagonalError {
+    fn from(err: DecodeError) -> Self {
+        HexagonalError {
+            error: error::HexagonalErrorCode::AdaptorError,
+            message: "Unable to read stored item".to_string(),
+            trace: err.to_string(),
+        }
+    }
+}
+
+// List and batch reads leave out items they can't decode, so bad data costs the caller that one
+// item rather than the whole request. Each one is logged and counted so it still gets noticed
+fn decode_or_skip<'a, T: 'a>(
+    repository: &'a DynamoDBSingleTableRepository,
+    decode: fn(HashMap<String, AttributeValue>) -> Result<T, DecodeError>,
+) -> impl FnMut(HashMap<String, AttributeValue>) -> Option<T> + 'a {
+    move |item| match decode(item) {
+        Ok(model) => Some(model),
+        Err(err) => {
+            tracing::warn!(key = err.key(), error = %err, "Skipping unreadable item");
+            repository.record_skipped_item();
+            None
+        }
+    }
+}
+
+// Typed access to the attributes of a stored item for from_attr_map
+struct AttrReader<'a> {
+    attr_map: &'a HashMap<String, AttributeValue>,
+}
+
+impl<'a> AttrReader<'a> {
+    fn new(attr_map: &'a HashMap<String, AttributeValue>) -> Self {
+        Self { attr_map }
+    }
+
+    fn key(&self) -> String {
+        let key_part = |name: &str| {
+            self.attr_map
+                .get(name)
+                .and_then(|value| value.as_s().ok())
+                .map(|value| value.to_string())
+                .unwrap_or("?".to_string())
+        };
+        format!("(Pkey: {}, Skey: {})", key_part("Pkey"), key_part("Skey"))
+    }
+
+    fn get(&self, attribute: &str) -> Result<&'a AttributeValue, DecodeError> {
+        self.attr_map
+            .get(attribute)
+            .ok_or_else(|| DecodeError::Missing {
+                attribute: attribute.to_string(),
+                key: self.key(),
+            })
+    }
+
+    fn has(&self, attribute: &str) -> bool {
+        self.attr_map.contains_key(attribute)
+    }
+
+    fn wrong_type(&self, attribute: &str, expected: &'static str) -> DecodeError {
+        DecodeError::WrongType {
+            attribute: attribute.to_string(),
+            expected,
+            key: self.key(),
+        }
+    }
+
+    fn string(&self, attribute: &str) -> Result<String, DecodeError> {
+        self.get(attribute)?
+            .as_s()
+            .map(|value| value.to_string())
+            .map_err(|_| self.wrong_type(attribute, "S"))
+    }
+
+    // Numbers we keep as strings, e.g. timestamps
+    fn number(&self, attribute: &str) -> Result<String, DecodeError> {
+        self.get(attribute)?
+            .as_n()
+            .map(|value| value.to_string())
+            .map_err(|_| self.wrong_type(attribute, "N"))
+    }
+
+    fn parse_number<T: FromStr>(&self, attribute: &str) -> Result<T, DecodeError> {
+        let value = self.number(attribute)?;
+        value.parse().map_err(|_| DecodeError::Unparsable {
+            attribute: attribute.to_string(),
+            value,
+            key: self.key(),
+        })
+    }
+}
+
+// Condition that the stored item still holds every attribute it had when it was read, so anything
+// written between our read and our transaction cancels it rather than being overwritten. The
+// cancellation carries the item as it now is, which is none at all if it was deleted
+fn unchanged_since_read(read: &HashMap<String, AttributeValue>) -> Condition {
+    let mut attribute_names: Vec<&String> = read.keys().collect();
+    attribute_names.sort();
+
+    let conditions = attribute_names
+        .into_iter()
+        .map(|attribute_name| Condition::equals(attribute_name, read[attribute_name].clone()))
+        .collect();
+    Condition::And(conditions)
+}
+
+pub(crate) fn put_if_unchanged(
+    table_name: &str,
+    read: &HashMap<String, AttributeValue>,
+    item: HashMap<String, AttributeValue>,
+) -> TransactWriteItem {
+    let expression = unchanged_since_read(read).build();
+    let put = Put::builder()
+        .table_name(table_name)
+        .set_item(Some(item))
+        .set_condition_expression(expression.condition.clone())
+        .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+        .set_expression_attribute_names(expression.attribute_names())
+        .set_expression_attribute_values(expression.attribute_values())
+        .build()
+        .unwrap(); // table name and item is always set so unwrap is safe
+    TransactWriteItem::builder().put(put).build()
+}
+
+pub(crate) fn delete_if_unchanged(
+    table_name: &str,
+    read: &HashMap<String, AttributeValue>,
+) -> TransactWriteItem {
+    let expression = unchanged_since_read(read).build();
+    let delete = Delete::builder()
+        .table_name(table_name)
+        .key("Pkey", read["Pkey"].clone())
+        .key("Skey", read["Skey"].clone())
+        .set_condition_expression(expression.condition.clone())
+        .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+        .set_expression_attribute_names(expression.attribute_names())
+        .set_expression_attribute_values(expression.attribute_values())
+        .build()
+        .unwrap(); // Key is always set so unwrap is safe
+    TransactWriteItem::builder().delete(delete).build()
+}
+
+// An If-Match that doesn't match what we read fails before anything is written
+pub fn check_expected_version(
+    expected_version: Option<u64>,
+    stored_version: u64,
+    message: &str,
+) -> Result<(), HexagonalError> {
+    match expected_version {
+        Some(expected) if expected != stored_version => Err(HexagonalError {
+            error: error::HexagonalErrorCode::PreconditionFailed,
+            message: message.to_string(),
+            trace: format!("expected version {}, found {}", expected, stored_version),
+        }),
+        _ => Ok(()),
+    }
+}
+
+// A write cancelled because the item changed after we read it. When the caller asked for a
+// version that has now moved on it is their precondition that failed, otherwise it's a conflict
+pub fn changed_since_read_error(
+    expected_version: Option<u64>,
+    message: &str,
+    trace: String,
+) -> HexagonalError {
+    HexagonalError {
+        error: match expected_version {
+            Some(_) => error::HexagonalErrorCode::PreconditionFailed,
+            None => error::HexagonalErrorCode::Conflict,
+        },
+        message: message.to_string(),
+        trace,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, DynamoDbModel)]
+    #[dynamo(
+        pkey = "ORDER#{user_id}",
+        skey = "ORDER#{placed_at}#{order_id}",
+        gsi2_pkey = "ORDER#STATUS#{status}",
+        gsi2_skey = "{placed_at}"
+    )]
+    struct Order {
+        order_id: String,
+        user_id: String,
+        status: String,
+        total_cents: i64,
+        #[dynamo(n)]
+        placed_at: String,
+    }
+
+    #[test]
+    fn test_derived_model_keys_and_round_trip() {
+        let order = Order {
+            order_id: "o1".to_string(),
+            user_id: "u1".to_string(),
+            status: "PLACED".to_string(),
+            total_cents: 1250,
+            placed_at: "1700000000".to_string(),
+        };
+
+        assert_eq!(Order::pkey("u1"), "ORDER#u1");
+        assert_eq!(Order::skey("1700000000", "o1"), "ORDER#1700000000#o1");
+        assert_eq!(
+            Order::primary_key("u1", "1700000000", "o1"),
+            HashMap::from([
+                (
+                    "Pkey".to_string(),
+                    AttributeValue::S("ORDER#u1".to_string())
+                ),
+                (
+                    "Skey".to_string(),
+                    AttributeValue::S("ORDER#1700000000#o1".to_string())
+                ),
+            ])
+        );
+
+        let attr_map = order.into_attr_map();
+        assert_eq!(
+            attr_map["GSI2Pkey"],
+            AttributeValue::S("ORDER#STATUS#PLACED".to_string())
+        );
+        assert_eq!(
+            attr_map["GSI2Skey"],
+            AttributeValue::S("1700000000".to_string())
+        );
+        assert_eq!(
+            attr_map["total_cents"],
+            AttributeValue::N("1250".to_string())
+        );
+        assert_eq!(
+            attr_map["placed_at"],
+            AttributeValue::N("1700000000".to_string())
+        );
+        assert!(!attr_map.contains_key("GSI1Pkey"));
+        assert_eq!(Order::from_attr_map(attr_map), Ok(order));
+    }
+
+    #[derive(Debug, PartialEq, DynamoDbModel)]
+    #[dynamo(pkey = "NOTE#{note_id}", skey = "-")]
+    struct Note {
+        note_id: String,
+        #[dynamo(default)]
+        version: u64,
+    }
+
+    #[test]
+    fn test_default_field_reads_missing_attribute_as_default() {
+        let mut attr_map = Note {
+            note_id: "n1".to_string(),
+            version: 3,
+        }
+        .into_attr_map();
+        assert_eq!(Note::from_attr_map(attr_map.clone()).unwrap().version, 3);
+
+        attr_map.remove("version");
+        assert_eq!(Note::from_attr_map(attr_map.clone()).unwrap().version, 0);
+
+        // present but malformed is still an error
+        attr_map.insert("version".to_string(), AttributeValue::S("3".to_string()));
+        assert!(matches!(
+            Note::from_attr_map(attr_map),
+            Err(DecodeError::WrongType { .. })
+        ));
+    }
+
+    #[test]
+    fn test_check_expected_version() {
+        assert!(check_expected_version(None, 4, "stale").is_ok());
+        assert!(check_expected_version(Some(4), 4, "stale").is_ok());
+        assert_eq!(
+            check_expected_version(Some(3), 4, "stale")
+                .unwrap_err()
+                .error,
+            error::HexagonalErrorCode::PreconditionFailed
+        );
+    }
 }
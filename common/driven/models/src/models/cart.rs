@@ -5,35 +5,68 @@
 // 4. Update quantity of Product in Cart by user_id, product_id, quantity
 // 5. Delete Cart by user_id
 // 6. Remove product from all carts by product_id
+// 7. Get the history of a user's cart, see history
 
 // Model:
 // Pkey = CART#USER#<user_id>
 // Skey = CART#PRODUCT#<product_id>
 // GSI1-Pkey = CART#PRODUCT#<product_id>
 // GSI1-Skey = CART#USER#<user_id>
+// Both partition keys are the tenant's, see persistance_repository::tenant
+// History records sit alongside the items, Skey = HISTORY#<changed_at>#<product_id>#<version>
 
-// Assumptions:
-// 1. A single cart does not exceed 1MB of data
+// Carts are read a page at a time, see persistance_repository::pagination
 
-use std::{collections::HashMap, error::Error};
+// Cart items carry a TTL in TimeToExist. Every add, update or remove pushes it back on each item
+// left in the cart, so a cart in use expires as a whole rather than item by item. Once it passes
+// DynamoDB deletes the item and cart_expired picks the removal up from the stream. History records
+// carry it too and are pushed back along with the items, so a cart's history goes with the cart.
+// DynamoDB can take a while to get round to deleting, items past their expiry read as gone
+pub const CART_EXPIRY_ATTRIBUTE: &str = "TimeToExist";
 
-use crate::{default_time, DynamoDbModel};
+// Transactions are capped at 100 actions, one is taken by the outbox item and each removed item
+// takes two, its delete and its history record
+const CART_CLEAR_CHUNK_SIZE: usize = 49;
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::history::{history_query, HistoryAction, HistoryRecord};
+use super::outbox::OutboxEvent;
+use crate::{
+    changed_since_read_error, check_expected_version, decode_or_skip, default_time,
+    delete_if_unchanged, initial_version, put_if_unchanged, DecodeError, DynamoDbModel,
+};
 use async_trait::async_trait;
-use aws_sdk_dynamodb::types::{AttributeValue, WriteRequest};
+use aws_sdk_dynamodb::types::{AttributeValue, Put, PutRequest, TransactWriteItem, WriteRequest};
 use error::HexagonalError;
 use mockall::automock;
-use persistance_repository::DynamoDBSingleTableRepository;
+use persistance_repository::{
+    CancellationCode, DynamoDBSingleTableRepository, GSIs, IndexQuery, Page, PageRequest,
+    SortKeyCondition, Tenant, TransactionError, UpdateExpression,
+};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, DynamoDbModel)]
+#[dynamo(
+    pkey = "CART#USER#{user_id}",
+    skey = "CART#PRODUCT#{product_id}",
+    gsi1_pkey = "CART#PRODUCT#{product_id}",
+    gsi1_skey = "CART#USER#{user_id}"
+)]
 pub struct CartItem {
     pub product_id: String,
     pub user_id: String,
     pub quantity: u32,
     #[serde(default = "default_time")]
+    #[dynamo(n)]
     pub created_at: String,
     #[serde(default = "default_time")]
+    #[dynamo(n)]
     pub updated_at: String,
+    #[serde(default = "initial_version")]
+    #[dynamo(default)]
+    pub version: u64,
 }
 
 impl CartItem {
@@ -44,200 +77,220 @@ impl CartItem {
             quantity,
             created_at: default_time(),
             updated_at: default_time(),
+            version: initial_version(),
         }
     }
-}
 
-impl DynamoDbModel for CartItem {
-    fn from_attr_map(attr_map: std::collections::HashMap<String, AttributeValue>) -> Self {
+    // The item as its removal leaves it, for the history record of the removal
+    fn removed(&self) -> CartItem {
         CartItem {
-            product_id: attr_map
-                .get("product_id")
-                .unwrap()
-                .as_s()
-                .unwrap()
-                .to_string(),
-            user_id: attr_map.get("user_id").unwrap().as_s().unwrap().to_string(),
-            quantity: attr_map
-                .get("quantity")
-                .unwrap()
-                .as_n()
-                .unwrap()
-                .parse::<u32>()
-                .unwrap(),
-            created_at: attr_map
-                .get("created_at")
-                .unwrap()
-                .as_n()
-                .unwrap()
-                .to_string(),
-            updated_at: attr_map
-                .get("updated_at")
-                .unwrap()
-                .as_n()
-                .unwrap()
-                .to_string(),
+            updated_at: default_time(),
+            version: self.version + 1,
+            ..self.clone()
         }
     }
 
-    fn into_attr_map(&self) -> std::collections::HashMap<String, AttributeValue> {
-        let mut attr_map = std::collections::HashMap::new();
-        attr_map.insert(
-            "Pkey".to_string(),
-            AttributeValue::S("CART#USER#".to_string() + &self.user_id.to_string()),
-        );
-        attr_map.insert(
-            "Skey".to_string(),
-            AttributeValue::S("CART#PRODUCT#".to_string() + &self.product_id.to_string()),
-        );
-        attr_map.insert(
-            "GSI1Pkey".to_string(),
-            AttributeValue::S("CART#PRODUCT#".to_string() + &self.product_id.to_string()),
-        );
-        attr_map.insert(
-            "GSI1Skey".to_string(),
-            AttributeValue::S("CART#USER#".to_string() + &self.user_id.to_string()),
-        );
-        attr_map.insert(
-            "product_id".to_string(),
-            AttributeValue::S(self.product_id.to_string()),
-        );
-        attr_map.insert(
-            "user_id".to_string(),
-            AttributeValue::S(self.user_id.to_string()),
-        );
-        attr_map.insert(
-            "quantity".to_string(),
-            AttributeValue::N(self.quantity.to_string()),
-        );
-        attr_map.insert(
-            "created_at".to_string(),
-            AttributeValue::N(self.created_at.to_string()),
-        );
-        attr_map.insert(
-            "updated_at".to_string(),
-            AttributeValue::N(self.updated_at.to_string()),
-        );
-        attr_map
+    // For readers outside the adaptor, e.g. table stream records, which see every item type of
+    // every tenant, the cart's history records included
+    pub fn from_item(
+        item: HashMap<String, AttributeValue>,
+    ) -> Option<Result<(Tenant, CartItem), DecodeError>> {
+        let (tenant, p_key) = Tenant::of_key(item.get("Pkey")?.as_s().ok()?);
+        let s_key = item.get("Skey")?.as_s().ok()?;
+        match p_key.starts_with("CART#USER#") && s_key.starts_with("CART#PRODUCT#") {
+            true => Some(CartItem::from_attr_map(item).map(|cart_item| (tenant, cart_item))),
+            false => None,
+        }
     }
 }
 
 #[automock]
 #[async_trait]
 pub trait CartRepositoryPort {
-    async fn cart_get_by_user_id(&self, user_id: &String) -> Result<Vec<CartItem>, HexagonalError>;
-    async fn cart_add_item(&self, item: &CartItem) -> Result<CartItem, HexagonalError>;
+    async fn cart_get_by_user_id(
+        &self,
+        user_id: &String,
+        page: &PageRequest,
+    ) -> Result<Page<CartItem>, HexagonalError>;
+    async fn cart_add_item(
+        &self,
+        item: &CartItem,
+        principal: &str,
+        outbox_event: OutboxEvent<CartItem>,
+    ) -> Result<CartItem, HexagonalError>;
     async fn cart_remove_item(
         &self,
         user_id: &String,
         product_id: &String,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<CartItem>,
     ) -> Result<CartItem, HexagonalError>;
     async fn cart_update_item(
         &self,
         user_id: &String,
         product_id: &String,
         quantity: u32,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<CartItem>,
     ) -> Result<CartItem, HexagonalError>;
-    async fn cart_clear(&self, user_id: &String) -> Result<Vec<CartItem>, HexagonalError>;
+    // Each chunk of removed items is written with its own event, so a large cart emits several
+    async fn cart_clear(
+        &self,
+        user_id: &String,
+        principal: &str,
+        outbox_event: OutboxEvent<[CartItem]>,
+    ) -> Result<Vec<CartItem>, HexagonalError>;
     async fn cart_global_remove_product(
         &self,
         product_id: &String,
+        principal: &str,
     ) -> Result<(), Vec<HexagonalError>>;
+    // Every write to any item of the cart, newest first
+    async fn cart_history_by_user_id(
+        &self,
+        user_id: &String,
+        page: &PageRequest,
+    ) -> Result<Page<HistoryRecord>, HexagonalError>;
 }
 
 pub struct CartRepositoryAdaptor<'a> {
     persistance_repository: &'a DynamoDBSingleTableRepository,
+    expiry: Duration,
 }
 
 impl<'a> CartRepositoryAdaptor<'a> {
+    // The expiry comes from the repository's config
     pub fn new(
         persistance_repository: &'a DynamoDBSingleTableRepository,
     ) -> CartRepositoryAdaptor<'a> {
         CartRepositoryAdaptor {
             persistance_repository,
+            expiry: persistance_repository.config.limits.cart_expiry,
         }
     }
+
+    pub fn with_expiry(self, expiry: Duration) -> CartRepositoryAdaptor<'a> {
+        CartRepositoryAdaptor { expiry, ..self }
+    }
 }
 
 #[async_trait]
 impl<'a> CartRepositoryPort for CartRepositoryAdaptor<'a> {
-    async fn cart_get_by_user_id(&self, user_id: &String) -> Result<Vec<CartItem>, HexagonalError> {
-        let query_expression = "Pkey = :pk AND begins_with(Skey, :sk)";
+    async fn cart_get_by_user_id(
+        &self,
+        user_id: &String,
+        page: &PageRequest,
+    ) -> Result<Page<CartItem>, HexagonalError> {
+        let now = now();
+        Ok(self
+            .persistance_repository
+            .query_index(&self.user_cart_query(user_id), page)
+            .await
+            .map_err(|e| e.into_hexagonal_error("Unable to get cart items"))?
+            .filter_map(|item| (!is_expired(&item, now)).then_some(item))
+            .filter_map(decode_or_skip(
+                self.persistance_repository,
+                CartItem::from_attr_map,
+            )))
+    }
 
-        let mut expression_attribute_values = std::collections::HashMap::new();
-        expression_attribute_values.insert(
-            ":pk".to_string(),
-            AttributeValue::S("CART#USER#".to_string() + &user_id.to_string()),
-        );
-        expression_attribute_values.insert(
-            ":sk".to_string(),
-            AttributeValue::S("CART#PRODUCT#".to_string()),
-        );
+    async fn cart_add_item(
+        &self,
+        item: &CartItem,
+        principal: &str,
+        outbox_event: OutboxEvent<CartItem>,
+    ) -> Result<CartItem, HexagonalError> {
+        let table_name = &self.persistance_repository.table_name;
+        let item_put = aws_sdk_dynamodb::types::Put::builder()
+            .table_name(table_name.clone())
+            .set_item(Some(self.expiring_attr_map(item)))
+            // an item past its expiry is gone as far as the cart is concerned, it's added over
+            .condition_expression(
+                "(attribute_not_exists(Pkey) AND attribute_not_exists(Skey)) OR #expiry <= :now",
+            )
+            .expression_attribute_names("#expiry", CART_EXPIRY_ATTRIBUTE)
+            .expression_attribute_values(":now", AttributeValue::N(now().to_string()))
+            .build()
+            .unwrap(); // table name and item is always set so unwrap is safe
 
-        let query_cart_items = self
-            .persistance_repository
-            .client
-            .query()
-            .table_name(self.persistance_repository.table_name.clone())
-            .key_condition_expression(query_expression.to_string())
-            .set_expression_attribute_values(Some(expression_attribute_values))
+        self.persistance_repository
+            .transaction()
+            .put(CartWrite::Item, item_put)
+            .operation(
+                CartWrite::History,
+                self.history_write(HistoryAction::Created, principal, None, item),
+            )
+            .operation(
+                CartWrite::Outbox,
+                outbox_event(item).into_transact_write_item(self.persistance_repository),
+            )
             .send()
-            .await; // we're making the assumption here that a single cart does not excede 1MB of data
-
-        match query_cart_items {
-            Ok(result) => {
-                let items = result.items.unwrap();
-                let mut cart_items = Vec::new();
-                for item in items {
-                    cart_items.push(CartItem::from_attr_map(item));
-                }
-                Ok(cart_items)
-            }
-            Err(e) => Err(HexagonalError {
-                error: error::HexagonalErrorCode::AdaptorError,
-                message: "Unable to get cart items".to_string(),
-                trace: e.to_string(),
-            }),
-        }
-    }
+            .await
+            .map_err(|err| match err.condition_failed(&CartWrite::Item) {
+                true => HexagonalError {
+                    error: error::HexagonalErrorCode::Conflict,
+                    message: "Unable to add item to cart, already in cart".to_string(),
+                    trace: err.to_string(),
+                },
+                false => cart_write_error("add item to cart", err, None),
+            })?;
 
-    async fn cart_add_item(&self, item: &CartItem) -> Result<CartItem, HexagonalError> {
-        let result = self
-            .persistance_repository
-            .put_new_item(item.into_attr_map())
+        self.refresh_cart_expiry(&item.user_id, Some(&item.product_id))
             .await;
-
-        match result {
-            Ok(_) => Ok(item.clone()),
-            Err(e) => Err(HexagonalError {
-                error: error::HexagonalErrorCode::AdaptorError,
-                message: "Unable to add item to cart".to_string(),
-                trace: e.to_string(),
-            }),
-        }
+        Ok(item.clone())
     }
 
     async fn cart_remove_item(
         &self,
         user_id: &String,
         product_id: &String,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<CartItem>,
     ) -> Result<CartItem, HexagonalError> {
-        let result = self
-            .persistance_repository
-            .delete_item(
-                "CART#USER#".to_string() + &user_id.to_string(),
-                "CART#PRODUCT#".to_string() + &product_id.to_string(),
+        let read = match self.get_cart_item(user_id, product_id).await? {
+            Some(read) => read,
+            None => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to remove item from cart, not in cart".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+        };
+
+        let removed = CartItem::from_attr_map(read.clone())?;
+        check_expected_version(
+            expected_version,
+            removed.version,
+            "Unable to remove item from cart, version does not match",
+        )?;
+        let table_name = &self.persistance_repository.table_name;
+
+        self.persistance_repository
+            .transaction()
+            .operation(CartWrite::Item, delete_if_unchanged(table_name, &read))
+            .operation(
+                CartWrite::History,
+                self.history_write(
+                    HistoryAction::Deleted,
+                    principal,
+                    Some(&removed),
+                    &removed.removed(),
+                ),
             )
-            .await;
+            .operation(
+                CartWrite::Outbox,
+                outbox_event(&removed).into_transact_write_item(self.persistance_repository),
+            )
+            .send()
+            .await
+            .map_err(|err| cart_write_error("remove item from cart", err, expected_version))?;
 
-        match result {
-            Ok(value) => Ok(CartItem::from_attr_map(value.attributes.unwrap())),
-            Err(e) => Err(HexagonalError {
-                error: error::HexagonalErrorCode::AdaptorError,
-                message: "Unable to remove item from cart".to_string(),
-                trace: e.to_string(),
-            }),
-        }
+        self.refresh_cart_expiry(user_id, None).await;
+        Ok(removed)
     }
 
     async fn cart_update_item(
@@ -245,257 +298,1124 @@ impl<'a> CartRepositoryPort for CartRepositoryAdaptor<'a> {
         user_id: &String,
         product_id: &String,
         quantity: u32,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<CartItem>,
     ) -> Result<CartItem, HexagonalError> {
-        let mut update_expression = "SET ".to_string();
-        let mut attr_names = HashMap::new();
+        let read = match self.get_cart_item(user_id, product_id).await? {
+            Some(read) => read,
+            None => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to update item in cart, not in cart".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+        };
 
-        update_expression.push_str("#quantity_key = :quantity, ");
-        attr_names.insert("#quantity_key".to_string(), "quantity".to_string());
+        let current = CartItem::from_attr_map(read.clone())?;
+        check_expected_version(
+            expected_version,
+            current.version,
+            "Unable to update item in cart, version does not match",
+        )?;
+        let updated = CartItem {
+            quantity,
+            updated_at: default_time(),
+            version: current.version + 1,
+            ..current.clone()
+        };
+        let table_name = &self.persistance_repository.table_name;
 
-        update_expression.push_str("updated_at = :updated_at");
+        self.persistance_repository
+            .transaction()
+            .operation(
+                CartWrite::Item,
+                put_if_unchanged(table_name, &read, self.expiring_attr_map(&updated)),
+            )
+            .operation(
+                CartWrite::History,
+                self.history_write(HistoryAction::Updated, principal, Some(&current), &updated),
+            )
+            .operation(
+                CartWrite::Outbox,
+                outbox_event(&updated).into_transact_write_item(self.persistance_repository),
+            )
+            .send()
+            .await
+            .map_err(|err| cart_write_error("update item in cart", err, expected_version))?;
 
-        let result = self
-            .persistance_repository
-            .update_item(
-                "CART#USER#".to_string() + &user_id.to_string(),
-                "CART#PRODUCT#".to_string() + &product_id.to_string(),
-                update_expression,
-                Some(attr_names),
-                Some(
-                    [
-                        (
-                            ":quantity".to_string(),
-                            AttributeValue::N(quantity.to_string()),
+        self.refresh_cart_expiry(user_id, Some(product_id)).await;
+        Ok(updated)
+    }
+
+    async fn cart_clear(
+        &self,
+        user_id: &String,
+        principal: &str,
+        outbox_event: OutboxEvent<[CartItem]>,
+    ) -> Result<Vec<CartItem>, HexagonalError> {
+        let table_name = &self.persistance_repository.table_name;
+        let mut cart_items = Vec::new();
+        let mut page_request = PageRequest::new(Some(CART_CLEAR_CHUNK_SIZE as i32), None);
+
+        // each page is removed in its own transaction along with its event
+        loop {
+            let page = self
+                .persistance_repository
+                .query_index(&self.user_cart_query(user_id), &page_request)
+                .await
+                .map_err(|e| e.into_hexagonal_error("Unable to get cart items"))?;
+
+            // unreadable items are cleared too, they just can't be named in the event or history
+            let removed: Vec<CartItem> = page
+                .items
+                .iter()
+                .cloned()
+                .filter_map(decode_or_skip(
+                    self.persistance_repository,
+                    CartItem::from_attr_map,
+                ))
+                .collect();
+
+            if !page.items.is_empty() {
+                let transaction = page.items.iter().fold(
+                    self.persistance_repository.transaction(),
+                    |transaction, item| {
+                        transaction.operation(
+                            CartWrite::Item,
+                            delete_if_unchanged(table_name, &without_expiry(item.clone())),
+                        )
+                    },
+                );
+                let transaction = removed.iter().fold(transaction, |transaction, item| {
+                    transaction.operation(
+                        CartWrite::History,
+                        self.history_write(
+                            HistoryAction::Deleted,
+                            principal,
+                            Some(item),
+                            &item.removed(),
                         ),
-                        (":updated_at".to_string(), AttributeValue::N(default_time())),
-                    ]
-                    .iter()
-                    .cloned()
-                    .collect(),
-                ),
-            )
-            .await;
+                    )
+                });
+                transaction
+                    .operation(
+                        CartWrite::Outbox,
+                        outbox_event(&removed)
+                            .into_transact_write_item(self.persistance_repository),
+                    )
+                    .send()
+                    .await
+                    // an item removed since the page was read is a change like any other here
+                    .map_err(|err| match err.condition_failed(&CartWrite::Item) {
+                        true => HexagonalError {
+                            error: error::HexagonalErrorCode::Conflict,
+                            message: "Unable to clear cart, it was changed by another request"
+                                .to_string(),
+                            trace: err.to_string(),
+                        },
+                        false => cart_write_error("clear cart", err, None),
+                    })?;
+                cart_items.extend(removed);
+            }
 
-        match result {
-            Ok(value) => Ok(CartItem::from_attr_map(value.attributes.unwrap())),
-            Err(e) => Err(HexagonalError {
-                error: error::HexagonalErrorCode::AdaptorError,
-                message: "Unable to update item in cart".to_string(),
-                trace: e.to_string(),
-            }),
+            match page.next_cursor {
+                Some(cursor) => page_request = page_request.next(cursor),
+                None => return Ok(cart_items),
+            }
         }
     }
 
-    async fn cart_clear(&self, user_id: &String) -> Result<Vec<CartItem>, HexagonalError> {
-        let query_expression = "Pkey = :pk AND begins_with(Skey, :sk)";
+    async fn cart_global_remove_product(
+        &self,
+        product_id: &String,
+        principal: &str,
+    ) -> Result<(), Vec<HexagonalError>> {
+        println!("Removing product {} from all carts", product_id);
 
-        let mut expression_attribute_values = std::collections::HashMap::new();
-        expression_attribute_values.insert(
-            ":pk".to_string(),
-            AttributeValue::S("CART#USER#".to_string() + &user_id.to_string()),
-        );
-        expression_attribute_values.insert(
-            ":sk".to_string(),
-            AttributeValue::S("CART#PRODUCT#".to_string()),
-        );
+        let mut errors = Vec::new();
 
-        let query_cart_items = self
-            .persistance_repository
-            .client
-            .query()
-            .table_name(self.persistance_repository.table_name.clone())
-            .key_condition_expression(query_expression.to_string())
-            .set_expression_attribute_values(Some(expression_attribute_values))
-            .send()
-            .await;
+        let query = IndexQuery::gsi(GSIs::GSI1, CartItem::gsi1_pkey(product_id))
+            .sort_key(SortKeyCondition::BeginsWith("CART#USER#".to_string()));
+
+        let mut page_request = PageRequest::all();
+
+        loop {
+            let page = match self
+                .persistance_repository
+                .query_index(&query, &page_request)
+                .await
+            {
+                Ok(page) => page,
+                Err(e) => {
+                    // stop paginating on failure
+                    errors.push(e.into_hexagonal_error("Unable to get cart items"));
+                    break;
+                }
+            };
 
-        match query_cart_items {
-            Ok(result) => {
-                let items = result.items.unwrap();
-                let mut cart_items = Vec::new();
-                for item in items {
-                    cart_items.push(CartItem::from_attr_map(item));
+            let mut cart_items = Vec::new();
+            for item in page.items {
+                // an unreadable item is reported but doesn't stop the rest being removed
+                match CartItem::from_attr_map(item) {
+                    Ok(cart_item) => cart_items.push(cart_item),
+                    Err(e) => errors.push(e.into()),
                 }
+            }
 
-                let delete_requests: Vec<WriteRequest> = cart_items
-                    .iter()
-                    .map(|item| {
+            // a batch isn't a transaction, so a record may be left for a removal that failed
+            let write_requests: Vec<WriteRequest> = cart_items
+                .iter()
+                .flat_map(|item| {
+                    [
                         WriteRequest::builder()
                             .delete_request(
                                 aws_sdk_dynamodb::types::DeleteRequest::builder()
-                                    .key(
-                                        "Pkey".to_string(),
-                                        AttributeValue::S(
-                                            "CART#USER#".to_string() + &user_id.to_string(),
-                                        ),
-                                    )
-                                    .key(
-                                        "Skey".to_string(),
-                                        AttributeValue::S(
-                                            "CART#PRODUCT#".to_string()
-                                                + &item.product_id.to_string(),
-                                        ),
-                                    )
+                                    .set_key(Some(CartItem::primary_key(
+                                        &item.user_id,
+                                        &item.product_id,
+                                    )))
                                     .build()
                                     .unwrap(), // Key is always set so unwrap is safe
                             )
-                            .build()
-                    })
-                    .collect();
-
-                let delete_chunk_iterator = delete_requests.chunks(25); // need to chunk due to item limit of 25
-
-                for delete_chunk in delete_chunk_iterator {
-                    let result = self
-                        .persistance_repository
-                        .client
-                        .batch_write_item()
-                        .request_items(
-                            self.persistance_repository.table_name.clone(),
-                            delete_chunk.to_vec(),
-                        )
-                        .send()
-                        .await;
-                    if result.is_err() {
-                        return Err(HexagonalError {
-                            error: error::HexagonalErrorCode::AdaptorError,
-                            message: "Unable to clear cart".to_string(),
-                            trace: result.unwrap_err().to_string(),
-                        });
-                    }
-                }
+                            .build(),
+                        WriteRequest::builder()
+                            .put_request(
+                                PutRequest::builder()
+                                    .set_item(Some(self.expiring_history(
+                                        HistoryAction::Deleted,
+                                        principal,
+                                        Some(item),
+                                        &item.removed(),
+                                    )))
+                                    .build()
+                                    .unwrap(), // Item is always set so unwrap is safe
+                            )
+                            .build(),
+                    ]
+                })
+                .collect();
 
-                Ok(cart_items)
+            if let Err(e) = self
+                .persistance_repository
+                .batch_write(write_requests)
+                .await
+            {
+                errors.push(HexagonalError {
+                    error: error::HexagonalErrorCode::AdaptorError,
+                    message: "Unable to remove product from carts".to_string(),
+                    trace: e.to_string(),
+                });
+            }
+
+            match page.next_cursor {
+                Some(cursor) => page_request = page_request.next(cursor),
+                None => break,
             }
-            Err(e) => Err(HexagonalError {
-                error: error::HexagonalErrorCode::AdaptorError,
-                message: "Unable to get cart items".to_string(),
-                trace: e.to_string(),
-            }),
         }
+        if errors.len() > 0 {
+            return Err(errors);
+        }
+        return Ok(());
     }
 
-    async fn cart_global_remove_product(
+    async fn cart_history_by_user_id(
         &self,
-        product_id: &String,
-    ) -> Result<(), Vec<HexagonalError>> {
-        println!("Removing product {} from all carts", product_id);
-        let query_expression = "GSI1Pkey = :pk AND begins_with(GSI1Skey, :sk)";
+        user_id: &String,
+        page: &PageRequest,
+    ) -> Result<Page<HistoryRecord>, HexagonalError> {
+        let now = now();
+        Ok(self
+            .persistance_repository
+            .query_index(&history_query(CartItem::pkey(user_id)), page)
+            .await
+            .map_err(|e| e.into_hexagonal_error("Unable to get cart history"))?
+            .filter_map(|record| (!is_expired(&record, now)).then_some(record))
+            .filter_map(decode_or_skip(
+                self.persistance_repository,
+                HistoryRecord::from_attr_map,
+            )))
+    }
+}
 
-        let mut errors = Vec::new();
+impl<'a> CartRepositoryAdaptor<'a> {
+    fn expires_at(&self) -> u64 {
+        now() + self.expiry.as_secs()
+    }
 
-        let mut expression_attribute_values = std::collections::HashMap::new();
-        expression_attribute_values.insert(
-            ":pk".to_string(),
-            AttributeValue::S("CART#PRODUCT#".to_string() + &product_id.to_string()),
-        );
-        expression_attribute_values.insert(
-            ":sk".to_string(),
-            AttributeValue::S("CART#USER#".to_string()),
+    // The item as stored, with its expiry set from now
+    fn expiring_attr_map(&self, item: &CartItem) -> HashMap<String, AttributeValue> {
+        let mut attr_map = item.into_attr_map();
+        attr_map.insert(
+            CART_EXPIRY_ATTRIBUTE.to_string(),
+            AttributeValue::N(self.expires_at().to_string()),
         );
+        attr_map
+    }
 
-        let mut pagination = true;
+    // The history record of a write to an item, kept on its cart and expiring along with the item
+    fn expiring_history(
+        &self,
+        action: HistoryAction,
+        principal: &str,
+        before: Option<&CartItem>,
+        after: &CartItem,
+    ) -> HashMap<String, AttributeValue> {
+        let mut attr_map = HistoryRecord::new(action, principal, before, after)
+            .with_item(&after.product_id)
+            .into_attr_map(CartItem::pkey(&after.user_id));
+        attr_map.insert(
+            CART_EXPIRY_ATTRIBUTE.to_string(),
+            AttributeValue::N(self.expires_at().to_string()),
+        );
+        attr_map
+    }
 
-        let mut exclusive_start_key: Option<HashMap<String, AttributeValue>> = None;
+    // Goes in the transaction making the write it records
+    fn history_write(
+        &self,
+        action: HistoryAction,
+        principal: &str,
+        before: Option<&CartItem>,
+        after: &CartItem,
+    ) -> TransactWriteItem {
+        let put = Put::builder()
+            .table_name(&self.persistance_repository.table_name)
+            .set_item(Some(
+                self.expiring_history(action, principal, before, after),
+            ))
+            .build()
+            .unwrap(); // table name and item is always set so unwrap is safe
+        TransactWriteItem::builder().put(put).build()
+    }
 
-        while pagination {
-            let query_cart_items = self
+    // Pushes the expiry of the rest of the user's cart and its history back after a write,
+    // skipping the item the write already set it on. The write has committed by then, so a failure
+    // is only logged and leaves those records on their earlier expiry. Records already expiring
+    // within a hundredth of the expiry of the new one are left be, so filling a cart doesn't
+    // rewrite it on every add. Items already past their expiry are left to go rather than revived
+    async fn refresh_cart_expiry(&self, user_id: &str, written_product_id: Option<&str>) {
+        let now = now();
+        let expires_at = self.expires_at();
+        let refresh_before = expires_at - self.expiry.as_secs() / 100;
+        let written_skey = written_product_id.map(CartItem::skey);
+        // the whole partition, items and history records alike
+        let cart_query = IndexQuery::table(CartItem::pkey(user_id));
+        let mut page_request = PageRequest::all();
+        loop {
+            let page = match self
                 .persistance_repository
-                .client
-                .query()
-                .table_name(self.persistance_repository.table_name.clone())
-                .index_name("GSI1".to_string())
-                .key_condition_expression(query_expression.to_string())
-                .set_expression_attribute_values(Some(expression_attribute_values.clone()))
-                .set_exclusive_start_key(exclusive_start_key.clone())
-                .send()
-                .await; // we're making the assumption here that a single cart does not excede 1MB of data
-
-            println!("Query result: {:?}", query_cart_items);
-
-            let delete_result = match query_cart_items {
-                Ok(result) => {
-                    exclusive_start_key = result.last_evaluated_key;
-
-                    if exclusive_start_key.is_none() {
-                        pagination = false;
-                    }
-                    let items = result.items.unwrap();
-                    let mut cart_items = Vec::new();
-                    for item in items {
-                        cart_items.push(CartItem::from_attr_map(item));
-                    }
-
-                    let delete_requests: Vec<WriteRequest> = cart_items
-                        .iter()
-                        .map(|item| {
-                            WriteRequest::builder()
-                                .delete_request(
-                                    aws_sdk_dynamodb::types::DeleteRequest::builder()
-                                        .key(
-                                            "Pkey".to_string(),
-                                            AttributeValue::S(
-                                                "CART#USER#".to_string()
-                                                    + &item.user_id.to_string(),
-                                            ),
-                                        )
-                                        .key(
-                                            "Skey".to_string(),
-                                            AttributeValue::S(
-                                                "CART#PRODUCT#".to_string()
-                                                    + &item.product_id.to_string(),
-                                            ),
-                                        )
-                                        .build()
-                                        .unwrap(), // Key is always set so unwrap is safe
-                                )
-                                .build()
-                        })
-                        .collect();
-
-                    let delete_chunk_iterator = delete_requests.chunks(25); // need to chunk due to item limit of 25
-
-                    for delete_chunk in delete_chunk_iterator {
-                        let result = self
-                            .persistance_repository
-                            .client
-                            .batch_write_item()
-                            .request_items(
-                                self.persistance_repository.table_name.clone(),
-                                delete_chunk.to_vec(),
-                            )
-                            .send()
-                            .await;
-
-                        if result.is_err() {
-                            errors.push(HexagonalError {
-                                error: error::HexagonalErrorCode::AdaptorError,
-                                message: "Unable to remove product from carts".to_string(),
-                                trace: result.unwrap_err().to_string(),
-                            });
-                        }
-                    }
-
-                    Ok(())
-                }
+                .query_index(&cart_query, &page_request)
+                .await
+            {
+                Ok(page) => page,
                 Err(e) => {
-                    pagination = false; // stop pagination if failure
-                    println!("Error: {:?}", e.source());
-                    Err(HexagonalError {
-                        error: error::HexagonalErrorCode::AdaptorError,
-                        message: "Unable to get cart items".to_string(),
-                        trace: "".to_string(),
-                    })
+                    tracing::warn!(
+                        user_id,
+                        error = %e.into_hexagonal_error("Unable to get cart items"),
+                        "Unable to refresh cart expiry"
+                    );
+                    return;
                 }
             };
+            for item in &page.items {
+                let s_key = match item.get("Skey").and_then(|s_key| s_key.as_s().ok()) {
+                    Some(s_key) if Some(s_key) != written_skey.as_ref() => s_key,
+                    _ => continue,
+                };
+                if is_expired(item, now) || expiry_of(item).unwrap_or_default() >= refresh_before {
+                    continue;
+                }
+                // an item removed since the query is left removed, update_item needs it to exist
+                if let Err(e) = self
+                    .persistance_repository
+                    .update_item(
+                        CartItem::pkey(user_id),
+                        s_key.clone(),
+                        UpdateExpression::new().set(
+                            CART_EXPIRY_ATTRIBUTE,
+                            AttributeValue::N(expires_at.to_string()),
+                        ),
+                    )
+                    .await
+                {
+                    tracing::warn!(
+                        user_id,
+                        s_key = s_key.as_str(),
+                        error = %e,
+                        "Unable to refresh cart expiry"
+                    );
+                }
+            }
+            match page.next_cursor {
+                Some(cursor) => page_request = page_request.next(cursor),
+                None => return,
+            }
+        }
+    }
+
+    async fn get_cart_item(
+        &self,
+        user_id: &String,
+        product_id: &String,
+    ) -> Result<Option<HashMap<String, AttributeValue>>, HexagonalError> {
+        self.persistance_repository
+            .get_item_primary(CartItem::pkey(user_id), CartItem::skey(product_id))
+            .await
+            .map(|output| {
+                output
+                    .item
+                    .filter(|item| !is_expired(item, now()))
+                    .map(without_expiry)
+            })
+            .map_err(|e| HexagonalError {
+                error: error::HexagonalErrorCode::AdaptorError,
+                message: "Unable to get cart item".to_string(),
+                trace: e.to_string(),
+            })
+    }
+
+    // Every item in a user's cart
+    fn user_cart_query(&self, user_id: &str) -> IndexQuery {
+        IndexQuery::table(CartItem::pkey(user_id))
+            .sort_key(SortKeyCondition::BeginsWith("CART#PRODUCT#".to_string()))
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
 
-            if delete_result.is_err() {
-                errors.push(delete_result.unwrap_err())
+fn expiry_of(item: &HashMap<String, AttributeValue>) -> Option<u64> {
+    item.get(CART_EXPIRY_ATTRIBUTE)
+        .and_then(|expires_at| expires_at.as_n().ok())
+        .and_then(|expires_at| expires_at.parse::<u64>().ok())
+}
+
+// Past its expiry but not yet deleted, items stored before expiry was added never expire
+fn is_expired(item: &HashMap<String, AttributeValue>, now: u64) -> bool {
+    expiry_of(item).is_some_and(|expires_at| expires_at <= now)
+}
+
+// Writes to one item push back the expiry of the whole cart, so it's left out of what writes check
+// is unchanged since their read. Otherwise writes to two items of a cart would conflict
+fn without_expiry(mut item: HashMap<String, AttributeValue>) -> HashMap<String, AttributeValue> {
+    item.remove(CART_EXPIRY_ATTRIBUTE);
+    item
+}
+
+// The operations cart writes are made of, so a cancelled transaction says which one failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CartWrite {
+    // CART#PRODUCT# item, one per product in the cart
+    Item,
+    History,
+    Outbox,
+}
+
+fn cart_write_error(
+    action: &str,
+    err: TransactionError<CartWrite>,
+    expected_version: Option<u64>,
+) -> HexagonalError {
+    match err.cancellation(&CartWrite::Item) {
+        // the condition was checked against nothing, the item was removed after we read it
+        Some(cancellation)
+            if cancellation.code == CancellationCode::ConditionalCheckFailed
+                && cancellation.item.is_none() =>
+        {
+            HexagonalError {
+                error: error::HexagonalErrorCode::NotFound,
+                message: format!("Unable to {}, no longer in cart", action),
+                trace: err.to_string(),
             }
         }
-        if errors.len() > 0 {
-            return Err(errors);
+        Some(cancellation) if cancellation.code == CancellationCode::ConditionalCheckFailed => {
+            changed_since_read_error(
+                expected_version,
+                &format!("Unable to {}, it was changed by another request", action),
+                err.to_string(),
+            )
         }
-        return Ok(());
+        _ => HexagonalError {
+            error: error::HexagonalErrorCode::AdaptorError,
+            message: format!("Unable to {}, error in transaction write call", action),
+            trace: err.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use in_memory_persistance_repository::InMemorySingleTable;
+    use metrics::InMemoryMetricsAdaptor;
+
+    use super::*;
+    use crate::models::outbox::{OutboxItem, OutboxRepositoryAdaptor, OutboxRepositoryPort};
+
+    fn test_event(item: &CartItem) -> OutboxItem {
+        OutboxItem::new("CartTest".to_string(), 1, item.product_id.clone())
+    }
+
+    fn test_events(items: &[CartItem]) -> OutboxItem {
+        OutboxItem::new("CartClearTest".to_string(), 1, items.len().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_cart_writes_refresh_expiry() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let expires_at = || async {
+            repository
+                .get_item_primary(CartItem::pkey("a"), CartItem::skey("1"))
+                .await
+                .unwrap()
+                .item
+                .unwrap()[CART_EXPIRY_ATTRIBUTE]
+                .as_n()
+                .unwrap()
+                .parse::<u64>()
+                .unwrap()
+        };
+
+        let adaptor = CartRepositoryAdaptor::new(&repository).with_expiry(Duration::from_secs(60));
+        adaptor
+            .cart_add_item(
+                &CartItem::new("1".to_string(), "a".to_string(), 1),
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        let added = expires_at().await;
+        assert!(added >= default_time().parse::<u64>().unwrap() + 60);
+
+        let adaptor = adaptor.with_expiry(Duration::from_secs(3600));
+        adaptor
+            .cart_update_item(
+                &"a".to_string(),
+                &"1".to_string(),
+                2,
+                None,
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        let updated = expires_at().await;
+        assert!(updated >= added + 3540);
+
+        // writes to the rest of the cart keep the older item from expiring on its own
+        let adaptor = adaptor.with_expiry(Duration::from_secs(7200));
+        adaptor
+            .cart_add_item(
+                &CartItem::new("2".to_string(), "a".to_string(), 1),
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        let refreshed = expires_at().await;
+        assert!(refreshed >= updated + 3600);
+
+        let adaptor = adaptor.with_expiry(Duration::from_secs(10800));
+        adaptor
+            .cart_remove_item(
+                &"a".to_string(),
+                &"2".to_string(),
+                None,
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        assert!(expires_at().await >= refreshed + 3600);
+        assert_eq!(
+            adaptor
+                .cart_get_by_user_id(&"a".to_string(), &PageRequest::default())
+                .await
+                .unwrap()
+                .items[0]
+                .version,
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cart_writes_refresh_history_expiry() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let first_history_expires_at = || async {
+            repository
+                .query_index(&history_query(CartItem::pkey("a")), &PageRequest::all())
+                .await
+                .unwrap()
+                .items
+                .into_iter()
+                .find(|record| record["Skey"].as_s().unwrap().contains("#1#"))
+                .unwrap()[CART_EXPIRY_ATTRIBUTE]
+                .as_n()
+                .unwrap()
+                .parse::<u64>()
+                .unwrap()
+        };
+
+        let adaptor = CartRepositoryAdaptor::new(&repository).with_expiry(Duration::from_secs(60));
+        adaptor
+            .cart_add_item(
+                &CartItem::new("1".to_string(), "a".to_string(), 1),
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        let added = first_history_expires_at().await;
+
+        // a later write to another item keeps the first write's history as long as the cart
+        let adaptor = adaptor.with_expiry(Duration::from_secs(3600));
+        adaptor
+            .cart_add_item(
+                &CartItem::new("2".to_string(), "a".to_string(), 1),
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        assert!(first_history_expires_at().await >= added + 3540);
+    }
+
+    #[tokio::test]
+    async fn test_cart_expired_items_read_as_gone() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let expired = CartRepositoryAdaptor::new(&repository).with_expiry(Duration::ZERO);
+        let adaptor = CartRepositoryAdaptor::new(&repository);
+        let item = CartItem::new("1".to_string(), "a".to_string(), 1);
+        expired
+            .cart_add_item(&item, "tester", test_event)
+            .await
+            .unwrap();
+
+        // still in the table, DynamoDB hasn't got round to deleting it
+        assert!(repository
+            .get_item_primary(CartItem::pkey("a"), CartItem::skey("1"))
+            .await
+            .unwrap()
+            .item
+            .is_some());
+        assert!(adaptor
+            .cart_get_by_user_id(&"a".to_string(), &PageRequest::default())
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+        let missing = adaptor
+            .cart_update_item(
+                &"a".to_string(),
+                &"1".to_string(),
+                2,
+                None,
+                "tester",
+                test_event,
+            )
+            .await;
+        assert_eq!(
+            missing.err().unwrap().error,
+            error::HexagonalErrorCode::NotFound
+        );
+
+        // and adding it again starts it afresh
+        adaptor
+            .cart_add_item(&item, "tester", test_event)
+            .await
+            .unwrap();
+        let cart = adaptor
+            .cart_get_by_user_id(&"a".to_string(), &PageRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(cart.items.len(), 1);
+        assert_eq!(cart.items[0].version, 1);
+    }
+
+    #[tokio::test]
+    async fn test_cart_against_in_memory_table() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = CartRepositoryAdaptor::new(&repository);
+        for (user, product) in [("a", "1"), ("a", "2"), ("b", "1")] {
+            adaptor
+                .cart_add_item(
+                    &CartItem::new(product.to_string(), user.to_string(), 1),
+                    "tester",
+                    test_event,
+                )
+                .await
+                .unwrap();
+        }
+
+        let updated = adaptor
+            .cart_update_item(
+                &"a".to_string(),
+                &"2".to_string(),
+                3,
+                None,
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        assert_eq!(updated.quantity, 3);
+        assert_eq!(updated.version, 2);
+        let stale = adaptor
+            .cart_update_item(
+                &"a".to_string(),
+                &"2".to_string(),
+                4,
+                Some(1),
+                "tester",
+                test_event,
+            )
+            .await;
+        assert_eq!(
+            stale.err().unwrap().error,
+            error::HexagonalErrorCode::PreconditionFailed
+        );
+        let stale = adaptor
+            .cart_remove_item(
+                &"a".to_string(),
+                &"2".to_string(),
+                Some(1),
+                "tester",
+                test_event,
+            )
+            .await;
+        assert_eq!(
+            stale.err().unwrap().error,
+            error::HexagonalErrorCode::PreconditionFailed
+        );
+        assert_eq!(
+            adaptor
+                .cart_get_by_user_id(&"a".to_string(), &PageRequest::default())
+                .await
+                .unwrap()
+                .items
+                .len(),
+            2
+        );
+
+        adaptor
+            .cart_global_remove_product(&"1".to_string(), "tester")
+            .await
+            .unwrap();
+        let remaining = adaptor
+            .cart_get_by_user_id(&"a".to_string(), &PageRequest::default())
+            .await
+            .unwrap()
+            .items;
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].product_id, "2");
+        assert!(adaptor
+            .cart_get_by_user_id(&"b".to_string(), &PageRequest::default())
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+
+        assert_eq!(
+            adaptor
+                .cart_clear(&"a".to_string(), "tester", test_events)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(adaptor
+            .cart_get_by_user_id(&"a".to_string(), &PageRequest::default())
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cart_clear_writes_an_event_per_chunk() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = CartRepositoryAdaptor::new(&repository);
+        let outbox = OutboxRepositoryAdaptor::new(&repository);
+        for product in 0..(CART_CLEAR_CHUNK_SIZE + 2) {
+            adaptor
+                .cart_add_item(
+                    &CartItem::new(product.to_string(), "a".to_string(), 1),
+                    "tester",
+                    test_event,
+                )
+                .await
+                .unwrap();
+        }
+        adaptor
+            .cart_remove_item(
+                &"a".to_string(),
+                &"0".to_string(),
+                None,
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        let missing = adaptor
+            .cart_remove_item(
+                &"a".to_string(),
+                &"0".to_string(),
+                None,
+                "tester",
+                test_event,
+            )
+            .await;
+        assert_eq!(
+            missing.err().unwrap().error,
+            error::HexagonalErrorCode::NotFound
+        );
+
+        let cleared = adaptor
+            .cart_clear(&"a".to_string(), "tester", test_events)
+            .await
+            .unwrap();
+        assert_eq!(cleared.len(), CART_CLEAR_CHUNK_SIZE + 1);
+
+        let mut clear_events: Vec<String> = outbox
+            .outbox_get_pending(1000)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|event| event.event_type == "CartClearTest")
+            .map(|event| event.detail)
+            .collect();
+        clear_events.sort();
+        assert_eq!(
+            clear_events,
+            vec!["1".to_string(), CART_CLEAR_CHUNK_SIZE.to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cart_global_remove_product_skips_malformed_item() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = CartRepositoryAdaptor::new(&repository);
+        adaptor
+            .cart_add_item(
+                &CartItem::new("1".to_string(), "a".to_string(), 1),
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        let mut malformed = CartItem::new("1".to_string(), "b".to_string(), 1).into_attr_map();
+        malformed.remove("quantity");
+        repository.put_new_item(malformed).await.unwrap();
+
+        let errors = adaptor
+            .cart_global_remove_product(&"1".to_string(), "tester")
+            .await
+            .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].error, error::HexagonalErrorCode::AdaptorError);
+        assert_eq!(
+            errors[0].trace,
+            "Item (Pkey: CART#USER#b, Skey: CART#PRODUCT#1) is missing attribute quantity"
+        );
+        assert!(adaptor
+            .cart_get_by_user_id(&"a".to_string(), &PageRequest::default())
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+        // the unreadable item is left out of b's cart rather than failing the read
+        adaptor
+            .cart_add_item(
+                &CartItem::new("2".to_string(), "b".to_string(), 1),
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        let cart = adaptor
+            .cart_get_by_user_id(&"b".to_string(), &PageRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(cart.items.len(), 1);
+        assert_eq!(cart.items[0].product_id, "2");
+
+        // and cleared along with the rest
+        let removed = adaptor
+            .cart_clear(&"b".to_string(), "tester", test_events)
+            .await
+            .unwrap();
+        assert_eq!(removed.len(), 1);
+        assert!(repository
+            .get_item_primary(CartItem::pkey("b"), CartItem::skey("1"))
+            .await
+            .unwrap()
+            .item
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cart_write_error_says_which_operation_failed() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = CartRepositoryAdaptor::new(&repository);
+        let item = CartItem::new("1".to_string(), "a".to_string(), 1);
+        adaptor
+            .cart_add_item(&item, "tester", test_event)
+            .await
+            .unwrap();
+        let read = adaptor
+            .get_cart_item(&"a".to_string(), &"1".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        let table_name = &repository.table_name;
+        let remove_over_read = || {
+            repository
+                .transaction()
+                .operation(CartWrite::Item, delete_if_unchanged(table_name, &read))
+                .operation(
+                    CartWrite::Outbox,
+                    test_event(&item).into_transact_write_item(&repository),
+                )
+        };
+
+        // changed after it was read
+        adaptor
+            .cart_update_item(
+                &"a".to_string(),
+                &"1".to_string(),
+                2,
+                None,
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        let err = remove_over_read().send().await.unwrap_err();
+        assert!(err.cancellation(&CartWrite::Outbox).is_none());
+        let changed = cart_write_error("remove item from cart", err, Some(1));
+        assert_eq!(changed.error, error::HexagonalErrorCode::PreconditionFailed);
+
+        // removed after it was read
+        repository
+            .delete_item(CartItem::pkey("a"), CartItem::skey("1"))
+            .await
+            .unwrap();
+        let err = remove_over_read().send().await.unwrap_err();
+        let gone = cart_write_error("remove item from cart", err, Some(1));
+        assert_eq!(gone.error, error::HexagonalErrorCode::NotFound);
+        assert_eq!(
+            gone.message,
+            "Unable to remove item from cart, no longer in cart"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cart_get_counts_skipped_items() {
+        let metrics = Arc::new(InMemoryMetricsAdaptor::new());
+        let repository = InMemorySingleTable::new()
+            .repository("table")
+            .with_metrics(metrics.clone())
+            .with_operation("cart_get");
+        let adaptor = CartRepositoryAdaptor::new(&repository);
+        let mut malformed = CartItem::new("1".to_string(), "a".to_string(), 1).into_attr_map();
+        malformed.remove("quantity");
+        repository.put_new_item(malformed).await.unwrap();
+
+        let cart = adaptor
+            .cart_get_by_user_id(&"a".to_string(), &PageRequest::default())
+            .await
+            .unwrap();
+        assert!(cart.items.is_empty());
+        assert_eq!(metrics.skipped_items(), vec!["cart_get.decode".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_cart_global_remove_product_stays_within_tenant() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let acme_repository = repository.for_tenant(Tenant::new("acme").unwrap());
+        let globex_repository = repository.for_tenant(Tenant::new("globex").unwrap());
+        let acme = CartRepositoryAdaptor::new(&acme_repository);
+        let globex = CartRepositoryAdaptor::new(&globex_repository);
+        for adaptor in [&acme, &globex] {
+            adaptor
+                .cart_add_item(
+                    &CartItem::new("1".to_string(), "a".to_string(), 1),
+                    "tester",
+                    test_event,
+                )
+                .await
+                .unwrap();
+        }
+
+        acme.cart_global_remove_product(&"1".to_string(), "tester")
+            .await
+            .unwrap();
+        for (adaptor, left) in [(&acme, 0), (&globex, 1)] {
+            let cart = adaptor
+                .cart_get_by_user_id(&"a".to_string(), &PageRequest::default())
+                .await
+                .unwrap();
+            assert_eq!(cart.items.len(), left);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cart_history_records_each_write() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = CartRepositoryAdaptor::new(&repository);
+        let user_id = "a".to_string();
+        adaptor
+            .cart_add_item(
+                &CartItem::new("1".to_string(), user_id.clone(), 1),
+                "adder",
+                test_event,
+            )
+            .await
+            .unwrap();
+        adaptor
+            .cart_update_item(&user_id, &"1".to_string(), 2, None, "updater", test_event)
+            .await
+            .unwrap();
+        adaptor
+            .cart_remove_item(&user_id, &"1".to_string(), None, "remover", test_event)
+            .await
+            .unwrap();
+        for product in ["2", "3"] {
+            adaptor
+                .cart_add_item(
+                    &CartItem::new(product.to_string(), user_id.clone(), 1),
+                    "adder",
+                    test_event,
+                )
+                .await
+                .unwrap();
+        }
+        adaptor
+            .cart_global_remove_product(&"2".to_string(), "event:product_deleted")
+            .await
+            .unwrap();
+        adaptor
+            .cart_clear(&user_id, "clearer", test_events)
+            .await
+            .unwrap();
+
+        let history = adaptor
+            .cart_history_by_user_id(&user_id, &PageRequest::default())
+            .await
+            .unwrap()
+            .items;
+        assert_eq!(history.len(), 7);
+        // newest first for each item, the writes of one second are kept apart by item
+        let of_item = |product_id: &str| {
+            history
+                .iter()
+                .filter(|record| record.item.as_deref() == Some(product_id))
+                .map(|record| (record.action, record.version, record.principal.as_str()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            of_item("1"),
+            vec![
+                (HistoryAction::Deleted, 3, "remover"),
+                (HistoryAction::Updated, 2, "updater"),
+                (HistoryAction::Created, 1, "adder")
+            ]
+        );
+        assert_eq!(
+            of_item("2"),
+            vec![
+                (HistoryAction::Deleted, 2, "event:product_deleted"),
+                (HistoryAction::Created, 1, "adder")
+            ]
+        );
+        assert_eq!(
+            of_item("3"),
+            vec![
+                (HistoryAction::Deleted, 2, "clearer"),
+                (HistoryAction::Created, 1, "adder")
+            ]
+        );
+
+        // kept out of the cart itself, and left to expire with it
+        assert!(adaptor
+            .cart_get_by_user_id(&user_id, &PageRequest::default())
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+        let stored = repository
+            .query_index(
+                &history_query(CartItem::pkey(&user_id)),
+                &PageRequest::default(),
+            )
+            .await
+            .unwrap()
+            .items;
+        assert!(stored
+            .iter()
+            .all(|record| record.contains_key(CART_EXPIRY_ATTRIBUTE)));
+        assert!(CartItem::from_item(stored[0].clone()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cart_get_pages_with_cursor() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = CartRepositoryAdaptor::new(&repository);
+        for product in 0..5 {
+            adaptor
+                .cart_add_item(
+                    &CartItem::new(product.to_string(), "a".to_string(), 1),
+                    "tester",
+                    test_event,
+                )
+                .await
+                .unwrap();
+        }
+
+        let mut page_request = PageRequest::new(Some(2), None);
+        let mut page_sizes = Vec::new();
+        loop {
+            let page = adaptor
+                .cart_get_by_user_id(&"a".to_string(), &page_request)
+                .await
+                .unwrap();
+            page_sizes.push(page.items.len());
+            match page.next_cursor {
+                Some(cursor) => page_request = page_request.next(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(page_sizes, vec![2, 2, 1]);
+
+        // a cursor only works for the cart it came from
+        let first = adaptor
+            .cart_get_by_user_id(&"a".to_string(), &PageRequest::new(Some(2), None))
+            .await
+            .unwrap();
+        let other_cart = adaptor
+            .cart_get_by_user_id(
+                &"b".to_string(),
+                &PageRequest::new(Some(2), first.next_cursor),
+            )
+            .await;
+        assert_eq!(
+            other_cart.err().unwrap().error,
+            error::HexagonalErrorCode::BadInput
+        );
+        let too_large = adaptor
+            .cart_get_by_user_id(&"a".to_string(), &PageRequest::new(Some(1000), None))
+            .await;
+        assert_eq!(
+            too_large.err().unwrap().error,
+            error::HexagonalErrorCode::BadInput
+        );
     }
 }
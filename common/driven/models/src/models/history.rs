@@ -0,0 +1,317 @@
+// History Access Patterns
+// 1. Record every write to a user, product or cart item in the same transaction as the write
+// 2. Get an entity's history a page at a time, newest first
+// 3. Remove it all once the entity is purged
+
+// Model:
+// Pkey = the entity's Pkey, e.g. PRODUCT#<id>
+// Skey = HISTORY#<changed_at>#<version>, the version keeps two writes in the same second apart
+// A cart's history is kept on the cart, Skey = HISTORY#<changed_at>#<product_id>#<version>, as
+// each of its items has versions of its own
+
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::types::{
+    AttributeValue, DeleteRequest, Put, TransactWriteItem, WriteRequest,
+};
+use error::HexagonalError;
+use persistance_repository::{
+    DynamoDBSingleTableRepository, IndexQuery, PageRequest, SortKeyCondition,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{AttrReader, DecodeError};
+
+const HISTORY_PREFIX: &str = "HISTORY#";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryAction {
+    Created,
+    Updated,
+    Deleted,
+    Restored,
+}
+
+impl HistoryAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HistoryAction::Created => "created",
+            HistoryAction::Updated => "updated",
+            HistoryAction::Deleted => "deleted",
+            HistoryAction::Restored => "restored",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "created" => Some(HistoryAction::Created),
+            "updated" => Some(HistoryAction::Updated),
+            "deleted" => Some(HistoryAction::Deleted),
+            "restored" => Some(HistoryAction::Restored),
+            _ => None,
+        }
+    }
+}
+
+// A field as it was before and after the write, None where it didn't exist
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Option<serde_json::Value>,
+    pub new: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HistoryRecord {
+    pub action: HistoryAction,
+    pub principal: String,
+    pub changed_at: String,
+    // the entity's version once the write committed
+    pub version: u64,
+    // which of the entity's items was written, for entities made up of several
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item: Option<String>,
+    pub changes: Vec<FieldChange>,
+}
+
+// Bookkeeping every model carries, already on the record as changed_at and version
+const UNTRACKED_FIELDS: [&str; 2] = ["updated_at", "version"];
+
+impl HistoryRecord {
+    // The record of `before` becoming `after`, both serialised so any model can be compared
+    // field by field. `after` must have the `updated_at` and `version` every model carries
+    pub fn new<T: Serialize>(
+        action: HistoryAction,
+        principal: &str,
+        before: Option<&T>,
+        after: &T,
+    ) -> Self {
+        let before = before.map(to_fields).unwrap_or_default();
+        let after = to_fields(after);
+
+        let mut fields: Vec<&String> = before.keys().chain(after.keys()).collect();
+        fields.sort();
+        fields.dedup();
+        let changes = fields
+            .into_iter()
+            .filter(|field| !UNTRACKED_FIELDS.contains(&field.as_str()))
+            .filter(|field| before.get(*field) != after.get(*field))
+            .map(|field| FieldChange {
+                field: field.clone(),
+                old: before.get(field).cloned(),
+                new: after.get(field).cloned(),
+            })
+            .collect();
+
+        HistoryRecord {
+            action,
+            principal: principal.to_string(),
+            changed_at: match after.get("updated_at") {
+                Some(serde_json::Value::String(updated_at)) => updated_at.clone(),
+                _ => crate::default_time(),
+            },
+            version: after
+                .get("version")
+                .and_then(|version| version.as_u64())
+                .unwrap_or_default(),
+            item: None,
+            changes,
+        }
+    }
+
+    pub fn with_item(self, item: &str) -> Self {
+        HistoryRecord {
+            item: Some(item.to_string()),
+            ..self
+        }
+    }
+
+    // Also what the sql adaptors order a cart's history by
+    pub fn skey(&self) -> String {
+        match &self.item {
+            Some(item) => format!(
+                "{}{}#{}#{:020}",
+                HISTORY_PREFIX, self.changed_at, item, self.version
+            ),
+            None => format!("{}{}#{:020}", HISTORY_PREFIX, self.changed_at, self.version),
+        }
+    }
+
+    pub fn into_attr_map(&self, pkey: String) -> HashMap<String, AttributeValue> {
+        let mut attr_map = HashMap::from([
+            ("Pkey".to_string(), AttributeValue::S(pkey)),
+            ("Skey".to_string(), AttributeValue::S(self.skey())),
+            (
+                "action".to_string(),
+                AttributeValue::S(self.action.as_str().to_string()),
+            ),
+            (
+                "principal".to_string(),
+                AttributeValue::S(self.principal.clone()),
+            ),
+            (
+                "changed_at".to_string(),
+                AttributeValue::N(self.changed_at.clone()),
+            ),
+            (
+                "version".to_string(),
+                AttributeValue::N(self.version.to_string()),
+            ),
+            (
+                "changes".to_string(),
+                AttributeValue::S(serde_json::to_string(&self.changes).unwrap()),
+            ),
+        ]);
+        if let Some(item) = &self.item {
+            attr_map.insert("item".to_string(), AttributeValue::S(item.clone()));
+        }
+        attr_map
+    }
+
+    pub fn from_attr_map(attr_map: HashMap<String, AttributeValue>) -> Result<Self, DecodeError> {
+        let reader = AttrReader::new(&attr_map);
+        let unparsable = |attribute: &str, value: String| DecodeError::Unparsable {
+            attribute: attribute.to_string(),
+            value,
+            key: reader.key(),
+        };
+        let action = reader.string("action")?;
+        let changes = reader.string("changes")?;
+        Ok(HistoryRecord {
+            action: HistoryAction::parse(&action)
+                .ok_or_else(|| unparsable("action", action.clone()))?,
+            principal: reader.string("principal")?,
+            changed_at: reader.number("changed_at")?,
+            version: reader.parse_number("version")?,
+            item: match reader.has("item") {
+                true => Some(reader.string("item")?),
+                false => None,
+            },
+            changes: serde_json::from_str(&changes)
+                .map_err(|_| unparsable("changes", changes.clone()))?,
+        })
+    }
+
+    // Goes in the transaction making the write it records
+    pub fn into_transact_write_item(&self, table_name: &str, pkey: String) -> TransactWriteItem {
+        let put = Put::builder()
+            .table_name(table_name)
+            .set_item(Some(self.into_attr_map(pkey)))
+            .build()
+            .unwrap(); // table name and item is always set so unwrap is safe
+        TransactWriteItem::builder().put(put).build()
+    }
+}
+
+fn to_fields<T: Serialize>(model: &T) -> serde_json::Map<String, serde_json::Value> {
+    match serde_json::to_value(model) {
+        Ok(serde_json::Value::Object(fields)) => fields,
+        _ => serde_json::Map::new(),
+    }
+}
+
+// Every history record of the entity at `pkey`, newest first
+pub(crate) fn history_query(pkey: String) -> IndexQuery {
+    IndexQuery::table(pkey)
+        .sort_key(SortKeyCondition::BeginsWith(HISTORY_PREFIX.to_string()))
+        .descending()
+}
+
+// Run by the purge once it has claimed the tombstone and before deleting it, see soft_delete
+pub(crate) async fn delete_history(
+    persistance_repository: &DynamoDBSingleTableRepository,
+    pkey: String,
+) -> Result<(), HexagonalError> {
+    let query = history_query(pkey).project(&["Pkey", "Skey"]);
+    let mut page_request = PageRequest::all();
+    loop {
+        let page = persistance_repository
+            .query_index(&query, &page_request)
+            .await
+            .map_err(|e| e.into_hexagonal_error("Unable to delete history"))?;
+
+        let delete_requests: Vec<WriteRequest> = page
+            .items
+            .into_iter()
+            .map(|key| {
+                WriteRequest::builder()
+                    .delete_request(
+                        DeleteRequest::builder().set_key(Some(key)).build().unwrap(), // Key is always set so unwrap is safe
+                    )
+                    .build()
+            })
+            .collect();
+        persistance_repository
+            .batch_write(delete_requests)
+            .await
+            .map_err(|e| HexagonalError {
+                error: error::HexagonalErrorCode::AdaptorError,
+                message: "Unable to delete history".to_string(),
+                trace: e.to_string(),
+            })?;
+
+        match page.next_cursor {
+            Some(cursor) => page_request = page_request.next(cursor),
+            None => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Model {
+        name: String,
+        price: u32,
+        updated_at: String,
+        version: u64,
+    }
+
+    #[test]
+    fn test_history_record_only_holds_changed_fields() {
+        let before = Model {
+            name: "Widget".to_string(),
+            price: 100,
+            updated_at: "1".to_string(),
+            version: 1,
+        };
+        let after = Model {
+            name: "Widget".to_string(),
+            price: 150,
+            updated_at: "2".to_string(),
+            version: 2,
+        };
+
+        let record = HistoryRecord::new(HistoryAction::Updated, "someone", Some(&before), &after);
+        assert_eq!(record.changed_at, "2");
+        assert_eq!(record.version, 2);
+        assert_eq!(
+            record.changes,
+            vec![FieldChange {
+                field: "price".to_string(),
+                old: Some(serde_json::json!(100)),
+                new: Some(serde_json::json!(150)),
+            }]
+        );
+        assert_eq!(
+            HistoryRecord::from_attr_map(record.into_attr_map("MODEL#1".to_string())).unwrap(),
+            record
+        );
+
+        // nothing before a create, so every field is new
+        let created = HistoryRecord::new(HistoryAction::Created, "someone", None, &before);
+        assert_eq!(created.changes.len(), 2);
+        assert!(created.changes.iter().all(|change| change.old.is_none()));
+
+        // and a record of one of the entity's items keeps which
+        let of_item = created.clone().with_item("1");
+        assert_ne!(of_item.skey(), created.skey());
+        assert_eq!(
+            HistoryRecord::from_attr_map(of_item.into_attr_map("MODEL#1".to_string())).unwrap(),
+            of_item
+        );
+    }
+}
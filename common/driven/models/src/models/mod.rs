@@ -1,3 +1,6 @@
 pub mod cart;
+pub mod history;
+pub mod outbox;
 pub mod product;
+pub mod product_cache;
 pub mod user;
@@ -0,0 +1,243 @@
+// Outbox Access Patterns
+// 1. Write an event in the same transaction as the entity change that caused it
+// 2. Get pending (unsent) events, oldest first to the second (EventBridge does not keep order anyway)
+// 3. Mark an event as sent
+
+// Model:
+// Pkey = OUTBOX#<id>
+// Skey = -
+// GSI2-Pkey = OUTBOX#PENDING (removed once sent)
+// GSI2-Skey = <created_at>#<id>
+// TimeToExist = sent_at + retention, so sent events are cleaned up by the table TTL
+//
+// The outbox is shared by every tenant so one relay publishes them all, each item naming the
+// tenant whose change it was. Its partitions are the repository's shared ones, written as they are
+// whichever tenant the write is for
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use aws_sdk_dynamodb::types::{AttributeValue, Put, TransactWriteItem};
+use error::HexagonalError;
+use mockall::automock;
+use persistance_repository::{
+    DynamoDBSingleTableRepository, GSIs, IndexQuery, MeasuredSend, UpdateExpression,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{decode_or_skip, default_time, new_uuid, DecodeError, DynamoDbModel};
+
+const SENT_RETENTION_SECONDS: u64 = 7 * 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, DynamoDbModel)]
+#[dynamo(
+    pkey = "OUTBOX#{id}",
+    skey = "-",
+    gsi2_pkey = "OUTBOX#PENDING",
+    gsi2_skey = "{created_at}#{id}"
+)]
+pub struct OutboxItem {
+    pub id: String,
+    pub event_type: String,
+    pub version: u32,
+    // the serialised event, published as is
+    pub detail: String,
+    #[dynamo(n)]
+    pub created_at: String,
+    // empty for the default tenant
+    #[serde(default)]
+    #[dynamo(default)]
+    pub tenant: String,
+}
+
+// Builds the event for an entity as it will be once the write commits. Repository adaptors call
+// it inside the write so the event and the change land in the same transaction.
+pub type OutboxEvent<T> = fn(&T) -> OutboxItem;
+
+impl OutboxItem {
+    pub fn new(event_type: String, version: u32, detail: String) -> Self {
+        Self {
+            id: new_uuid(),
+            event_type,
+            version,
+            detail,
+            created_at: default_time(),
+            tenant: String::new(),
+        }
+    }
+
+    // Written for the repository's tenant, whatever the event was built with, and naming what
+    // caused it, see Correlation
+    pub fn into_transact_write_item(
+        &self,
+        repository: &DynamoDBSingleTableRepository,
+    ) -> TransactWriteItem {
+        let item = OutboxItem {
+            tenant: repository.tenant().id().unwrap_or_default().to_string(),
+            detail: repository.correlation().stamp(&self.detail),
+            ..self.clone()
+        };
+        let put = Put::builder()
+            .table_name(&repository.table_name)
+            .set_item(Some(item.into_attr_map()))
+            .condition_expression("attribute_not_exists(Pkey)")
+            .build()
+            .unwrap(); // table name and item is always set so unwrap is safe
+        TransactWriteItem::builder().put(put).build()
+    }
+
+    // For readers outside the adaptor, e.g. table stream records, which see every item type
+    pub fn from_item(
+        item: HashMap<String, AttributeValue>,
+    ) -> Option<Result<OutboxItem, DecodeError>> {
+        match item.get("Pkey").and_then(|p_key| p_key.as_s().ok()) {
+            Some(p_key) if p_key.starts_with("OUTBOX#") => Some(OutboxItem::from_attr_map(item)),
+            _ => None,
+        }
+    }
+}
+
+#[automock]
+#[async_trait]
+pub trait OutboxRepositoryPort {
+    async fn outbox_get_pending(&self, limit: i32) -> Result<Vec<OutboxItem>, HexagonalError>;
+    async fn outbox_mark_sent(&self, item: &OutboxItem) -> Result<(), HexagonalError>;
+}
+
+pub struct OutboxRepositoryAdaptor<'a> {
+    persistance_repository: &'a DynamoDBSingleTableRepository,
+}
+
+impl<'a> OutboxRepositoryAdaptor<'a> {
+    pub fn new(
+        persistance_repository: &'a DynamoDBSingleTableRepository,
+    ) -> OutboxRepositoryAdaptor<'a> {
+        OutboxRepositoryAdaptor {
+            persistance_repository,
+        }
+    }
+}
+
+#[async_trait]
+impl<'a> OutboxRepositoryPort for OutboxRepositoryAdaptor<'a> {
+    async fn outbox_get_pending(&self, limit: i32) -> Result<Vec<OutboxItem>, HexagonalError> {
+        let result = self
+            .persistance_repository
+            .index_query(&IndexQuery::gsi(GSIs::GSI2, OutboxItem::gsi2_pkey()))
+            .limit(limit)
+            .send_measured(self.persistance_repository)
+            .await;
+
+        match result {
+            Ok(output) => Ok(output
+                .items
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(decode_or_skip(
+                    self.persistance_repository,
+                    OutboxItem::from_attr_map,
+                ))
+                .collect::<Vec<OutboxItem>>()),
+            Err(e) => Err(HexagonalError {
+                error: error::HexagonalErrorCode::AdaptorError,
+                message: "Unable to get pending outbox events".to_string(),
+                trace: e.to_string(),
+            }),
+        }
+    }
+
+    async fn outbox_mark_sent(&self, item: &OutboxItem) -> Result<(), HexagonalError> {
+        let sent_at = default_time();
+        let expires_at = sent_at.parse::<u64>().unwrap_or_default() + SENT_RETENTION_SECONDS;
+
+        let result = self
+            .persistance_repository
+            .update_item(
+                OutboxItem::pkey(&item.id),
+                "-".to_string(),
+                UpdateExpression::new()
+                    .set("sent_at", AttributeValue::N(sent_at))
+                    .set("TimeToExist", AttributeValue::N(expires_at.to_string()))
+                    .remove("GSI2Pkey")
+                    .remove("GSI2Skey"),
+            )
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => match e.is_conditional_check_failed_exception() {
+                true => Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to mark outbox event sent, does not exist".to_string(),
+                    trace: "".to_string(),
+                }),
+                false => Err(HexagonalError {
+                    error: error::HexagonalErrorCode::AdaptorError,
+                    message: "Unable to mark outbox event sent".to_string(),
+                    trace: e.to_string(),
+                }),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use in_memory_persistance_repository::InMemorySingleTable;
+    use persistance_repository::{Correlation, Tenant};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_outbox_pending_until_marked_sent() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = OutboxRepositoryAdaptor::new(&repository);
+        // pending events of every tenant are published together
+        let tenant_repository = repository.for_tenant(Tenant::new("acme").unwrap());
+        let item = OutboxItem::new("Test".to_string(), 1, "{}".to_string());
+        tenant_repository
+            .transaction()
+            .operation((), item.into_transact_write_item(&tenant_repository))
+            .send()
+            .await
+            .unwrap();
+        let item = OutboxItem {
+            tenant: "acme".to_string(),
+            ..item
+        };
+
+        assert_eq!(
+            adaptor.outbox_get_pending(10).await.unwrap(),
+            vec![item.clone()]
+        );
+        adaptor.outbox_mark_sent(&item).await.unwrap();
+        assert!(adaptor.outbox_get_pending(10).await.unwrap().is_empty());
+
+        let missing = OutboxItem::new("Test".to_string(), 1, "{}".to_string());
+        assert_eq!(
+            adaptor.outbox_mark_sent(&missing).await.unwrap_err().error,
+            error::HexagonalErrorCode::NotFound
+        );
+        assert_eq!(OutboxItem::from_item(item.into_attr_map()), Some(Ok(item)));
+    }
+
+    #[tokio::test]
+    async fn test_outbox_item_names_what_caused_it() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = OutboxRepositoryAdaptor::new(&repository);
+        let request_repository = repository
+            .for_tenant(Tenant::default())
+            .with_correlation(Correlation::new(Some("request-1".to_string()), None));
+        let item = OutboxItem::new("Test".to_string(), 1, "{}".to_string());
+        request_repository
+            .transaction()
+            .operation((), item.into_transact_write_item(&request_repository))
+            .send()
+            .await
+            .unwrap();
+
+        let pending = adaptor.outbox_get_pending(10).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].detail, r#"{"correlation_id":"request-1"}"#);
+    }
+}
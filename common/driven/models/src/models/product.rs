@@ -1,15 +1,31 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
 use async_trait::async_trait;
-use aws_sdk_dynamodb::types::{AttributeValue, KeysAndAttributes};
+use aws_sdk_dynamodb::types::AttributeValue;
 use error::HexagonalError;
 use mockall::automock;
-use persistance_repository::DynamoDBSingleTableRepository;
+use persistance_repository::{
+    CancellationCode, DynamoDBSingleTableRepository, Page, PageRequest, Tenant, TenantScoped,
+    TransactionError,
+};
 use serde::{Deserialize, Serialize};
 
-use crate::{default_time, new_uuid, DynamoDbModel};
+use super::history::{delete_history, history_query, HistoryAction, HistoryRecord};
+use super::outbox::OutboxEvent;
+use crate::soft_delete::{
+    expired_tombstones_query, into_purging, into_tombstone, is_tombstone, within_retention,
+};
+use crate::{
+    changed_since_read_error, check_expected_version, decode_or_skip, default_time,
+    delete_if_unchanged, initial_version, new_uuid, put_if_unchanged, DynamoDbModel,
+};
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+// GSI2 partition of deleted products, see soft_delete
+const PRODUCT_DELETED_PARTITION: &str = "PRODUCT#DELETED";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, DynamoDbModel)]
+#[dynamo(pkey = "PRODUCT#{id}", skey = "-")]
 pub struct Product {
     #[serde(default = "new_uuid")]
     pub id: String,
@@ -17,9 +33,14 @@ pub struct Product {
     pub price_cents: i32,
     pub description: String,
     #[serde(default = "default_time")]
+    #[dynamo(n)]
     pub created_at: String,
     #[serde(default = "default_time")]
+    #[dynamo(n)]
     pub updated_at: String,
+    #[serde(default = "initial_version")]
+    #[dynamo(default)]
+    pub version: u64,
 }
 
 impl Product {
@@ -31,80 +52,11 @@ impl Product {
             description,
             created_at: default_time(),
             updated_at: default_time(),
+            version: initial_version(),
         }
     }
 }
 
-impl DynamoDbModel for Product {
-    fn from_attr_map(attr_map: std::collections::HashMap<String, AttributeValue>) -> Self {
-        Product {
-            id: attr_map.get("id").unwrap().as_s().unwrap().to_string(),
-            product_name: attr_map
-                .get("product_name")
-                .unwrap()
-                .as_s()
-                .unwrap()
-                .to_string(),
-            price_cents: attr_map
-                .get("price_cents")
-                .unwrap()
-                .as_n()
-                .unwrap()
-                .parse::<i32>()
-                .unwrap(),
-            description: attr_map
-                .get("description")
-                .unwrap()
-                .as_s()
-                .unwrap()
-                .to_string(),
-            created_at: attr_map
-                .get("created_at")
-                .unwrap()
-                .as_n()
-                .unwrap()
-                .to_string(),
-            updated_at: attr_map
-                .get("updated_at")
-                .unwrap()
-                .as_n()
-                .unwrap()
-                .to_string(),
-        }
-    }
-
-    fn into_attr_map(&self) -> std::collections::HashMap<String, AttributeValue> {
-        let mut attr_map = std::collections::HashMap::new();
-        attr_map.insert(
-            "Pkey".to_string(),
-            AttributeValue::S("PRODUCT#".to_string() + &self.id.to_string()),
-        );
-        attr_map.insert("Skey".to_string(), AttributeValue::S("-".to_string()));
-        attr_map.insert("id".to_string(), AttributeValue::S(self.id.to_string()));
-        attr_map.insert(
-            "product_name".to_string(),
-            AttributeValue::S(self.product_name.to_string()),
-        );
-        attr_map.insert(
-            "price_cents".to_string(),
-            AttributeValue::N(self.price_cents.to_string()),
-        );
-        attr_map.insert(
-            "description".to_string(),
-            AttributeValue::S(self.description.to_string()),
-        );
-        attr_map.insert(
-            "created_at".to_string(),
-            AttributeValue::N(self.created_at.to_string()),
-        );
-        attr_map.insert(
-            "updated_at".to_string(),
-            AttributeValue::N(self.updated_at.to_string()),
-        );
-        attr_map
-    }
-}
-
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct MutableProduct {
     pub product_name: Option<String>,
@@ -113,28 +65,22 @@ pub struct MutableProduct {
 }
 
 impl MutableProduct {
-    pub fn into_attr_map(&self) -> std::collections::HashMap<String, AttributeValue> {
-        let mut attr_map = std::collections::HashMap::new();
-        if self.product_name.is_some() {
-            attr_map.insert(
-                ":product_name".to_string(),
-                AttributeValue::S(self.product_name.clone().unwrap()),
-            );
-        }
-        if self.price_cents.is_some() {
-            attr_map.insert(
-                ":price_cents".to_string(),
-                AttributeValue::N(self.price_cents.unwrap().to_string()),
-            );
-        }
-        if self.description.is_some() {
-            attr_map.insert(
-                ":description".to_string(),
-                AttributeValue::S(self.description.clone().unwrap().to_string()),
-            );
+    // The product as it will be once this update is written
+    pub fn apply_to(&self, product: &Product) -> Product {
+        Product {
+            product_name: self
+                .product_name
+                .clone()
+                .unwrap_or(product.product_name.clone()),
+            price_cents: self.price_cents.unwrap_or(product.price_cents),
+            description: self
+                .description
+                .clone()
+                .unwrap_or(product.description.clone()),
+            updated_at: default_time(),
+            version: product.version + 1,
+            ..product.clone()
         }
-        attr_map.insert(":updated_at".to_string(), AttributeValue::N(default_time()));
-        attr_map
     }
 }
 
@@ -144,41 +90,92 @@ impl MutableProduct {
 pub trait ProductRepositoryPort {
     async fn product_get_by_id(&self, id: &String) -> Result<Option<Product>, HexagonalError>;
     async fn product_get_by_ids(&self, id: &Vec<String>) -> Result<Vec<Product>, HexagonalError>;
-    async fn product_create(&self, product: &Product) -> Result<Product, HexagonalError>;
+    async fn product_create(
+        &self,
+        product: &Product,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Product, HexagonalError>;
     async fn product_update_by_id(
         &self,
         id: &String,
         product_update: &MutableProduct,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Product, HexagonalError>;
+    // Soft deletes, carts keep the product until it is purged
+    async fn product_delete_by_id(
+        &self,
+        id: &String,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
     ) -> Result<Product, HexagonalError>;
-    async fn product_delete_by_id(&self, id: &String) -> Result<Product, HexagonalError>;
+    // Brings back a deleted product within the retention window
+    async fn product_restore_by_id(
+        &self,
+        id: &String,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Product, HexagonalError>;
+    // Removes up to `limit` products whose retention window has passed for good, returning them
+    async fn product_purge_deleted(
+        &self,
+        limit: i32,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Vec<Product>, HexagonalError>;
+    // Every write made to the product, newest first. Kept through a soft delete, gone once purged
+    async fn product_history_by_id(
+        &self,
+        id: &String,
+        page: &PageRequest,
+    ) -> Result<Page<HistoryRecord>, HexagonalError>;
 }
 
 pub struct ProductRepositoryAdaptor<'a> {
     persistance_repository: &'a DynamoDBSingleTableRepository,
+    delete_retention: Duration,
 }
 
 impl<'a> ProductRepositoryAdaptor<'a> {
+    // The retention window comes from the repository's config
     pub fn new(
         persistance_repository: &'a DynamoDBSingleTableRepository,
     ) -> ProductRepositoryAdaptor<'a> {
         ProductRepositoryAdaptor {
             persistance_repository,
+            delete_retention: persistance_repository.config.limits.delete_retention,
+        }
+    }
+
+    pub fn with_delete_retention(self, delete_retention: Duration) -> ProductRepositoryAdaptor<'a> {
+        ProductRepositoryAdaptor {
+            delete_retention,
+            ..self
         }
     }
 }
 
+// The repository's, every read and write goes out for it
+impl<'a> TenantScoped for ProductRepositoryAdaptor<'a> {
+    fn tenant(&self) -> &Tenant {
+        self.persistance_repository.tenant()
+    }
+}
+
 #[async_trait]
 impl<'a> ProductRepositoryPort for ProductRepositoryAdaptor<'a> {
     async fn product_get_by_id(&self, id: &String) -> Result<Option<Product>, HexagonalError> {
         let result = self
             .persistance_repository
-            .get_item_primary("PRODUCT#".to_string() + id, "-".to_string())
+            .get_item_primary(Product::pkey(id), Product::skey())
             .await;
 
         match result {
             Ok(result) => match result.item {
-                Some(item) => Ok(Some(Product::from_attr_map(item))),
-                None => Ok(None),
+                Some(item) if !is_tombstone(&item) => Ok(Some(Product::from_attr_map(item)?)),
+                _ => Ok(None),
             },
             Err(e) => Err(HexagonalError {
                 error: error::HexagonalErrorCode::AdaptorError,
@@ -189,48 +186,21 @@ impl<'a> ProductRepositoryPort for ProductRepositoryAdaptor<'a> {
     }
 
     async fn product_get_by_ids(&self, id: &Vec<String>) -> Result<Vec<Product>, HexagonalError> {
-        let get_item_key_vec = id
+        let keys = id
             .iter()
-            .map(|id| {
-                let mut key = HashMap::new();
-                key.insert(
-                    "Pkey".to_string(),
-                    AttributeValue::S("PRODUCT#".to_string() + id),
-                );
-                key.insert("Skey".to_string(), AttributeValue::S("-".to_string()));
-                key
-            })
+            .map(|id| Product::primary_key(id))
             .collect::<Vec<HashMap<String, AttributeValue>>>();
 
-        let keys_and_attributes = KeysAndAttributes::builder()
-            .set_keys(Some(get_item_key_vec))
-            .build()
-            .unwrap(); // Key is always set so unwrap is safe
-
-        let mut request_items = HashMap::new();
-        request_items.insert(
-            self.persistance_repository.table_name.clone(),
-            keys_and_attributes,
-        );
-
-        let result = self
-            .persistance_repository
-            .client
-            .batch_get_item()
-            .set_request_items(Some(request_items))
-            .send()
-            .await;
-
-        match result {
-            Ok(result) => match result.responses {
-                Some(item) => Ok(item
-                    .get(&self.persistance_repository.table_name)
-                    .unwrap()
-                    .iter()
-                    .map(|item| Product::from_attr_map(item.clone()))
-                    .collect()),
-                None => Ok(Vec::new()),
-            },
+        match self.persistance_repository.batch_get(keys).await {
+            Ok(items) => Ok(items
+                .into_iter()
+                .filter(|item| !is_tombstone(item))
+                .filter_map(decode_or_skip(
+                    self.persistance_repository,
+                    Product::from_attr_map,
+                ))
+                .collect::<Vec<Product>>()),
+            // a partial list would look like the missing products don't exist
             Err(e) => Err(HexagonalError {
                 error: error::HexagonalErrorCode::AdaptorError,
                 message: "Unable to get product".to_string(),
@@ -239,97 +209,760 @@ impl<'a> ProductRepositoryPort for ProductRepositoryAdaptor<'a> {
         }
     }
 
-    async fn product_create(&self, product: &Product) -> Result<Product, HexagonalError> {
-        let result = self
-            .persistance_repository
-            .put_new_item(product.into_attr_map())
-            .await;
-
-        println!("result: {:?}", result);
+    async fn product_create(
+        &self,
+        product: &Product,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Product, HexagonalError> {
+        let table_name = &self.persistance_repository.table_name;
+        let product_put = aws_sdk_dynamodb::types::Put::builder()
+            .table_name(table_name.clone())
+            .set_item(Some(product.into_attr_map()))
+            .condition_expression("attribute_not_exists(Pkey) AND attribute_not_exists(Skey)")
+            .build()
+            .unwrap(); // table name and item is always set so unwrap is safe
 
-        match result {
-            Ok(_) => Ok(product.clone()),
-            Err(e) => Err(HexagonalError {
-                error: error::HexagonalErrorCode::AdaptorError,
-                message: "Unable to create product".to_string(),
-                trace: e.to_string(),
-            }),
-        }
+        self.persistance_repository
+            .transaction()
+            .put(ProductWrite::Product, product_put)
+            .operation(
+                ProductWrite::History,
+                HistoryRecord::new(HistoryAction::Created, principal, None, product)
+                    .into_transact_write_item(table_name, Product::pkey(&product.id)),
+            )
+            .operation(
+                ProductWrite::Outbox,
+                outbox_event(product).into_transact_write_item(self.persistance_repository),
+            )
+            .send()
+            .await
+            .map_err(|err| match err.condition_failed(&ProductWrite::Product) {
+                true => HexagonalError {
+                    error: error::HexagonalErrorCode::Conflict,
+                    message: "Unable to create product, already exists".to_string(),
+                    trace: err.to_string(),
+                },
+                false => product_write_error("create product", err, None),
+            })
+            .map(|_| product.clone())
     }
 
     async fn product_update_by_id(
         &self,
         id: &String,
         product_update: &MutableProduct,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Product, HexagonalError> {
+        let read = match self.get_product_item(id).await? {
+            Some(read) if !is_tombstone(&read) => read,
+            _ => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to update product, does not exist".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+        };
+
+        let current = Product::from_attr_map(read.clone())?;
+        check_expected_version(
+            expected_version,
+            current.version,
+            "Unable to update product, version does not match",
+        )?;
+        let updated = product_update.apply_to(&current);
+        let table_name = &self.persistance_repository.table_name;
+
+        self.persistance_repository
+            .transaction()
+            .operation(
+                ProductWrite::Product,
+                put_if_unchanged(table_name, &read, updated.into_attr_map()),
+            )
+            .operation(
+                ProductWrite::History,
+                HistoryRecord::new(HistoryAction::Updated, principal, Some(&current), &updated)
+                    .into_transact_write_item(table_name, Product::pkey(id)),
+            )
+            .operation(
+                ProductWrite::Outbox,
+                outbox_event(&updated).into_transact_write_item(self.persistance_repository),
+            )
+            .send()
+            .await
+            .map_err(|err| product_write_error("update product", err, expected_version))
+            .map(|_| updated)
+    }
+
+    async fn product_delete_by_id(
+        &self,
+        id: &String,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
     ) -> Result<Product, HexagonalError> {
-        let mut update_expression = "SET ".to_string();
-        let mut attr_names = HashMap::new();
+        let read = match self.get_product_item(id).await? {
+            Some(read) if !is_tombstone(&read) => read,
+            _ => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to delete product, does not exist".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+        };
 
-        if product_update.product_name.is_some() {
-            update_expression.push_str("#name_key = :product_name, ");
-            attr_names.insert("#name_key".to_string(), "product_name".to_string());
+        let current = Product::from_attr_map(read.clone())?;
+        check_expected_version(
+            expected_version,
+            current.version,
+            "Unable to delete product, version does not match",
+        )?;
+        let deleted = Product {
+            updated_at: default_time(),
+            version: current.version + 1,
+            ..current.clone()
+        };
+        let tombstone = into_tombstone(
+            deleted.into_attr_map(),
+            PRODUCT_DELETED_PARTITION,
+            id,
+            &deleted.updated_at,
+        );
+        let table_name = &self.persistance_repository.table_name;
+
+        self.persistance_repository
+            .transaction()
+            .operation(
+                ProductWrite::Product,
+                put_if_unchanged(table_name, &read, tombstone),
+            )
+            .operation(
+                ProductWrite::History,
+                HistoryRecord::new(HistoryAction::Deleted, principal, Some(&current), &deleted)
+                    .into_transact_write_item(table_name, Product::pkey(id)),
+            )
+            .operation(
+                ProductWrite::Outbox,
+                outbox_event(&deleted).into_transact_write_item(self.persistance_repository),
+            )
+            .send()
+            .await
+            .map_err(|err| product_write_error("delete product", err, expected_version))
+            .map(|_| deleted)
+    }
+
+    async fn product_restore_by_id(
+        &self,
+        id: &String,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Product, HexagonalError> {
+        let read = match self.get_product_item(id).await? {
+            Some(read) => read,
+            None => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to restore product, does not exist".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+        };
+        if !is_tombstone(&read) {
+            return Err(HexagonalError {
+                error: error::HexagonalErrorCode::Conflict,
+                message: "Unable to restore product, not deleted".to_string(),
+                trace: "".to_string(),
+            });
+        }
+        if !within_retention(&read, self.delete_retention) {
+            return Err(HexagonalError {
+                error: error::HexagonalErrorCode::NotFound,
+                message: "Unable to restore product, retention window has passed".to_string(),
+                trace: "".to_string(),
+            });
         }
 
-        if product_update.price_cents.is_some() {
-            update_expression.push_str("#price_cents_key = :price_cents, ");
-            attr_names.insert("#price_cents_key".to_string(), "price_cents".to_string());
+        let deleted = Product::from_attr_map(read.clone())?;
+        let restored = Product {
+            updated_at: default_time(),
+            version: deleted.version + 1,
+            ..deleted.clone()
+        };
+        let table_name = &self.persistance_repository.table_name;
+
+        self.persistance_repository
+            .transaction()
+            .operation(
+                ProductWrite::Product,
+                put_if_unchanged(table_name, &read, restored.into_attr_map()),
+            )
+            .operation(
+                ProductWrite::History,
+                HistoryRecord::new(
+                    HistoryAction::Restored,
+                    principal,
+                    Some(&deleted),
+                    &restored,
+                )
+                .into_transact_write_item(table_name, Product::pkey(id)),
+            )
+            .operation(
+                ProductWrite::Outbox,
+                outbox_event(&restored).into_transact_write_item(self.persistance_repository),
+            )
+            .send()
+            .await
+            .map_err(|err| product_write_error("restore product", err, None))
+            .map(|_| restored)
+    }
+
+    async fn product_purge_deleted(
+        &self,
+        limit: i32,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Vec<Product>, HexagonalError> {
+        let expired = self
+            .persistance_repository
+            .query_index(
+                &expired_tombstones_query(PRODUCT_DELETED_PARTITION, self.delete_retention),
+                &PageRequest::new(Some(limit), None),
+            )
+            .await
+            .map_err(|e| e.into_hexagonal_error("Unable to purge products"))?;
+
+        let mut purged = Vec::new();
+        for read in expired.items {
+            if let Some(deleted) = self.purge_tombstone(read, outbox_event).await? {
+                purged.push(deleted);
+            }
         }
+        Ok(purged)
+    }
 
-        if product_update.description.is_some() {
-            update_expression.push_str("#description_key = :description, ");
-            attr_names.insert("#description_key".to_string(), "description".to_string());
+    async fn product_history_by_id(
+        &self,
+        id: &String,
+        page: &PageRequest,
+    ) -> Result<Page<HistoryRecord>, HexagonalError> {
+        if self.get_product_item(id).await?.is_none() {
+            return Err(HexagonalError {
+                error: error::HexagonalErrorCode::NotFound,
+                message: "Unable to get product history, does not exist".to_string(),
+                trace: "".to_string(),
+            });
         }
+        Ok(self
+            .persistance_repository
+            .query_index(&history_query(Product::pkey(id)), page)
+            .await
+            .map_err(|e| e.into_hexagonal_error("Unable to get product history"))?
+            .filter_map(decode_or_skip(
+                self.persistance_repository,
+                HistoryRecord::from_attr_map,
+            )))
+    }
+}
 
-        update_expression.push_str("updated_at = :updated_at");
+impl<'a> ProductRepositoryAdaptor<'a> {
+    // None when the tombstone was restored, or purged by another run, since it was read
+    async fn purge_tombstone(
+        &self,
+        read: HashMap<String, AttributeValue>,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Option<Product>, HexagonalError> {
+        let deleted = Product::from_attr_map(read.clone())?;
+        let table_name = &self.persistance_repository.table_name;
 
+        let claimed = into_purging(read.clone());
         let result = self
             .persistance_repository
-            .update_item(
-                "PRODUCT#".to_string() + id,
-                "-".to_string(),
-                update_expression,
-                Some(attr_names),
-                Some(product_update.into_attr_map()),
+            .transaction()
+            .operation(
+                ProductWrite::Product,
+                put_if_unchanged(table_name, &read, claimed.clone()),
             )
+            .send()
             .await;
-
         match result {
-            Ok(output) => Ok(Product::from_attr_map(output.attributes.unwrap())),
-            Err(e) => match e.is_conditional_check_failed_exception() {
-                true => Err(HexagonalError {
-                    error: error::HexagonalErrorCode::NotFound,
-                    message: "Unable to update product, does not exist".to_string(),
-                    trace: "".to_string(),
-                }),
-                false => Err(HexagonalError {
-                    error: error::HexagonalErrorCode::AdaptorError,
-                    message: "Unable to update product".to_string(),
-                    trace: e.to_string(),
-                }),
-            },
+            Ok(_) => {}
+            Err(err) if err.condition_failed(&ProductWrite::Product) => return Ok(None),
+            Err(err) => return Err(product_write_error("purge product", err, None)),
         }
-    }
 
-    async fn product_delete_by_id(&self, id: &String) -> Result<Product, HexagonalError> {
+        delete_history(self.persistance_repository, Product::pkey(&deleted.id)).await?;
         let result = self
             .persistance_repository
-            .delete_item("PRODUCT#".to_string() + id, "-".to_string())
+            .transaction()
+            .operation(
+                ProductWrite::Product,
+                delete_if_unchanged(table_name, &claimed),
+            )
+            .operation(
+                ProductWrite::Outbox,
+                outbox_event(&deleted).into_transact_write_item(self.persistance_repository),
+            )
+            .send()
             .await;
-
         match result {
-            Ok(output) => match output.attributes {
-                Some(attributes) => Ok(Product::from_attr_map(attributes)),
-                None => Err(HexagonalError {
-                    error: error::HexagonalErrorCode::NotFound,
-                    message: "Unable to delete product, does not exist".to_string(),
-                    trace: "".to_string(),
-                }),
-            },
-            Err(e) => Err(HexagonalError {
+            Ok(_) => Ok(Some(deleted)),
+            Err(err) if err.condition_failed(&ProductWrite::Product) => Ok(None),
+            Err(err) => Err(product_write_error("purge product", err, None)),
+        }
+    }
+
+    async fn get_product_item(
+        &self,
+        id: &str,
+    ) -> Result<Option<HashMap<String, AttributeValue>>, HexagonalError> {
+        self.persistance_repository
+            .get_item_primary(Product::pkey(id), Product::skey())
+            .await
+            .map(|output| output.item)
+            .map_err(|e| HexagonalError {
                 error: error::HexagonalErrorCode::AdaptorError,
-                message: "Unable to delete product".to_string(),
+                message: "Unable to get product".to_string(),
                 trace: e.to_string(),
-            }),
+            })
+    }
+}
+
+// The operations product writes are made of, so a cancelled transaction says which one failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProductWrite {
+    Product,
+    History,
+    Outbox,
+}
+
+fn product_write_error(
+    action: &str,
+    err: TransactionError<ProductWrite>,
+    expected_version: Option<u64>,
+) -> HexagonalError {
+    match err.cancellation(&ProductWrite::Product) {
+        // the condition was checked against nothing, the product was purged after we read it
+        Some(cancellation)
+            if cancellation.code == CancellationCode::ConditionalCheckFailed
+                && cancellation.item.is_none() =>
+        {
+            HexagonalError {
+                error: error::HexagonalErrorCode::NotFound,
+                message: format!("Unable to {}, product no longer exists", action),
+                trace: err.to_string(),
+            }
         }
+        Some(cancellation) if cancellation.code == CancellationCode::ConditionalCheckFailed => {
+            changed_since_read_error(
+                expected_version,
+                &format!("Unable to {}, it was changed by another request", action),
+                err.to_string(),
+            )
+        }
+        _ => HexagonalError {
+            error: error::HexagonalErrorCode::AdaptorError,
+            message: format!("Unable to {}, error in transaction write call", action),
+            trace: err.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use in_memory_persistance_repository::InMemorySingleTable;
+
+    use super::*;
+    use crate::models::history::FieldChange;
+    use crate::models::outbox::{OutboxItem, OutboxRepositoryAdaptor, OutboxRepositoryPort};
+    use crate::DecodeError;
+
+    fn test_event(product: &Product) -> OutboxItem {
+        OutboxItem::new("ProductTest".to_string(), 1, product.id.clone())
+    }
+
+    #[tokio::test]
+    async fn test_product_lifecycle_against_in_memory_table() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = ProductRepositoryAdaptor::new(&repository);
+        let outbox = OutboxRepositoryAdaptor::new(&repository);
+        let product = Product::new("Widget".to_string(), 1000, "A widget".to_string());
+
+        adaptor
+            .product_create(&product, "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(
+            adaptor.product_get_by_id(&product.id).await.unwrap(),
+            Some(product.clone())
+        );
+
+        let updated = adaptor
+            .product_update_by_id(
+                &product.id,
+                &MutableProduct {
+                    product_name: None,
+                    price_cents: Some(1500),
+                    description: None,
+                },
+                None,
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        assert_eq!(updated.price_cents, 1500);
+        assert_eq!(updated.product_name, product.product_name);
+
+        let found = adaptor
+            .product_get_by_ids(&vec![product.id.clone(), "missing".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(found, vec![updated]);
+
+        adaptor
+            .product_delete_by_id(&product.id, None, "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(adaptor.product_get_by_id(&product.id).await.unwrap(), None);
+        assert_eq!(
+            adaptor
+                .product_delete_by_id(&product.id, None, "tester", test_event)
+                .await
+                .unwrap_err()
+                .error,
+            error::HexagonalErrorCode::NotFound
+        );
+        // one event per successful write, none for the failed delete
+        assert_eq!(outbox.outbox_get_pending(10).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_product_soft_delete_restore_and_purge() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = ProductRepositoryAdaptor::new(&repository);
+        let expired =
+            ProductRepositoryAdaptor::new(&repository).with_delete_retention(Duration::ZERO);
+        let product = Product::new("Widget".to_string(), 1000, "A widget".to_string());
+        adaptor
+            .product_create(&product, "tester", test_event)
+            .await
+            .unwrap();
+
+        let deleted = adaptor
+            .product_delete_by_id(&product.id, None, "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(deleted.version, 2);
+        assert!(adaptor
+            .product_get_by_ids(&vec![product.id.clone()])
+            .await
+            .unwrap()
+            .is_empty());
+
+        let restored = adaptor
+            .product_restore_by_id(&product.id, "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(restored.version, 3);
+        assert_eq!(
+            adaptor.product_get_by_id(&product.id).await.unwrap(),
+            Some(restored)
+        );
+        let err = adaptor
+            .product_restore_by_id(&product.id, "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::Conflict);
+
+        // still within the default window, so nothing to purge yet
+        adaptor
+            .product_delete_by_id(&product.id, None, "tester", test_event)
+            .await
+            .unwrap();
+        assert!(adaptor
+            .product_purge_deleted(10, test_event)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let err = expired
+            .product_restore_by_id(&product.id, "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::NotFound);
+        assert_eq!(
+            err.message,
+            "Unable to restore product, retention window has passed"
+        );
+        let purged = expired.product_purge_deleted(10, test_event).await.unwrap();
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].version, 4);
+        let err = adaptor
+            .product_restore_by_id(&product.id, "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(err.message, "Unable to restore product, does not exist");
+    }
+
+    #[tokio::test]
+    async fn test_product_purge_leaves_a_restored_product_alone() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = ProductRepositoryAdaptor::new(&repository);
+        let expired =
+            ProductRepositoryAdaptor::new(&repository).with_delete_retention(Duration::ZERO);
+        let product = Product::new("Widget".to_string(), 1000, "A widget".to_string());
+        adaptor
+            .product_create(&product, "tester", test_event)
+            .await
+            .unwrap();
+        adaptor
+            .product_delete_by_id(&product.id, None, "tester", test_event)
+            .await
+            .unwrap();
+
+        // the purge reads the tombstone, then it's restored by a caller with a longer window
+        let read = expired
+            .get_product_item(&product.id)
+            .await
+            .unwrap()
+            .unwrap();
+        adaptor
+            .product_restore_by_id(&product.id, "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(
+            expired.purge_tombstone(read, test_event).await.unwrap(),
+            None
+        );
+        assert!(adaptor
+            .product_get_by_id(&product.id)
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            adaptor
+                .product_history_by_id(&product.id, &PageRequest::default())
+                .await
+                .unwrap()
+                .items
+                .len(),
+            3
+        );
+
+        // and once claimed it can't be restored, whatever the window
+        adaptor
+            .product_delete_by_id(&product.id, None, "tester", test_event)
+            .await
+            .unwrap();
+        let read = expired
+            .get_product_item(&product.id)
+            .await
+            .unwrap()
+            .unwrap();
+        let table_name = &repository.table_name;
+        repository
+            .transaction()
+            .operation(
+                (),
+                put_if_unchanged(table_name, &read, into_purging(read.clone())),
+            )
+            .send()
+            .await
+            .unwrap();
+        let err = adaptor
+            .product_restore_by_id(&product.id, "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.message,
+            "Unable to restore product, retention window has passed"
+        );
+        assert_eq!(
+            expired
+                .product_purge_deleted(10, test_event)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_product_history_records_each_write() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = ProductRepositoryAdaptor::new(&repository);
+        let product = Product::new("Widget".to_string(), 1000, "A widget".to_string());
+        adaptor
+            .product_create(&product, "creator", test_event)
+            .await
+            .unwrap();
+        adaptor
+            .product_update_by_id(
+                &product.id,
+                &MutableProduct {
+                    product_name: None,
+                    price_cents: Some(1500),
+                    description: None,
+                },
+                None,
+                "pricer",
+                test_event,
+            )
+            .await
+            .unwrap();
+        adaptor
+            .product_delete_by_id(&product.id, None, "deleter", test_event)
+            .await
+            .unwrap();
+
+        let first = adaptor
+            .product_history_by_id(&product.id, &PageRequest::new(Some(2), None))
+            .await
+            .unwrap();
+        // newest first, and still there while the product is only soft deleted
+        assert_eq!(
+            first
+                .items
+                .iter()
+                .map(|record| (record.action, record.version, record.principal.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (HistoryAction::Deleted, 3, "deleter"),
+                (HistoryAction::Updated, 2, "pricer")
+            ]
+        );
+        assert!(first.items[0].changes.is_empty());
+        assert_eq!(
+            first.items[1].changes,
+            vec![FieldChange {
+                field: "price_cents".to_string(),
+                old: Some(serde_json::json!(1000)),
+                new: Some(serde_json::json!(1500)),
+            }]
+        );
+        let rest = adaptor
+            .product_history_by_id(&product.id, &PageRequest::new(Some(2), first.next_cursor))
+            .await
+            .unwrap();
+        assert_eq!(rest.items.len(), 1);
+        assert_eq!(rest.items[0].action, HistoryAction::Created);
+
+        // purged along with the product
+        let expired =
+            ProductRepositoryAdaptor::new(&repository).with_delete_retention(Duration::ZERO);
+        expired.product_purge_deleted(10, test_event).await.unwrap();
+        let err = adaptor
+            .product_history_by_id(&product.id, &PageRequest::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::NotFound);
+        assert!(repository
+            .query_index(
+                &history_query(Product::pkey(&product.id)),
+                &PageRequest::default()
+            )
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_product_stale_version_is_precondition_failed() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = ProductRepositoryAdaptor::new(&repository);
+        let product = Product::new("Widget".to_string(), 1000, "A widget".to_string());
+        adaptor
+            .product_create(&product, "tester", test_event)
+            .await
+            .unwrap();
+        let update = MutableProduct {
+            product_name: None,
+            price_cents: Some(1500),
+            description: None,
+        };
+
+        let updated = adaptor
+            .product_update_by_id(&product.id, &update, Some(1), "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(updated.version, 2);
+
+        let stale = adaptor
+            .product_update_by_id(&product.id, &update, Some(1), "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(stale.error, error::HexagonalErrorCode::PreconditionFailed);
+        let stale = adaptor
+            .product_delete_by_id(&product.id, Some(1), "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(stale.error, error::HexagonalErrorCode::PreconditionFailed);
+
+        adaptor
+            .product_delete_by_id(&product.id, Some(2), "tester", test_event)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_product_update_missing_is_not_found() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = ProductRepositoryAdaptor::new(&repository);
+
+        let err = adaptor
+            .product_update_by_id(
+                &"missing".to_string(),
+                &MutableProduct {
+                    product_name: Some("Widget".to_string()),
+                    price_cents: None,
+                    description: None,
+                },
+                None,
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_product_malformed_item_is_adaptor_error() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = ProductRepositoryAdaptor::new(&repository);
+        let product = Product::new("Widget".to_string(), 100, "A widget".to_string());
+        let mut malformed = product.into_attr_map();
+        malformed.insert(
+            "price_cents".to_string(),
+            AttributeValue::S("cheap".to_string()),
+        );
+        repository.put_new_item(malformed).await.unwrap();
+
+        let err = adaptor.product_get_by_id(&product.id).await.unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::AdaptorError);
+        assert_eq!(
+            err.trace,
+            DecodeError::WrongType {
+                attribute: "price_cents".to_string(),
+                expected: "N",
+                key: format!("(Pkey: PRODUCT#{}, Skey: -)", product.id),
+            }
+            .to_string()
+        );
+
+        // only the request touching the bad item fails
+        let other = Product::new("Gadget".to_string(), 200, "A gadget".to_string());
+        adaptor
+            .product_create(&other, "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(
+            adaptor.product_get_by_id(&other.id).await.unwrap(),
+            Some(other)
+        );
     }
 }
@@ -0,0 +1,431 @@
+// Read-through cache in front of any ProductRepositoryPort
+// Products change rarely and are read on every product and cart page, so reads are served from
+// memory for a short while. The cache is made once per process, in main, so it survives warm
+// invocations, and each request wraps its own repository with it.
+//
+// Entries are keyed by the product's partition key, which already names the tenant, so one
+// tenant's products are never served to another.
+//
+// Past their TTL entries are refetched but kept until they are evicted, so when the table errors
+// or throttles the last known product is served rather than failing the read.
+//
+// Products are written by other functions, so the cache is invalidated from the events they
+// raise, see product_cache_events. Each event reaches the one instance it is delivered to, any
+// other warm instance serves the product it has until its TTL runs out.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use error::HexagonalError;
+use persistance_repository::{Page, PageRequest, Tenant, TenantScoped};
+
+use super::history::HistoryRecord;
+use super::outbox::OutboxEvent;
+use super::product::{MutableProduct, Product, ProductRepositoryPort};
+
+pub struct ProductCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    products: HashMap<String, Entry>,
+    // bumped on every use, the entry with the lowest is the least recently used
+    clock: u64,
+}
+
+struct Entry {
+    product: Product,
+    fetched_at: Instant,
+    used_at: u64,
+}
+
+enum Cached {
+    Fresh(Product),
+    Stale(Product),
+    Missing,
+}
+
+impl ProductCache {
+    // A capacity of 0 turns the cache off, every read goes to the repository
+    pub fn new(ttl: Duration, capacity: usize) -> ProductCache {
+        ProductCache {
+            ttl,
+            capacity,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    pub fn invalidate(&self, tenant: &Tenant, id: &str) {
+        self.lock().products.remove(&tenant.key(&Product::pkey(id)));
+    }
+
+    fn get(&self, tenant: &Tenant, id: &str) -> Cached {
+        let mut entries = self.lock();
+        entries.clock += 1;
+        let clock = entries.clock;
+        match entries.products.get_mut(&tenant.key(&Product::pkey(id))) {
+            Some(entry) => {
+                entry.used_at = clock;
+                match entry.fetched_at.elapsed() < self.ttl {
+                    true => Cached::Fresh(entry.product.clone()),
+                    false => Cached::Stale(entry.product.clone()),
+                }
+            }
+            None => Cached::Missing,
+        }
+    }
+
+    fn insert(&self, tenant: &Tenant, product: &Product) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.lock();
+        entries.clock += 1;
+        let entry = Entry {
+            product: product.clone(),
+            fetched_at: Instant::now(),
+            used_at: entries.clock,
+        };
+        entries
+            .products
+            .insert(tenant.key(&Product::pkey(&product.id)), entry);
+        while entries.products.len() > self.capacity {
+            let least_recently_used = entries
+                .products
+                .iter()
+                .min_by_key(|(_, entry)| entry.used_at)
+                .map(|(key, _)| key.clone());
+            match least_recently_used {
+                Some(key) => entries.products.remove(&key),
+                None => break,
+            };
+        }
+    }
+
+    // A panic while the lock was held can't leave an entry half written, so the cache stays usable
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// Only failures to reach the table fall back to stale products, anything else is an answer
+fn is_unavailable(err: &HexagonalError) -> bool {
+    err.error == error::HexagonalErrorCode::AdaptorError
+}
+
+pub struct CachedProductRepositoryAdaptor<'a, T: ProductRepositoryPort + TenantScoped> {
+    product_repository: T,
+    cache: &'a ProductCache,
+}
+
+impl<'a, T: ProductRepositoryPort + TenantScoped> CachedProductRepositoryAdaptor<'a, T> {
+    pub fn new(
+        product_repository: T,
+        cache: &'a ProductCache,
+    ) -> CachedProductRepositoryAdaptor<'a, T> {
+        CachedProductRepositoryAdaptor {
+            product_repository,
+            cache,
+        }
+    }
+
+    // Entries are the wrapped repository's tenant's
+    fn tenant(&self) -> &Tenant {
+        self.product_repository.tenant()
+    }
+}
+
+#[async_trait]
+impl<'a, T: ProductRepositoryPort + TenantScoped + Send + Sync> ProductRepositoryPort
+    for CachedProductRepositoryAdaptor<'a, T>
+{
+    async fn product_get_by_id(&self, id: &String) -> Result<Option<Product>, HexagonalError> {
+        let stale = match self.cache.get(self.tenant(), id) {
+            Cached::Fresh(product) => return Ok(Some(product)),
+            Cached::Stale(product) => Some(product),
+            Cached::Missing => None,
+        };
+        match self.product_repository.product_get_by_id(id).await {
+            Ok(Some(product)) => {
+                self.cache.insert(self.tenant(), &product);
+                Ok(Some(product))
+            }
+            Ok(None) => {
+                self.cache.invalidate(self.tenant(), id);
+                Ok(None)
+            }
+            Err(err) if is_unavailable(&err) && stale.is_some() => Ok(stale),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn product_get_by_ids(&self, id: &Vec<String>) -> Result<Vec<Product>, HexagonalError> {
+        let mut found: HashMap<String, Product> = HashMap::new();
+        let mut stale: HashMap<String, Product> = HashMap::new();
+        let mut missing: Vec<String> = Vec::new();
+        for product_id in id {
+            if found.contains_key(product_id) || missing.contains(product_id) {
+                continue;
+            }
+            match self.cache.get(self.tenant(), product_id) {
+                Cached::Fresh(product) => {
+                    found.insert(product_id.clone(), product);
+                }
+                Cached::Stale(product) => {
+                    stale.insert(product_id.clone(), product);
+                    missing.push(product_id.clone());
+                }
+                Cached::Missing => missing.push(product_id.clone()),
+            }
+        }
+
+        if !missing.is_empty() {
+            match self.product_repository.product_get_by_ids(&missing).await {
+                Ok(products) => {
+                    for product_id in &missing {
+                        self.cache.invalidate(self.tenant(), product_id);
+                    }
+                    for product in products {
+                        self.cache.insert(self.tenant(), &product);
+                        found.insert(product.id.clone(), product);
+                    }
+                }
+                // stale products only stand in when every one missing has one, a partial list
+                // would look like the others don't exist
+                Err(err)
+                    if is_unavailable(&err)
+                        && missing
+                            .iter()
+                            .all(|product_id| stale.contains_key(product_id)) =>
+                {
+                    found.extend(stale);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        // in the order asked for, once each
+        Ok(id
+            .iter()
+            .filter_map(|product_id| found.remove(product_id))
+            .collect())
+    }
+
+    async fn product_create(
+        &self,
+        product: &Product,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Product, HexagonalError> {
+        self.product_repository
+            .product_create(product, principal, outbox_event)
+            .await
+    }
+
+    // Only this instance's entry, the rest are invalidated from the event the write raises. On
+    // failure too, a stale version is the likely cause
+    async fn product_update_by_id(
+        &self,
+        id: &String,
+        product_update: &MutableProduct,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Product, HexagonalError> {
+        let result = self
+            .product_repository
+            .product_update_by_id(
+                id,
+                product_update,
+                expected_version,
+                principal,
+                outbox_event,
+            )
+            .await;
+        self.cache.invalidate(self.tenant(), id);
+        result
+    }
+
+    async fn product_delete_by_id(
+        &self,
+        id: &String,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Product, HexagonalError> {
+        let result = self
+            .product_repository
+            .product_delete_by_id(id, expected_version, principal, outbox_event)
+            .await;
+        self.cache.invalidate(self.tenant(), id);
+        result
+    }
+
+    // Deleted products are never cached, so restores and purges have nothing to invalidate
+    async fn product_restore_by_id(
+        &self,
+        id: &String,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Product, HexagonalError> {
+        self.product_repository
+            .product_restore_by_id(id, principal, outbox_event)
+            .await
+    }
+
+    async fn product_purge_deleted(
+        &self,
+        limit: i32,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Vec<Product>, HexagonalError> {
+        self.product_repository
+            .product_purge_deleted(limit, outbox_event)
+            .await
+    }
+
+    async fn product_history_by_id(
+        &self,
+        id: &String,
+        page: &PageRequest,
+    ) -> Result<Page<HistoryRecord>, HexagonalError> {
+        self.product_repository
+            .product_history_by_id(id, page)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::predicate::eq;
+
+    use super::super::outbox::OutboxItem;
+    use super::super::product::MockProductRepositoryPort;
+    use super::*;
+
+    impl TenantScoped for MockProductRepositoryPort {
+        fn tenant(&self) -> &Tenant {
+            static DEFAULT_TENANT: std::sync::LazyLock<Tenant> =
+                std::sync::LazyLock::new(Tenant::default);
+            &DEFAULT_TENANT
+        }
+    }
+
+    fn unavailable() -> HexagonalError {
+        HexagonalError {
+            error: error::HexagonalErrorCode::AdaptorError,
+            message: "Unable to get product".to_string(),
+            trace: "throttled".to_string(),
+        }
+    }
+
+    fn product(id: &str) -> Product {
+        Product {
+            id: id.to_string(),
+            ..Product::new("Lamp".to_string(), 1000, "A lamp".to_string())
+        }
+    }
+
+    fn no_event(_: &Product) -> OutboxItem {
+        OutboxItem::new("product_updated".to_string(), 1, "{}".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_reads_are_cached_until_updated() {
+        let mut repository = MockProductRepositoryPort::new();
+        repository
+            .expect_product_get_by_id()
+            .with(eq("1".to_string()))
+            .times(2)
+            .returning(|id| Ok(Some(product(id))));
+        repository
+            .expect_product_update_by_id()
+            .times(1)
+            .returning(|id, _, _, _, _| Ok(product(id)));
+        let cache = ProductCache::new(Duration::from_secs(60), 10);
+        let cached = CachedProductRepositoryAdaptor::new(repository, &cache);
+
+        for _ in 0..3 {
+            assert!(cached
+                .product_get_by_id(&"1".to_string())
+                .await
+                .unwrap()
+                .is_some());
+        }
+        let update = MutableProduct {
+            product_name: None,
+            price_cents: Some(2000),
+            description: None,
+        };
+        cached
+            .product_update_by_id(&"1".to_string(), &update, None, "bob", no_event)
+            .await
+            .unwrap();
+        // refetched once after the update
+        cached.product_get_by_id(&"1".to_string()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stale_products_are_served_when_the_table_is_unavailable() {
+        let mut repository = MockProductRepositoryPort::new();
+        let mut calls = 0;
+        repository
+            .expect_product_get_by_ids()
+            .times(3)
+            .returning(move |ids| {
+                calls += 1;
+                match calls {
+                    1 => Ok(ids.iter().map(|id| product(id)).collect()),
+                    _ => Err(unavailable()),
+                }
+            });
+        // every entry is stale as soon as it's written
+        let cache = ProductCache::new(Duration::ZERO, 10);
+        let cached = CachedProductRepositoryAdaptor::new(repository, &cache);
+
+        let ids = vec!["1".to_string(), "2".to_string()];
+        assert_eq!(cached.product_get_by_ids(&ids).await.unwrap().len(), 2);
+        let products = cached.product_get_by_ids(&ids).await.unwrap();
+        assert_eq!(
+            products.iter().map(|p| p.id.as_str()).collect::<Vec<_>>(),
+            vec!["1", "2"]
+        );
+
+        // nothing to fall back to for a product never read
+        let err = cached
+            .product_get_by_ids(&vec!["1".to_string(), "3".to_string()])
+            .await;
+        assert!(err.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tenants_and_capacity() {
+        let mut repository = MockProductRepositoryPort::new();
+        repository
+            .expect_product_get_by_id()
+            .returning(|id| Ok(Some(product(id))));
+        let cache = ProductCache::new(Duration::from_secs(60), 2);
+        let cached = CachedProductRepositoryAdaptor::new(repository, &cache);
+        for id in ["1", "2", "1", "3"] {
+            cached.product_get_by_id(&id.to_string()).await.unwrap();
+        }
+
+        // 2 was the least recently used when 3 came in
+        assert!(matches!(
+            cache.get(&Tenant::default(), "1"),
+            Cached::Fresh(_)
+        ));
+        assert!(matches!(
+            cache.get(&Tenant::default(), "2"),
+            Cached::Missing
+        ));
+        let acme = Tenant::new("acme").unwrap();
+        assert!(matches!(cache.get(&acme, "1"), Cached::Missing));
+    }
+}
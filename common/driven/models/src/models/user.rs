@@ -1,26 +1,52 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
 use async_trait::async_trait;
-use aws_sdk_dynamodb::types::error::TransactionCanceledException;
-use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::types::{AttributeValue, Delete, Put, ReturnValuesOnConditionCheckFailure};
 use error::HexagonalError;
 use mockall::automock;
-use persistance_repository::DynamoDBSingleTableRepository;
+use persistance_repository::{
+    CancellationCode, Condition, DynamoDBSingleTableRepository, GSIs, IndexQuery, Page,
+    PageRequest, SortKeyCondition, TransactionError,
+};
 use serde::{Deserialize, Serialize};
 
-use crate::{default_time, DynamoDbModel};
+use super::history::{delete_history, history_query, HistoryAction, HistoryRecord};
+use super::outbox::OutboxEvent;
+use crate::soft_delete::{
+    expired_tombstones_query, into_purging, into_tombstone, is_tombstone, within_retention,
+    without_tombstones,
+};
+use crate::{
+    changed_since_read_error, check_expected_version, decode_or_skip, default_time,
+    delete_if_unchanged, initial_version, put_if_unchanged, DynamoDbModel,
+};
+
+// GSI2 partition of deleted users, see soft_delete
+const USER_DELETED_PARTITION: &str = "USER#DELETED";
 
 // First we define our model
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, DynamoDbModel)]
+#[dynamo(
+    pkey = "USER#{username}",
+    skey = "-",
+    gsi1_pkey = "USER#{email}",
+    gsi1_skey = "-"
+)]
 pub struct User {
     pub first: String,
     pub last: String,
     pub email: String,
     pub username: String,
     #[serde(default = "default_time")]
+    #[dynamo(n)]
     pub created_at: String,
     #[serde(default = "default_time")]
+    #[dynamo(n)]
     pub updated_at: String,
+    #[serde(default = "initial_version")]
+    #[dynamo(default)]
+    pub version: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -29,56 +55,17 @@ pub struct MutableUser {
     pub last: Option<String>,
 }
 
-// Adaptor Transform Traits
-impl DynamoDbModel for User {
-    fn into_attr_map(&self) -> HashMap<String, AttributeValue> {
-        let mut item = HashMap::new();
-        item.insert(
-            "Pkey".to_string(),
-            AttributeValue::S("USER#".to_string() + &self.username.clone()),
-        );
-        item.insert("Skey".to_string(), AttributeValue::S("-".to_string()));
-        item.insert(
-            "GSI1Pkey".to_string(),
-            AttributeValue::S("USER#".to_string() + &self.email.clone()),
-        );
-        item.insert("GSI1Skey".to_string(), AttributeValue::S("-".to_string()));
-        item.insert("first".to_string(), AttributeValue::S(self.first.clone()));
-        item.insert("last".to_string(), AttributeValue::S(self.last.clone()));
-        item.insert("email".to_string(), AttributeValue::S(self.email.clone()));
-        item.insert(
-            "username".to_string(),
-            AttributeValue::S(self.username.clone()),
-        );
-        item.insert(
-            "created_at".to_string(),
-            AttributeValue::N(self.created_at.clone()),
-        );
-        item.insert(
-            "updated_at".to_string(),
-            AttributeValue::N(self.updated_at.clone()),
-        );
-        item
-    }
-
-    fn from_attr_map(attr: HashMap<String, AttributeValue>) -> User {
-        User {
-            first: attr.get("first").unwrap().as_s().unwrap().to_string(),
-            last: attr.get("last").unwrap().as_s().unwrap().to_string(),
-            email: attr.get("email").unwrap().as_s().unwrap().to_string(),
-            username: attr.get("username").unwrap().as_s().unwrap().to_string(),
-            created_at: attr.get("created_at").unwrap().as_n().unwrap().to_string(),
-            updated_at: attr.get("updated_at").unwrap().as_n().unwrap().to_string(),
-        }
+impl User {
+    // Key of the item claiming an email, so no two users of a tenant share one
+    pub fn email_pkey(email: &str) -> String {
+        format!("USER#EMAIL#{}", email)
     }
-}
 
-impl User {
     pub fn into_attr_map_unique_email(&self) -> HashMap<String, AttributeValue> {
         let mut item = HashMap::new();
         item.insert(
             "Pkey".to_string(),
-            AttributeValue::S("USER#EMAIL#".to_string() + &self.email.clone()),
+            AttributeValue::S(User::email_pkey(&self.email)),
         );
         item.insert("Skey".to_string(), AttributeValue::S("-".to_string()));
         item.insert(
@@ -90,22 +77,15 @@ impl User {
 }
 
 impl MutableUser {
-    fn into_attr_map(&self) -> HashMap<String, AttributeValue> {
-        let mut attribute_values = HashMap::new();
-        if Option::is_some(&self.first) {
-            attribute_values.insert(
-                ":first".to_string(),
-                AttributeValue::S(self.first.clone().unwrap()),
-            );
-        }
-        if Option::is_some(&self.last) {
-            attribute_values.insert(
-                ":last".to_string(),
-                AttributeValue::S(self.last.clone().unwrap()),
-            );
+    // The user as it will be once this update is written
+    pub fn apply_to(&self, user: &User) -> User {
+        User {
+            first: self.first.clone().unwrap_or(user.first.clone()),
+            last: self.last.clone().unwrap_or(user.last.clone()),
+            updated_at: default_time(),
+            version: user.version + 1,
+            ..user.clone()
         }
-        attribute_values.insert(":updated_at".to_string(), AttributeValue::N(default_time()));
-        attribute_values
     }
 }
 
@@ -113,31 +93,159 @@ impl MutableUser {
 #[automock]
 #[async_trait]
 pub trait UserRepositoryPort {
-    async fn user_get_by_email(&self, email: &String) -> Result<Vec<User>, HexagonalError>;
+    async fn user_get_by_email(
+        &self,
+        email: &String,
+        page: &PageRequest,
+    ) -> Result<Page<User>, HexagonalError>;
     async fn user_get_by_username(&self, username: &String)
         -> Result<Option<User>, HexagonalError>;
-    async fn user_create(&self, user: &User) -> Result<User, HexagonalError>;
+    async fn user_create(
+        &self,
+        user: &User,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<User, HexagonalError>;
     async fn user_update_by_username(
         &self,
         username: &String,
         user: MutableUser,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
     ) -> Result<User, HexagonalError>;
     async fn user_update_email_by_username(
         &self,
         username: &String,
         new_email: &String,
-    ) -> Result<(), HexagonalError>;
-    async fn user_delete_by_username(&self, email: &String) -> Result<User, HexagonalError>;
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<User, HexagonalError>;
+    // Soft deletes, the user keeps its username and email until purged
+    async fn user_delete_by_username(
+        &self,
+        username: &String,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<User, HexagonalError>;
+    // Brings back a deleted user within the retention window
+    async fn user_restore_by_username(
+        &self,
+        username: &String,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<User, HexagonalError>;
+    // Removes up to `limit` users whose retention window has passed for good, returning them
+    async fn user_purge_deleted(
+        &self,
+        limit: i32,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<Vec<User>, HexagonalError>;
+    // Every write made to the user, newest first. Kept through a soft delete, gone once purged
+    async fn user_history_by_username(
+        &self,
+        username: &String,
+        page: &PageRequest,
+    ) -> Result<Page<HistoryRecord>, HexagonalError>;
 }
 
 pub struct UserRepositoryAdaptor<'a> {
     persistance_repository: &'a DynamoDBSingleTableRepository,
+    delete_retention: Duration,
 }
 
 impl<'a> UserRepositoryAdaptor<'a> {
-    pub fn new(persistance_repository: &DynamoDBSingleTableRepository) -> UserRepositoryAdaptor {
+    // The retention window comes from the repository's config
+    pub fn new(
+        persistance_repository: &'a DynamoDBSingleTableRepository,
+    ) -> UserRepositoryAdaptor<'a> {
         UserRepositoryAdaptor {
             persistance_repository,
+            delete_retention: persistance_repository.config.limits.delete_retention,
+        }
+    }
+
+    pub fn with_delete_retention(self, delete_retention: Duration) -> UserRepositoryAdaptor<'a> {
+        UserRepositoryAdaptor {
+            delete_retention,
+            ..self
+        }
+    }
+
+    // The stored user item, tombstone or not
+    async fn get_user_item(
+        &self,
+        username: &str,
+        action: &str,
+    ) -> Result<Option<HashMap<String, AttributeValue>>, HexagonalError> {
+        self.persistance_repository
+            .get_item_primary(User::pkey(username), User::skey())
+            .await
+            .map(|output| output.item)
+            .map_err(|err| HexagonalError {
+                error: error::HexagonalErrorCode::AdaptorError,
+                message: format!("Unable to {}, error in get call", action),
+                trace: err.to_string(),
+            })
+    }
+
+    // None when the tombstone was restored, or purged by another run, since it was read
+    async fn purge_tombstone(
+        &self,
+        read: HashMap<String, AttributeValue>,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<Option<User>, HexagonalError> {
+        let deleted_user = User::from_attr_map(read.clone())?;
+        let table_name = &self.persistance_repository.table_name;
+
+        let claimed = into_purging(read.clone());
+        let result = self
+            .persistance_repository
+            .transaction()
+            .operation(
+                UserWrite::User,
+                put_if_unchanged(table_name, &read, claimed.clone()),
+            )
+            .send()
+            .await;
+        match result {
+            Ok(_) => {}
+            Err(err) if err.condition_failed(&UserWrite::User) => return Ok(None),
+            Err(err) => return Err(user_write_error("purge user", err, None)),
+        }
+
+        delete_history(
+            self.persistance_repository,
+            User::pkey(&deleted_user.username),
+        )
+        .await?;
+        let email_delete = Delete::builder()
+            .table_name(table_name.clone())
+            .key(
+                "Pkey",
+                AttributeValue::S(User::email_pkey(&deleted_user.email)),
+            )
+            .key("Skey", AttributeValue::S("-".to_string()))
+            .build()
+            .unwrap(); // Key is always set so unwrap is safe
+
+        let result = self
+            .persistance_repository
+            .transaction()
+            .operation(UserWrite::User, delete_if_unchanged(table_name, &claimed))
+            .delete(UserWrite::OldEmail, email_delete)
+            .operation(
+                UserWrite::Outbox,
+                outbox_event(&deleted_user).into_transact_write_item(self.persistance_repository),
+            )
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(Some(deleted_user)),
+            Err(err) if err.condition_failed(&UserWrite::User) => Ok(None),
+            Err(err) => Err(user_write_error("purge user", err, None)),
         }
     }
 }
@@ -150,13 +258,13 @@ impl<'a> UserRepositoryPort for UserRepositoryAdaptor<'a> {
     ) -> Result<Option<User>, HexagonalError> {
         let result = self
             .persistance_repository
-            .get_item_primary(format!("USER#{}", username.to_string()), "-".to_string())
+            .get_item_primary(User::pkey(username), User::skey())
             .await;
 
         match result {
             Ok(x) => match x.item {
-                Some(y) => Ok(Some(User::from_attr_map(y))),
-                None => Ok(None),
+                Some(y) if !is_tombstone(&y) => Ok(Some(User::from_attr_map(y)?)),
+                _ => Ok(None),
             },
             Err(err) => Err(HexagonalError {
                 error: error::HexagonalErrorCode::AdaptorError,
@@ -166,117 +274,73 @@ impl<'a> UserRepositoryPort for UserRepositoryAdaptor<'a> {
         }
     }
 
-    async fn user_get_by_email(&self, email: &String) -> Result<Vec<User>, HexagonalError> {
-        let result = self
-            .persistance_repository
-            .get_item_index(
-                email.to_string(),
-                "-".to_string(),
-                persistance_repository::GSIs::GSI1,
-            )
-            .await;
+    async fn user_get_by_email(
+        &self,
+        email: &String,
+        page: &PageRequest,
+    ) -> Result<Page<User>, HexagonalError> {
+        let query = without_tombstones(
+            IndexQuery::gsi(GSIs::GSI1, User::gsi1_pkey(email)).sort_key(SortKeyCondition::Equals(
+                AttributeValue::S(User::gsi1_skey()),
+            )),
+        );
 
-        match result {
-            Ok(x) => match x.items {
-                Some(y) => {
-                    let mut users = Vec::new();
-                    for user in y {
-                        users.push(User::from_attr_map(user));
-                    }
-                    Ok(users)
-                }
-                None => Err(HexagonalError {
-                    error: error::HexagonalErrorCode::NotFound,
-                    message: "Unable to fetch user, does not exist".to_string(),
-                    trace: "".to_string(),
-                }),
-            },
-            Err(err) => Err(HexagonalError {
-                error: error::HexagonalErrorCode::NotFound,
-                message: "Unable to fetch user, error in get call".to_string(),
-                trace: err.to_string(),
-            }),
-        }
+        Ok(self
+            .persistance_repository
+            .query_index(&query, page)
+            .await
+            .map_err(|e| e.into_hexagonal_error("Unable to fetch user, error in query call"))?
+            .filter_map(decode_or_skip(
+                self.persistance_repository,
+                User::from_attr_map,
+            )))
     }
 
-    async fn user_create(&self, user: &User) -> Result<User, HexagonalError> {
-        let user_model: HashMap<String, AttributeValue> = user.into_attr_map();
-        let email_model: HashMap<String, AttributeValue> = user.into_attr_map_unique_email();
-
+    async fn user_create(
+        &self,
+        user: &User,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<User, HexagonalError> {
+        let table_name = &self.persistance_repository.table_name;
         let pkey_unique = "attribute_not_exists(Pkey) AND attribute_not_exists(Skey)";
 
-        let user_put = aws_sdk_dynamodb::types::Put::builder()
-            .table_name(self.persistance_repository.table_name.clone())
-            .set_item(Some(user_model))
-            .condition_expression(pkey_unique.to_string())
+        let user_put = Put::builder()
+            .table_name(table_name.clone())
+            .set_item(Some(user.into_attr_map()))
+            .condition_expression(pkey_unique)
             .build()
             .unwrap(); // table name and item is always set so unwrap is safe
 
-        let email_put = aws_sdk_dynamodb::types::Put::builder()
-            .table_name(self.persistance_repository.table_name.clone())
-            .set_item(Some(email_model))
-            .condition_expression(pkey_unique.to_string())
+        let email_put = Put::builder()
+            .table_name(table_name.clone())
+            .set_item(Some(user.into_attr_map_unique_email()))
+            .condition_expression(pkey_unique)
             .build()
             .unwrap(); // table name and item is always set so unwrap is safe
 
-        let user_transact = aws_sdk_dynamodb::types::TransactWriteItem::builder()
-            .put(user_put)
-            .build();
-
-        let username_transact = aws_sdk_dynamodb::types::TransactWriteItem::builder()
-            .put(email_put)
-            .build();
-
         self.persistance_repository
-            .client
-            .transact_write_items()
-            .transact_items(user_transact)
-            .transact_items(username_transact)
+            .transaction()
+            .put(UserWrite::User, user_put)
+            .put(UserWrite::Email, email_put)
+            .operation(
+                UserWrite::History,
+                HistoryRecord::new(HistoryAction::Created, principal, None, user)
+                    .into_transact_write_item(table_name, User::pkey(&user.username)),
+            )
+            .operation(
+                UserWrite::Outbox,
+                outbox_event(user).into_transact_write_item(self.persistance_repository),
+            )
             .send()
             .await
-            .map_err(|err| {
-                let err = err.into_source();
-                let default_err = HexagonalError {
-                    error: error::HexagonalErrorCode::AdaptorError,
-                    message: "Unable to create user, error in transaction write call".to_string(),
-                    trace: "".to_string(),
-                };
-
-                if err.is_err() {
-                    return default_err;
-                }
-
-                let err_unwrap1 = err.unwrap();
-                let err_unwrap2 = err_unwrap1.source();
-
-                if err_unwrap2.is_none() {
-                    return default_err;
-                }
-
-                let err_unwrap3 = err_unwrap2.unwrap();
-
-                let err_transaction_failure =
-                    err_unwrap3.downcast_ref::<TransactionCanceledException>();
-                if err_transaction_failure.is_some() {
-                    let err_reasons = err_transaction_failure
-                        .unwrap()
-                        .cancellation_reasons
-                        .clone()
-                        .unwrap();
-                    for reason in err_reasons {
-                        if reason.code.unwrap_or_default() == *"ConditionalCheckFailed" {
-                            return HexagonalError {
-                                error: error::HexagonalErrorCode::Conflict,
-                                message:
-                                    "Unable to create user, user email or username already exists"
-                                        .to_string(),
-                                trace: err_unwrap1.to_string(),
-                            };
-                        }
-                    }
-                }
-
-                default_err
+            .map_err(|err| match err.condition_failed(&UserWrite::User) {
+                true => HexagonalError {
+                    error: error::HexagonalErrorCode::Conflict,
+                    message: "Unable to create user, username already taken".to_string(),
+                    trace: err.to_string(),
+                },
+                false => user_write_error("create user", err, None),
             })
             .map(|_| user.clone())
     }
@@ -285,243 +349,877 @@ impl<'a> UserRepositoryPort for UserRepositoryAdaptor<'a> {
         &self,
         username: &String,
         user: MutableUser,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
     ) -> Result<User, HexagonalError> {
-        let mut update_expression = "SET ".to_string();
-        let mut attr_names = HashMap::new();
-
-        if user.first.is_some() {
-            update_expression.push_str("#first_key = :first, ");
-            attr_names.insert("#first_key".to_string(), "first".to_string());
-        }
-
-        if user.last.is_some() {
-            update_expression.push_str("#last_key = :last, ");
-            attr_names.insert("#last_key".to_string(), "last".to_string());
-        }
+        let read = self
+            .get_user_item(username, "update user")
+            .await?
+            .filter(|item| !is_tombstone(item))
+            .ok_or(HexagonalError {
+                error: error::HexagonalErrorCode::NotFound,
+                message: "Unable to update user, does not exist".to_string(),
+                trace: "".to_string(),
+            })?;
 
-        update_expression.push_str("updated_at = :updated_at");
+        let current_user = User::from_attr_map(read.clone())?;
+        check_expected_version(
+            expected_version,
+            current_user.version,
+            "Unable to update user, version does not match",
+        )?;
+        let updated_user = user.apply_to(&current_user);
+        let table_name = &self.persistance_repository.table_name;
 
-        let result = self
-            .persistance_repository
-            .update_item(
-                format!("USER#{}", username.to_string()),
-                "-".to_string(),
-                update_expression.to_string(),
-                Some(attr_names),
-                Some(user.into_attr_map()),
+        self.persistance_repository
+            .transaction()
+            .operation(
+                UserWrite::User,
+                put_if_unchanged(table_name, &read, updated_user.into_attr_map()),
             )
-            .await;
-
-        match result {
-            Ok(x) => match x.attributes {
-                Some(y) => Ok(User::from_attr_map(y)),
-                None => Err(HexagonalError {
-                    error: error::HexagonalErrorCode::Unkown,
-                    message: "User attributes were not returned".to_string(),
-                    trace: "".to_string(),
-                }),
-            },
-            Err(err) => Err(if err.is_conditional_check_failed_exception() {
-                HexagonalError {
-                    error: error::HexagonalErrorCode::NotFound,
-                    message: "Unable to update user, does not exist".to_string(),
-                    trace: err.to_string(),
-                }
-            } else {
-                HexagonalError {
-                    error: error::HexagonalErrorCode::AdaptorError,
-                    message: "Unable to update user, error in put call".to_string(),
-                    trace: err.to_string(),
-                }
-            }),
-        }
+            .operation(
+                UserWrite::History,
+                HistoryRecord::new(
+                    HistoryAction::Updated,
+                    principal,
+                    Some(&current_user),
+                    &updated_user,
+                )
+                .into_transact_write_item(table_name, User::pkey(username)),
+            )
+            .operation(
+                UserWrite::Outbox,
+                outbox_event(&updated_user).into_transact_write_item(self.persistance_repository),
+            )
+            .send()
+            .await
+            .map_err(|err| user_write_error("update user", err, expected_version))
+            .map(|_| updated_user)
     }
 
     async fn user_update_email_by_username(
         &self,
         username: &String,
         new_email: &String,
-    ) -> Result<(), HexagonalError> {
-        let get_user = self.user_get_by_username(username).await;
-        if let Err(err) = get_user {
-            return Err(err);
-        }
-        let user = get_user.unwrap();
-
-        if user.is_none() {
-            return Err(HexagonalError {
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<User, HexagonalError> {
+        let user = self
+            .user_get_by_username(username)
+            .await?
+            .ok_or(HexagonalError {
                 error: error::HexagonalErrorCode::NotFound,
-                message: "Unable to delete user, does not exist".to_string(),
+                message: "Unable to update user email, does not exist".to_string(),
                 trace: "".to_string(),
-            });
-        }
-
-        let old_email = user.unwrap().email;
+            })?;
+        check_expected_version(
+            expected_version,
+            user.version,
+            "Unable to update user email, version does not match",
+        )?;
+        let old_email = user.email.clone();
+        let read_version = user.version;
+        let updated_user = User {
+            email: new_email.clone(),
+            updated_at: default_time(),
+            version: user.version + 1,
+            ..user.clone()
+        };
+        let table_name = &self.persistance_repository.table_name;
 
-        let mut email_item = HashMap::new();
-        email_item.insert(
-            "Pkey".to_string(),
-            AttributeValue::S("USER#EMAIL#".to_string() + new_email),
-        );
-        email_item.insert("Skey".to_string(), AttributeValue::S("-".to_string()));
-        email_item.insert("username".to_string(), AttributeValue::S(username.clone()));
-        let pkey_unique = "attribute_not_exists(Pkey) AND attribute_not_exists(Skey)";
-        let email_put = aws_sdk_dynamodb::types::Put::builder()
-            .table_name(self.persistance_repository.table_name.clone())
-            .set_item(Some(email_item))
-            .condition_expression(pkey_unique.to_string())
+        let email_put = Put::builder()
+            .table_name(table_name.clone())
+            .set_item(Some(updated_user.into_attr_map_unique_email()))
+            .condition_expression("attribute_not_exists(Pkey) AND attribute_not_exists(Skey)")
             .build()
-            .unwrap(); // Key is always set so unwrap is safe
-        let email_put_action = aws_sdk_dynamodb::types::TransactWriteItem::builder()
-            .put(email_put)
+            .unwrap(); // table name and item is always set so unwrap is safe
+
+        // the email index key moves with the email, and the condition stops a concurrent email
+        // change from leaving a uniqueness item behind
+        let user_condition = Condition::equals("email", AttributeValue::S(old_email.clone()))
+            // users stored before versioning have no version attribute to compare
+            .and(match read_version {
+                0 => Condition::not_exists("version"),
+                _ => Condition::equals("version", AttributeValue::N(read_version.to_string())),
+            })
             .build();
+        let user_put = Put::builder()
+            .table_name(table_name.clone())
+            .set_item(Some(updated_user.into_attr_map()))
+            .set_condition_expression(user_condition.condition.clone())
+            .set_expression_attribute_names(user_condition.attribute_names())
+            .set_expression_attribute_values(user_condition.attribute_values())
+            // lets a failed condition tell a deleted user apart from a changed one
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+            .build()
+            .unwrap(); // table name and item is always set so unwrap is safe
 
-        let update_user_expression = "SET email = :email, updated_at = :updated_at";
-        let mut attribute_values = HashMap::new();
-        attribute_values.insert(":email".to_string(), AttributeValue::S(new_email.clone()));
-        attribute_values.insert(":updated_at".to_string(), AttributeValue::N(default_time()));
-        let email_update = aws_sdk_dynamodb::types::Update::builder()
-            .table_name(self.persistance_repository.table_name.clone())
-            .update_expression(update_user_expression.to_string())
-            .key("Pkey", AttributeValue::S("USER#".to_string() + &username))
+        let old_email_delete = Delete::builder()
+            .table_name(table_name.clone())
+            .key("Pkey", AttributeValue::S(User::email_pkey(&old_email)))
             .key("Skey", AttributeValue::S("-".to_string()))
-            .set_expression_attribute_values(Some(attribute_values))
             .build()
             .unwrap(); // Key is always set so unwrap is safe
-        let email_update_action = aws_sdk_dynamodb::types::TransactWriteItem::builder()
-            .update(email_update)
-            .build();
 
-        let email_delete = aws_sdk_dynamodb::types::Delete::builder()
-            .table_name(self.persistance_repository.table_name.clone())
-            .key(
-                "Pkey",
-                AttributeValue::S("USER#EMAIL#".to_string() + &old_email),
+        self.persistance_repository
+            .transaction()
+            .put(UserWrite::Email, email_put)
+            .put(UserWrite::User, user_put)
+            .delete(UserWrite::OldEmail, old_email_delete)
+            .operation(
+                UserWrite::History,
+                HistoryRecord::new(
+                    HistoryAction::Updated,
+                    principal,
+                    Some(&user),
+                    &updated_user,
+                )
+                .into_transact_write_item(table_name, User::pkey(username)),
             )
-            .key("Skey", AttributeValue::S("-".to_string()))
-            .build()
-            .unwrap(); // Key is always set so unwrap is safe
-        let email_delete_action = aws_sdk_dynamodb::types::TransactWriteItem::builder()
-            .delete(email_delete)
-            .build();
+            .operation(
+                UserWrite::Outbox,
+                outbox_event(&updated_user).into_transact_write_item(self.persistance_repository),
+            )
+            .send()
+            .await
+            .map_err(|err| user_write_error("update user email", err, expected_version))
+            .map(|_| updated_user)
+    }
+
+    async fn user_delete_by_username(
+        &self,
+        username: &String,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<User, HexagonalError> {
+        let read = self
+            .get_user_item(username, "fetch user")
+            .await?
+            .filter(|item| !is_tombstone(item))
+            .ok_or(HexagonalError {
+                error: error::HexagonalErrorCode::NotFound,
+                message: "Unable to delete user, does not exist".to_string(),
+                trace: "".to_string(),
+            })?;
+
+        let current_user = User::from_attr_map(read.clone())?;
+        check_expected_version(
+            expected_version,
+            current_user.version,
+            "Unable to delete user, version does not match",
+        )?;
+        let deleted_user = User {
+            updated_at: default_time(),
+            version: current_user.version + 1,
+            ..current_user.clone()
+        };
+        let tombstone = into_tombstone(
+            deleted_user.into_attr_map(),
+            USER_DELETED_PARTITION,
+            username,
+            &deleted_user.updated_at,
+        );
+        let table_name = &self.persistance_repository.table_name;
 
         self.persistance_repository
-            .client
-            .transact_write_items()
-            .transact_items(email_put_action)
-            .transact_items(email_update_action)
-            .transact_items(email_delete_action)
+            .transaction()
+            .operation(
+                UserWrite::User,
+                put_if_unchanged(table_name, &read, tombstone),
+            )
+            .operation(
+                UserWrite::History,
+                HistoryRecord::new(
+                    HistoryAction::Deleted,
+                    principal,
+                    Some(&current_user),
+                    &deleted_user,
+                )
+                .into_transact_write_item(table_name, User::pkey(username)),
+            )
+            .operation(
+                UserWrite::Outbox,
+                outbox_event(&deleted_user).into_transact_write_item(self.persistance_repository),
+            )
             .send()
             .await
-            .map_err(|err| {
-                let err = err.into_source();
-                let default_err = HexagonalError {
-                    error: error::HexagonalErrorCode::AdaptorError,
-                    message: "Unable to create user, error in transaction write call".to_string(),
-                    trace: "".to_string(),
-                };
-
-                if err.is_err() {
-                    return default_err;
-                }
-
-                println!("{:?}", err);
-
-                let err_unwrap1 = err.unwrap();
-                let err_unwrap2 = err_unwrap1.source();
-
-                if err_unwrap2.is_none() {
-                    return default_err;
-                }
-
-                let err_unwrap3 = err_unwrap2.unwrap();
-
-                let err_transaction_failure =
-                    err_unwrap3.downcast_ref::<TransactionCanceledException>();
-                if err_transaction_failure.is_some() {
-                    let err_reasons = err_transaction_failure
-                        .unwrap()
-                        .cancellation_reasons
-                        .clone()
-                        .unwrap();
-                    for reason in err_reasons {
-                        if reason.code.unwrap_or_default() == *"ConditionalCheckFailed" {
-                            return HexagonalError {
-                                error: error::HexagonalErrorCode::Conflict,
-                                message:
-                                    "Unable to create user, user email or username already exists"
-                                        .to_string(),
-                                trace: err_unwrap1.to_string(),
-                            };
-                        }
-                    }
-                }
-
-                default_err
-            })
-            .map(|_| ())
+            .map_err(|err| user_write_error("delete user", err, expected_version))
+            .map(|_| deleted_user)
     }
 
-    async fn user_delete_by_username(&self, username: &String) -> Result<User, HexagonalError> {
-        let get_user = self.user_get_by_username(&username).await;
-        if let Err(err) = get_user {
-            return Err(err);
+    async fn user_restore_by_username(
+        &self,
+        username: &String,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<User, HexagonalError> {
+        let read = self
+            .get_user_item(username, "restore user")
+            .await?
+            .ok_or(HexagonalError {
+                error: error::HexagonalErrorCode::NotFound,
+                message: "Unable to restore user, does not exist".to_string(),
+                trace: "".to_string(),
+            })?;
+        if !is_tombstone(&read) {
+            return Err(HexagonalError {
+                error: error::HexagonalErrorCode::Conflict,
+                message: "Unable to restore user, not deleted".to_string(),
+                trace: "".to_string(),
+            });
+        }
+        if !within_retention(&read, self.delete_retention) {
+            return Err(HexagonalError {
+                error: error::HexagonalErrorCode::NotFound,
+                message: "Unable to restore user, retention window has passed".to_string(),
+                trace: "".to_string(),
+            });
         }
-        let user = get_user.unwrap();
 
-        if user.is_none() {
+        let deleted_user = User::from_attr_map(read.clone())?;
+        let restored_user = User {
+            updated_at: default_time(),
+            version: deleted_user.version + 1,
+            ..deleted_user.clone()
+        };
+        let table_name = &self.persistance_repository.table_name;
+
+        // the plain item replaces the tombstone, taking DeletedAt and the GSI2 keys with it
+        self.persistance_repository
+            .transaction()
+            .operation(
+                UserWrite::User,
+                put_if_unchanged(table_name, &read, restored_user.into_attr_map()),
+            )
+            .operation(
+                UserWrite::History,
+                HistoryRecord::new(
+                    HistoryAction::Restored,
+                    principal,
+                    Some(&deleted_user),
+                    &restored_user,
+                )
+                .into_transact_write_item(table_name, User::pkey(username)),
+            )
+            .operation(
+                UserWrite::Outbox,
+                outbox_event(&restored_user).into_transact_write_item(self.persistance_repository),
+            )
+            .send()
+            .await
+            .map_err(|err| user_write_error("restore user", err, None))
+            .map(|_| restored_user)
+    }
+
+    async fn user_purge_deleted(
+        &self,
+        limit: i32,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<Vec<User>, HexagonalError> {
+        let expired = self
+            .persistance_repository
+            .query_index(
+                &expired_tombstones_query(USER_DELETED_PARTITION, self.delete_retention),
+                &PageRequest::new(Some(limit), None),
+            )
+            .await
+            .map_err(|e| e.into_hexagonal_error("Unable to purge users, error in query call"))?;
+
+        let mut purged = Vec::new();
+        for read in expired.items {
+            if let Some(deleted_user) = self.purge_tombstone(read, outbox_event).await? {
+                purged.push(deleted_user);
+            }
+        }
+        Ok(purged)
+    }
+
+    async fn user_history_by_username(
+        &self,
+        username: &String,
+        page: &PageRequest,
+    ) -> Result<Page<HistoryRecord>, HexagonalError> {
+        if self
+            .get_user_item(username, "get user history")
+            .await?
+            .is_none()
+        {
             return Err(HexagonalError {
                 error: error::HexagonalErrorCode::NotFound,
-                message: "Unable to delete user, does not exist".to_string(),
+                message: "Unable to get user history, does not exist".to_string(),
                 trace: "".to_string(),
             });
         }
+        Ok(self
+            .persistance_repository
+            .query_index(&history_query(User::pkey(username)), page)
+            .await
+            .map_err(|e| e.into_hexagonal_error("Unable to get user history, error in query call"))?
+            .filter_map(decode_or_skip(
+                self.persistance_repository,
+                HistoryRecord::from_attr_map,
+            )))
+    }
+}
 
-        let unwrapped_user = user.unwrap();
+// The operations user writes are made of, so a cancelled transaction says which one failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UserWrite {
+    User,
+    // USER#EMAIL# item claiming an email for one user
+    Email,
+    OldEmail,
+    History,
+    Outbox,
+}
 
-        let email_delete = aws_sdk_dynamodb::types::Delete::builder()
-            .table_name(self.persistance_repository.table_name.clone())
-            .key(
-                "Pkey",
-                AttributeValue::S("USER#EMAIL#".to_string() + &unwrapped_user.username),
+fn user_write_error(
+    action: &str,
+    err: TransactionError<UserWrite>,
+    expected_version: Option<u64>,
+) -> HexagonalError {
+    if err.condition_failed(&UserWrite::Email) {
+        return HexagonalError {
+            error: error::HexagonalErrorCode::Conflict,
+            message: format!("Unable to {}, email already taken", action),
+            trace: err.to_string(),
+        };
+    }
+    match err.cancellation(&UserWrite::User) {
+        // the condition was checked against nothing, the user was deleted after we read it
+        Some(cancellation)
+            if cancellation.code == CancellationCode::ConditionalCheckFailed
+                && cancellation.item.is_none() =>
+        {
+            HexagonalError {
+                error: error::HexagonalErrorCode::NotFound,
+                message: format!("Unable to {}, user no longer exists", action),
+                trace: err.to_string(),
+            }
+        }
+        Some(cancellation) if cancellation.code == CancellationCode::ConditionalCheckFailed => {
+            changed_since_read_error(
+                expected_version,
+                &format!("Unable to {}, it was changed by another request", action),
+                err.to_string(),
             )
-            .key("Skey", AttributeValue::S("-".to_string()))
-            .build()
-            .unwrap(); // Key is always set so unwrap is safe
+        }
+        _ => HexagonalError {
+            error: error::HexagonalErrorCode::AdaptorError,
+            message: format!("Unable to {}, error in transaction write call", action),
+            trace: err.to_string(),
+        },
+    }
+}
 
-        let user_delete = aws_sdk_dynamodb::types::Delete::builder()
-            .table_name(self.persistance_repository.table_name.clone())
-            .key(
-                "Pkey",
-                AttributeValue::S("USERNAME#".to_string() + username),
+#[cfg(test)]
+mod tests {
+    use in_memory_persistance_repository::InMemorySingleTable;
+    use persistance_repository::Tenant;
+
+    use super::*;
+    use crate::models::outbox::{OutboxItem, OutboxRepositoryAdaptor, OutboxRepositoryPort};
+
+    fn test_event(user: &User) -> OutboxItem {
+        OutboxItem::new("UserTest".to_string(), 1, user.username.clone())
+    }
+
+    fn user(username: &str, email: &str) -> User {
+        User {
+            first: "First".to_string(),
+            last: "Last".to_string(),
+            email: email.to_string(),
+            username: username.to_string(),
+            created_at: default_time(),
+            updated_at: default_time(),
+            version: initial_version(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_user_create_and_get_against_in_memory_table() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = UserRepositoryAdaptor::new(&repository);
+        let created = user("user", "user@example.com");
+
+        adaptor
+            .user_create(&created, "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(
+            adaptor
+                .user_get_by_username(&"user".to_string())
+                .await
+                .unwrap(),
+            Some(created)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_user_create_conflicts_on_email_or_username() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = UserRepositoryAdaptor::new(&repository);
+        adaptor
+            .user_create(&user("user", "user@example.com"), "tester", test_event)
+            .await
+            .unwrap();
+
+        for (duplicate, message) in [
+            (
+                user("user", "other@example.com"),
+                "Unable to create user, username already taken",
+            ),
+            (
+                user("other", "user@example.com"),
+                "Unable to create user, email already taken",
+            ),
+        ] {
+            let err = adaptor
+                .user_create(&duplicate, "tester", test_event)
+                .await
+                .unwrap_err();
+            assert_eq!(err.error, error::HexagonalErrorCode::Conflict);
+            assert_eq!(err.message, message);
+        }
+        assert_eq!(
+            adaptor
+                .user_get_by_username(&"other".to_string())
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_users_are_isolated_by_tenant() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let acme_repository = repository.for_tenant(Tenant::new("acme").unwrap());
+        let globex_repository = repository.for_tenant(Tenant::new("globex").unwrap());
+        let acme = UserRepositoryAdaptor::new(&acme_repository);
+        let globex = UserRepositoryAdaptor::new(&globex_repository);
+        acme.user_create(&user("user", "user@example.com"), "tester", test_event)
+            .await
+            .unwrap();
+
+        // neither the username nor the email is taken for another tenant
+        assert_eq!(
+            globex
+                .user_get_by_username(&"user".to_string())
+                .await
+                .unwrap(),
+            None
+        );
+        assert!(globex
+            .user_get_by_email(&"user@example.com".to_string(), &PageRequest::default())
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+        globex
+            .user_create(&user("user", "user@example.com"), "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(
+            UserRepositoryAdaptor::new(&repository)
+                .user_get_by_username(&"user".to_string())
+                .await
+                .unwrap(),
+            None
+        );
+
+        // and within one it still is
+        assert_eq!(
+            acme.user_create(&user("other", "user@example.com"), "tester", test_event)
+                .await
+                .unwrap_err()
+                .error,
+            error::HexagonalErrorCode::Conflict
+        );
+        let stored = acme_repository
+            .get_item_primary(User::pkey("user"), User::skey())
+            .await
+            .unwrap()
+            .item
+            .unwrap();
+        assert_eq!(
+            stored["Pkey"],
+            AttributeValue::S("TENANT#acme#USER#user".to_string())
+        );
+
+        // the email index moves within the tenant too
+        let new_email = "new@example.com".to_string();
+        acme.user_update_email_by_username(
+            &"user".to_string(),
+            &new_email,
+            None,
+            "tester",
+            test_event,
+        )
+        .await
+        .unwrap();
+        let page = PageRequest::default();
+        assert_eq!(
+            acme.user_get_by_email(&new_email, &page)
+                .await
+                .unwrap()
+                .items
+                .len(),
+            1
+        );
+        assert!(globex
+            .user_get_by_email(&new_email, &page)
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_user_writes_outbox_event_with_each_change() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = UserRepositoryAdaptor::new(&repository).with_delete_retention(Duration::ZERO);
+        let outbox = OutboxRepositoryAdaptor::new(&repository);
+        let username = "user".to_string();
+        adaptor
+            .user_create(&user("user", "user@example.com"), "tester", test_event)
+            .await
+            .unwrap();
+
+        let updated = adaptor
+            .user_update_by_username(
+                &username,
+                MutableUser {
+                    first: Some("New".to_string()),
+                    last: None,
+                },
+                None,
+                "tester",
+                test_event,
             )
-            .key("Skey", AttributeValue::S("-".to_string()))
-            .build()
-            .unwrap(); // Key is always set so unwrap is safe
+            .await
+            .unwrap();
+        assert_eq!(updated.first, "New");
+        assert_eq!(updated.last, "Last");
 
-        let email_delete_action = aws_sdk_dynamodb::types::TransactWriteItem::builder()
-            .delete(email_delete)
-            .build();
+        adaptor
+            .user_update_email_by_username(
+                &username,
+                &"new@example.com".to_string(),
+                None,
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        let deleted = adaptor
+            .user_delete_by_username(&username, None, "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(deleted.email, "new@example.com");
+        assert_eq!(adaptor.user_get_by_username(&username).await.unwrap(), None);
 
-        let user_delete_action = aws_sdk_dynamodb::types::TransactWriteItem::builder()
-            .delete(user_delete)
-            .build();
+        // the email stays taken until the tombstone is purged
+        assert_eq!(
+            adaptor
+                .user_create(&user("other", "new@example.com"), "tester", test_event)
+                .await
+                .unwrap_err()
+                .error,
+            error::HexagonalErrorCode::Conflict
+        );
+        let purged = adaptor.user_purge_deleted(10, test_event).await.unwrap();
+        assert_eq!(purged, vec![deleted]);
+        adaptor
+            .user_create(&user("other", "new@example.com"), "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(outbox.outbox_get_pending(10).await.unwrap().len(), 6);
+        assert_eq!(
+            adaptor
+                .user_delete_by_username(&username, None, "tester", test_event)
+                .await
+                .unwrap_err()
+                .error,
+            error::HexagonalErrorCode::NotFound
+        );
+    }
 
-        self.persistance_repository
-            .client
-            .transact_write_items()
-            .transact_items(user_delete_action)
-            .transact_items(email_delete_action)
-            .send()
+    #[tokio::test]
+    async fn test_user_history_records_email_change() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = UserRepositoryAdaptor::new(&repository);
+        let username = "user".to_string();
+        adaptor
+            .user_create(&user("user", "user@example.com"), "tester", test_event)
             .await
-            .map_err(|err| {
-                let err_trace = err.to_string();
-                HexagonalError {
-                    error: error::HexagonalErrorCode::AdaptorError,
-                    message: "Unable to delete user, error in transaction write call".to_string(),
-                    trace: err_trace,
-                }
-            })
-            .map(|_| (unwrapped_user))
+            .unwrap();
+        adaptor
+            .user_update_email_by_username(
+                &username,
+                &"new@example.com".to_string(),
+                None,
+                "support",
+                test_event,
+            )
+            .await
+            .unwrap();
+
+        let history = adaptor
+            .user_history_by_username(&username, &PageRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(history.items.len(), 2);
+        assert_eq!(history.items[0].action, HistoryAction::Updated);
+        assert_eq!(history.items[0].principal, "support");
+        assert_eq!(history.items[0].version, 2);
+        assert_eq!(history.items[0].changes.len(), 1);
+        assert_eq!(history.items[0].changes[0].field, "email");
+        assert_eq!(
+            history.items[0].changes[0].old,
+            Some(serde_json::json!("user@example.com"))
+        );
+        assert_eq!(history.items[1].action, HistoryAction::Created);
+
+        let err = adaptor
+            .user_history_by_username(&"missing".to_string(), &PageRequest::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_user_purge_leaves_a_restored_user_alone() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = UserRepositoryAdaptor::new(&repository);
+        let expired = UserRepositoryAdaptor::new(&repository).with_delete_retention(Duration::ZERO);
+        let username = "user".to_string();
+        adaptor
+            .user_create(&user("user", "user@example.com"), "tester", test_event)
+            .await
+            .unwrap();
+        adaptor
+            .user_delete_by_username(&username, None, "tester", test_event)
+            .await
+            .unwrap();
+
+        // the purge reads the tombstone, then it's restored by a caller with a longer window
+        let read = expired
+            .get_user_item(&username, "purge user")
+            .await
+            .unwrap()
+            .unwrap();
+        adaptor
+            .user_restore_by_username(&username, "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(
+            expired.purge_tombstone(read, test_event).await.unwrap(),
+            None
+        );
+        assert!(adaptor
+            .user_get_by_username(&username)
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            adaptor
+                .user_history_by_username(&username, &PageRequest::default())
+                .await
+                .unwrap()
+                .items
+                .len(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn test_user_writes_check_expected_version() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = UserRepositoryAdaptor::new(&repository);
+        let username = "user".to_string();
+        let created = adaptor
+            .user_create(&user("user", "user@example.com"), "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(created.version, 1);
+
+        let first = MutableUser {
+            first: Some("New".to_string()),
+            last: None,
+        };
+        let updated = adaptor
+            .user_update_by_username(&username, first.clone(), Some(1), "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(updated.version, 2);
+
+        // a writer still holding version 1 is turned away and nothing is written
+        let stale = adaptor
+            .user_update_by_username(&username, first, Some(1), "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(stale.error, error::HexagonalErrorCode::PreconditionFailed);
+        let stale = adaptor
+            .user_update_email_by_username(
+                &username,
+                &"new@example.com".to_string(),
+                Some(1),
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(stale.error, error::HexagonalErrorCode::PreconditionFailed);
+        let stale = adaptor
+            .user_delete_by_username(&username, Some(1), "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(stale.error, error::HexagonalErrorCode::PreconditionFailed);
+        assert_eq!(
+            adaptor.user_get_by_username(&username).await.unwrap(),
+            Some(updated)
+        );
+
+        let moved = adaptor
+            .user_update_email_by_username(
+                &username,
+                &"new@example.com".to_string(),
+                Some(2),
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        assert_eq!(moved.version, 3);
+        adaptor
+            .user_delete_by_username(&username, Some(3), "tester", test_event)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_user_email_update_conflicts_on_taken_email() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = UserRepositoryAdaptor::new(&repository);
+        for (username, email) in [("user", "user@example.com"), ("other", "other@example.com")] {
+            adaptor
+                .user_create(&user(username, email), "tester", test_event)
+                .await
+                .unwrap();
+        }
+
+        let err = adaptor
+            .user_update_email_by_username(
+                &"user".to_string(),
+                &"other@example.com".to_string(),
+                Some(1),
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::Conflict);
+        assert_eq!(
+            err.message,
+            "Unable to update user email, email already taken"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_user_write_error_says_which_operation_failed() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = UserRepositoryAdaptor::new(&repository);
+        let created = user("user", "user@example.com");
+        adaptor
+            .user_create(&created, "tester", test_event)
+            .await
+            .unwrap();
+        let read = repository
+            .get_item_primary(User::pkey("user"), User::skey())
+            .await
+            .unwrap()
+            .item
+            .unwrap();
+        let table_name = &repository.table_name;
+        let write_over_read = || {
+            let updated = User {
+                first: "New".to_string(),
+                ..created.clone()
+            };
+            repository
+                .transaction()
+                .operation(
+                    UserWrite::User,
+                    put_if_unchanged(table_name, &read, updated.into_attr_map()),
+                )
+                .operation(
+                    UserWrite::Outbox,
+                    test_event(&updated).into_transact_write_item(&repository),
+                )
+        };
+
+        // changed after it was read
+        adaptor
+            .user_update_by_username(
+                &"user".to_string(),
+                MutableUser {
+                    first: None,
+                    last: Some("Changed".to_string()),
+                },
+                None,
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        let err = write_over_read().send().await.unwrap_err();
+        assert_eq!(
+            err.cancellation(&UserWrite::User).unwrap().code,
+            CancellationCode::ConditionalCheckFailed
+        );
+        assert!(err.cancellation(&UserWrite::Outbox).is_none());
+        let changed = user_write_error("update user", err, Some(1));
+        assert_eq!(changed.error, error::HexagonalErrorCode::PreconditionFailed);
+
+        // purged after it was read
+        repository
+            .delete_item(User::pkey("user"), User::skey())
+            .await
+            .unwrap();
+        let err = write_over_read().send().await.unwrap_err();
+        let gone = user_write_error("update user", err, Some(1));
+        assert_eq!(gone.error, error::HexagonalErrorCode::NotFound);
+        assert_eq!(gone.message, "Unable to update user, user no longer exists");
+    }
+
+    #[tokio::test]
+    async fn test_user_stored_before_versioning_reads_as_version_0() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let adaptor = UserRepositoryAdaptor::new(&repository);
+        let mut legacy = user("user", "user@example.com").into_attr_map();
+        legacy.remove("version");
+        repository.put_new_item(legacy).await.unwrap();
+        repository
+            .put_new_item(user("user", "user@example.com").into_attr_map_unique_email())
+            .await
+            .unwrap();
+
+        let username = "user".to_string();
+        let found = adaptor.user_get_by_username(&username).await.unwrap();
+        assert_eq!(found.unwrap().version, 0);
+        let moved = adaptor
+            .user_update_email_by_username(
+                &username,
+                &"new@example.com".to_string(),
+                Some(0),
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        assert_eq!(moved.version, 1);
     }
 }
@@ -0,0 +1,104 @@
+// Soft delete
+// Deleting a user or product leaves a tombstone: the item stays where it was with DeletedAt set,
+// and is indexed on GSI2 under <ENTITY>#DELETED by when it was deleted. Reads skip tombstones,
+// restoring puts the plain item back, and once the retention window has passed the purge job
+// deletes the tombstone for good and emits the final deletion event.
+//
+// The purge claims a tombstone by setting PurgingAt before deleting anything, conditional on the
+// tombstone being as the purge read it. A restore since the read fails the claim, and a claimed
+// tombstone can't be restored, so history is only deleted once nothing can bring the entity back.
+// The tombstone goes last, a purge that fails part way is picked up by the next run.
+//
+// GSI2-Pkey = USER#DELETED | PRODUCT#DELETED
+// GSI2-Skey = <deleted_at>#<key>
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use persistance_repository::{GSIs, IndexQuery, SortKeyCondition};
+
+pub const DELETED_AT_ATTRIBUTE: &str = "DeletedAt";
+pub const PURGING_AT_ATTRIBUTE: &str = "PurgingAt";
+
+pub(crate) fn is_tombstone(item: &HashMap<String, AttributeValue>) -> bool {
+    item.contains_key(DELETED_AT_ATTRIBUTE)
+}
+
+// The item as stored once deleted, `key` orders tombstones deleted in the same second
+pub(crate) fn into_tombstone(
+    mut item: HashMap<String, AttributeValue>,
+    deleted_partition: &str,
+    key: &str,
+    deleted_at: &str,
+) -> HashMap<String, AttributeValue> {
+    item.insert(
+        DELETED_AT_ATTRIBUTE.to_string(),
+        AttributeValue::N(deleted_at.to_string()),
+    );
+    item.insert(
+        "GSI2Pkey".to_string(),
+        AttributeValue::S(deleted_partition.to_string()),
+    );
+    item.insert(
+        "GSI2Skey".to_string(),
+        AttributeValue::S(format!("{}#{}", deleted_at, key)),
+    );
+    item
+}
+
+// The tombstone as claimed by the purge
+pub(crate) fn into_purging(
+    mut item: HashMap<String, AttributeValue>,
+) -> HashMap<String, AttributeValue> {
+    item.insert(
+        PURGING_AT_ATTRIBUTE.to_string(),
+        AttributeValue::N(now_seconds().to_string()),
+    );
+    item
+}
+
+// Epoch seconds, the same clock default_time() reads
+pub fn now_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+// Deletions before this are past the retention window
+pub fn retention_cutoff(retention: Duration) -> u64 {
+    now_seconds().saturating_sub(retention.as_secs()) + 1
+}
+
+fn deleted_at(item: &HashMap<String, AttributeValue>) -> u64 {
+    item.get(DELETED_AT_ATTRIBUTE)
+        .and_then(|deleted_at| deleted_at.as_n().ok())
+        .and_then(|deleted_at| deleted_at.parse().ok())
+        .unwrap_or_default()
+}
+
+// Whether the tombstone can still be restored, anything else is the purge job's. A claimed
+// tombstone never can be, whatever window the restore is run with
+pub(crate) fn within_retention(
+    item: &HashMap<String, AttributeValue>,
+    retention: Duration,
+) -> bool {
+    !item.contains_key(PURGING_AT_ATTRIBUTE) && deleted_at(item) >= retention_cutoff(retention)
+}
+
+// Tombstones whose retention window has passed, oldest first
+pub(crate) fn expired_tombstones_query(deleted_partition: &str, retention: Duration) -> IndexQuery {
+    // `<cutoff>` sorts before every `<cutoff>#<key>`, so this takes deletions up to and
+    // including `<cutoff> - 1`, the newest that are no longer within the window
+    IndexQuery::gsi(GSIs::GSI2, deleted_partition.to_string()).sort_key(SortKeyCondition::LessThan(
+        AttributeValue::S(retention_cutoff(retention).to_string()),
+    ))
+}
+
+// Hides tombstones from an index query
+pub(crate) fn without_tombstones(query: IndexQuery) -> IndexQuery {
+    query
+        .filter("attribute_not_exists(#deleted_at)")
+        .name("#deleted_at", DELETED_AT_ATTRIBUTE)
+}
@@ -0,0 +1,397 @@
+// Derives the models crate's DynamoDbModel trait from the struct definition
+//
+// #[derive(DynamoDbModel)]
+// #[dynamo(pkey = "USER#{username}", skey = "-", gsi1_pkey = "USER#{email}", gsi1_skey = "-")]
+// pub struct User {
+//     pub username: String,     // String fields are stored as S
+//     #[dynamo(n)]
+//     pub created_at: String,   // unless marked n, e.g. timestamps kept as strings
+//     pub quantity: u32,        // number fields are stored as N
+//     #[dynamo(default)]
+//     pub version: u64,         // items written before the field existed read as Default
+// }
+//
+// Key templates cover Pkey, Skey, GSI1Pkey/GSI1Skey and GSI2Pkey/GSI2Skey, `{field}` is replaced
+// with the field's value. Each template also becomes a typed builder on the struct, taking the
+// fields it uses, e.g. User::pkey(username), along with User::primary_key(..) for Get/Delete keys.
+//
+// Keys are built as if there were one storefront, the repository puts the tenant on partition
+// keys as they go out, see persistance_repository's tenant.
+//
+// The generated code refers to the models crate as `crate`, so it is only for use inside it.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitStr, Type};
+
+const KEY_ATTRIBUTES: [(&str, &str); 6] = [
+    ("pkey", "Pkey"),
+    ("skey", "Skey"),
+    ("gsi1_pkey", "GSI1Pkey"),
+    ("gsi1_skey", "GSI1Skey"),
+    ("gsi2_pkey", "GSI2Pkey"),
+    ("gsi2_skey", "GSI2Skey"),
+];
+
+const NUMBER_TYPES: [&str; 14] = [
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize", "f32",
+    "f64",
+];
+
+#[proc_macro_derive(DynamoDbModel, attributes(dynamo))]
+pub fn derive_dynamo_db_model(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+enum Storage {
+    // String stored as S
+    S,
+    // String holding a number stored as N
+    NString,
+    // Number type stored as N
+    N,
+}
+
+struct Field {
+    ident: Ident,
+    ty: Type,
+    storage: Storage,
+    // read as Default when the attribute is missing
+    default: bool,
+}
+
+enum TemplatePart {
+    Literal(String),
+    Field(Ident),
+}
+
+struct KeyTemplate {
+    // builder name, e.g. gsi1_pkey
+    builder: Ident,
+    // table attribute, e.g. GSI1Pkey
+    attribute: String,
+    parts: Vec<TemplatePart>,
+}
+
+impl KeyTemplate {
+    // The fields used, in order of first use
+    fn fields(&self) -> Vec<&Ident> {
+        let mut fields = Vec::new();
+        for part in &self.parts {
+            if let TemplatePart::Field(ident) = part {
+                if !fields.contains(&ident) {
+                    fields.push(ident);
+                }
+            }
+        }
+        fields
+    }
+
+    fn format_string(&self) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Literal(literal) => literal.replace('{', "{{").replace('}', "}}"),
+                TemplatePart::Field(ident) => format!("{{{}}}", ident),
+            })
+            .collect()
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let fields = parse_fields(&input)?;
+    let templates = parse_key_templates(&input, &fields)?;
+
+    let key_inserts = templates.iter().map(|template| {
+        let builder = &template.builder;
+        let attribute = &template.attribute;
+        let args = template
+            .fields()
+            .into_iter()
+            .map(|field| quote!(&self.#field));
+        quote! {
+            attr_map.insert(
+                #attribute.to_string(),
+                ::aws_sdk_dynamodb::types::AttributeValue::S(Self::#builder(#(#args),*)),
+            );
+        }
+    });
+
+    let field_inserts = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let attribute = ident.to_string();
+        let value = match field.storage {
+            Storage::S => quote!(::aws_sdk_dynamodb::types::AttributeValue::S(self.#ident.clone())),
+            Storage::NString => {
+                quote!(::aws_sdk_dynamodb::types::AttributeValue::N(self.#ident.clone()))
+            }
+            Storage::N => {
+                quote!(::aws_sdk_dynamodb::types::AttributeValue::N(self.#ident.to_string()))
+            }
+        };
+        quote! {
+            attr_map.insert(#attribute.to_string(), #value);
+        }
+    });
+
+    let field_reads = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let attribute = ident.to_string();
+        let read = match field.storage {
+            Storage::S => quote!(attr.string(#attribute)?),
+            Storage::NString => quote!(attr.number(#attribute)?),
+            Storage::N => quote!(attr.parse_number(#attribute)?),
+        };
+        match field.default {
+            true => quote! {
+                #ident: match attr.has(#attribute) {
+                    true => #read,
+                    false => Default::default(),
+                }
+            },
+            false => quote!(#ident: #read),
+        }
+    });
+
+    let builders = templates.iter().map(|template| {
+        let builder = &template.builder;
+        let params = template
+            .fields()
+            .into_iter()
+            .map(|ident| key_param(ident, &fields));
+        let format_string = template.format_string();
+        let args = template.fields();
+        quote! {
+            pub fn #builder(#(#params),*) -> String {
+                format!(#format_string, #(#args = #args),*)
+            }
+        }
+    });
+
+    // Pkey and Skey are required so both are always the first two templates
+    let primary_key = {
+        let mut key_fields: Vec<&Ident> = Vec::new();
+        for ident in templates[0]
+            .fields()
+            .into_iter()
+            .chain(templates[1].fields())
+        {
+            if !key_fields.contains(&ident) {
+                key_fields.push(ident);
+            }
+        }
+        let params = key_fields.iter().map(|ident| key_param(ident, &fields));
+        let pkey_args = templates[0].fields();
+        let skey_args = templates[1].fields();
+        quote! {
+            pub fn primary_key(
+                #(#params),*
+            ) -> ::std::collections::HashMap<String, ::aws_sdk_dynamodb::types::AttributeValue> {
+                ::std::collections::HashMap::from([
+                    (
+                        "Pkey".to_string(),
+                        ::aws_sdk_dynamodb::types::AttributeValue::S(Self::pkey(#(#pkey_args),*)),
+                    ),
+                    (
+                        "Skey".to_string(),
+                        ::aws_sdk_dynamodb::types::AttributeValue::S(Self::skey(#(#skey_args),*)),
+                    ),
+                ])
+            }
+        }
+    };
+
+    Ok(quote! {
+        impl crate::DynamoDbModel for #name {
+            fn into_attr_map(
+                &self,
+            ) -> ::std::collections::HashMap<String, ::aws_sdk_dynamodb::types::AttributeValue> {
+                let mut attr_map = ::std::collections::HashMap::new();
+                #(#key_inserts)*
+                #(#field_inserts)*
+                attr_map
+            }
+
+            fn from_attr_map(
+                attr_map: ::std::collections::HashMap<String, ::aws_sdk_dynamodb::types::AttributeValue>,
+            ) -> Result<Self, crate::DecodeError> {
+                let attr = crate::AttrReader::new(&attr_map);
+                Ok(#name {
+                    #(#field_reads),*
+                })
+            }
+        }
+
+        impl #name {
+            #(#builders)*
+
+            #primary_key
+        }
+    })
+}
+
+// Key builder parameter for a field, strings are taken as &str and everything else by reference
+fn key_param(ident: &Ident, fields: &[Field]) -> proc_macro2::TokenStream {
+    let field = fields.iter().find(|field| &field.ident == ident).unwrap(); // checked when parsed
+    match field.storage {
+        Storage::S | Storage::NString => quote!(#ident: &str),
+        Storage::N => {
+            let ty = &field.ty;
+            quote!(#ident: &#ty)
+        }
+    }
+}
+
+fn parse_fields(input: &DeriveInput) -> syn::Result<Vec<Field>> {
+    let named = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => &named.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "DynamoDbModel can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "DynamoDbModel can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut fields = Vec::new();
+    for field in named {
+        let ident = field.ident.clone().unwrap(); // named fields always have an ident
+        let type_name = match &field.ty {
+            Type::Path(path) => path.path.get_ident().map(|ident| ident.to_string()),
+            _ => None,
+        };
+
+        let mut marked = None;
+        let mut default = false;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("dynamo"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("s") {
+                    marked = Some("S");
+                    Ok(())
+                } else if meta.path.is_ident("n") {
+                    marked = Some("N");
+                    Ok(())
+                } else if meta.path.is_ident("default") {
+                    default = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `s`, `n` or `default`"))
+                }
+            })?;
+        }
+
+        let storage = match (type_name.as_deref(), marked) {
+            (Some("String"), None | Some("S")) => Storage::S,
+            (Some("String"), Some(_)) => Storage::NString,
+            (Some(type_name), None | Some("N")) if NUMBER_TYPES.contains(&type_name) => Storage::N,
+            (Some(type_name), Some(_)) if NUMBER_TYPES.contains(&type_name) => {
+                return Err(syn::Error::new_spanned(
+                    &field.ty,
+                    "number fields can only be stored as N",
+                ))
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &field.ty,
+                    "DynamoDbModel fields must be a String or a number type",
+                ))
+            }
+        };
+
+        fields.push(Field {
+            ident,
+            ty: field.ty.clone(),
+            storage,
+            default,
+        });
+    }
+    Ok(fields)
+}
+
+fn parse_key_templates(input: &DeriveInput, fields: &[Field]) -> syn::Result<Vec<KeyTemplate>> {
+    let mut found: Vec<(String, LitStr)> = Vec::new();
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("dynamo"))
+    {
+        attr.parse_nested_meta(|meta| {
+            let builder = KEY_ATTRIBUTES
+                .iter()
+                .map(|(builder, _)| *builder)
+                .find(|builder| meta.path.is_ident(builder))
+                .ok_or_else(|| {
+                    meta.error(
+                        "expected one of pkey, skey, gsi1_pkey, gsi1_skey, gsi2_pkey, gsi2_skey",
+                    )
+                })?;
+            found.push((builder.to_string(), meta.value()?.parse()?));
+            Ok(())
+        })?;
+    }
+
+    let mut templates = Vec::new();
+    for (builder, attribute) in KEY_ATTRIBUTES {
+        match found.iter().find(|(found, _)| found == builder) {
+            Some((_, template)) => templates.push(KeyTemplate {
+                builder: Ident::new(builder, Span::call_site()),
+                attribute: attribute.to_string(),
+                parts: parse_template(template, fields)?,
+            }),
+            None if builder == "pkey" || builder == "skey" => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    format!("missing #[dynamo({} = \"...\")] key template", builder),
+                ))
+            }
+            None => {}
+        }
+    }
+    Ok(templates)
+}
+
+fn parse_template(template: &LitStr, fields: &[Field]) -> syn::Result<Vec<TemplatePart>> {
+    let value = template.value();
+    let mut parts = Vec::new();
+    let mut rest = value.as_str();
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            parts.push(TemplatePart::Literal(rest[..start].to_string()));
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| syn::Error::new_spanned(template, "unclosed `{` in key template"))?
+            + start;
+        let field_name = &rest[start + 1..end];
+        if !fields.iter().any(|field| field.ident == field_name) {
+            return Err(syn::Error::new_spanned(
+                template,
+                format!("key template refers to unknown field `{}`", field_name),
+            ));
+        }
+        parts.push(TemplatePart::Field(format_ident!("{}", field_name)));
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(TemplatePart::Literal(rest.to_string()));
+    }
+    Ok(parts)
+}
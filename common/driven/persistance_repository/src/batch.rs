@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use aws_sdk_dynamodb::types::{AttributeValue, KeysAndAttributes, WriteRequest};
+use futures::stream::{self, StreamExt};
+
+use crate::{DynamoDBSingleTableRepository, MeasuredSend};
+
+// Limits on a single call, anything bigger has to be chunked
+pub const BATCH_GET_CHUNK_SIZE: usize = 100;
+pub const BATCH_WRITE_CHUNK_SIZE: usize = 25;
+
+type Item = HashMap<String, AttributeValue>;
+
+// How hard batch_get and batch_write try before giving up on unprocessed entries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchPolicy {
+    // includes the first call, so 1 means never retry
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // chunks in flight at once
+    pub concurrency: usize,
+}
+
+impl Default for BatchPolicy {
+    fn default() -> Self {
+        BatchPolicy {
+            max_attempts: 8,
+            base_delay: Duration::from_millis(25),
+            max_delay: Duration::from_secs(2),
+            concurrency: 4,
+        }
+    }
+}
+
+impl BatchPolicy {
+    // Full jitter, anywhere between nothing and the capped exponential delay, so throttled
+    // chunks don't all come back at the same moment
+    fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)));
+        let capped = exponential.min(self.max_delay).as_millis() as u64;
+        Duration::from_millis(fastrand::u64(0..=capped))
+    }
+}
+
+// Entries that never made it, whether still unprocessed after the last attempt or part of a
+// chunk whose call failed outright. Everything else in the batch went through.
+#[derive(Debug)]
+pub struct BatchError<T> {
+    pub failed: Vec<T>,
+    pub errors: Vec<String>,
+}
+
+impl<T> fmt::Display for BatchError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} batch entries failed", self.failed.len())?;
+        if !self.errors.is_empty() {
+            write!(f, " [{}]", self.errors.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+struct ChunkOutcome<T> {
+    items: Vec<Item>,
+    failed: Vec<T>,
+    error: Option<String>,
+}
+
+impl DynamoDBSingleTableRepository {
+    // Every item found for the keys, in no particular order. Missing items are simply absent.
+    pub async fn batch_get(&self, keys: Vec<Item>) -> Result<Vec<Item>, BatchError<Item>> {
+        let keys = keys
+            .into_iter()
+            .map(|key| self.tenant().scope(key))
+            .collect();
+        self.run_chunks(keys, BATCH_GET_CHUNK_SIZE, |chunk| {
+            self.batch_get_chunk(chunk)
+        })
+        .await
+    }
+
+    pub async fn batch_write(
+        &self,
+        writes: Vec<WriteRequest>,
+    ) -> Result<(), BatchError<WriteRequest>> {
+        let writes = writes
+            .into_iter()
+            .map(|write| self.tenant().scope_write(write))
+            .collect();
+        self.run_chunks(writes, BATCH_WRITE_CHUNK_SIZE, |chunk| {
+            self.batch_write_chunk(chunk)
+        })
+        .await
+        .map(|_| ())
+    }
+
+    async fn run_chunks<T, F, Fut>(
+        &self,
+        entries: Vec<T>,
+        chunk_size: usize,
+        send_chunk: F,
+    ) -> Result<Vec<Item>, BatchError<T>>
+    where
+        T: Clone,
+        F: Fn(Vec<T>) -> Fut,
+        Fut: Future<Output = ChunkOutcome<T>>,
+    {
+        let chunks: Vec<Vec<T>> = entries
+            .chunks(chunk_size)
+            .map(|chunk| chunk.to_vec())
+            .collect();
+        let outcomes: Vec<ChunkOutcome<T>> = stream::iter(chunks)
+            .map(send_chunk)
+            .buffer_unordered(self.batch_policy.concurrency.max(1))
+            .collect()
+            .await;
+
+        let mut items = Vec::new();
+        let mut failed = Vec::new();
+        let mut errors = Vec::new();
+        for outcome in outcomes {
+            items.extend(outcome.items);
+            failed.extend(outcome.failed);
+            errors.extend(outcome.error);
+        }
+        match failed.is_empty() {
+            true => Ok(items),
+            false => Err(BatchError { failed, errors }),
+        }
+    }
+
+    async fn batch_get_chunk(&self, keys: Vec<Item>) -> ChunkOutcome<Item> {
+        let mut items = Vec::new();
+        let mut pending = keys;
+        let mut attempt = 1;
+        loop {
+            let request = KeysAndAttributes::builder()
+                .set_keys(Some(pending.clone()))
+                .build()
+                .unwrap(); // Keys is always set so unwrap is safe
+            let output = match self
+                .client
+                .batch_get_item()
+                .request_items(self.table_name.clone(), request)
+                .send_measured(self)
+                .await
+            {
+                Ok(output) => output,
+                Err(e) => {
+                    return ChunkOutcome {
+                        items,
+                        failed: pending,
+                        error: Some(e.into_service_error().to_string()),
+                    }
+                }
+            };
+            items.extend(
+                output
+                    .responses
+                    .and_then(|mut responses| responses.remove(&self.table_name))
+                    .unwrap_or_default(),
+            );
+            pending = output
+                .unprocessed_keys
+                .and_then(|mut unprocessed| unprocessed.remove(&self.table_name))
+                .map(|unprocessed| unprocessed.keys)
+                .unwrap_or_default();
+
+            if pending.is_empty() || attempt >= self.batch_policy.max_attempts {
+                return ChunkOutcome {
+                    items,
+                    failed: pending,
+                    error: None,
+                };
+            }
+            tokio::time::sleep(self.batch_policy.backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    async fn batch_write_chunk(&self, writes: Vec<WriteRequest>) -> ChunkOutcome<WriteRequest> {
+        let mut pending = writes;
+        let mut attempt = 1;
+        loop {
+            let output = match self
+                .client
+                .batch_write_item()
+                .request_items(self.table_name.clone(), pending.clone())
+                .send_measured(self)
+                .await
+            {
+                Ok(output) => output,
+                Err(e) => {
+                    return ChunkOutcome {
+                        items: Vec::new(),
+                        failed: pending,
+                        error: Some(e.into_service_error().to_string()),
+                    }
+                }
+            };
+            pending = output
+                .unprocessed_items
+                .and_then(|mut unprocessed| unprocessed.remove(&self.table_name))
+                .unwrap_or_default();
+
+            if pending.is_empty() || attempt >= self.batch_policy.max_attempts {
+                return ChunkOutcome {
+                    items: Vec::new(),
+                    failed: pending,
+                    error: None,
+                };
+            }
+            tokio::time::sleep(self.batch_policy.backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = BatchPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(50),
+            concurrency: 1,
+        };
+        for retry in 1..10 {
+            assert!(policy.backoff(retry) <= Duration::from_millis(50));
+        }
+        assert!(policy.backoff(1) <= Duration::from_millis(10));
+    }
+}
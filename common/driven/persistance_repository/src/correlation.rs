@@ -0,0 +1,88 @@
+// Correlation
+// Events written through a repository carry the ids of whatever caused the write, so a consumer
+// can follow one request through every event it led to.
+//
+// correlation_id is shared by the whole chain, from the HTTP request that started it
+// causation_id is the event that directly triggered the write, None for an HTTP request
+//
+// They are added to the event as it goes into the outbox, the domain building the event never
+// needs to know about them.
+
+use serde_json::Value;
+
+use crate::DynamoDBSingleTableRepository;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Correlation {
+    pub correlation_id: Option<String>,
+    pub causation_id: Option<String>,
+}
+
+impl Correlation {
+    pub fn new(correlation_id: Option<String>, causation_id: Option<String>) -> Correlation {
+        Correlation {
+            correlation_id,
+            causation_id,
+        }
+    }
+
+    // The serialised event with the ids added. Ids the event already has are kept, and events
+    // that aren't objects are left as they are
+    pub fn stamp(&self, detail: &str) -> String {
+        let mut fields = match serde_json::from_str::<Value>(detail) {
+            Ok(Value::Object(fields)) => fields,
+            _ => return detail.to_string(),
+        };
+        for (name, id) in [
+            ("correlation_id", &self.correlation_id),
+            ("causation_id", &self.causation_id),
+        ] {
+            if let Some(id) = id {
+                fields
+                    .entry(name)
+                    .or_insert_with(|| Value::String(id.clone()));
+            }
+        }
+        Value::Object(fields).to_string()
+    }
+}
+
+impl DynamoDBSingleTableRepository {
+    pub fn correlation(&self) -> &Correlation {
+        &self.correlation
+    }
+
+    // Usually set per request, next to for_tenant
+    pub fn with_correlation(self, correlation: Correlation) -> DynamoDBSingleTableRepository {
+        DynamoDBSingleTableRepository {
+            correlation,
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamp() {
+        let correlation = Correlation::new(Some("request-1".to_string()), None);
+        let detail: Value =
+            serde_json::from_str(&correlation.stamp(r#"{"event_type": "test"}"#)).unwrap();
+        assert_eq!(detail["correlation_id"], "request-1");
+        assert!(detail.get("causation_id").is_none());
+
+        // an event that already names its chain keeps it
+        let correlation = Correlation::new(Some("request-2".to_string()), Some("event-1".into()));
+        let detail: Value = serde_json::from_str(
+            &correlation.stamp(r#"{"event_type": "test", "correlation_id": "request-1"}"#),
+        )
+        .unwrap();
+        assert_eq!(detail["correlation_id"], "request-1");
+        assert_eq!(detail["causation_id"], "event-1");
+
+        assert_eq!(correlation.stamp("[]"), "[]");
+        assert_eq!(Correlation::default().stamp("{}"), "{}");
+    }
+}
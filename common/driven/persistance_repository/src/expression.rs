@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::types::AttributeValue;
+
+// A condition on the stored item. Attribute names always go through `#name` placeholders, so
+// reserved words like `name` or `version` need no special handling
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Equals(String, AttributeValue),
+    NotEquals(String, AttributeValue),
+    AttributeExists(String),
+    AttributeNotExists(String),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+}
+
+impl Condition {
+    pub fn equals(attribute: &str, value: AttributeValue) -> Self {
+        Condition::Equals(attribute.to_string(), value)
+    }
+
+    pub fn not_equals(attribute: &str, value: AttributeValue) -> Self {
+        Condition::NotEquals(attribute.to_string(), value)
+    }
+
+    pub fn exists(attribute: &str) -> Self {
+        Condition::AttributeExists(attribute.to_string())
+    }
+
+    pub fn not_exists(attribute: &str) -> Self {
+        Condition::AttributeNotExists(attribute.to_string())
+    }
+
+    pub fn and(self, other: Condition) -> Self {
+        match self {
+            Condition::And(mut conditions) => {
+                conditions.push(other);
+                Condition::And(conditions)
+            }
+            condition => Condition::And(vec![condition, other]),
+        }
+    }
+
+    pub fn or(self, other: Condition) -> Self {
+        match self {
+            Condition::Or(mut conditions) => {
+                conditions.push(other);
+                Condition::Or(conditions)
+            }
+            condition => Condition::Or(vec![condition, other]),
+        }
+    }
+
+    // For a Put, Delete or ConditionCheck, which only take a condition
+    pub fn build(&self) -> Expression {
+        let mut placeholders = Placeholders::default();
+        let condition = self.render(&mut placeholders);
+        placeholders.into_expression(None, Some(condition))
+    }
+
+    fn render(&self, placeholders: &mut Placeholders) -> String {
+        match self {
+            Condition::Equals(attribute, value) => format!(
+                "{} = {}",
+                placeholders.name(attribute),
+                placeholders.value(value)
+            ),
+            Condition::NotEquals(attribute, value) => format!(
+                "{} <> {}",
+                placeholders.name(attribute),
+                placeholders.value(value)
+            ),
+            Condition::AttributeExists(attribute) => {
+                format!("attribute_exists({})", placeholders.name(attribute))
+            }
+            Condition::AttributeNotExists(attribute) => {
+                format!("attribute_not_exists({})", placeholders.name(attribute))
+            }
+            Condition::And(conditions) => Self::render_all(conditions, " AND ", placeholders),
+            Condition::Or(conditions) => Self::render_all(conditions, " OR ", placeholders),
+        }
+    }
+
+    fn render_all(
+        conditions: &[Condition],
+        joiner: &str,
+        placeholders: &mut Placeholders,
+    ) -> String {
+        conditions
+            .iter()
+            .map(|condition| match condition {
+                // nested groups keep their own precedence
+                Condition::And(_) | Condition::Or(_) => {
+                    format!("({})", condition.render(placeholders))
+                }
+                condition => condition.render(placeholders),
+            })
+            .collect::<Vec<String>>()
+            .join(joiner)
+    }
+}
+
+// An UpdateItem or transactional Update, built up action by action
+//
+// UpdateExpression::new()
+//     .set("sent_at", AttributeValue::N(sent_at))
+//     .remove("GSI2Pkey")
+//     .add("attempts", AttributeValue::N("1".to_string()))
+//     .condition(Condition::exists("Pkey"))
+//     .build()
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UpdateExpression {
+    set: Vec<(String, AttributeValue)>,
+    remove: Vec<String>,
+    add: Vec<(String, AttributeValue)>,
+    condition: Option<Condition>,
+}
+
+impl UpdateExpression {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(mut self, attribute: &str, value: AttributeValue) -> Self {
+        self.set.push((attribute.to_string(), value));
+        self
+    }
+
+    pub fn remove(mut self, attribute: &str) -> Self {
+        self.remove.push(attribute.to_string());
+        self
+    }
+
+    // Adds to a number or a set, creating the attribute if it's missing
+    pub fn add(mut self, attribute: &str, value: AttributeValue) -> Self {
+        self.add.push((attribute.to_string(), value));
+        self
+    }
+
+    // ANDed with any condition already given
+    pub fn condition(mut self, condition: Condition) -> Self {
+        self.condition = Some(match self.condition {
+            Some(existing) => existing.and(condition),
+            None => condition,
+        });
+        self
+    }
+
+    pub fn build(&self) -> Expression {
+        let mut placeholders = Placeholders::default();
+        let mut clauses = Vec::new();
+        if !self.set.is_empty() {
+            let actions = self
+                .set
+                .iter()
+                .map(|(attribute, value)| {
+                    format!(
+                        "{} = {}",
+                        placeholders.name(attribute),
+                        placeholders.value(value)
+                    )
+                })
+                .collect::<Vec<String>>();
+            clauses.push(format!("SET {}", actions.join(", ")));
+        }
+        if !self.remove.is_empty() {
+            let actions = self
+                .remove
+                .iter()
+                .map(|attribute| placeholders.name(attribute))
+                .collect::<Vec<String>>();
+            clauses.push(format!("REMOVE {}", actions.join(", ")));
+        }
+        if !self.add.is_empty() {
+            let actions = self
+                .add
+                .iter()
+                .map(|(attribute, value)| {
+                    format!(
+                        "{} {}",
+                        placeholders.name(attribute),
+                        placeholders.value(value)
+                    )
+                })
+                .collect::<Vec<String>>();
+            clauses.push(format!("ADD {}", actions.join(", ")));
+        }
+        let condition = self
+            .condition
+            .as_ref()
+            .map(|condition| condition.render(&mut placeholders));
+        placeholders.into_expression((!clauses.is_empty()).then(|| clauses.join(" ")), condition)
+    }
+}
+
+// The expressions and the placeholders they use, ready to hand to an SDK builder
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Expression {
+    pub update: Option<String>,
+    pub condition: Option<String>,
+    pub names: HashMap<String, String>,
+    pub values: HashMap<String, AttributeValue>,
+}
+
+impl Expression {
+    // DynamoDB rejects empty placeholder maps, so these are None when there is nothing to send
+    pub fn attribute_names(&self) -> Option<HashMap<String, String>> {
+        (!self.names.is_empty()).then(|| self.names.clone())
+    }
+
+    pub fn attribute_values(&self) -> Option<HashMap<String, AttributeValue>> {
+        (!self.values.is_empty()).then(|| self.values.clone())
+    }
+}
+
+// Hands out `#n0`, `#n1`.. per attribute and `:v0`, `:v1`.. per value
+#[derive(Default)]
+struct Placeholders {
+    names: HashMap<String, String>,
+    by_attribute: HashMap<String, String>,
+    values: HashMap<String, AttributeValue>,
+}
+
+impl Placeholders {
+    fn name(&mut self, attribute: &str) -> String {
+        if let Some(placeholder) = self.by_attribute.get(attribute) {
+            return placeholder.clone();
+        }
+        let placeholder = format!("#n{}", self.names.len());
+        self.names
+            .insert(placeholder.clone(), attribute.to_string());
+        self.by_attribute
+            .insert(attribute.to_string(), placeholder.clone());
+        placeholder
+    }
+
+    fn value(&mut self, value: &AttributeValue) -> String {
+        let placeholder = format!(":v{}", self.values.len());
+        self.values.insert(placeholder.clone(), value.clone());
+        placeholder
+    }
+
+    fn into_expression(self, update: Option<String>, condition: Option<String>) -> Expression {
+        Expression {
+            update,
+            condition,
+            names: self.names,
+            values: self.values,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(value: u64) -> AttributeValue {
+        AttributeValue::N(value.to_string())
+    }
+
+    #[test]
+    fn test_update_expression_allocates_placeholders() {
+        let expression = UpdateExpression::new()
+            .set("version", n(2))
+            .set("name", AttributeValue::S("new".to_string()))
+            .remove("GSI2Pkey")
+            .add("views", n(1))
+            .condition(Condition::equals("version", n(1)))
+            .build();
+
+        assert_eq!(
+            expression.update.as_deref(),
+            Some("SET #n0 = :v0, #n1 = :v1 REMOVE #n2 ADD #n3 :v2")
+        );
+        // a repeated attribute keeps its placeholder, values always get a new one
+        assert_eq!(expression.condition.as_deref(), Some("#n0 = :v3"));
+        assert_eq!(expression.names["#n0"], "version");
+        assert_eq!(expression.names.len(), 4);
+        assert_eq!(expression.values[":v3"], n(1));
+        assert_eq!(expression.values.len(), 4);
+    }
+
+    #[test]
+    fn test_condition_groups_nest() {
+        let expression = Condition::equals("email", AttributeValue::S("a".to_string()))
+            .and(Condition::equals("version", n(1)).or(Condition::not_exists("version")))
+            .and(Condition::exists("Pkey"))
+            .build();
+
+        assert_eq!(expression.update, None);
+        assert_eq!(
+            expression.condition.as_deref(),
+            Some(
+                "#n0 = :v0 AND (#n1 = :v1 OR attribute_not_exists(#n1)) AND attribute_exists(#n2)"
+            )
+        );
+        assert_eq!(Condition::exists("Pkey").build().attribute_values(), None);
+    }
+}
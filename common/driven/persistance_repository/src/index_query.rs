@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::operation::query::builders::QueryFluentBuilder;
+use aws_sdk_dynamodb::types::AttributeValue;
+
+use crate::pagination::{Page, PageError, PageRequest};
+use crate::{DynamoDBSingleTableRepository, GSIs};
+
+// Placeholders used for the key condition and projection, filters must use others
+const PARTITION_KEY_VALUE: &str = ":key_pk";
+const SORT_KEY_VALUE: &str = ":key_sk";
+const SORT_KEY_END_VALUE: &str = ":key_sk_end";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortKeyCondition {
+    Equals(AttributeValue),
+    BeginsWith(String),
+    // inclusive at both ends
+    Between(AttributeValue, AttributeValue),
+    LessThan(AttributeValue),
+    LessThanOrEqual(AttributeValue),
+    GreaterThan(AttributeValue),
+    GreaterThanOrEqual(AttributeValue),
+}
+
+impl SortKeyCondition {
+    fn expression(&self, attribute: &str) -> String {
+        let compare = |operator: &str| format!("{} {} {}", attribute, operator, SORT_KEY_VALUE);
+        match self {
+            SortKeyCondition::Equals(_) => compare("="),
+            SortKeyCondition::BeginsWith(_) => {
+                format!("begins_with({}, {})", attribute, SORT_KEY_VALUE)
+            }
+            SortKeyCondition::Between(_, _) => format!(
+                "{} BETWEEN {} AND {}",
+                attribute, SORT_KEY_VALUE, SORT_KEY_END_VALUE
+            ),
+            SortKeyCondition::LessThan(_) => compare("<"),
+            SortKeyCondition::LessThanOrEqual(_) => compare("<="),
+            SortKeyCondition::GreaterThan(_) => compare(">"),
+            SortKeyCondition::GreaterThanOrEqual(_) => compare(">="),
+        }
+    }
+
+    fn values(&self) -> Vec<(&'static str, AttributeValue)> {
+        match self {
+            SortKeyCondition::BeginsWith(prefix) => {
+                vec![(SORT_KEY_VALUE, AttributeValue::S(prefix.clone()))]
+            }
+            SortKeyCondition::Between(start, end) => vec![
+                (SORT_KEY_VALUE, start.clone()),
+                (SORT_KEY_END_VALUE, end.clone()),
+            ],
+            SortKeyCondition::Equals(value)
+            | SortKeyCondition::LessThan(value)
+            | SortKeyCondition::LessThanOrEqual(value)
+            | SortKeyCondition::GreaterThan(value)
+            | SortKeyCondition::GreaterThanOrEqual(value) => vec![(SORT_KEY_VALUE, value.clone())],
+        }
+    }
+}
+
+// A query against the table's own key or one of its GSIs
+//
+// IndexQuery::gsi(GSIs::GSI1, CartItem::gsi1_pkey(product_id))
+//     .sort_key(SortKeyCondition::BeginsWith("CART#USER#".to_string()))
+//     .filter("quantity > :min")
+//     .value(":min", AttributeValue::N("1".to_string()))
+//     .project(&["user_id", "quantity"])
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexQuery {
+    index: Option<GSIs>,
+    partition_key: String,
+    sort_key: Option<SortKeyCondition>,
+    filter: Option<String>,
+    names: HashMap<String, String>,
+    values: HashMap<String, AttributeValue>,
+    projection: Option<Vec<String>>,
+    descending: bool,
+}
+
+impl IndexQuery {
+    // Pkey/Skey
+    pub fn table(partition_key: String) -> Self {
+        Self::new(None, partition_key)
+    }
+
+    pub fn gsi(index: GSIs, partition_key: String) -> Self {
+        Self::new(Some(index), partition_key)
+    }
+
+    fn new(index: Option<GSIs>, partition_key: String) -> Self {
+        Self {
+            index,
+            partition_key,
+            sort_key: None,
+            filter: None,
+            names: HashMap::new(),
+            values: HashMap::new(),
+            projection: None,
+            descending: false,
+        }
+    }
+
+    pub fn sort_key(mut self, condition: SortKeyCondition) -> Self {
+        self.sort_key = Some(condition);
+        self
+    }
+
+    // Applied after the key condition, so filtered out items still count towards the page limit
+    pub fn filter(mut self, expression: &str) -> Self {
+        self.filter = Some(expression.to_string());
+        self
+    }
+
+    // `#name` placeholder for the filter
+    pub fn name(mut self, placeholder: &str, attribute: &str) -> Self {
+        self.names
+            .insert(placeholder.to_string(), attribute.to_string());
+        self
+    }
+
+    // `:value` placeholder for the filter
+    pub fn value(mut self, placeholder: &str, value: AttributeValue) -> Self {
+        self.values.insert(placeholder.to_string(), value);
+        self
+    }
+
+    // Only return these attributes
+    pub fn project(mut self, attributes: &[&str]) -> Self {
+        self.projection = Some(attributes.iter().map(|a| a.to_string()).collect());
+        self
+    }
+
+    pub fn descending(mut self) -> Self {
+        self.descending = true;
+        self
+    }
+
+    fn key_attributes(&self) -> (&'static str, &'static str) {
+        match self.index {
+            None => ("Pkey", "Skey"),
+            Some(GSIs::GSI1) => ("GSI1Pkey", "GSI1Skey"),
+            Some(GSIs::GSI2) => ("GSI2Pkey", "GSI2Skey"),
+        }
+    }
+
+    fn key_condition(&self) -> String {
+        let (partition_attribute, sort_attribute) = self.key_attributes();
+        let partition = format!("{} = {}", partition_attribute, PARTITION_KEY_VALUE);
+        match &self.sort_key {
+            Some(condition) => {
+                format!("{} AND {}", partition, condition.expression(sort_attribute))
+            }
+            None => partition,
+        }
+    }
+}
+
+impl DynamoDBSingleTableRepository {
+    // The query without a limit or start key, ready for query_page
+    pub fn index_query(&self, query: &IndexQuery) -> QueryFluentBuilder {
+        let mut names = query.names.clone();
+        let mut values = query.values.clone();
+        values.insert(
+            PARTITION_KEY_VALUE.to_string(),
+            AttributeValue::S(self.tenant().key(&query.partition_key)),
+        );
+        for (placeholder, value) in query.sort_key.iter().flat_map(|sort_key| sort_key.values()) {
+            values.insert(placeholder.to_string(), value);
+        }
+
+        // projected attributes go through names as plenty of attribute names are reserved words
+        let projection = query.projection.as_ref().map(|attributes| {
+            attributes
+                .iter()
+                .enumerate()
+                .map(|(index, attribute)| {
+                    let placeholder = format!("#key_projection{}", index);
+                    names.insert(placeholder.clone(), attribute.clone());
+                    placeholder
+                })
+                .collect::<Vec<String>>()
+                .join(", ")
+        });
+
+        self.client
+            .query()
+            .table_name(self.table_name.clone())
+            .set_index_name(query.index.map(|index| self.index_name(index)))
+            .key_condition_expression(query.key_condition())
+            .set_filter_expression(query.filter.clone())
+            .set_projection_expression(projection)
+            .set_expression_attribute_names((!names.is_empty()).then_some(names))
+            .set_expression_attribute_values(Some(values))
+            .scan_index_forward(!query.descending)
+    }
+
+    pub async fn query_index(
+        &self,
+        query: &IndexQuery,
+        page: &PageRequest,
+    ) -> Result<Page<HashMap<String, AttributeValue>>, PageError> {
+        self.query_page(self.index_query(query), page).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_conditions() {
+        let query = IndexQuery::table("USER#a".to_string());
+        assert_eq!(query.key_condition(), "Pkey = :key_pk");
+
+        let query = IndexQuery::gsi(GSIs::GSI2, "OUTBOX#PENDING".to_string()).sort_key(
+            SortKeyCondition::Between(
+                AttributeValue::S("1".to_string()),
+                AttributeValue::S("2".to_string()),
+            ),
+        );
+        assert_eq!(
+            query.key_condition(),
+            "GSI2Pkey = :key_pk AND GSI2Skey BETWEEN :key_sk AND :key_sk_end"
+        );
+
+        let query = IndexQuery::gsi(GSIs::GSI1, "CART#PRODUCT#1".to_string())
+            .sort_key(SortKeyCondition::BeginsWith("CART#USER#".to_string()));
+        assert_eq!(
+            query.key_condition(),
+            "GSI1Pkey = :key_pk AND begins_with(GSI1Skey, :key_sk)"
+        );
+    }
+}
@@ -1,54 +1,155 @@
+pub mod batch;
+pub mod correlation;
+pub mod expression;
+pub mod index_query;
+pub mod measured;
+pub mod pagination;
+pub mod tenant;
+pub mod transaction;
+
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use aws_sdk_dynamodb::operation::delete_item::DeleteItemError;
 use aws_sdk_dynamodb::operation::get_item::GetItemError;
 use aws_sdk_dynamodb::operation::put_item::PutItemError;
 use aws_sdk_dynamodb::operation::query::QueryError;
+use aws_sdk_dynamodb::operation::scan::builders::ScanFluentBuilder;
 use aws_sdk_dynamodb::operation::update_item::UpdateItemError;
 use aws_sdk_dynamodb::types::AttributeValue;
 use aws_sdk_dynamodb::Client;
 
+use app_config::{AppConfig, ConfigError, CURSOR_SIGNING_KEY, DYNAMO_TABLE_NAME};
+use metrics::{EmfMetricsAdaptor, MetricsPort};
+use pagination::CursorSigner;
 use sdk_credential_meta_repository::SdkCredentialsMetaRepository;
 use tokio::sync::OnceCell;
 
+pub use batch::{BatchError, BatchPolicy};
+pub use correlation::Correlation;
+pub use expression::{Condition, Expression, UpdateExpression};
+pub use index_query::{IndexQuery, SortKeyCondition};
+pub use measured::MeasuredSend;
+pub use pagination::{Page, PageRequest};
+pub use tenant::{Tenant, TenantScoped};
+pub use transaction::{Cancellation, CancellationCode, Transaction, TransactionError};
+
 pub static AWS_DYNAMO_DB_REPOSITORY: OnceCell<DynamoDBSingleTableRepository> =
     OnceCell::const_new();
 
+// The table's global secondary indexes, named by the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GSIs {
     GSI1,
     GSI2,
 }
 
+// Model adaptors take the rest of their settings from `config`, so the repository is the one
+// thing a function has to build from it. Built for the default tenant, see for_tenant. The client
+// is kept to the repository so every call goes out with the tenant on its keys, see tenant
 pub struct DynamoDBSingleTableRepository {
-    pub client: Client,
+    client: Client,
     pub table_name: String,
+    pub cursor_signer: CursorSigner,
+    pub batch_policy: BatchPolicy,
+    pub config: AppConfig,
+    tenant: Tenant,
+    // where each call's consumed capacity and latency go, see measured
+    metrics: Option<Arc<dyn MetricsPort>>,
+    // the logical operation calls are recorded under, usually the function's name
+    operation: String,
+    // what caused the writes, added to the events they put in the outbox
+    correlation: Correlation,
 }
 
 impl DynamoDBSingleTableRepository {
     pub fn new(
         sdk_credential_meta_repository: &SdkCredentialsMetaRepository,
-    ) -> DynamoDBSingleTableRepository {
-        let table_name = std::env::var("DYNAMO_TABLE_NAME")
-            .expect("DYNAMO_TABLE_NAME environment variable not set");
-        DynamoDBSingleTableRepository {
+        config: &AppConfig,
+    ) -> Result<DynamoDBSingleTableRepository, ConfigError> {
+        // the signing key is shared by every function so a cursor from one invocation works in
+        // the next
+        let [table_name, cursor_signing_key] =
+            config.require([DYNAMO_TABLE_NAME, CURSOR_SIGNING_KEY])?;
+        Ok(DynamoDBSingleTableRepository {
             client: Client::new(&sdk_credential_meta_repository.sdk_config),
             table_name,
+            cursor_signer: CursorSigner::new(cursor_signing_key.into_bytes()),
+            batch_policy: BatchPolicy::default(),
+            config: config.clone(),
+            tenant: Tenant::default(),
+            metrics: Some(Arc::new(EmfMetricsAdaptor::new(config))),
+            operation: String::new(),
+            correlation: Correlation::default(),
+        })
+    }
+
+    // Every setting but the table name at its default, see with_config. Nothing is recorded
+    // until a metrics port is given, see with_metrics
+    pub fn new_with_client(client: Client, table_name: String) -> DynamoDBSingleTableRepository {
+        DynamoDBSingleTableRepository {
+            client,
+            config: AppConfig::default().with_table_name(&table_name),
+            table_name,
+            cursor_signer: CursorSigner::random(),
+            batch_policy: BatchPolicy::default(),
+            tenant: Tenant::default(),
+            metrics: None,
+            operation: String::new(),
+            correlation: Correlation::default(),
+        }
+    }
+
+    pub fn with_metrics(self, metrics: Arc<dyn MetricsPort>) -> DynamoDBSingleTableRepository {
+        DynamoDBSingleTableRepository {
+            metrics: Some(metrics),
+            ..self
+        }
+    }
+
+    // Calls are recorded as <operation>.<call>, e.g. with_operation("cart_clear") records
+    // cart_clear.query
+    pub fn with_operation(self, operation: &str) -> DynamoDBSingleTableRepository {
+        DynamoDBSingleTableRepository {
+            operation: operation.to_string(),
+            ..self
         }
     }
 
+    // Keeps the table the repository was built for, whatever the config names
+    pub fn with_config(self, config: AppConfig) -> DynamoDBSingleTableRepository {
+        DynamoDBSingleTableRepository {
+            config: config.with_table_name(&self.table_name),
+            ..self
+        }
+    }
+
+    pub fn index_name(&self, index: GSIs) -> String {
+        match index {
+            GSIs::GSI1 => self.config.table.gsi1_name.clone(),
+            GSIs::GSI2 => self.config.table.gsi2_name.clone(),
+        }
+    }
+
+    // Every item in the table as stored, whichever tenant it belongs to, for jobs that work on the
+    // whole table like backups
+    pub fn scan(&self) -> ScanFluentBuilder {
+        self.client.scan().table_name(self.table_name.clone())
+    }
+
     pub async fn get_item_primary(
         &self,
         p_key: String,
         s_key: String,
     ) -> Result<aws_sdk_dynamodb::operation::get_item::GetItemOutput, GetItemError> {
-        let p_key_att = AttributeValue::S(p_key);
+        let p_key_att = AttributeValue::S(self.tenant.key(&p_key));
         let s_key_att = AttributeValue::S(s_key);
         self.client
             .get_item()
             .table_name(self.table_name.clone())
             .key("Pkey", p_key_att)
             .key("Skey", s_key_att)
-            .send()
+            .send_measured(self)
             .await
             .map_err(|e| e.into_service_error())
     }
@@ -59,22 +160,10 @@ impl DynamoDBSingleTableRepository {
         s_key: String,
         index: GSIs,
     ) -> Result<aws_sdk_dynamodb::operation::query::QueryOutput, QueryError> {
-        let p_key_att = AttributeValue::S(p_key);
-        let s_key_att = AttributeValue::S(s_key);
-        self.client
-            .query()
-            .table_name(self.table_name.clone())
-            .index_name(match index {
-                GSIs::GSI1 => "GSI1",
-                GSIs::GSI2 => "GSI2",
-            })
-            .key_condition_expression(match index {
-                GSIs::GSI1 => "GSI1Pkey = :p_key AND GSI1Skey = :s_key",
-                GSIs::GSI2 => "GSI2Pkey = :p_key AND GSI2Skey = :s_key",
-            })
-            .expression_attribute_values("Pkey", p_key_att)
-            .expression_attribute_values("Skey", s_key_att)
-            .send()
+        let query = IndexQuery::gsi(index, p_key)
+            .sort_key(SortKeyCondition::Equals(AttributeValue::S(s_key)));
+        self.index_query(&query)
+            .send_measured(self)
             .await
             .map_err(|e| e.into_service_error())
     }
@@ -84,7 +173,7 @@ impl DynamoDBSingleTableRepository {
         p_key: String,
         s_key: String,
     ) -> Result<aws_sdk_dynamodb::operation::delete_item::DeleteItemOutput, DeleteItemError> {
-        let p_key_att = AttributeValue::S(p_key);
+        let p_key_att = AttributeValue::S(self.tenant.key(&p_key));
         let s_key_att = AttributeValue::S(s_key);
         self.client
             .delete_item()
@@ -92,7 +181,7 @@ impl DynamoDBSingleTableRepository {
             .key("Pkey", p_key_att)
             .key("Skey", s_key_att)
             .return_values(aws_sdk_dynamodb::types::ReturnValue::AllOld)
-            .send()
+            .send_measured(self)
             .await
             .map_err(|e| e.into_service_error())
     }
@@ -104,34 +193,36 @@ impl DynamoDBSingleTableRepository {
         self.client
             .put_item()
             .table_name(self.table_name.clone())
-            .set_item(Option::Some(payload))
+            .set_item(Option::Some(self.tenant.scope(payload)))
             .condition_expression("attribute_not_exists(Pkey) AND attribute_not_exists(Skey)")
-            .send()
+            .send_measured(self)
             .await
             .map_err(|e| e.into_service_error())
     }
 
+    // Only updates an item that exists, the update's own condition is ANDed on
     pub async fn update_item(
         &self,
         p_key: String,
         s_key: String,
-        update_expression: String,
-        attr_names: Option<HashMap<String, String>>,
-        attr_values: Option<HashMap<String, AttributeValue>>,
+        update: UpdateExpression,
     ) -> Result<aws_sdk_dynamodb::operation::update_item::UpdateItemOutput, UpdateItemError> {
-        let p_key_att = AttributeValue::S(p_key);
+        let expression = update
+            .condition(Condition::exists("Pkey").and(Condition::exists("Skey")))
+            .build();
+        let p_key_att = AttributeValue::S(self.tenant.key(&p_key));
         let s_key_att = AttributeValue::S(s_key);
         self.client
             .update_item()
             .table_name(self.table_name.clone())
             .key("Pkey", p_key_att)
             .key("Skey", s_key_att)
-            .update_expression(update_expression)
-            .condition_expression("attribute_exists(Pkey) AND attribute_exists(Skey)")
-            .set_expression_attribute_names(attr_names)
-            .set_expression_attribute_values(attr_values)
+            .set_update_expression(expression.update.clone())
+            .set_condition_expression(expression.condition.clone())
+            .set_expression_attribute_names(expression.attribute_names())
+            .set_expression_attribute_values(expression.attribute_values())
             .return_values(aws_sdk_dynamodb::types::ReturnValue::AllNew)
-            .send()
+            .send_measured(self)
             .await
             .map_err(|e| {
                 println!("error: {:?}", e);
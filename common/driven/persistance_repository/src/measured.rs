@@ -0,0 +1,234 @@
+// Every call to the table asks for its consumed capacity and is timed, then recorded through the
+// repository's metrics port as <operation>.<call>, e.g. cart_clear.query. Calls are made with
+// send_measured in place of send
+//
+// self.persistance_repository
+//     .index_query(&query)
+//     .limit(limit)
+//     .send_measured(self.persistance_repository)
+//     .await
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use aws_sdk_dynamodb::error::SdkError;
+use aws_sdk_dynamodb::operation::{
+    batch_get_item, batch_write_item, delete_item, get_item, put_item, query, scan,
+    transact_write_items, update_item,
+};
+use aws_sdk_dynamodb::types::{ConsumedCapacity, ReturnConsumedCapacity};
+use metrics::OperationMetrics;
+
+use crate::DynamoDBSingleTableRepository;
+
+// Which side the capacity lands on when DynamoDB only reports the total
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Capacity {
+    Read,
+    Write,
+}
+
+pub trait MeasuredSend {
+    type Output;
+    type Error;
+
+    fn send_measured(
+        self,
+        repository: &DynamoDBSingleTableRepository,
+    ) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send;
+}
+
+macro_rules! measured_send {
+    ($operation:ident, $builder:ident, $output:ident, $error:ident, $call:literal, $capacity:expr, $consumed:expr) => {
+        impl MeasuredSend for $operation::builders::$builder {
+            type Output = $operation::$output;
+            type Error = SdkError<$operation::$error>;
+
+            fn send_measured(
+                self,
+                repository: &DynamoDBSingleTableRepository,
+            ) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send {
+                async move {
+                    let started = Instant::now();
+                    let result = self
+                        .return_consumed_capacity(ReturnConsumedCapacity::Total)
+                        .send()
+                        .await;
+                    let consumed: Vec<ConsumedCapacity> = match &result {
+                        Ok(output) => $consumed(output),
+                        // nothing is reported for failed calls, their latency still counts
+                        Err(_) => Vec::new(),
+                    };
+                    repository.record($call, $capacity, &consumed, started.elapsed());
+                    result
+                }
+            }
+        }
+    };
+}
+
+fn one(consumed: &Option<ConsumedCapacity>) -> Vec<ConsumedCapacity> {
+    consumed.iter().cloned().collect()
+}
+
+fn many(consumed: &Option<Vec<ConsumedCapacity>>) -> Vec<ConsumedCapacity> {
+    consumed.clone().unwrap_or_default()
+}
+
+measured_send!(
+    get_item,
+    GetItemFluentBuilder,
+    GetItemOutput,
+    GetItemError,
+    "get",
+    Capacity::Read,
+    |output: &get_item::GetItemOutput| one(&output.consumed_capacity)
+);
+measured_send!(
+    query,
+    QueryFluentBuilder,
+    QueryOutput,
+    QueryError,
+    "query",
+    Capacity::Read,
+    |output: &query::QueryOutput| one(&output.consumed_capacity)
+);
+measured_send!(
+    scan,
+    ScanFluentBuilder,
+    ScanOutput,
+    ScanError,
+    "scan",
+    Capacity::Read,
+    |output: &scan::ScanOutput| one(&output.consumed_capacity)
+);
+measured_send!(
+    put_item,
+    PutItemFluentBuilder,
+    PutItemOutput,
+    PutItemError,
+    "put",
+    Capacity::Write,
+    |output: &put_item::PutItemOutput| one(&output.consumed_capacity)
+);
+measured_send!(
+    update_item,
+    UpdateItemFluentBuilder,
+    UpdateItemOutput,
+    UpdateItemError,
+    "update",
+    Capacity::Write,
+    |output: &update_item::UpdateItemOutput| one(&output.consumed_capacity)
+);
+measured_send!(
+    delete_item,
+    DeleteItemFluentBuilder,
+    DeleteItemOutput,
+    DeleteItemError,
+    "delete",
+    Capacity::Write,
+    |output: &delete_item::DeleteItemOutput| one(&output.consumed_capacity)
+);
+measured_send!(
+    batch_get_item,
+    BatchGetItemFluentBuilder,
+    BatchGetItemOutput,
+    BatchGetItemError,
+    "batch_get",
+    Capacity::Read,
+    |output: &batch_get_item::BatchGetItemOutput| many(&output.consumed_capacity)
+);
+measured_send!(
+    batch_write_item,
+    BatchWriteItemFluentBuilder,
+    BatchWriteItemOutput,
+    BatchWriteItemError,
+    "batch_write",
+    Capacity::Write,
+    |output: &batch_write_item::BatchWriteItemOutput| many(&output.consumed_capacity)
+);
+measured_send!(
+    transact_write_items,
+    TransactWriteItemsFluentBuilder,
+    TransactWriteItemsOutput,
+    TransactWriteItemsError,
+    "transact",
+    Capacity::Write,
+    |output: &transact_write_items::TransactWriteItemsOutput| many(&output.consumed_capacity)
+);
+
+// Read and write units are used when DynamoDB splits them out, otherwise the total is put down
+// to the side the call is on
+fn capacity_units(capacity: Capacity, consumed: &[ConsumedCapacity]) -> (f64, f64) {
+    consumed.iter().fold((0.0, 0.0), |(read, write), units| {
+        match (units.read_capacity_units, units.write_capacity_units) {
+            (None, None) => match capacity {
+                Capacity::Read => (read + units.capacity_units.unwrap_or_default(), write),
+                Capacity::Write => (read, write + units.capacity_units.unwrap_or_default()),
+            },
+            (reads, writes) => (
+                read + reads.unwrap_or_default(),
+                write + writes.unwrap_or_default(),
+            ),
+        }
+    })
+}
+
+impl DynamoDBSingleTableRepository {
+    fn record(
+        &self,
+        call: &str,
+        capacity: Capacity,
+        consumed: &[ConsumedCapacity],
+        latency: Duration,
+    ) {
+        let metrics = match &self.metrics {
+            Some(metrics) => metrics,
+            None => return,
+        };
+        let (read_capacity_units, write_capacity_units) = capacity_units(capacity, consumed);
+        metrics.record(&OperationMetrics {
+            operation: self.operation_call(call),
+            read_capacity_units,
+            write_capacity_units,
+            latency,
+        });
+    }
+
+    // Counted under <operation>.decode, for reads that leave out an item they couldn't decode
+    pub fn record_skipped_item(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_skipped_item(&self.operation_call("decode"));
+        }
+    }
+
+    fn operation_call(&self, call: &str) -> String {
+        match self.operation.is_empty() {
+            true => call.to_string(),
+            false => format!("{}.{}", self.operation, call),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capacity_units() {
+        let total = ConsumedCapacity::builder().capacity_units(2.0).build();
+        assert_eq!(
+            capacity_units(Capacity::Read, &[total.clone(), total.clone()]),
+            (4.0, 0.0)
+        );
+        assert_eq!(capacity_units(Capacity::Write, &[total]), (0.0, 2.0));
+
+        // a transaction that reads to check conditions reports both
+        let split = ConsumedCapacity::builder()
+            .capacity_units(3.0)
+            .read_capacity_units(1.0)
+            .write_capacity_units(2.0)
+            .build();
+        assert_eq!(capacity_units(Capacity::Write, &[split]), (1.0, 2.0));
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::operation::query::builders::QueryFluentBuilder;
+use aws_sdk_dynamodb::operation::query::QueryError;
+use aws_sdk_dynamodb::primitives::Blob;
+use aws_sdk_dynamodb::types::AttributeValue;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use error::HexagonalError;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sha2::Sha256;
+
+use crate::{DynamoDBSingleTableRepository, MeasuredSend};
+
+pub const DEFAULT_PAGE_SIZE: i32 = 25;
+pub const MAX_PAGE_SIZE: i32 = 100;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PageRequest {
+    pub limit: Option<i32>,
+    pub cursor: Option<String>,
+}
+
+impl PageRequest {
+    pub fn new(limit: Option<i32>, cursor: Option<String>) -> Self {
+        Self { limit, cursor }
+    }
+
+    // First page of the largest size, for callers walking every page
+    pub fn all() -> Self {
+        Self {
+            limit: Some(MAX_PAGE_SIZE),
+            cursor: None,
+        }
+    }
+
+    pub fn next(&self, cursor: String) -> Self {
+        Self {
+            limit: self.limit,
+            cursor: Some(cursor),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    // Opaque, pass back as `cursor` for the next page. None once there are no more pages
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    // Items f returns None for are left out, the cursor still continues after them
+    pub fn filter_map<U>(self, f: impl FnMut(T) -> Option<U>) -> Page<U> {
+        Page {
+            items: self.items.into_iter().filter_map(f).collect(),
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PageError {
+    InvalidLimit(i32),
+    InvalidCursor,
+    Query(Box<QueryError>),
+}
+
+impl PageError {
+    // Bad limits and cursors are the caller's fault, failed queries are ours
+    pub fn into_hexagonal_error(self, message: &str) -> HexagonalError {
+        match self {
+            PageError::InvalidLimit(limit) => HexagonalError {
+                error: error::HexagonalErrorCode::BadInput,
+                message: format!("limit must be between 1 and {}", MAX_PAGE_SIZE),
+                trace: limit.to_string(),
+            },
+            PageError::InvalidCursor => HexagonalError {
+                error: error::HexagonalErrorCode::BadInput,
+                message: "cursor is invalid".to_string(),
+                trace: "".to_string(),
+            },
+            PageError::Query(err) => HexagonalError {
+                error: error::HexagonalErrorCode::AdaptorError,
+                message: message.to_string(),
+                trace: err.to_string(),
+            },
+        }
+    }
+}
+
+// Cursors are the query's LastEvaluatedKey signed together with the query they came from, so
+// they can't be edited or replayed against a different query (e.g. another user's cart)
+#[derive(Clone)]
+pub struct CursorSigner {
+    key: Vec<u8>,
+}
+
+impl CursorSigner {
+    pub fn new(key: Vec<u8>) -> Self {
+        Self { key }
+    }
+
+    // For in process tables, cursors only need to outlive the process
+    pub fn random() -> Self {
+        Self {
+            key: [uuid::Uuid::new_v4(), uuid::Uuid::new_v4()]
+                .iter()
+                .flat_map(|id| id.as_bytes().to_vec())
+                .collect(),
+        }
+    }
+
+    fn mac(&self, scope: &str, payload: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).unwrap(); // HMAC takes keys of any size
+        mac.update(scope.as_bytes());
+        mac.update(&[0]);
+        mac.update(payload);
+        mac
+    }
+
+    pub fn encode(&self, scope: &str, key: &HashMap<String, AttributeValue>) -> String {
+        let payload = serde_json::to_vec(&key_to_json(key)).unwrap(); // json values always serialise
+        let signature = self.mac(scope, &payload).finalize().into_bytes();
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    pub fn decode(
+        &self,
+        scope: &str,
+        cursor: &str,
+    ) -> Result<HashMap<String, AttributeValue>, PageError> {
+        let (payload, signature) = cursor.split_once('.').ok_or(PageError::InvalidCursor)?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| PageError::InvalidCursor)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| PageError::InvalidCursor)?;
+        self.mac(scope, &payload)
+            .verify_slice(&signature)
+            .map_err(|_| PageError::InvalidCursor)?;
+        let json = serde_json::from_slice(&payload).map_err(|_| PageError::InvalidCursor)?;
+        key_from_json(json).ok_or(PageError::InvalidCursor)
+    }
+}
+
+// Keys are only ever S, N or B
+fn key_to_json(key: &HashMap<String, AttributeValue>) -> Value {
+    let mut json = Map::new();
+    for (name, value) in key {
+        let value = match value {
+            AttributeValue::S(s) => {
+                Value::from(Map::from_iter([("S".to_string(), s.as_str().into())]))
+            }
+            AttributeValue::N(n) => {
+                Value::from(Map::from_iter([("N".to_string(), n.as_str().into())]))
+            }
+            AttributeValue::B(b) => Value::from(Map::from_iter([(
+                "B".to_string(),
+                URL_SAFE_NO_PAD.encode(b.as_ref()).into(),
+            )])),
+            _ => continue,
+        };
+        json.insert(name.clone(), value);
+    }
+    Value::Object(json)
+}
+
+fn key_from_json(json: Value) -> Option<HashMap<String, AttributeValue>> {
+    let mut key = HashMap::new();
+    for (name, value) in json.as_object()? {
+        let (attribute_type, value) = value.as_object()?.iter().next()?;
+        let value = value.as_str()?.to_string();
+        let value = match attribute_type.as_str() {
+            "S" => AttributeValue::S(value),
+            "N" => AttributeValue::N(value),
+            "B" => AttributeValue::B(Blob::new(URL_SAFE_NO_PAD.decode(value).ok()?)),
+            _ => return None,
+        };
+        key.insert(name.clone(), value);
+    }
+    Some(key)
+}
+
+// What makes one query different from another, so a cursor only works for the query it came from
+fn query_scope(query: &QueryFluentBuilder) -> String {
+    let mut values: Vec<String> = query
+        .get_expression_attribute_values()
+        .iter()
+        .flatten()
+        .map(|(name, value)| format!("{}={:?}", name, value))
+        .collect();
+    values.sort();
+    format!(
+        "{:?}|{:?}|{:?}|{}",
+        query.get_table_name(),
+        query.get_index_name(),
+        query.get_key_condition_expression(),
+        values.join(",")
+    )
+}
+
+impl DynamoDBSingleTableRepository {
+    // Runs one page of a query, the query is built by the caller without a limit or start key
+    pub async fn query_page(
+        &self,
+        query: QueryFluentBuilder,
+        page: &PageRequest,
+    ) -> Result<Page<HashMap<String, AttributeValue>>, PageError> {
+        let limit = page.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(PageError::InvalidLimit(limit));
+        }
+        let scope = query_scope(&query);
+        let exclusive_start_key = match &page.cursor {
+            Some(cursor) => Some(self.cursor_signer.decode(&scope, cursor)?),
+            None => None,
+        };
+
+        let output = query
+            .limit(limit)
+            .set_exclusive_start_key(exclusive_start_key)
+            .send_measured(self)
+            .await
+            .map_err(|e| PageError::Query(Box::new(e.into_service_error())))?;
+
+        Ok(Page {
+            items: output.items.unwrap_or_default(),
+            next_cursor: output
+                .last_evaluated_key
+                .map(|key| self.cursor_signer.encode(&scope, &key)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip_and_tampering() {
+        let signer = CursorSigner::random();
+        let key = HashMap::from([
+            (
+                "Pkey".to_string(),
+                AttributeValue::S("CART#USER#a".to_string()),
+            ),
+            (
+                "Skey".to_string(),
+                AttributeValue::S("CART#PRODUCT#1".to_string()),
+            ),
+        ]);
+
+        let cursor = signer.encode("scope", &key);
+        assert_eq!(signer.decode("scope", &cursor).unwrap(), key);
+
+        // another query, another signer or an edited key are all rejected
+        assert!(signer.decode("other scope", &cursor).is_err());
+        assert!(CursorSigner::random().decode("scope", &cursor).is_err());
+        let (_, signature) = cursor.split_once('.').unwrap();
+        let forged = HashMap::from([
+            (
+                "Pkey".to_string(),
+                AttributeValue::S("CART#USER#b".to_string()),
+            ),
+            (
+                "Skey".to_string(),
+                AttributeValue::S("CART#PRODUCT#1".to_string()),
+            ),
+        ]);
+        let forged_payload = serde_json::to_vec(&key_to_json(&forged)).unwrap();
+        let forged_cursor = format!("{}.{}", URL_SAFE_NO_PAD.encode(forged_payload), signature);
+        assert!(signer.decode("scope", &forged_cursor).is_err());
+        assert!(signer.decode("scope", "not a cursor").is_err());
+    }
+}
@@ -0,0 +1,278 @@
+// Tenancy
+// Several storefronts share the one table. The repository is scoped to a tenant, see for_tenant,
+// and puts that tenant on every partition key (Pkey, GSI1Pkey and GSI2Pkey) it is given, in the
+// keys it reads and deletes by, the items it writes and the partitions it queries. Models build
+// their keys as if there were one storefront, so a repository scoped to one tenant has no way to
+// name another tenant's items. Sort keys stay as they are, they only order items within a
+// partition.
+//
+// Pkey = TENANT#<id>#USER#<username>
+//
+// The default tenant has no prefix at all, which keeps the items of a single storefront
+// deployment where they have always been. No model key starts with TENANT# so the two can't meet.
+// Keys read back from the table already carry the prefix and are left as they are, so an item
+// that was read can be written or deleted by its own key.
+//
+// The shared partitions below are the one exception, every tenant writes to them as they are.
+
+use std::collections::HashMap;
+
+use app_config::{AppConfig, ConfigError, ConfigProblem, TENANTS};
+use aws_sdk_dynamodb::types::{AttributeValue, TransactWriteItem, WriteRequest};
+use error::HexagonalError;
+
+use crate::DynamoDBSingleTableRepository;
+
+const TENANT_PREFIX: &str = "TENANT#";
+const MAX_TENANT_ID_LENGTH: usize = 64;
+const PARTITION_KEYS: [&str; 3] = ["Pkey", "GSI1Pkey", "GSI2Pkey"];
+// The outbox, which one relay publishes for every tenant, each item naming whose change it was
+const SHARED_PARTITIONS: [&str; 1] = ["OUTBOX#"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Tenant {
+    id: Option<String>,
+}
+
+impl Tenant {
+    // Ids are kept to letters, digits, - and _ so they can't carry a # into the key
+    pub fn new(id: &str) -> Result<Tenant, HexagonalError> {
+        let valid = !id.is_empty()
+            && id.len() <= MAX_TENANT_ID_LENGTH
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        match valid {
+            true => Ok(Tenant {
+                id: Some(id.to_string()),
+            }),
+            false => Err(HexagonalError {
+                error: error::HexagonalErrorCode::BadInput,
+                message: "Invalid tenant id".to_string(),
+                trace: format!(
+                    "tenant ids are 1 to {} letters, digits, - or _, got {:?}",
+                    MAX_TENANT_ID_LENGTH, id
+                ),
+            }),
+        }
+    }
+
+    // The tenant a request named, if it named one
+    pub fn from_request(id: Option<&str>) -> Result<Tenant, HexagonalError> {
+        match id {
+            Some(id) => Tenant::new(id),
+            None => Ok(Tenant::default()),
+        }
+    }
+
+    // The default tenant then each one the config names, for scheduled jobs that sweep them all
+    pub fn configured(config: &AppConfig) -> Result<Vec<Tenant>, ConfigError> {
+        let mut tenants = vec![Tenant::default()];
+        let mut problems = Vec::new();
+        for id in &config.tenants {
+            match Tenant::new(id) {
+                Ok(tenant) => tenants.push(tenant),
+                Err(_) => problems.push(ConfigProblem::Invalid {
+                    setting: TENANTS.to_string(),
+                    value: id.clone(),
+                    reason: "must be 1 to 64 letters, digits, - or _",
+                }),
+            }
+        }
+        match problems.is_empty() {
+            true => Ok(tenants),
+            false => Err(ConfigError { problems }),
+        }
+    }
+
+    // None for the default tenant
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    // The partition key as stored for this tenant
+    pub fn key(&self, key: &str) -> String {
+        let shared = SHARED_PARTITIONS
+            .iter()
+            .any(|partition| key.starts_with(partition));
+        match &self.id {
+            Some(id) if !shared && !key.starts_with(&self.prefix(id)) => {
+                format!("{}{}", self.prefix(id), key)
+            }
+            _ => key.to_string(),
+        }
+    }
+
+    fn prefix(&self, id: &str) -> String {
+        format!("{}{}#", TENANT_PREFIX, id)
+    }
+
+    // An item or key with this tenant on its partition keys
+    pub(crate) fn scope(
+        &self,
+        item: HashMap<String, AttributeValue>,
+    ) -> HashMap<String, AttributeValue> {
+        item.into_iter()
+            .map(|(attribute, value)| match value {
+                AttributeValue::S(key) if PARTITION_KEYS.contains(&attribute.as_str()) => {
+                    let key = self.key(&key);
+                    (attribute, AttributeValue::S(key))
+                }
+                value => (attribute, value),
+            })
+            .collect()
+    }
+
+    pub(crate) fn scope_write(&self, mut write: WriteRequest) -> WriteRequest {
+        if let Some(put) = write.put_request.as_mut() {
+            put.item = self.scope(std::mem::take(&mut put.item));
+        }
+        if let Some(delete) = write.delete_request.as_mut() {
+            delete.key = self.scope(std::mem::take(&mut delete.key));
+        }
+        write
+    }
+
+    // Updates are scoped by their key alone, a partition key set by an update expression would go
+    // out as it was built, so items whose partition keys change are put whole
+    pub(crate) fn scope_operation(&self, mut operation: TransactWriteItem) -> TransactWriteItem {
+        if let Some(put) = operation.put.as_mut() {
+            put.item = self.scope(std::mem::take(&mut put.item));
+        }
+        if let Some(update) = operation.update.as_mut() {
+            update.key = self.scope(std::mem::take(&mut update.key));
+        }
+        if let Some(delete) = operation.delete.as_mut() {
+            delete.key = self.scope(std::mem::take(&mut delete.key));
+        }
+        if let Some(condition_check) = operation.condition_check.as_mut() {
+            condition_check.key = self.scope(std::mem::take(&mut condition_check.key));
+        }
+        operation
+    }
+
+    // The tenant a stored partition key belongs to and the key as the models built it, for
+    // readers that see every tenant's items, e.g. table streams
+    pub fn of_key(stored_key: &str) -> (Tenant, &str) {
+        let tenant_key = stored_key
+            .strip_prefix(TENANT_PREFIX)
+            .and_then(|rest| rest.split_once('#'));
+        match tenant_key {
+            Some((id, key)) => (
+                Tenant {
+                    id: Some(id.to_string()),
+                },
+                key,
+            ),
+            None => (Tenant::default(), stored_key),
+        }
+    }
+}
+
+// Anything that reads and writes as one tenant, so what wraps it can tell which
+pub trait TenantScoped {
+    fn tenant(&self) -> &Tenant;
+}
+
+impl DynamoDBSingleTableRepository {
+    pub fn tenant(&self) -> &Tenant {
+        &self.tenant
+    }
+
+    // The same table seen by one tenant. Cheap, the client and its connections are shared
+    pub fn for_tenant(&self, tenant: Tenant) -> DynamoDBSingleTableRepository {
+        DynamoDBSingleTableRepository {
+            client: self.client.clone(),
+            table_name: self.table_name.clone(),
+            cursor_signer: self.cursor_signer.clone(),
+            batch_policy: self.batch_policy.clone(),
+            config: self.config.clone(),
+            tenant,
+            metrics: self.metrics.clone(),
+            operation: self.operation.clone(),
+            correlation: self.correlation.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenant_keys() {
+        let tenant = Tenant::new("acme-1").unwrap();
+        assert_eq!(tenant.key("USER#bob"), "TENANT#acme-1#USER#bob");
+        assert_eq!(Tenant::default().key("USER#bob"), "USER#bob");
+
+        assert_eq!(
+            Tenant::of_key("TENANT#acme-1#CART#USER#bob"),
+            (tenant, "CART#USER#bob")
+        );
+        assert_eq!(
+            Tenant::of_key("CART#USER#bob"),
+            (Tenant::default(), "CART#USER#bob")
+        );
+    }
+
+    #[test]
+    fn test_scoped_items() {
+        let tenant = Tenant::new("acme").unwrap();
+        let item = HashMap::from([
+            (
+                "Pkey".to_string(),
+                AttributeValue::S("USER#bob".to_string()),
+            ),
+            ("Skey".to_string(), AttributeValue::S("-".to_string())),
+            (
+                "GSI1Pkey".to_string(),
+                AttributeValue::S("USER#bob@example.com".to_string()),
+            ),
+            (
+                "GSI2Pkey".to_string(),
+                AttributeValue::S("OUTBOX#PENDING".to_string()),
+            ),
+            ("username".to_string(), AttributeValue::S("bob".to_string())),
+        ]);
+        let scoped = tenant.scope(item);
+        assert_eq!(
+            scoped["Pkey"],
+            AttributeValue::S("TENANT#acme#USER#bob".to_string())
+        );
+        assert_eq!(
+            scoped["GSI1Pkey"],
+            AttributeValue::S("TENANT#acme#USER#bob@example.com".to_string())
+        );
+        // shared partitions, sort keys and everything else are left alone
+        assert_eq!(
+            scoped["GSI2Pkey"],
+            AttributeValue::S("OUTBOX#PENDING".to_string())
+        );
+        assert_eq!(scoped["Skey"], AttributeValue::S("-".to_string()));
+        assert_eq!(scoped["username"], AttributeValue::S("bob".to_string()));
+
+        // an item read back can be written by its own key
+        assert_eq!(tenant.scope(scoped.clone()), scoped);
+        assert_eq!(
+            Tenant::new("globex").unwrap().key("TENANT#acme#USER#bob"),
+            "TENANT#globex#TENANT#acme#USER#bob"
+        );
+    }
+
+    #[test]
+    fn test_invalid_tenant_ids() {
+        for id in ["", "acme#other", "acme corp", &"a".repeat(65)] {
+            assert_eq!(
+                Tenant::new(id).unwrap_err().error,
+                error::HexagonalErrorCode::BadInput
+            );
+        }
+        assert_eq!(Tenant::from_request(None), Ok(Tenant::default()));
+
+        let config = AppConfig {
+            tenants: vec!["acme".to_string(), "acme corp".to_string()],
+            ..AppConfig::default()
+        };
+        assert_eq!(Tenant::configured(&config).unwrap_err().problems.len(), 1);
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
+use aws_sdk_dynamodb::types::{
+    AttributeValue, ConditionCheck, Delete, Put, TransactWriteItem, Update,
+};
+
+use crate::{DynamoDBSingleTableRepository, MeasuredSend};
+
+// A TransactWriteItems call where every operation carries a tag, so a cancellation can be reported
+// against the operations that caused it rather than as one opaque error
+//
+// repository
+//     .transaction()
+//     .put(UserWrite::User, user_put)
+//     .put(UserWrite::Email, email_put)
+//     .send()
+//     .await
+pub struct Transaction<'a, T> {
+    repository: &'a DynamoDBSingleTableRepository,
+    operations: Vec<(T, TransactWriteItem)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CancellationCode {
+    ConditionalCheckFailed,
+    // another transaction or write touched the same item at the same time
+    TransactionConflict,
+    Other(String),
+}
+
+impl From<&str> for CancellationCode {
+    fn from(code: &str) -> Self {
+        match code {
+            "ConditionalCheckFailed" => CancellationCode::ConditionalCheckFailed,
+            "TransactionConflict" => CancellationCode::TransactionConflict,
+            other => CancellationCode::Other(other.to_string()),
+        }
+    }
+}
+
+// Why one operation stopped the transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cancellation<T> {
+    pub tag: T,
+    pub code: CancellationCode,
+    // The item as it was when its condition failed, only returned for operations asking for
+    // ReturnValuesOnConditionCheckFailure, and None when there was no item
+    pub item: Option<HashMap<String, AttributeValue>>,
+}
+
+#[derive(Debug)]
+pub enum TransactionError<T> {
+    // Only the operations that failed are listed, the rest were fine
+    Cancelled(Vec<Cancellation<T>>),
+    Failed(Box<TransactWriteItemsError>),
+}
+
+impl<T: PartialEq> TransactionError<T> {
+    pub fn cancellation(&self, tag: &T) -> Option<&Cancellation<T>> {
+        match self {
+            TransactionError::Cancelled(cancellations) => cancellations
+                .iter()
+                .find(|cancellation| &cancellation.tag == tag),
+            TransactionError::Failed(_) => None,
+        }
+    }
+
+    pub fn condition_failed(&self, tag: &T) -> bool {
+        self.cancellation(tag).is_some_and(|cancellation| {
+            cancellation.code == CancellationCode::ConditionalCheckFailed
+        })
+    }
+
+    // True when any operation's condition failed
+    pub fn is_condition_failure(&self) -> bool {
+        match self {
+            TransactionError::Cancelled(cancellations) => cancellations
+                .iter()
+                .any(|cancellation| cancellation.code == CancellationCode::ConditionalCheckFailed),
+            TransactionError::Failed(_) => false,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Display for TransactionError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransactionError::Cancelled(cancellations) => {
+                let reasons: Vec<String> = cancellations
+                    .iter()
+                    .map(|cancellation| format!("{:?}: {:?}", cancellation.tag, cancellation.code))
+                    .collect();
+                write!(f, "Transaction cancelled [{}]", reasons.join(", "))
+            }
+            TransactionError::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl<'a, T> Transaction<'a, T> {
+    pub fn put(self, tag: T, put: Put) -> Self {
+        self.operation(tag, TransactWriteItem::builder().put(put).build())
+    }
+
+    pub fn update(self, tag: T, update: Update) -> Self {
+        self.operation(tag, TransactWriteItem::builder().update(update).build())
+    }
+
+    pub fn delete(self, tag: T, delete: Delete) -> Self {
+        self.operation(tag, TransactWriteItem::builder().delete(delete).build())
+    }
+
+    pub fn condition_check(self, tag: T, condition_check: ConditionCheck) -> Self {
+        self.operation(
+            tag,
+            TransactWriteItem::builder()
+                .condition_check(condition_check)
+                .build(),
+        )
+    }
+
+    // For operations that come ready built, e.g. outbox items
+    pub fn operation(mut self, tag: T, operation: TransactWriteItem) -> Self {
+        let operation = self.repository.tenant().scope_operation(operation);
+        self.operations.push((tag, operation));
+        self
+    }
+
+    pub async fn send(self) -> Result<(), TransactionError<T>> {
+        let (tags, operations): (Vec<T>, Vec<TransactWriteItem>) =
+            self.operations.into_iter().unzip();
+
+        let err = match self
+            .repository
+            .client
+            .transact_write_items()
+            .set_transact_items(Some(operations))
+            .send_measured(self.repository)
+            .await
+        {
+            Ok(_) => return Ok(()),
+            Err(err) => err.into_service_error(),
+        };
+
+        let reasons = match &err {
+            TransactWriteItemsError::TransactionCanceledException(cancelled) => {
+                cancelled.cancellation_reasons().to_vec()
+            }
+            _ => return Err(TransactionError::Failed(Box::new(err))),
+        };
+        // reasons line up with the operations, with a code of None for those that were fine
+        let cancellations = tags
+            .into_iter()
+            .zip(reasons)
+            .filter_map(|(tag, reason)| match reason.code.as_deref() {
+                None | Some("None") => None,
+                Some(code) => Some(Cancellation {
+                    tag,
+                    code: code.into(),
+                    item: reason.item,
+                }),
+            })
+            .collect();
+        Err(TransactionError::Cancelled(cancellations))
+    }
+}
+
+impl DynamoDBSingleTableRepository {
+    pub fn transaction<T>(&self) -> Transaction<'_, T> {
+        Transaction {
+            repository: self,
+            operations: Vec::new(),
+        }
+    }
+}
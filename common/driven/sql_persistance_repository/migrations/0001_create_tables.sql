@@ -0,0 +1,45 @@
+-- Kept to types SQLite and Postgres share so the same migrations run on both
+
+CREATE TABLE users (
+    username TEXT PRIMARY KEY,
+    email TEXT NOT NULL UNIQUE,
+    first TEXT NOT NULL,
+    last TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    version BIGINT NOT NULL
+);
+
+CREATE TABLE products (
+    id TEXT PRIMARY KEY,
+    product_name TEXT NOT NULL,
+    price_cents BIGINT NOT NULL,
+    description TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    version BIGINT NOT NULL
+);
+
+CREATE TABLE cart_items (
+    user_id TEXT NOT NULL,
+    product_id TEXT NOT NULL,
+    quantity BIGINT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    version BIGINT NOT NULL,
+    PRIMARY KEY (user_id, product_id)
+);
+
+-- removing a product from every cart
+CREATE INDEX cart_items_product_id ON cart_items (product_id);
+
+CREATE TABLE outbox (
+    id TEXT PRIMARY KEY,
+    event_type TEXT NOT NULL,
+    version BIGINT NOT NULL,
+    detail TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    sent_at TEXT
+);
+
+CREATE INDEX outbox_pending ON outbox (sent_at, created_at, id);
@@ -0,0 +1,8 @@
+-- Deleted users and products keep their row with deleted_at (epoch seconds) set until purged
+
+ALTER TABLE users ADD COLUMN deleted_at BIGINT;
+ALTER TABLE products ADD COLUMN deleted_at BIGINT;
+
+-- the purge job, oldest deletions first
+CREATE INDEX users_deleted_at ON users (deleted_at);
+CREATE INDEX products_deleted_at ON products (deleted_at);
@@ -0,0 +1,13 @@
+-- Every write to a user or product, kept until the entity is purged. entity is the table the
+-- write went to and entity_key its primary key there
+
+CREATE TABLE history (
+    entity TEXT NOT NULL,
+    entity_key TEXT NOT NULL,
+    version BIGINT NOT NULL,
+    action TEXT NOT NULL,
+    principal TEXT NOT NULL,
+    changed_at TEXT NOT NULL,
+    changes TEXT NOT NULL,
+    PRIMARY KEY (entity, entity_key, version)
+);
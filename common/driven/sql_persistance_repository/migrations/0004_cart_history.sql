@@ -0,0 +1,15 @@
+-- Every write to a cart item, kept on the cart as each item has versions of its own. sort_key is
+-- the record's DynamoDB sort key, HISTORY#<changed_at>#<product_id>#<version>, so pages come
+-- newest first the same way
+
+CREATE TABLE cart_history (
+    user_id TEXT NOT NULL,
+    sort_key TEXT NOT NULL,
+    product_id TEXT NOT NULL,
+    version BIGINT NOT NULL,
+    action TEXT NOT NULL,
+    principal TEXT NOT NULL,
+    changed_at TEXT NOT NULL,
+    changes TEXT NOT NULL,
+    PRIMARY KEY (user_id, sort_key)
+);
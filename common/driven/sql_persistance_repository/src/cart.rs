@@ -0,0 +1,680 @@
+use async_trait::async_trait;
+use error::HexagonalError;
+use models::models::cart::{CartItem, CartRepositoryPort};
+use models::models::history::{HistoryAction, HistoryRecord};
+use models::models::outbox::OutboxEvent;
+use models::{changed_since_read_error, check_expected_version, default_time};
+use persistance_repository::{Page, PageRequest};
+use sqlx::any::{Any, AnyRow};
+use sqlx::{Row, Transaction};
+
+use crate::{
+    database_error, get_u64, history_from_row, insert_outbox_item, is_unique_violation, to_i64,
+    SqlRepository,
+};
+
+// Carts don't expire here, there is no TTL to hand the clean up to
+const CART_COLUMNS: &str = "user_id, product_id, quantity, created_at, updated_at, version";
+
+pub struct CartSqlAdaptor<'a> {
+    sql_repository: &'a SqlRepository,
+}
+
+impl<'a> CartSqlAdaptor<'a> {
+    pub fn new(sql_repository: &'a SqlRepository) -> CartSqlAdaptor<'a> {
+        CartSqlAdaptor { sql_repository }
+    }
+
+    async fn get_cart_item(
+        &self,
+        user_id: &String,
+        product_id: &String,
+    ) -> Result<Option<CartItem>, HexagonalError> {
+        sqlx::query(&format!(
+            "SELECT {} FROM cart_items WHERE user_id = $1 AND product_id = $2",
+            CART_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(product_id)
+        .fetch_optional(&self.sql_repository.pool)
+        .await
+        .and_then(|row| row.as_ref().map(cart_item_from_row).transpose())
+        .map_err(|e| database_error("Unable to get cart item", e))
+    }
+}
+
+// The history row goes in the same transaction as the write it records
+async fn insert_cart_history(
+    transaction: &mut Transaction<'static, Any>,
+    action: HistoryAction,
+    principal: &str,
+    before: Option<&CartItem>,
+    after: &CartItem,
+) -> Result<(), sqlx::Error> {
+    let record = HistoryRecord::new(action, principal, before, after).with_item(&after.product_id);
+    sqlx::query(
+        "INSERT INTO cart_history \
+         (user_id, sort_key, product_id, version, action, principal, changed_at, changes) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(&after.user_id)
+    .bind(record.skey())
+    .bind(&after.product_id)
+    .bind(to_i64(record.version))
+    .bind(record.action.as_str())
+    .bind(&record.principal)
+    .bind(&record.changed_at)
+    .bind(serde_json::to_string(&record.changes).unwrap())
+    .execute(&mut **transaction)
+    .await
+    .map(|_| ())
+}
+
+// The item as its removal leaves it, for the history record of the removal
+fn removed_item(item: &CartItem) -> CartItem {
+    CartItem {
+        updated_at: default_time(),
+        version: item.version + 1,
+        ..item.clone()
+    }
+}
+
+fn cart_item_from_row(row: &AnyRow) -> Result<CartItem, sqlx::Error> {
+    let quantity: i64 = row.try_get("quantity")?;
+    Ok(CartItem {
+        user_id: row.try_get("user_id")?,
+        product_id: row.try_get("product_id")?,
+        quantity: u32::try_from(quantity).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "quantity".to_string(),
+            source: Box::new(e),
+        })?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+        version: get_u64(row, "version")?,
+    })
+}
+
+#[async_trait]
+impl<'a> CartRepositoryPort for CartSqlAdaptor<'a> {
+    async fn cart_get_by_user_id(
+        &self,
+        user_id: &String,
+        page: &PageRequest,
+    ) -> Result<Page<CartItem>, HexagonalError> {
+        let scope = format!("cart|{}", user_id);
+        let (limit, start_after) = self
+            .sql_repository
+            .page_bounds(&scope, "product_id", page)
+            .map_err(|e| e.into_hexagonal_error("Unable to get cart items"))?;
+        // one extra row says whether there is another page
+        let mut items = sqlx::query(&format!(
+            "SELECT {} FROM cart_items WHERE user_id = $1 AND product_id > $2 \
+             ORDER BY product_id LIMIT $3",
+            CART_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(start_after.unwrap_or_default())
+        .bind(limit + 1)
+        .fetch_all(&self.sql_repository.pool)
+        .await
+        .and_then(|rows| {
+            rows.iter()
+                .map(cart_item_from_row)
+                .collect::<Result<Vec<CartItem>, _>>()
+        })
+        .map_err(|e| database_error("Unable to get cart items", e))?;
+
+        let next_cursor = match items.len() as i64 > limit {
+            true => {
+                items.truncate(limit as usize);
+                items.last().map(|item| {
+                    self.sql_repository
+                        .next_cursor(&scope, "product_id", &item.product_id)
+                })
+            }
+            false => None,
+        };
+        Ok(Page { items, next_cursor })
+    }
+
+    async fn cart_add_item(
+        &self,
+        item: &CartItem,
+        principal: &str,
+        outbox_event: OutboxEvent<CartItem>,
+    ) -> Result<CartItem, HexagonalError> {
+        let message = "Unable to add item to cart";
+        let mut transaction = self.sql_repository.begin(message).await?;
+        sqlx::query(&format!(
+            "INSERT INTO cart_items ({}) VALUES ($1, $2, $3, $4, $5, $6)",
+            CART_COLUMNS
+        ))
+        .bind(&item.user_id)
+        .bind(&item.product_id)
+        .bind(item.quantity as i64)
+        .bind(&item.created_at)
+        .bind(&item.updated_at)
+        .bind(to_i64(item.version))
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| match is_unique_violation(&e) {
+            true => HexagonalError {
+                error: error::HexagonalErrorCode::Conflict,
+                message: "Unable to add item to cart, already in cart".to_string(),
+                trace: e.to_string(),
+            },
+            false => database_error(message, e),
+        })?;
+        insert_cart_history(
+            &mut transaction,
+            HistoryAction::Created,
+            principal,
+            None,
+            item,
+        )
+        .await
+        .map_err(|e| database_error(message, e))?;
+        insert_outbox_item(&mut transaction, &outbox_event(item))
+            .await
+            .map_err(|e| database_error(message, e))?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| database_error(message, e))?;
+        Ok(item.clone())
+    }
+
+    async fn cart_remove_item(
+        &self,
+        user_id: &String,
+        product_id: &String,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<CartItem>,
+    ) -> Result<CartItem, HexagonalError> {
+        let removed = match self.get_cart_item(user_id, product_id).await? {
+            Some(removed) => removed,
+            None => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to remove item from cart, not in cart".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+        };
+        check_expected_version(
+            expected_version,
+            removed.version,
+            "Unable to remove item from cart, version does not match",
+        )?;
+
+        let message = "Unable to remove item from cart";
+        let mut transaction = self.sql_repository.begin(message).await?;
+        let result = sqlx::query(
+            "DELETE FROM cart_items WHERE user_id = $1 AND product_id = $2 AND version = $3",
+        )
+        .bind(&removed.user_id)
+        .bind(&removed.product_id)
+        .bind(to_i64(removed.version))
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| database_error(message, e))?;
+        if result.rows_affected() == 0 {
+            return Err(changed_since_read_error(
+                expected_version,
+                "Unable to remove item from cart, it was changed by another request",
+                "version changed since read".to_string(),
+            ));
+        }
+        insert_cart_history(
+            &mut transaction,
+            HistoryAction::Deleted,
+            principal,
+            Some(&removed),
+            &removed_item(&removed),
+        )
+        .await
+        .map_err(|e| database_error(message, e))?;
+        insert_outbox_item(&mut transaction, &outbox_event(&removed))
+            .await
+            .map_err(|e| database_error(message, e))?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| database_error(message, e))?;
+        Ok(removed)
+    }
+
+    async fn cart_update_item(
+        &self,
+        user_id: &String,
+        product_id: &String,
+        quantity: u32,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<CartItem>,
+    ) -> Result<CartItem, HexagonalError> {
+        let current = match self.get_cart_item(user_id, product_id).await? {
+            Some(current) => current,
+            None => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to update item in cart, not in cart".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+        };
+        check_expected_version(
+            expected_version,
+            current.version,
+            "Unable to update item in cart, version does not match",
+        )?;
+        let updated = CartItem {
+            quantity,
+            updated_at: default_time(),
+            version: current.version + 1,
+            ..current.clone()
+        };
+
+        let message = "Unable to update item in cart";
+        let mut transaction = self.sql_repository.begin(message).await?;
+        let result = sqlx::query(
+            "UPDATE cart_items SET quantity = $1, updated_at = $2, version = $3 \
+             WHERE user_id = $4 AND product_id = $5 AND version = $6",
+        )
+        .bind(updated.quantity as i64)
+        .bind(&updated.updated_at)
+        .bind(to_i64(updated.version))
+        .bind(&current.user_id)
+        .bind(&current.product_id)
+        .bind(to_i64(current.version))
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| database_error(message, e))?;
+        if result.rows_affected() == 0 {
+            return Err(changed_since_read_error(
+                expected_version,
+                "Unable to update item in cart, it was changed by another request",
+                "version changed since read".to_string(),
+            ));
+        }
+        insert_cart_history(
+            &mut transaction,
+            HistoryAction::Updated,
+            principal,
+            Some(&current),
+            &updated,
+        )
+        .await
+        .map_err(|e| database_error(message, e))?;
+        insert_outbox_item(&mut transaction, &outbox_event(&updated))
+            .await
+            .map_err(|e| database_error(message, e))?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| database_error(message, e))?;
+        Ok(updated)
+    }
+
+    // No transaction size cap here, so the whole cart goes in one transaction with one event
+    async fn cart_clear(
+        &self,
+        user_id: &String,
+        principal: &str,
+        outbox_event: OutboxEvent<[CartItem]>,
+    ) -> Result<Vec<CartItem>, HexagonalError> {
+        let message = "Unable to clear cart";
+        let mut transaction = self.sql_repository.begin(message).await?;
+        let removed = sqlx::query(&format!(
+            "SELECT {} FROM cart_items WHERE user_id = $1 ORDER BY product_id",
+            CART_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_all(&mut *transaction)
+        .await
+        .and_then(|rows| {
+            rows.iter()
+                .map(cart_item_from_row)
+                .collect::<Result<Vec<CartItem>, _>>()
+        })
+        .map_err(|e| database_error("Unable to get cart items", e))?;
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+
+        for item in &removed {
+            let result = sqlx::query(
+                "DELETE FROM cart_items WHERE user_id = $1 AND product_id = $2 AND version = $3",
+            )
+            .bind(&item.user_id)
+            .bind(&item.product_id)
+            .bind(to_i64(item.version))
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| database_error(message, e))?;
+            if result.rows_affected() == 0 {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::Conflict,
+                    message: "Unable to clear cart, it was changed by another request".to_string(),
+                    trace: "".to_string(),
+                });
+            }
+            insert_cart_history(
+                &mut transaction,
+                HistoryAction::Deleted,
+                principal,
+                Some(item),
+                &removed_item(item),
+            )
+            .await
+            .map_err(|e| database_error(message, e))?;
+        }
+        insert_outbox_item(&mut transaction, &outbox_event(&removed))
+            .await
+            .map_err(|e| database_error(message, e))?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| database_error(message, e))?;
+        Ok(removed)
+    }
+
+    async fn cart_global_remove_product(
+        &self,
+        product_id: &String,
+        principal: &str,
+    ) -> Result<(), Vec<HexagonalError>> {
+        println!("Removing product {} from all carts", product_id);
+
+        let message = "Unable to remove product from carts";
+        let mut transaction = self
+            .sql_repository
+            .begin(message)
+            .await
+            .map_err(|e| vec![e])?;
+        let removed = sqlx::query(&format!(
+            "SELECT {} FROM cart_items WHERE product_id = $1",
+            CART_COLUMNS
+        ))
+        .bind(product_id)
+        .fetch_all(&mut *transaction)
+        .await
+        .and_then(|rows| {
+            rows.iter()
+                .map(cart_item_from_row)
+                .collect::<Result<Vec<CartItem>, _>>()
+        })
+        .map_err(|e| vec![database_error("Unable to get cart items", e)])?;
+
+        for item in &removed {
+            let result = sqlx::query(
+                "DELETE FROM cart_items WHERE user_id = $1 AND product_id = $2 AND version = $3",
+            )
+            .bind(&item.user_id)
+            .bind(&item.product_id)
+            .bind(to_i64(item.version))
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| vec![database_error(message, e)])?;
+            // changed since the read, so a later write removes it, or already has
+            if result.rows_affected() == 0 {
+                continue;
+            }
+            insert_cart_history(
+                &mut transaction,
+                HistoryAction::Deleted,
+                principal,
+                Some(item),
+                &removed_item(item),
+            )
+            .await
+            .map_err(|e| vec![database_error(message, e)])?;
+        }
+        transaction
+            .commit()
+            .await
+            .map_err(|e| vec![database_error(message, e)])
+    }
+
+    async fn cart_history_by_user_id(
+        &self,
+        user_id: &String,
+        page: &PageRequest,
+    ) -> Result<Page<HistoryRecord>, HexagonalError> {
+        let message = "Unable to get cart history";
+        let scope = format!("cart_history|{}", user_id);
+        let (limit, start_after) = self
+            .sql_repository
+            .page_bounds(&scope, "sort_key", page)
+            .map_err(|e| e.into_hexagonal_error(message))?;
+        // one extra row says whether there is another page
+        let mut rows = sqlx::query(
+            "SELECT sort_key, product_id, version, action, principal, changed_at, changes \
+             FROM cart_history WHERE user_id = $1 AND sort_key < $2 \
+             ORDER BY sort_key DESC LIMIT $3",
+        )
+        .bind(user_id)
+        // every sort key starts HISTORY#, so sorts before this
+        .bind(start_after.unwrap_or_else(|| "HISTORY$".to_string()))
+        .bind(limit + 1)
+        .fetch_all(&self.sql_repository.pool)
+        .await
+        .and_then(|rows| {
+            rows.iter()
+                .map(|row| {
+                    let product_id: String = row.try_get("product_id")?;
+                    let sort_key: String = row.try_get("sort_key")?;
+                    history_from_row(row).map(|record| (sort_key, record.with_item(&product_id)))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|e| database_error(message, e))?;
+
+        let next_cursor = match rows.len() as i64 > limit {
+            true => {
+                rows.truncate(limit as usize);
+                rows.last().map(|(sort_key, _)| {
+                    self.sql_repository
+                        .next_cursor(&scope, "sort_key", sort_key)
+                })
+            }
+            false => None,
+        };
+        Ok(Page {
+            items: rows.into_iter().map(|(_, record)| record).collect(),
+            next_cursor,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use models::models::outbox::{OutboxItem, OutboxRepositoryPort};
+
+    use super::*;
+    use crate::outbox::OutboxSqlAdaptor;
+
+    fn test_event(item: &CartItem) -> OutboxItem {
+        OutboxItem::new("CartTest".to_string(), 1, item.product_id.clone())
+    }
+
+    fn test_clear_event(items: &[CartItem]) -> OutboxItem {
+        OutboxItem::new("CartClearTest".to_string(), 1, items.len().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_cart_pages_and_global_remove() {
+        let repository = SqlRepository::sqlite_in_memory().await.unwrap();
+        let adaptor = CartSqlAdaptor::new(&repository);
+        for user_id in ["a", "b"] {
+            for product_id in ["p1", "p2", "p3"] {
+                adaptor
+                    .cart_add_item(
+                        &CartItem::new(product_id.to_string(), user_id.to_string(), 1),
+                        "tester",
+                        test_event,
+                    )
+                    .await
+                    .unwrap();
+            }
+        }
+        let duplicate = adaptor
+            .cart_add_item(
+                &CartItem::new("p1".to_string(), "a".to_string(), 2),
+                "tester",
+                test_event,
+            )
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(duplicate.error, error::HexagonalErrorCode::Conflict);
+
+        let user_id = "a".to_string();
+        let first = adaptor
+            .cart_get_by_user_id(&user_id, &PageRequest::new(Some(2), None))
+            .await
+            .unwrap();
+        assert_eq!(first.items.len(), 2);
+        let second = adaptor
+            .cart_get_by_user_id(&user_id, &PageRequest::new(Some(2), first.next_cursor))
+            .await
+            .unwrap();
+        assert_eq!(second.items[0].product_id, "p3");
+        assert!(second.next_cursor.is_none());
+
+        adaptor
+            .cart_global_remove_product(&"p2".to_string(), "tester")
+            .await
+            .unwrap();
+        for user_id in ["a", "b"] {
+            let page = adaptor
+                .cart_get_by_user_id(&user_id.to_string(), &PageRequest::default())
+                .await
+                .unwrap();
+            let product_ids: Vec<String> =
+                page.items.into_iter().map(|item| item.product_id).collect();
+            assert_eq!(product_ids, vec!["p1", "p3"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cart_writes_land_with_their_events() {
+        let repository = SqlRepository::sqlite_in_memory().await.unwrap();
+        let adaptor = CartSqlAdaptor::new(&repository);
+        let outbox = OutboxSqlAdaptor::new(&repository);
+        let user_id = "a".to_string();
+        for product_id in ["p1", "p2"] {
+            adaptor
+                .cart_add_item(
+                    &CartItem::new(product_id.to_string(), user_id.clone(), 1),
+                    "tester",
+                    test_event,
+                )
+                .await
+                .unwrap();
+        }
+        let updated = adaptor
+            .cart_update_item(
+                &user_id,
+                &"p1".to_string(),
+                5,
+                Some(1),
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        assert_eq!(updated.quantity, 5);
+        let stale = adaptor
+            .cart_remove_item(&user_id, &"p1".to_string(), Some(1), "tester", test_event)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(stale.error, error::HexagonalErrorCode::PreconditionFailed);
+
+        let cleared = adaptor
+            .cart_clear(&user_id, "tester", test_clear_event)
+            .await
+            .unwrap();
+        assert_eq!(cleared.len(), 2);
+        let missing = adaptor
+            .cart_update_item(&user_id, &"p1".to_string(), 1, None, "tester", test_event)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(missing.error, error::HexagonalErrorCode::NotFound);
+
+        let event_types: Vec<String> = outbox
+            .outbox_get_pending(10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|item| item.event_type)
+            .collect();
+        assert_eq!(event_types.len(), 4);
+        assert!(event_types.contains(&"CartClearTest".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_cart_history_pages_newest_first() {
+        let repository = SqlRepository::sqlite_in_memory().await.unwrap();
+        let adaptor = CartSqlAdaptor::new(&repository);
+        let user_id = "a".to_string();
+        for product_id in ["p1", "p2"] {
+            adaptor
+                .cart_add_item(
+                    &CartItem::new(product_id.to_string(), user_id.clone(), 1),
+                    "adder",
+                    test_event,
+                )
+                .await
+                .unwrap();
+        }
+        adaptor
+            .cart_update_item(&user_id, &"p1".to_string(), 5, None, "updater", test_event)
+            .await
+            .unwrap();
+        adaptor
+            .cart_global_remove_product(&"p2".to_string(), "remover")
+            .await
+            .unwrap();
+        adaptor
+            .cart_clear(&user_id, "clearer", test_clear_event)
+            .await
+            .unwrap();
+
+        let first = adaptor
+            .cart_history_by_user_id(&user_id, &PageRequest::new(Some(3), None))
+            .await
+            .unwrap();
+        let rest = adaptor
+            .cart_history_by_user_id(&user_id, &PageRequest::new(Some(3), first.next_cursor))
+            .await
+            .unwrap();
+        assert!(rest.next_cursor.is_none());
+        // the writes of one second are kept apart by item, newest first for each
+        assert_eq!(
+            first
+                .items
+                .iter()
+                .chain(rest.items.iter())
+                .map(|record| (
+                    record.item.as_deref().unwrap(),
+                    record.action,
+                    record.version,
+                    record.principal.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("p2", HistoryAction::Deleted, 2, "remover"),
+                ("p2", HistoryAction::Created, 1, "adder"),
+                ("p1", HistoryAction::Deleted, 3, "clearer"),
+                ("p1", HistoryAction::Updated, 2, "updater"),
+                ("p1", HistoryAction::Created, 1, "adder")
+            ]
+        );
+        assert!(first.items[0].changes.is_empty());
+        assert_eq!(rest.items[0].changes[0].field, "quantity");
+    }
+}
@@ -0,0 +1,260 @@
+pub mod cart;
+pub mod outbox;
+pub mod product;
+pub mod user;
+
+use std::collections::HashMap;
+
+use app_config::{AppConfig, CURSOR_SIGNING_KEY, DATABASE_URL};
+use aws_sdk_dynamodb::types::AttributeValue;
+use error::HexagonalError;
+use models::models::history::{HistoryAction, HistoryRecord};
+use models::models::outbox::OutboxItem;
+use persistance_repository::pagination::{
+    CursorSigner, Page, PageError, PageRequest, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+};
+use sqlx::any::{AnyPoolOptions, AnyRow};
+use sqlx::migrate::{MigrateError, Migrator};
+use sqlx::{Any, AnyPool, Row, Transaction};
+
+// Shared by SQLite and Postgres, run with SqlRepository::migrate
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+// The SQL counterpart of DynamoDBSingleTableRepository. Queries only use what SQLite and Postgres
+// have in common, so one set of adaptors serves both
+pub struct SqlRepository {
+    pub pool: AnyPool,
+    pub cursor_signer: CursorSigner,
+    pub config: AppConfig,
+}
+
+impl SqlRepository {
+    pub async fn new(
+        config: &AppConfig,
+    ) -> Result<SqlRepository, Box<dyn std::error::Error + Send + Sync>> {
+        // the signing key is shared by every function so a cursor from one invocation works in
+        // the next
+        let [database_url, cursor_signing_key] =
+            config.require([DATABASE_URL, CURSOR_SIGNING_KEY])?;
+        sqlx::any::install_default_drivers();
+        Ok(SqlRepository {
+            pool: AnyPool::connect(&database_url).await?,
+            cursor_signer: CursorSigner::new(cursor_signing_key.into_bytes()),
+            config: config.clone(),
+        })
+    }
+
+    // A private, migrated SQLite database that lives as long as the repository, for tests. Held
+    // on one connection as every connection to :memory: gets its own database
+    pub async fn sqlite_in_memory() -> Result<SqlRepository, MigrateError> {
+        sqlx::any::install_default_drivers();
+        let repository = SqlRepository {
+            pool: AnyPoolOptions::new()
+                .max_connections(1)
+                .connect("sqlite::memory:")
+                .await?,
+            cursor_signer: CursorSigner::random(),
+            config: AppConfig::default(),
+        };
+        repository.migrate().await?;
+        Ok(repository)
+    }
+
+    pub async fn migrate(&self) -> Result<(), MigrateError> {
+        MIGRATOR.run(&self.pool).await
+    }
+
+    pub(crate) async fn begin(
+        &self,
+        message: &str,
+    ) -> Result<Transaction<'static, Any>, HexagonalError> {
+        self.pool
+            .begin()
+            .await
+            .map_err(|e| database_error(message, e))
+    }
+
+    // The limit and the key to start after for a page of `scope`, keyed by `key_column`
+    pub(crate) fn page_bounds(
+        &self,
+        scope: &str,
+        key_column: &str,
+        page: &PageRequest,
+    ) -> Result<(i64, Option<String>), PageError> {
+        let limit = page.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(PageError::InvalidLimit(limit));
+        }
+        let start_after = match &page.cursor {
+            Some(cursor) => Some(
+                self.cursor_signer
+                    .decode(scope, cursor)?
+                    .remove(key_column)
+                    .and_then(|key| key.as_s().ok().cloned())
+                    .ok_or(PageError::InvalidCursor)?,
+            ),
+            None => None,
+        };
+        Ok((limit as i64, start_after))
+    }
+
+    // A page of the history of the `entity` row keyed `entity_key`, newest first
+    pub(crate) async fn history_page(
+        &self,
+        entity: &str,
+        entity_key: &str,
+        page: &PageRequest,
+        message: &str,
+    ) -> Result<Page<HistoryRecord>, HexagonalError> {
+        let scope = format!("history|{}|{}", entity, entity_key);
+        let (limit, start_after) = self
+            .page_bounds(&scope, "version", page)
+            .map_err(|e| e.into_hexagonal_error(message))?;
+        let before_version = match start_after {
+            Some(version) => version
+                .parse::<i64>()
+                .map_err(|_| PageError::InvalidCursor.into_hexagonal_error(message))?,
+            None => i64::MAX,
+        };
+        // one extra row says whether there is another page
+        let mut items = sqlx::query(
+            "SELECT version, action, principal, changed_at, changes FROM history \
+             WHERE entity = $1 AND entity_key = $2 AND version < $3 \
+             ORDER BY version DESC LIMIT $4",
+        )
+        .bind(entity)
+        .bind(entity_key)
+        .bind(before_version)
+        .bind(limit + 1)
+        .fetch_all(&self.pool)
+        .await
+        .and_then(|rows| {
+            rows.iter()
+                .map(history_from_row)
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|e| database_error(message, e))?;
+
+        let next_cursor = match items.len() as i64 > limit {
+            true => {
+                items.truncate(limit as usize);
+                items
+                    .last()
+                    .map(|record| self.next_cursor(&scope, "version", &record.version.to_string()))
+            }
+            false => None,
+        };
+        Ok(Page { items, next_cursor })
+    }
+
+    pub(crate) fn next_cursor(&self, scope: &str, key_column: &str, last_key: &str) -> String {
+        self.cursor_signer.encode(
+            scope,
+            &HashMap::from([(
+                key_column.to_string(),
+                AttributeValue::S(last_key.to_string()),
+            )]),
+        )
+    }
+}
+
+pub(crate) fn database_error(message: &str, err: sqlx::Error) -> HexagonalError {
+    HexagonalError {
+        error: error::HexagonalErrorCode::AdaptorError,
+        message: message.to_string(),
+        trace: err.to_string(),
+    }
+}
+
+pub(crate) fn is_unique_violation(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .is_some_and(|err| err.is_unique_violation())
+}
+
+// Versions and other counters are stored as BIGINT, the one integer type both databases read
+// back the same way through the Any driver
+pub(crate) fn get_u64(row: &AnyRow, column: &str) -> Result<u64, sqlx::Error> {
+    let value: i64 = row.try_get(column)?;
+    u64::try_from(value).map_err(|e| sqlx::Error::ColumnDecode {
+        index: column.to_string(),
+        source: Box::new(e),
+    })
+}
+
+pub(crate) fn to_i64(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+// The outbox row goes in the same transaction as the change it describes
+pub(crate) async fn insert_outbox_item(
+    transaction: &mut Transaction<'static, Any>,
+    item: &OutboxItem,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO outbox (id, event_type, version, detail, created_at) VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(&item.id)
+    .bind(&item.event_type)
+    .bind(item.version as i64)
+    .bind(&item.detail)
+    .bind(&item.created_at)
+    .execute(&mut **transaction)
+    .await
+    .map(|_| ())
+}
+
+// The history row goes in the same transaction as the write it records
+pub(crate) async fn insert_history(
+    transaction: &mut Transaction<'static, Any>,
+    entity: &str,
+    entity_key: &str,
+    record: &HistoryRecord,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO history (entity, entity_key, version, action, principal, changed_at, changes) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(entity)
+    .bind(entity_key)
+    .bind(to_i64(record.version))
+    .bind(record.action.as_str())
+    .bind(&record.principal)
+    .bind(&record.changed_at)
+    .bind(serde_json::to_string(&record.changes).unwrap())
+    .execute(&mut **transaction)
+    .await
+    .map(|_| ())
+}
+
+// Goes in the purge's transaction, nothing can restore the entity by then
+pub(crate) async fn delete_history(
+    transaction: &mut Transaction<'static, Any>,
+    entity: &str,
+    entity_key: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM history WHERE entity = $1 AND entity_key = $2")
+        .bind(entity)
+        .bind(entity_key)
+        .execute(&mut **transaction)
+        .await
+        .map(|_| ())
+}
+
+pub(crate) fn history_from_row(row: &AnyRow) -> Result<HistoryRecord, sqlx::Error> {
+    let decode_error = |column: &str, value: String| sqlx::Error::ColumnDecode {
+        index: column.to_string(),
+        source: format!("unexpected value {}", value).into(),
+    };
+    let action: String = row.try_get("action")?;
+    let changes: String = row.try_get("changes")?;
+    Ok(HistoryRecord {
+        action: HistoryAction::parse(&action)
+            .ok_or_else(|| decode_error("action", action.clone()))?,
+        principal: row.try_get("principal")?,
+        changed_at: row.try_get("changed_at")?,
+        version: get_u64(row, "version")?,
+        item: None,
+        changes: serde_json::from_str(&changes)
+            .map_err(|_| decode_error("changes", changes.clone()))?,
+    })
+}
@@ -0,0 +1,94 @@
+use async_trait::async_trait;
+use error::HexagonalError;
+use models::default_time;
+use models::models::outbox::{OutboxItem, OutboxRepositoryPort};
+use sqlx::any::AnyRow;
+use sqlx::Row;
+
+use crate::{database_error, SqlRepository};
+
+// Sent rows are kept with sent_at set rather than deleted, prune them as suits the database
+pub struct OutboxSqlAdaptor<'a> {
+    sql_repository: &'a SqlRepository,
+}
+
+impl<'a> OutboxSqlAdaptor<'a> {
+    pub fn new(sql_repository: &'a SqlRepository) -> OutboxSqlAdaptor<'a> {
+        OutboxSqlAdaptor { sql_repository }
+    }
+}
+
+fn outbox_item_from_row(row: &AnyRow) -> Result<OutboxItem, sqlx::Error> {
+    let version: i64 = row.try_get("version")?;
+    Ok(OutboxItem {
+        id: row.try_get("id")?,
+        event_type: row.try_get("event_type")?,
+        version: u32::try_from(version).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "version".to_string(),
+            source: Box::new(e),
+        })?,
+        detail: row.try_get("detail")?,
+        created_at: row.try_get("created_at")?,
+        // the SQL stores are single tenant
+        tenant: String::new(),
+    })
+}
+
+#[async_trait]
+impl<'a> OutboxRepositoryPort for OutboxSqlAdaptor<'a> {
+    async fn outbox_get_pending(&self, limit: i32) -> Result<Vec<OutboxItem>, HexagonalError> {
+        sqlx::query(
+            "SELECT id, event_type, version, detail, created_at FROM outbox \
+             WHERE sent_at IS NULL ORDER BY created_at, id LIMIT $1",
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.sql_repository.pool)
+        .await
+        .and_then(|rows| rows.iter().map(outbox_item_from_row).collect())
+        .map_err(|e| database_error("Unable to get pending outbox events", e))
+    }
+
+    async fn outbox_mark_sent(&self, item: &OutboxItem) -> Result<(), HexagonalError> {
+        let result = sqlx::query("UPDATE outbox SET sent_at = $1 WHERE id = $2")
+            .bind(default_time())
+            .bind(&item.id)
+            .execute(&self.sql_repository.pool)
+            .await
+            .map_err(|e| database_error("Unable to mark outbox event sent", e))?;
+        match result.rows_affected() {
+            0 => Err(HexagonalError {
+                error: error::HexagonalErrorCode::NotFound,
+                message: "Unable to mark outbox event sent, does not exist".to_string(),
+                trace: "".to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::insert_outbox_item;
+
+    #[tokio::test]
+    async fn test_outbox_pending_until_marked_sent() {
+        let repository = SqlRepository::sqlite_in_memory().await.unwrap();
+        let adaptor = OutboxSqlAdaptor::new(&repository);
+        let item = OutboxItem::new("Test".to_string(), 1, "{}".to_string());
+        let mut transaction = repository.begin("begin").await.unwrap();
+        insert_outbox_item(&mut transaction, &item).await.unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(
+            adaptor.outbox_get_pending(10).await.unwrap(),
+            vec![item.clone()]
+        );
+        adaptor.outbox_mark_sent(&item).await.unwrap();
+        assert!(adaptor.outbox_get_pending(10).await.unwrap().is_empty());
+
+        let missing = OutboxItem::new("Test".to_string(), 1, "{}".to_string());
+        let err = adaptor.outbox_mark_sent(&missing).await.unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::NotFound);
+    }
+}
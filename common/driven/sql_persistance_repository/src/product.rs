@@ -0,0 +1,607 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use error::HexagonalError;
+use models::changed_since_read_error;
+use models::check_expected_version;
+use models::default_time;
+use models::models::history::{HistoryAction, HistoryRecord};
+use models::models::outbox::OutboxEvent;
+use models::models::product::{MutableProduct, Product, ProductRepositoryPort};
+use models::soft_delete::{now_seconds, retention_cutoff};
+use persistance_repository::{Page, PageRequest};
+use sqlx::any::AnyRow;
+use sqlx::Row;
+
+use crate::{
+    database_error, delete_history, get_u64, insert_history, insert_outbox_item,
+    is_unique_violation, to_i64, SqlRepository,
+};
+
+const PRODUCT_COLUMNS: &str =
+    "id, product_name, price_cents, description, created_at, updated_at, version";
+const PRODUCTS: &str = "products";
+
+// Deleted products keep their row with deleted_at set until they are purged
+pub struct ProductSqlAdaptor<'a> {
+    sql_repository: &'a SqlRepository,
+    delete_retention: Duration,
+}
+
+impl<'a> ProductSqlAdaptor<'a> {
+    // The retention window comes from the repository's config
+    pub fn new(sql_repository: &'a SqlRepository) -> ProductSqlAdaptor<'a> {
+        ProductSqlAdaptor {
+            sql_repository,
+            delete_retention: sql_repository.config.limits.delete_retention,
+        }
+    }
+
+    pub fn with_delete_retention(self, delete_retention: Duration) -> ProductSqlAdaptor<'a> {
+        ProductSqlAdaptor {
+            delete_retention,
+            ..self
+        }
+    }
+}
+
+fn product_from_row(row: &AnyRow) -> Result<Product, sqlx::Error> {
+    let price_cents: i64 = row.try_get("price_cents")?;
+    Ok(Product {
+        id: row.try_get("id")?,
+        product_name: row.try_get("product_name")?,
+        price_cents: i32::try_from(price_cents).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "price_cents".to_string(),
+            source: Box::new(e),
+        })?,
+        description: row.try_get("description")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+        version: get_u64(row, "version")?,
+    })
+}
+
+#[async_trait]
+impl<'a> ProductRepositoryPort for ProductSqlAdaptor<'a> {
+    async fn product_get_by_id(&self, id: &String) -> Result<Option<Product>, HexagonalError> {
+        sqlx::query(&format!(
+            "SELECT {} FROM products WHERE id = $1 AND deleted_at IS NULL",
+            PRODUCT_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.sql_repository.pool)
+        .await
+        .and_then(|row| row.as_ref().map(product_from_row).transpose())
+        .map_err(|e| database_error("Unable to get product", e))
+    }
+
+    async fn product_get_by_ids(&self, id: &Vec<String>) -> Result<Vec<Product>, HexagonalError> {
+        if id.is_empty() {
+            return Ok(Vec::new());
+        }
+        let placeholders = (1..=id.len())
+            .map(|i| format!("${}", i))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let sql = format!(
+            "SELECT {} FROM products WHERE id IN ({}) AND deleted_at IS NULL",
+            PRODUCT_COLUMNS, placeholders
+        );
+        id.iter()
+            .fold(sqlx::query(&sql), |query, id| query.bind(id))
+            .fetch_all(&self.sql_repository.pool)
+            .await
+            .and_then(|rows| rows.iter().map(product_from_row).collect())
+            .map_err(|e| database_error("Unable to get product", e))
+    }
+
+    async fn product_create(
+        &self,
+        product: &Product,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Product, HexagonalError> {
+        let message = "Unable to create product";
+        let mut transaction = self.sql_repository.begin(message).await?;
+        sqlx::query(&format!(
+            "INSERT INTO products ({}) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            PRODUCT_COLUMNS
+        ))
+        .bind(&product.id)
+        .bind(&product.product_name)
+        .bind(product.price_cents as i64)
+        .bind(&product.description)
+        .bind(&product.created_at)
+        .bind(&product.updated_at)
+        .bind(to_i64(product.version))
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| match is_unique_violation(&e) {
+            true => HexagonalError {
+                error: error::HexagonalErrorCode::Conflict,
+                message: "Unable to create product, already exists".to_string(),
+                trace: e.to_string(),
+            },
+            false => database_error(message, e),
+        })?;
+        let history = HistoryRecord::new(HistoryAction::Created, principal, None, product);
+        insert_history(&mut transaction, PRODUCTS, &product.id, &history)
+            .await
+            .map_err(|e| database_error(message, e))?;
+        insert_outbox_item(&mut transaction, &outbox_event(product))
+            .await
+            .map_err(|e| database_error(message, e))?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| database_error(message, e))?;
+        Ok(product.clone())
+    }
+
+    async fn product_update_by_id(
+        &self,
+        id: &String,
+        product_update: &MutableProduct,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Product, HexagonalError> {
+        let current = match self.product_get_by_id(id).await? {
+            Some(current) => current,
+            None => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to update product, does not exist".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+        };
+        check_expected_version(
+            expected_version,
+            current.version,
+            "Unable to update product, version does not match",
+        )?;
+        let updated = product_update.apply_to(&current);
+
+        let message = "Unable to update product";
+        let mut transaction = self.sql_repository.begin(message).await?;
+        let result = sqlx::query(
+            "UPDATE products SET product_name = $1, price_cents = $2, description = $3, \
+             updated_at = $4, version = $5 WHERE id = $6 AND version = $7",
+        )
+        .bind(&updated.product_name)
+        .bind(updated.price_cents as i64)
+        .bind(&updated.description)
+        .bind(&updated.updated_at)
+        .bind(to_i64(updated.version))
+        .bind(&current.id)
+        .bind(to_i64(current.version))
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| database_error(message, e))?;
+        if result.rows_affected() == 0 {
+            return Err(changed_since_read_error(
+                expected_version,
+                "Unable to update product, it was changed by another request",
+                "version changed since read".to_string(),
+            ));
+        }
+        let history =
+            HistoryRecord::new(HistoryAction::Updated, principal, Some(&current), &updated);
+        insert_history(&mut transaction, PRODUCTS, &current.id, &history)
+            .await
+            .map_err(|e| database_error(message, e))?;
+        insert_outbox_item(&mut transaction, &outbox_event(&updated))
+            .await
+            .map_err(|e| database_error(message, e))?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| database_error(message, e))?;
+        Ok(updated)
+    }
+
+    async fn product_delete_by_id(
+        &self,
+        id: &String,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Product, HexagonalError> {
+        let current = match self.product_get_by_id(id).await? {
+            Some(current) => current,
+            None => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to delete product, does not exist".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+        };
+        check_expected_version(
+            expected_version,
+            current.version,
+            "Unable to delete product, version does not match",
+        )?;
+
+        let deleted_at = now_seconds();
+        let deleted = Product {
+            updated_at: deleted_at.to_string(),
+            version: current.version + 1,
+            ..current.clone()
+        };
+
+        let message = "Unable to delete product";
+        let mut transaction = self.sql_repository.begin(message).await?;
+        let result = sqlx::query(
+            "UPDATE products SET updated_at = $1, version = $2, deleted_at = $3 \
+             WHERE id = $4 AND version = $5",
+        )
+        .bind(&deleted.updated_at)
+        .bind(to_i64(deleted.version))
+        .bind(to_i64(deleted_at))
+        .bind(&current.id)
+        .bind(to_i64(current.version))
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| database_error(message, e))?;
+        if result.rows_affected() == 0 {
+            return Err(changed_since_read_error(
+                expected_version,
+                "Unable to delete product, it was changed by another request",
+                "version changed since read".to_string(),
+            ));
+        }
+        let history =
+            HistoryRecord::new(HistoryAction::Deleted, principal, Some(&current), &deleted);
+        insert_history(&mut transaction, PRODUCTS, &current.id, &history)
+            .await
+            .map_err(|e| database_error(message, e))?;
+        insert_outbox_item(&mut transaction, &outbox_event(&deleted))
+            .await
+            .map_err(|e| database_error(message, e))?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| database_error(message, e))?;
+        Ok(deleted)
+    }
+
+    async fn product_restore_by_id(
+        &self,
+        id: &String,
+        principal: &str,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Product, HexagonalError> {
+        let message = "Unable to restore product";
+        let row = sqlx::query(&format!(
+            "SELECT {}, deleted_at FROM products WHERE id = $1",
+            PRODUCT_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.sql_repository.pool)
+        .await
+        .and_then(|row| {
+            row.as_ref()
+                .map(|row| Ok((product_from_row(row)?, row.try_get("deleted_at")?)))
+                .transpose()
+        })
+        .map_err(|e| database_error(message, e))?;
+        let (deleted, deleted_at): (Product, Option<i64>) = match row {
+            Some(row) => row,
+            None => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to restore product, does not exist".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+        };
+        match deleted_at {
+            None => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::Conflict,
+                    message: "Unable to restore product, not deleted".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+            Some(deleted_at) if deleted_at < to_i64(retention_cutoff(self.delete_retention)) => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to restore product, retention window has passed".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+            Some(_) => {}
+        }
+        let restored = Product {
+            updated_at: default_time(),
+            version: deleted.version + 1,
+            ..deleted.clone()
+        };
+
+        let mut transaction = self.sql_repository.begin(message).await?;
+        let result = sqlx::query(
+            "UPDATE products SET updated_at = $1, version = $2, deleted_at = NULL \
+             WHERE id = $3 AND version = $4",
+        )
+        .bind(&restored.updated_at)
+        .bind(to_i64(restored.version))
+        .bind(&deleted.id)
+        .bind(to_i64(deleted.version))
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| database_error(message, e))?;
+        if result.rows_affected() == 0 {
+            return Err(HexagonalError {
+                error: error::HexagonalErrorCode::Conflict,
+                message: "Unable to restore product, it was changed by another request".to_string(),
+                trace: "version changed since read".to_string(),
+            });
+        }
+        let history = HistoryRecord::new(
+            HistoryAction::Restored,
+            principal,
+            Some(&deleted),
+            &restored,
+        );
+        insert_history(&mut transaction, PRODUCTS, &deleted.id, &history)
+            .await
+            .map_err(|e| database_error(message, e))?;
+        insert_outbox_item(&mut transaction, &outbox_event(&restored))
+            .await
+            .map_err(|e| database_error(message, e))?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| database_error(message, e))?;
+        Ok(restored)
+    }
+
+    async fn product_purge_deleted(
+        &self,
+        limit: i32,
+        outbox_event: OutboxEvent<Product>,
+    ) -> Result<Vec<Product>, HexagonalError> {
+        let message = "Unable to purge products";
+        let expired = sqlx::query(&format!(
+            "SELECT {} FROM products WHERE deleted_at < $1 ORDER BY deleted_at, id LIMIT $2",
+            PRODUCT_COLUMNS
+        ))
+        .bind(to_i64(retention_cutoff(self.delete_retention)))
+        .bind(limit as i64)
+        .fetch_all(&self.sql_repository.pool)
+        .await
+        .and_then(|rows| {
+            rows.iter()
+                .map(product_from_row)
+                .collect::<Result<Vec<Product>, _>>()
+        })
+        .map_err(|e| database_error(message, e))?;
+
+        let mut purged = Vec::new();
+        for deleted in expired {
+            let mut transaction = self.sql_repository.begin(message).await?;
+            let result = sqlx::query(
+                "DELETE FROM products WHERE id = $1 AND version = $2 AND deleted_at IS NOT NULL",
+            )
+            .bind(&deleted.id)
+            .bind(to_i64(deleted.version))
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| database_error(message, e))?;
+            // restored, or purged by another run, since it was read
+            if result.rows_affected() == 0 {
+                continue;
+            }
+            delete_history(&mut transaction, PRODUCTS, &deleted.id)
+                .await
+                .map_err(|e| database_error(message, e))?;
+            insert_outbox_item(&mut transaction, &outbox_event(&deleted))
+                .await
+                .map_err(|e| database_error(message, e))?;
+            transaction
+                .commit()
+                .await
+                .map_err(|e| database_error(message, e))?;
+            purged.push(deleted);
+        }
+        Ok(purged)
+    }
+
+    async fn product_history_by_id(
+        &self,
+        id: &String,
+        page: &PageRequest,
+    ) -> Result<Page<HistoryRecord>, HexagonalError> {
+        let message = "Unable to get product history";
+        // deleted products keep their history until they are purged
+        let exists = sqlx::query("SELECT id FROM products WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.sql_repository.pool)
+            .await
+            .map_err(|e| database_error(message, e))?
+            .is_some();
+        if !exists {
+            return Err(HexagonalError {
+                error: error::HexagonalErrorCode::NotFound,
+                message: "Unable to get product history, does not exist".to_string(),
+                trace: "".to_string(),
+            });
+        }
+        self.sql_repository
+            .history_page(PRODUCTS, id, page, message)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use models::models::outbox::OutboxItem;
+
+    use super::*;
+
+    fn test_event(product: &Product) -> OutboxItem {
+        OutboxItem::new("ProductTest".to_string(), 1, product.id.clone())
+    }
+
+    #[tokio::test]
+    async fn test_product_lifecycle_and_batch_get() {
+        let repository = SqlRepository::sqlite_in_memory().await.unwrap();
+        let adaptor = ProductSqlAdaptor::new(&repository);
+        let mut ids = Vec::new();
+        for i in 0..3 {
+            let product = Product::new(format!("product {}", i), 100 * i, "".to_string());
+            ids.push(
+                adaptor
+                    .product_create(&product, "tester", test_event)
+                    .await
+                    .unwrap()
+                    .id,
+            );
+        }
+        let duplicate = Product {
+            id: ids[0].clone(),
+            ..Product::new("again".to_string(), 1, "".to_string())
+        };
+        let err = adaptor
+            .product_create(&duplicate, "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::Conflict);
+
+        let mut found = adaptor
+            .product_get_by_ids(&vec![ids[2].clone(), "missing".to_string(), ids[0].clone()])
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|product| product.price_cents)
+            .collect::<Vec<i32>>();
+        found.sort();
+        assert_eq!(found, vec![0, 200]);
+        assert!(adaptor
+            .product_get_by_ids(&vec![])
+            .await
+            .unwrap()
+            .is_empty());
+
+        let update = MutableProduct {
+            product_name: None,
+            price_cents: Some(150),
+            description: None,
+        };
+        let updated = adaptor
+            .product_update_by_id(&ids[1], &update, Some(1), "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(updated.version, 2);
+        let stale = adaptor
+            .product_update_by_id(&ids[1], &update, Some(1), "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(stale.error, error::HexagonalErrorCode::PreconditionFailed);
+
+        adaptor
+            .product_delete_by_id(&ids[1], None, "tester", test_event)
+            .await
+            .unwrap();
+        let gone = adaptor
+            .product_delete_by_id(&ids[1], None, "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(gone.error, error::HexagonalErrorCode::NotFound);
+        assert_eq!(adaptor.product_get_by_ids(&ids).await.unwrap().len(), 2);
+
+        let restored = adaptor
+            .product_restore_by_id(&ids[1], "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(restored.version, 4);
+        adaptor
+            .product_delete_by_id(&ids[1], None, "tester", test_event)
+            .await
+            .unwrap();
+        let expired = ProductSqlAdaptor::new(&repository).with_delete_retention(Duration::ZERO);
+        let err = expired
+            .product_restore_by_id(&ids[1], "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::NotFound);
+        assert_eq!(
+            expired
+                .product_purge_deleted(10, test_event)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        let err = expired
+            .product_restore_by_id(&ids[1], "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(err.message, "Unable to restore product, does not exist");
+    }
+
+    #[tokio::test]
+    async fn test_product_history_pages_newest_first_until_purged() {
+        let repository = SqlRepository::sqlite_in_memory().await.unwrap();
+        let adaptor = ProductSqlAdaptor::new(&repository);
+        let product = Product::new("widget".to_string(), 100, "".to_string());
+        adaptor
+            .product_create(&product, "creator", test_event)
+            .await
+            .unwrap();
+        let update = MutableProduct {
+            product_name: None,
+            price_cents: Some(150),
+            description: None,
+        };
+        adaptor
+            .product_update_by_id(&product.id, &update, None, "pricer", test_event)
+            .await
+            .unwrap();
+        adaptor
+            .product_delete_by_id(&product.id, None, "deleter", test_event)
+            .await
+            .unwrap();
+
+        let first = adaptor
+            .product_history_by_id(&product.id, &PageRequest::new(Some(2), None))
+            .await
+            .unwrap();
+        assert_eq!(
+            first
+                .items
+                .iter()
+                .map(|record| (record.action, record.principal.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (HistoryAction::Deleted, "deleter"),
+                (HistoryAction::Updated, "pricer")
+            ]
+        );
+        assert_eq!(first.items[1].changes[0].field, "price_cents");
+        let rest = adaptor
+            .product_history_by_id(&product.id, &PageRequest::new(Some(2), first.next_cursor))
+            .await
+            .unwrap();
+        assert_eq!(rest.items.len(), 1);
+        assert_eq!(rest.items[0].action, HistoryAction::Created);
+        assert!(rest.next_cursor.is_none());
+
+        let expired = ProductSqlAdaptor::new(&repository).with_delete_retention(Duration::ZERO);
+        expired.product_purge_deleted(10, test_event).await.unwrap();
+        let err = adaptor
+            .product_history_by_id(&product.id, &PageRequest::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::NotFound);
+        let remaining: i64 = sqlx::query("SELECT COUNT(*) AS remaining FROM history")
+            .fetch_one(&repository.pool)
+            .await
+            .unwrap()
+            .try_get("remaining")
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+}
@@ -0,0 +1,749 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use error::HexagonalError;
+use models::models::history::{HistoryAction, HistoryRecord};
+use models::models::outbox::OutboxEvent;
+use models::models::user::{MutableUser, User, UserRepositoryPort};
+use models::soft_delete::{now_seconds, retention_cutoff};
+use models::{changed_since_read_error, check_expected_version, default_time};
+use persistance_repository::{Page, PageRequest};
+use sqlx::any::AnyRow;
+use sqlx::{Any, Row, Transaction};
+
+use crate::{
+    database_error, delete_history, get_u64, insert_history, insert_outbox_item,
+    is_unique_violation, to_i64, SqlRepository,
+};
+
+const USER_COLUMNS: &str = "username, email, first, last, created_at, updated_at, version";
+const USERS: &str = "users";
+
+// Deleted users keep their row, and their email, with deleted_at set until they are purged
+pub struct UserSqlAdaptor<'a> {
+    sql_repository: &'a SqlRepository,
+    delete_retention: Duration,
+}
+
+impl<'a> UserSqlAdaptor<'a> {
+    // The retention window comes from the repository's config
+    pub fn new(sql_repository: &'a SqlRepository) -> UserSqlAdaptor<'a> {
+        UserSqlAdaptor {
+            sql_repository,
+            delete_retention: sql_repository.config.limits.delete_retention,
+        }
+    }
+
+    pub fn with_delete_retention(self, delete_retention: Duration) -> UserSqlAdaptor<'a> {
+        UserSqlAdaptor {
+            delete_retention,
+            ..self
+        }
+    }
+
+    async fn get_user(
+        &self,
+        username: &str,
+        message: &str,
+    ) -> Result<Option<User>, HexagonalError> {
+        Ok(self
+            .get_user_row(username, message)
+            .await?
+            .filter(|(_, deleted_at)| deleted_at.is_none())
+            .map(|(user, _)| user))
+    }
+
+    // The stored user and when it was deleted, if it was
+    async fn get_user_row(
+        &self,
+        username: &str,
+        message: &str,
+    ) -> Result<Option<(User, Option<i64>)>, HexagonalError> {
+        sqlx::query(&format!(
+            "SELECT {}, deleted_at FROM users WHERE username = $1",
+            USER_COLUMNS
+        ))
+        .bind(username)
+        .fetch_optional(&self.sql_repository.pool)
+        .await
+        .and_then(|row| row.as_ref().map(user_row).transpose())
+        .map_err(|e| database_error(message, e))
+    }
+
+    // Writes `updated` over the user as it was read, along with its history and event
+    async fn write_user(
+        &self,
+        action: &str,
+        read: &User,
+        updated: &User,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<(), HexagonalError> {
+        let message = format!("Unable to {}", action);
+        let mut transaction = self.sql_repository.begin(&message).await?;
+        let result = sqlx::query(
+            "UPDATE users SET email = $1, first = $2, last = $3, updated_at = $4, version = $5 \
+             WHERE username = $6 AND version = $7",
+        )
+        .bind(&updated.email)
+        .bind(&updated.first)
+        .bind(&updated.last)
+        .bind(&updated.updated_at)
+        .bind(to_i64(updated.version))
+        .bind(&read.username)
+        .bind(to_i64(read.version))
+        .execute(&mut *transaction)
+        .await;
+        match result {
+            Ok(result) if result.rows_affected() == 1 => {}
+            Ok(_) => {
+                return Err(self
+                    .changed_since_read(action, &read.username, expected_version)
+                    .await)
+            }
+            Err(e) => return Err(user_write_error(action, e)),
+        }
+        let history = HistoryRecord::new(HistoryAction::Updated, principal, Some(read), updated);
+        commit_with_history(
+            transaction,
+            &message,
+            &read.username,
+            &history,
+            outbox_event(updated),
+        )
+        .await
+    }
+
+    // Nothing matched the version that was read, either the user is gone or it moved on
+    async fn changed_since_read(
+        &self,
+        action: &str,
+        username: &str,
+        expected_version: Option<u64>,
+    ) -> HexagonalError {
+        match self
+            .get_user(username, &format!("Unable to {}", action))
+            .await
+        {
+            Ok(None) => HexagonalError {
+                error: error::HexagonalErrorCode::NotFound,
+                message: format!("Unable to {}, user no longer exists", action),
+                trace: "".to_string(),
+            },
+            Ok(Some(_)) => changed_since_read_error(
+                expected_version,
+                &format!("Unable to {}, it was changed by another request", action),
+                "version changed since read".to_string(),
+            ),
+            Err(err) => err,
+        }
+    }
+}
+
+fn user_from_row(row: &AnyRow) -> Result<User, sqlx::Error> {
+    Ok(User {
+        username: row.try_get("username")?,
+        email: row.try_get("email")?,
+        first: row.try_get("first")?,
+        last: row.try_get("last")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+        version: get_u64(row, "version")?,
+    })
+}
+
+fn user_row(row: &AnyRow) -> Result<(User, Option<i64>), sqlx::Error> {
+    Ok((user_from_row(row)?, row.try_get("deleted_at")?))
+}
+
+// Emails are the only unique column left once the username is settled
+fn user_write_error(action: &str, err: sqlx::Error) -> HexagonalError {
+    match is_unique_violation(&err) {
+        true => HexagonalError {
+            error: error::HexagonalErrorCode::Conflict,
+            message: format!("Unable to {}, email already taken", action),
+            trace: err.to_string(),
+        },
+        false => database_error(&format!("Unable to {}", action), err),
+    }
+}
+
+async fn commit_with_history(
+    mut transaction: Transaction<'static, Any>,
+    message: &str,
+    username: &str,
+    history: &HistoryRecord,
+    event: models::models::outbox::OutboxItem,
+) -> Result<(), HexagonalError> {
+    insert_history(&mut transaction, USERS, username, history)
+        .await
+        .map_err(|e| database_error(message, e))?;
+    commit_with_event(transaction, message, event).await
+}
+
+async fn commit_with_event(
+    mut transaction: Transaction<'static, Any>,
+    message: &str,
+    event: models::models::outbox::OutboxItem,
+) -> Result<(), HexagonalError> {
+    insert_outbox_item(&mut transaction, &event)
+        .await
+        .map_err(|e| database_error(message, e))?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| database_error(message, e))
+}
+
+#[async_trait]
+impl<'a> UserRepositoryPort for UserSqlAdaptor<'a> {
+    async fn user_get_by_email(
+        &self,
+        email: &String,
+        page: &PageRequest,
+    ) -> Result<Page<User>, HexagonalError> {
+        // emails are unique so there is never more than the one page
+        self.sql_repository
+            .page_bounds("users|email", "username", page)
+            .map_err(|e| e.into_hexagonal_error("Unable to fetch user"))?;
+        let items = sqlx::query(&format!(
+            "SELECT {} FROM users WHERE email = $1 AND deleted_at IS NULL",
+            USER_COLUMNS
+        ))
+        .bind(email)
+        .fetch_all(&self.sql_repository.pool)
+        .await
+        .and_then(|rows| rows.iter().map(user_from_row).collect())
+        .map_err(|e| database_error("Unable to fetch user, error in query call", e))?;
+        Ok(Page {
+            items,
+            next_cursor: None,
+        })
+    }
+
+    async fn user_get_by_username(
+        &self,
+        username: &String,
+    ) -> Result<Option<User>, HexagonalError> {
+        self.get_user(username, "Unable to fetch user, error in get call")
+            .await
+    }
+
+    async fn user_create(
+        &self,
+        user: &User,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<User, HexagonalError> {
+        let message = "Unable to create user";
+        let mut transaction = self.sql_repository.begin(message).await?;
+        let result = sqlx::query(&format!(
+            "INSERT INTO users ({}) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            USER_COLUMNS
+        ))
+        .bind(&user.username)
+        .bind(&user.email)
+        .bind(&user.first)
+        .bind(&user.last)
+        .bind(&user.created_at)
+        .bind(&user.updated_at)
+        .bind(to_i64(user.version))
+        .execute(&mut *transaction)
+        .await;
+        if let Err(e) = result {
+            // the failed insert leaves nothing to roll back, so look for the username outside it
+            drop(transaction);
+            // deleted users hold on to their username until purged
+            let username_taken = is_unique_violation(&e)
+                && self.get_user_row(&user.username, message).await?.is_some();
+            return Err(match username_taken {
+                true => HexagonalError {
+                    error: error::HexagonalErrorCode::Conflict,
+                    message: "Unable to create user, username already taken".to_string(),
+                    trace: e.to_string(),
+                },
+                false => user_write_error("create user", e),
+            });
+        }
+        let history = HistoryRecord::new(HistoryAction::Created, principal, None, user);
+        commit_with_history(
+            transaction,
+            message,
+            &user.username,
+            &history,
+            outbox_event(user),
+        )
+        .await?;
+        Ok(user.clone())
+    }
+
+    async fn user_update_by_username(
+        &self,
+        username: &String,
+        user: MutableUser,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<User, HexagonalError> {
+        let current = match self
+            .get_user(username, "Unable to update user, error in get call")
+            .await?
+        {
+            Some(current) => current,
+            None => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to update user, does not exist".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+        };
+        check_expected_version(
+            expected_version,
+            current.version,
+            "Unable to update user, version does not match",
+        )?;
+        let updated = user.apply_to(&current);
+        self.write_user(
+            "update user",
+            &current,
+            &updated,
+            expected_version,
+            principal,
+            outbox_event,
+        )
+        .await?;
+        Ok(updated)
+    }
+
+    async fn user_update_email_by_username(
+        &self,
+        username: &String,
+        new_email: &String,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<User, HexagonalError> {
+        let current = match self
+            .get_user(username, "Unable to update user email, error in get call")
+            .await?
+        {
+            Some(current) => current,
+            None => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to update user email, does not exist".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+        };
+        check_expected_version(
+            expected_version,
+            current.version,
+            "Unable to update user email, version does not match",
+        )?;
+        let updated = User {
+            email: new_email.clone(),
+            updated_at: default_time(),
+            version: current.version + 1,
+            ..current.clone()
+        };
+        self.write_user(
+            "update user email",
+            &current,
+            &updated,
+            expected_version,
+            principal,
+            outbox_event,
+        )
+        .await?;
+        Ok(updated)
+    }
+
+    async fn user_delete_by_username(
+        &self,
+        username: &String,
+        expected_version: Option<u64>,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<User, HexagonalError> {
+        let current = match self
+            .get_user(username, "Unable to fetch user, error in get call")
+            .await?
+        {
+            Some(current) => current,
+            None => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to delete user, does not exist".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+        };
+        check_expected_version(
+            expected_version,
+            current.version,
+            "Unable to delete user, version does not match",
+        )?;
+
+        let deleted_at = now_seconds();
+        let deleted = User {
+            updated_at: deleted_at.to_string(),
+            version: current.version + 1,
+            ..current.clone()
+        };
+
+        let message = "Unable to delete user";
+        let mut transaction = self.sql_repository.begin(message).await?;
+        let result = sqlx::query(
+            "UPDATE users SET updated_at = $1, version = $2, deleted_at = $3 \
+             WHERE username = $4 AND version = $5",
+        )
+        .bind(&deleted.updated_at)
+        .bind(to_i64(deleted.version))
+        .bind(to_i64(deleted_at))
+        .bind(&current.username)
+        .bind(to_i64(current.version))
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| database_error(message, e))?;
+        if result.rows_affected() == 0 {
+            drop(transaction);
+            return Err(self
+                .changed_since_read("delete user", username, expected_version)
+                .await);
+        }
+        let history =
+            HistoryRecord::new(HistoryAction::Deleted, principal, Some(&current), &deleted);
+        commit_with_history(
+            transaction,
+            message,
+            &current.username,
+            &history,
+            outbox_event(&deleted),
+        )
+        .await?;
+        Ok(deleted)
+    }
+
+    async fn user_restore_by_username(
+        &self,
+        username: &String,
+        principal: &str,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<User, HexagonalError> {
+        let message = "Unable to restore user";
+        let (deleted, deleted_at) = match self.get_user_row(username, message).await? {
+            Some(row) => row,
+            None => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to restore user, does not exist".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+        };
+        match deleted_at {
+            None => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::Conflict,
+                    message: "Unable to restore user, not deleted".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+            Some(deleted_at) if deleted_at < to_i64(retention_cutoff(self.delete_retention)) => {
+                return Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "Unable to restore user, retention window has passed".to_string(),
+                    trace: "".to_string(),
+                })
+            }
+            Some(_) => {}
+        }
+        let restored = User {
+            updated_at: default_time(),
+            version: deleted.version + 1,
+            ..deleted.clone()
+        };
+
+        let mut transaction = self.sql_repository.begin(message).await?;
+        let result = sqlx::query(
+            "UPDATE users SET updated_at = $1, version = $2, deleted_at = NULL \
+             WHERE username = $3 AND version = $4",
+        )
+        .bind(&restored.updated_at)
+        .bind(to_i64(restored.version))
+        .bind(&deleted.username)
+        .bind(to_i64(deleted.version))
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| database_error(message, e))?;
+        if result.rows_affected() == 0 {
+            return Err(HexagonalError {
+                error: error::HexagonalErrorCode::Conflict,
+                message: "Unable to restore user, it was changed by another request".to_string(),
+                trace: "version changed since read".to_string(),
+            });
+        }
+        let history = HistoryRecord::new(
+            HistoryAction::Restored,
+            principal,
+            Some(&deleted),
+            &restored,
+        );
+        commit_with_history(
+            transaction,
+            message,
+            &deleted.username,
+            &history,
+            outbox_event(&restored),
+        )
+        .await?;
+        Ok(restored)
+    }
+
+    async fn user_purge_deleted(
+        &self,
+        limit: i32,
+        outbox_event: OutboxEvent<User>,
+    ) -> Result<Vec<User>, HexagonalError> {
+        let message = "Unable to purge users";
+        let expired = sqlx::query(&format!(
+            "SELECT {} FROM users WHERE deleted_at < $1 ORDER BY deleted_at, username LIMIT $2",
+            USER_COLUMNS
+        ))
+        .bind(to_i64(retention_cutoff(self.delete_retention)))
+        .bind(limit as i64)
+        .fetch_all(&self.sql_repository.pool)
+        .await
+        .and_then(|rows| {
+            rows.iter()
+                .map(user_from_row)
+                .collect::<Result<Vec<User>, _>>()
+        })
+        .map_err(|e| database_error(message, e))?;
+
+        let mut purged = Vec::new();
+        for deleted in expired {
+            let mut transaction = self.sql_repository.begin(message).await?;
+            let result = sqlx::query(
+                "DELETE FROM users WHERE username = $1 AND version = $2 AND deleted_at IS NOT NULL",
+            )
+            .bind(&deleted.username)
+            .bind(to_i64(deleted.version))
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| database_error(message, e))?;
+            // restored, or purged by another run, since it was read
+            if result.rows_affected() == 0 {
+                continue;
+            }
+            delete_history(&mut transaction, USERS, &deleted.username)
+                .await
+                .map_err(|e| database_error(message, e))?;
+            commit_with_event(transaction, message, outbox_event(&deleted)).await?;
+            purged.push(deleted);
+        }
+        Ok(purged)
+    }
+
+    async fn user_history_by_username(
+        &self,
+        username: &String,
+        page: &PageRequest,
+    ) -> Result<Page<HistoryRecord>, HexagonalError> {
+        let message = "Unable to get user history";
+        // deleted users keep their history until they are purged
+        if self.get_user_row(username, message).await?.is_none() {
+            return Err(HexagonalError {
+                error: error::HexagonalErrorCode::NotFound,
+                message: "Unable to get user history, does not exist".to_string(),
+                trace: "".to_string(),
+            });
+        }
+        self.sql_repository
+            .history_page(USERS, username, page, message)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use models::models::outbox::OutboxItem;
+
+    use super::*;
+
+    fn test_event(user: &User) -> OutboxItem {
+        OutboxItem::new("UserTest".to_string(), 1, user.username.clone())
+    }
+
+    fn user(username: &str, email: &str) -> User {
+        User {
+            first: "first".to_string(),
+            last: "last".to_string(),
+            email: email.to_string(),
+            username: username.to_string(),
+            created_at: default_time(),
+            updated_at: default_time(),
+            version: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_user_create_keeps_username_and_email_unique() {
+        let repository = SqlRepository::sqlite_in_memory().await.unwrap();
+        let adaptor = UserSqlAdaptor::new(&repository);
+        adaptor
+            .user_create(&user("a", "a@example.com"), "tester", test_event)
+            .await
+            .unwrap();
+
+        for (duplicate, message) in [
+            (
+                user("a", "other@example.com"),
+                "Unable to create user, username already taken",
+            ),
+            (
+                user("b", "a@example.com"),
+                "Unable to create user, email already taken",
+            ),
+        ] {
+            let err = adaptor
+                .user_create(&duplicate, "tester", test_event)
+                .await
+                .unwrap_err();
+            assert_eq!(err.error, error::HexagonalErrorCode::Conflict);
+            assert_eq!(err.message, message);
+        }
+
+        let page = adaptor
+            .user_get_by_email(&"a@example.com".to_string(), &PageRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(page.items, vec![user("a", "a@example.com")]);
+    }
+
+    #[tokio::test]
+    async fn test_user_update_email_and_delete_check_versions() {
+        let repository = SqlRepository::sqlite_in_memory().await.unwrap();
+        let adaptor = UserSqlAdaptor::new(&repository);
+        for (username, email) in [("a", "a@example.com"), ("b", "b@example.com")] {
+            adaptor
+                .user_create(&user(username, email), "tester", test_event)
+                .await
+                .unwrap();
+        }
+
+        let taken = adaptor
+            .user_update_email_by_username(
+                &"a".to_string(),
+                &"b@example.com".to_string(),
+                None,
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            taken.message,
+            "Unable to update user email, email already taken"
+        );
+
+        let updated = adaptor
+            .user_update_email_by_username(
+                &"a".to_string(),
+                &"new@example.com".to_string(),
+                Some(1),
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+        assert_eq!(updated.version, 2);
+
+        let stale = adaptor
+            .user_delete_by_username(&"a".to_string(), Some(1), "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(stale.error, error::HexagonalErrorCode::PreconditionFailed);
+        adaptor
+            .user_delete_by_username(&"a".to_string(), Some(2), "tester", test_event)
+            .await
+            .unwrap();
+        assert!(adaptor
+            .user_get_by_username(&"a".to_string())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_user_soft_delete_restore_and_purge() {
+        let repository = SqlRepository::sqlite_in_memory().await.unwrap();
+        let adaptor = UserSqlAdaptor::new(&repository);
+        let expired = UserSqlAdaptor::new(&repository).with_delete_retention(Duration::ZERO);
+        let username = "a".to_string();
+        adaptor
+            .user_create(&user("a", "a@example.com"), "tester", test_event)
+            .await
+            .unwrap();
+
+        let deleted = adaptor
+            .user_delete_by_username(&username, None, "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(deleted.version, 2);
+        // the username and email stay taken until the user is purged
+        let err = adaptor
+            .user_create(&user("a", "other@example.com"), "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(err.message, "Unable to create user, username already taken");
+        assert!(adaptor
+            .user_get_by_email(&"a@example.com".to_string(), &PageRequest::default())
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+
+        let restored = adaptor
+            .user_restore_by_username(&username, "tester", test_event)
+            .await
+            .unwrap();
+        assert_eq!(restored.version, 3);
+        assert_eq!(
+            adaptor.user_get_by_username(&username).await.unwrap(),
+            Some(restored)
+        );
+        let err = adaptor
+            .user_restore_by_username(&username, "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::Conflict);
+
+        adaptor
+            .user_delete_by_username(&username, None, "tester", test_event)
+            .await
+            .unwrap();
+        assert!(adaptor
+            .user_purge_deleted(10, test_event)
+            .await
+            .unwrap()
+            .is_empty());
+        let err = expired
+            .user_restore_by_username(&username, "tester", test_event)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.message,
+            "Unable to restore user, retention window has passed"
+        );
+        let purged = expired.user_purge_deleted(10, test_event).await.unwrap();
+        assert_eq!(purged.len(), 1);
+        adaptor
+            .user_create(&user("b", "a@example.com"), "tester", test_event)
+            .await
+            .unwrap();
+    }
+}
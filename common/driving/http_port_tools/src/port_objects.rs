@@ -1,7 +1,10 @@
 use std::future::Future;
 use std::pin::Pin;
 
+use error::HexagonalError;
+use lambda_http::request::RequestContext;
 use lambda_http::RequestExt;
+use persistance_repository::{Correlation, DynamoDBSingleTableRepository, PageRequest, Tenant};
 
 pub struct HttpPortResponse<T>(pub http::Response<T>);
 
@@ -10,6 +13,115 @@ pub struct HttpPortRequest {
     pub query_string_parameters: query_map::QueryMap,
     pub payload: Option<String>,
     pub headers: http::HeaderMap,
+    // The caller API Gateway authenticated, None for unauthenticated requests
+    pub principal: Option<String>,
+    // The tenant the authorizer put the caller in, if it did
+    pub tenant_claim: Option<String>,
+    // API Gateway's id for the request
+    pub request_id: Option<String>,
+}
+
+// Who a request is attributed to when it carried no identity
+pub const ANONYMOUS_PRINCIPAL: &str = "anonymous";
+
+// Names the tenant of requests the authorizer doesn't
+pub const TENANT_HEADER: &str = "x-tenant-id";
+
+// Lets a caller tie the events a request causes to its own trace
+pub const CORRELATION_HEADER: &str = "x-correlation-id";
+
+impl HttpPortRequest {
+    // The principal writes are recorded against in an entity's history
+    pub fn principal(&self) -> String {
+        self.principal
+            .clone()
+            .unwrap_or_else(|| ANONYMOUS_PRINCIPAL.to_string())
+    }
+
+    // The storefront the request is for. An authorizer's claim wins over the header, so an
+    // authenticated caller can't reach another tenant by naming it. Neither is the default tenant
+    pub fn tenant(&self) -> Result<Tenant, HexagonalError> {
+        let header = match self.headers.get(TENANT_HEADER) {
+            Some(header) => Some(header.to_str().map_err(|_| HexagonalError {
+                error: error::HexagonalErrorCode::BadInput,
+                message: "Invalid tenant id".to_string(),
+                trace: format!("{:?}", header),
+            })?),
+            None => None,
+        };
+        Tenant::from_request(self.tenant_claim.as_deref().or(header))
+    }
+
+    // What the events written for this request are correlated by, the caller's correlation id
+    // if it sent one, otherwise the request's own id
+    pub fn correlation(&self) -> Correlation {
+        let header = self
+            .headers
+            .get(CORRELATION_HEADER)
+            .and_then(|header| header.to_str().ok())
+            .filter(|header| !header.is_empty())
+            .map(|header| header.to_string());
+        Correlation::new(header.or_else(|| self.request_id.clone()), None)
+    }
+
+    // The repository a request is served with. Repositories are scoped per request, to the
+    // tenant the request is for, and the keys of every read and write go out as that tenant's,
+    // see persistance_repository's tenant. The events it writes are correlated to the request
+    pub fn scoped_repository(
+        &self,
+        repository: &DynamoDBSingleTableRepository,
+    ) -> Result<DynamoDBSingleTableRepository, HexagonalError> {
+        Ok(repository
+            .for_tenant(self.tenant()?)
+            .with_correlation(self.correlation()))
+    }
+
+    // `?limit=&cursor=` for list endpoints, the limit range is checked by the repository
+    pub fn page_request(&self) -> Result<PageRequest, HexagonalError> {
+        let limit = match self.query_string_parameters.first("limit") {
+            Some(limit) => Some(limit.parse::<i32>().map_err(|_| HexagonalError {
+                error: error::HexagonalErrorCode::BadInput,
+                message: "limit must be a number".to_string(),
+                trace: "".to_string(),
+            })?),
+            None => None,
+        };
+        let cursor = self
+            .query_string_parameters
+            .first("cursor")
+            .map(|cursor| cursor.to_string());
+        Ok(PageRequest::new(limit, cursor))
+    }
+
+    // The version an `If-Match` header asks a write to apply to, None when there is no header or
+    // it is `*`. Weak tags are accepted as we only ever compare versions
+    pub fn if_match(&self) -> Result<Option<u64>, HexagonalError> {
+        let header = match self.headers.get(http::header::IF_MATCH) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let invalid = || HexagonalError {
+            error: error::HexagonalErrorCode::BadInput,
+            message: "If-Match must be an ETag returned by this API".to_string(),
+            trace: format!("{:?}", header),
+        };
+        let value = header.to_str().map_err(|_| invalid())?.trim();
+        if value == "*" {
+            return Ok(None);
+        }
+        value
+            .trim_start_matches("W/")
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .and_then(|version| version.parse::<u64>().ok())
+            .map(Some)
+            .ok_or_else(invalid)
+    }
+}
+
+// ETag header value for a model at `version`, what If-Match expects back
+pub fn etag(version: u64) -> String {
+    format!("\"{}\"", version)
 }
 
 impl lambda_http::IntoResponse for HttpPortResponse<String> {
@@ -57,6 +169,132 @@ impl From<lambda_http::Request> for HttpPortRequest {
             query_string_parameters: request.query_string_parameters().clone(),
             payload: Some(body),
             headers: request.headers().clone(),
+            principal: principal(&request),
+            tenant_claim: tenant_claim(&request),
+            request_id: request_id(&request),
+        }
+    }
+}
+
+// A Lambda authorizer's tenant_id, or a Cognito user pool's custom:tenant_id attribute
+fn tenant_claim(request: &lambda_http::Request) -> Option<String> {
+    match request.request_context_ref() {
+        Some(RequestContext::ApiGatewayV1(context)) => {
+            let fields = &context.authorizer.fields;
+            fields
+                .get("tenant_id")
+                .or_else(|| {
+                    fields
+                        .get("claims")
+                        .and_then(|claims| claims.get("custom:tenant_id"))
+                })
+                .and_then(|tenant| tenant.as_str())
+                .map(|tenant| tenant.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn request_id(request: &lambda_http::Request) -> Option<String> {
+    match request.request_context_ref() {
+        Some(RequestContext::ApiGatewayV1(context)) => context.request_id.clone(),
+        _ => None,
+    }
+}
+
+// IAM callers have an ARN, Cognito callers an identity id, anything else API Gateway knows the user as
+fn principal(request: &lambda_http::Request) -> Option<String> {
+    match request.request_context_ref() {
+        Some(RequestContext::ApiGatewayV1(context)) => context
+            .identity
+            .user_arn
+            .clone()
+            .or_else(|| context.identity.cognito_identity_id.clone())
+            .or_else(|| context.identity.user.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_with_if_match(value: Option<&str>) -> HttpPortRequest {
+        let mut headers = http::HeaderMap::new();
+        if let Some(value) = value {
+            headers.insert(http::header::IF_MATCH, value.parse().unwrap());
+        }
+        HttpPortRequest {
+            path_parameters: query_map::QueryMap::default(),
+            query_string_parameters: query_map::QueryMap::default(),
+            payload: None,
+            headers,
+            principal: None,
+            tenant_claim: None,
+            request_id: None,
+        }
+    }
+
+    #[test]
+    fn test_if_match() {
+        assert_eq!(request_with_if_match(None).if_match().unwrap(), None);
+        assert_eq!(request_with_if_match(Some("*")).if_match().unwrap(), None);
+        assert_eq!(
+            request_with_if_match(Some(&etag(3))).if_match().unwrap(),
+            Some(3)
+        );
+        assert_eq!(
+            request_with_if_match(Some("W/\"3\"")).if_match().unwrap(),
+            Some(3)
+        );
+        for invalid in ["3", "\"three\"", "\"3"] {
+            assert_eq!(
+                request_with_if_match(Some(invalid))
+                    .if_match()
+                    .unwrap_err()
+                    .error,
+                error::HexagonalErrorCode::BadInput
+            );
         }
     }
+
+    #[test]
+    fn test_tenant_claim_wins_over_header() {
+        let mut request = request_with_if_match(None);
+        assert_eq!(request.tenant().unwrap(), Tenant::default());
+
+        request
+            .headers
+            .insert(TENANT_HEADER, "globex".parse().unwrap());
+        assert_eq!(request.tenant().unwrap(), Tenant::new("globex").unwrap());
+
+        request.tenant_claim = Some("acme".to_string());
+        assert_eq!(request.tenant().unwrap(), Tenant::new("acme").unwrap());
+
+        request.tenant_claim = Some("acme#globex".to_string());
+        assert_eq!(
+            request.tenant().unwrap_err().error,
+            error::HexagonalErrorCode::BadInput
+        );
+    }
+
+    #[test]
+    fn test_correlation_prefers_the_callers_id() {
+        let mut request = request_with_if_match(None);
+        assert_eq!(request.correlation(), Correlation::default());
+
+        request.request_id = Some("request-1".to_string());
+        assert_eq!(
+            request.correlation(),
+            Correlation::new(Some("request-1".to_string()), None)
+        );
+
+        request
+            .headers
+            .insert(CORRELATION_HEADER, "trace-1".parse().unwrap());
+        assert_eq!(
+            request.correlation(),
+            Correlation::new(Some("trace-1".to_string()), None)
+        );
+    }
 }
@@ -0,0 +1,36 @@
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::primitives::Blob;
+use aws_sdk_dynamodb::types::AttributeValue;
+
+// Stream records arrive as serde_dynamo items, which have no conversion into the SDK types the
+// models are built from, so images are converted here before reaching a port
+pub fn into_attr_map(item: serde_dynamo::Item) -> HashMap<String, AttributeValue> {
+    item.into_inner()
+        .into_iter()
+        .map(|(name, value)| (name, into_attribute_value(value)))
+        .collect()
+}
+
+fn into_attribute_value(value: serde_dynamo::AttributeValue) -> AttributeValue {
+    match value {
+        serde_dynamo::AttributeValue::N(n) => AttributeValue::N(n),
+        serde_dynamo::AttributeValue::S(s) => AttributeValue::S(s),
+        serde_dynamo::AttributeValue::Bool(b) => AttributeValue::Bool(b),
+        serde_dynamo::AttributeValue::B(b) => AttributeValue::B(Blob::new(b)),
+        serde_dynamo::AttributeValue::Null(null) => AttributeValue::Null(null),
+        serde_dynamo::AttributeValue::M(m) => AttributeValue::M(
+            m.into_iter()
+                .map(|(name, value)| (name, into_attribute_value(value)))
+                .collect(),
+        ),
+        serde_dynamo::AttributeValue::L(l) => {
+            AttributeValue::L(l.into_iter().map(into_attribute_value).collect())
+        }
+        serde_dynamo::AttributeValue::Ss(ss) => AttributeValue::Ss(ss),
+        serde_dynamo::AttributeValue::Ns(ns) => AttributeValue::Ns(ns),
+        serde_dynamo::AttributeValue::Bs(bs) => {
+            AttributeValue::Bs(bs.into_iter().map(Blob::new).collect())
+        }
+    }
+}
@@ -0,0 +1,190 @@
+// One function can consume several event types. Each event is routed by its detail-type to the
+// handler registered for that type, and read through the event registry first, so handlers are
+// typed and always get the current version whatever version was published
+//
+// EventDispatcher::new(EventRegistry::storefront())
+//     .on(|event: TenantEvent<EventUserDeletedV1>| async move { .. })
+//     .on(|event: TenantEvent<EventProductDeletedV1>| async move { .. })
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+use aws_lambda_events::cloudwatch_events::CloudWatchEvent;
+use error::HexagonalError;
+use eventing::events::event_registry::{EventRegistry, VersionedEvent};
+use eventing::events::event_wrapper::TenantEvent;
+use serde_json::Value;
+
+type HandlerFuture<'a> = Pin<Box<dyn Future<Output = Result<(), HexagonalError>> + Send + 'a>>;
+type Handler<'a> = Box<dyn Fn(&EventRegistry, Value) -> HandlerFuture<'a> + Send + Sync + 'a>;
+
+pub struct EventDispatcher<'a> {
+    registry: EventRegistry,
+    // keyed by detail-type, which is the event_type events are published with
+    handlers: HashMap<String, Handler<'a>>,
+}
+
+impl<'a> EventDispatcher<'a> {
+    pub fn new(registry: EventRegistry) -> EventDispatcher<'a> {
+        EventDispatcher {
+            registry,
+            handlers: HashMap::new(),
+        }
+    }
+
+    // Handles events of T's type, replacing any handler already registered for it
+    pub fn on<T, F, Fut>(mut self, handler: F) -> EventDispatcher<'a>
+    where
+        T: VersionedEvent + Send + 'a,
+        F: Fn(TenantEvent<T>) -> Fut + Send + Sync + 'a,
+        Fut: Future<Output = Result<(), HexagonalError>> + Send + 'a,
+    {
+        self.handlers.insert(
+            T::EVENT_TYPE.to_string(),
+            Box::new(move |registry, detail| -> HandlerFuture<'a> {
+                match registry.decode::<T>(detail) {
+                    Ok(event) => Box::pin(handler(event)),
+                    Err(err) => Box::pin(async move { Err(err) }),
+                }
+            }),
+        );
+        self
+    }
+
+    // The event types handlers are registered for, what the function's rule should match
+    pub fn event_types(&self) -> Vec<&str> {
+        let mut event_types: Vec<&str> = self.handlers.keys().map(String::as_str).collect();
+        event_types.sort();
+        event_types
+    }
+
+    // Events with no handler, no detail or a detail that can't be read as the current version are
+    // rejected rather than retried into a panic
+    pub async fn dispatch(&self, event: CloudWatchEvent<Value>) -> Result<(), HexagonalError> {
+        let detail_type = event.detail_type.ok_or_else(|| HexagonalError {
+            error: error::HexagonalErrorCode::BadInput,
+            message: "Event has no detail-type".to_string(),
+            trace: format!("event id {:?}", event.id),
+        })?;
+        let handler = self
+            .handlers
+            .get(&detail_type)
+            .ok_or_else(|| HexagonalError {
+                error: error::HexagonalErrorCode::BadInput,
+                message: "Unhandled event type".to_string(),
+                trace: format!(
+                    "no handler for {}, handled are {:?}",
+                    detail_type,
+                    self.event_types()
+                ),
+            })?;
+        handler(&self.registry, event.detail.unwrap_or_default()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use eventing::events::event_emmiter::SerialisableEvent;
+    use eventing::events::user::username_updated::EventEmailUpdatedV1;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct EventRenamedV1 {
+        full_name: String,
+    }
+
+    impl VersionedEvent for EventRenamedV1 {
+        const EVENT_TYPE: &'static str = "renamed";
+        const VERSION: u32 = 1;
+    }
+
+    fn cloudwatch_event(detail_type: Option<&str>, detail: Option<Value>) -> CloudWatchEvent {
+        serde_json::from_value(json!({
+            "version": "0",
+            "detail-type": detail_type,
+            "source": "RUSTHEXAGONALSTOREFRONT.users",
+            "time": "2024-01-01T00:00:00Z",
+            "resources": [],
+            "detail": detail,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_routes_by_detail_type() {
+        let handled = Mutex::new(Vec::new());
+        let dispatcher =
+            EventDispatcher::new(EventRegistry::storefront().with_event::<EventRenamedV1>())
+                .on(|event: TenantEvent<EventEmailUpdatedV1>| {
+                    handled.lock().unwrap().push(event.event.username);
+                    async { Ok(()) }
+                })
+                .on(|event: TenantEvent<EventRenamedV1>| {
+                    handled.lock().unwrap().push(event.event.full_name);
+                    async { Ok(()) }
+                });
+        assert_eq!(
+            dispatcher.event_types(),
+            vec!["renamed", "user_email_updated"]
+        );
+
+        let event = EventEmailUpdatedV1::new("jdoe".to_string(), "jdoe@example.com".to_string());
+        dispatcher
+            .dispatch(cloudwatch_event(
+                Some("user_email_updated"),
+                Some(serde_json::from_str(&event.serialise()).unwrap()),
+            ))
+            .await
+            .unwrap();
+        dispatcher
+            .dispatch(cloudwatch_event(
+                Some("renamed"),
+                Some(json!({"version": 1, "event_type": "renamed", "full_name": "Jo Doe"})),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(*handled.lock().unwrap(), vec!["jdoe", "Jo Doe"]);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_rejects_what_it_cant_handle() {
+        let dispatcher = EventDispatcher::new(EventRegistry::storefront())
+            .on(|_: TenantEvent<EventEmailUpdatedV1>| async { Ok(()) });
+        let detail = json!({"version": 1, "event_type": "user_email_updated"});
+
+        let err = dispatcher
+            .dispatch(cloudwatch_event(Some("user_deleted"), Some(detail.clone())))
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::BadInput);
+        assert_eq!(err.message, "Unhandled event type");
+
+        let err = dispatcher
+            .dispatch(cloudwatch_event(None, Some(detail)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.message, "Event has no detail-type");
+
+        let err = dispatcher
+            .dispatch(cloudwatch_event(Some("user_email_updated"), None))
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::BadInput);
+
+        let err = dispatcher
+            .dispatch(cloudwatch_event(
+                Some("user_email_updated"),
+                Some(json!({"version": 2, "event_type": "user_email_updated"})),
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(err.message, "Unknown event version");
+    }
+}
@@ -0,0 +1,128 @@
+// A function behind API Gateway that is also the target of an EventBridge rule, for state that
+// lives in the function's process and is kept up to date by events, e.g. a cache. An invocation
+// with a detail-type is an event and goes to the dispatcher, anything else is an http request and
+// is handled as lambda_http::run would
+
+use std::future::Future;
+
+use aws_lambda_events::cloudwatch_events::CloudWatchEvent;
+use lambda_http::request::LambdaRequest;
+use lambda_http::{Adapter, IntoResponse, Request, Service};
+use lambda_runtime::{service_fn, Error, LambdaEvent};
+use serde_json::Value;
+
+use crate::eventbridge::EventDispatcher;
+
+pub async fn run_http_with_events<F, Fut, R>(
+    handler: F,
+    event_dispatcher: &EventDispatcher<'_>,
+) -> Result<(), Error>
+where
+    F: Fn(Request) -> Fut,
+    Fut: Future<Output = Result<R, Error>> + Send,
+    R: IntoResponse,
+{
+    lambda_runtime::run(service_fn(|event| {
+        handle_http_or_event(&handler, event_dispatcher, event)
+    }))
+    .await
+}
+
+async fn handle_http_or_event<F, Fut, R>(
+    handler: &F,
+    event_dispatcher: &EventDispatcher<'_>,
+    event: LambdaEvent<Value>,
+) -> Result<Value, Error>
+where
+    F: Fn(Request) -> Fut,
+    Fut: Future<Output = Result<R, Error>> + Send,
+    R: IntoResponse,
+{
+    let (payload, context) = event.into_parts();
+    if is_event(&payload) {
+        let event: CloudWatchEvent<Value> = serde_json::from_value(payload)?;
+        event_dispatcher
+            .dispatch(event)
+            .await
+            .map_err(|err| err.to_string())?;
+        return Ok(Value::Null);
+    }
+    let request: LambdaRequest = serde_json::from_value(payload)?;
+    // what lambda_http::run wraps its handler in
+    let response = Adapter::from(service_fn(handler))
+        .call(LambdaEvent::new(request, context))
+        .await?;
+    Ok(serde_json::to_value(response)?)
+}
+
+fn is_event(payload: &Value) -> bool {
+    payload.get("detail-type").is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use eventing::events::event_registry::EventRegistry;
+    use eventing::events::event_wrapper::TenantEvent;
+    use eventing::events::user::username_updated::EventEmailUpdatedV1;
+    use lambda_runtime::Context;
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_events_go_to_the_dispatcher_and_requests_to_the_handler() {
+        let handled = Mutex::new(Vec::new());
+        let dispatcher = EventDispatcher::new(EventRegistry::storefront()).on(
+            |event: TenantEvent<EventEmailUpdatedV1>| {
+                handled.lock().unwrap().push(event.event.username);
+                async { Ok(()) }
+            },
+        );
+        let handler = |request: Request| async move {
+            Ok::<String, Error>(format!("{} {}", request.method(), request.uri().path()))
+        };
+
+        let event = json!({
+            "version": "0",
+            "id": "1",
+            "detail-type": "user_email_updated",
+            "source": "RUSTHEXAGONALSTOREFRONT.users",
+            "time": "2024-01-01T00:00:00Z",
+            "resources": [],
+            "detail": serde_json::to_value(EventEmailUpdatedV1::new(
+                "jdoe".to_string(),
+                "jdoe@example.com".to_string()
+            ))
+            .unwrap(),
+        });
+        let result = handle_http_or_event(
+            &handler,
+            &dispatcher,
+            LambdaEvent::new(event, Context::default()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result, Value::Null);
+        assert_eq!(*handled.lock().unwrap(), vec!["jdoe".to_string()]);
+
+        let request = json!({
+            "resource": "/product/{id}",
+            "path": "/product/1",
+            "httpMethod": "GET",
+            "headers": {},
+            "multiValueHeaders": {},
+            "requestContext": {"httpMethod": "GET", "path": "/product/1"},
+        });
+        let response = handle_http_or_event(
+            &handler,
+            &dispatcher,
+            LambdaEvent::new(request, Context::default()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response["statusCode"], 200);
+        assert_eq!(response["body"], "GET /product/1");
+    }
+}
@@ -0,0 +1,211 @@
+// EventBridge stand-in for local runs and tests
+// Emitted events are delivered to the same dispatchers the consumer functions run, once for each
+// rule whose detail-types match, as EventBridge does with the rules in infra, e.g.
+//
+// InProcessEventBus::new(&config)
+//     .with_rule(&["user_deleted", "product_deleted"], cart_event_dispatcher(&repository))
+
+use std::sync::Arc;
+
+use app_config::AppConfig;
+use async_trait::async_trait;
+use aws_lambda_events::cloudwatch_events::CloudWatchEvent;
+use error::HexagonalError;
+use eventing::events::event_emmiter::SerialisableEvent;
+use eventing::{event_source, EventingPort};
+use serde_json::Value;
+
+use crate::eventbridge::EventDispatcher;
+
+struct Rule<'a> {
+    detail_types: Vec<String>,
+    dispatcher: EventDispatcher<'a>,
+}
+
+// Delivers before emit returns, so whatever the event causes has happened by the time it does and
+// delivery failures come back from emit
+pub struct InProcessEventBus<'a> {
+    source: String,
+    rules: Vec<Rule<'a>>,
+}
+
+impl<'a> InProcessEventBus<'a> {
+    pub fn new(config: &AppConfig) -> InProcessEventBus<'a> {
+        InProcessEventBus {
+            source: config.eventing.source.clone(),
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn with_rule(
+        mut self,
+        detail_types: &[&str],
+        dispatcher: EventDispatcher<'a>,
+    ) -> InProcessEventBus<'a> {
+        self.rules.push(Rule {
+            detail_types: detail_types
+                .iter()
+                .map(|detail_type| detail_type.to_string())
+                .collect(),
+            dispatcher,
+        });
+        self
+    }
+
+    // The event as EventBridge would deliver it to a function
+    pub fn cloudwatch_event<T: SerialisableEvent>(
+        &self,
+        event: &T,
+    ) -> Result<CloudWatchEvent<Value>, HexagonalError> {
+        let detail: Value =
+            serde_json::from_str(&event.serialise()).map_err(|err| HexagonalError {
+                error: error::HexagonalErrorCode::BadInput,
+                message: format!("Unable to emit event: {}", event.get_event_type()),
+                trace: err.to_string(),
+            })?;
+        let event_id = event.get_envelope().event_id;
+        Ok(CloudWatchEvent {
+            version: Some("0".to_string()),
+            id: (!event_id.is_empty()).then_some(event_id),
+            detail_type: Some(event.get_event_type().clone()),
+            source: Some(event_source(&self.source, event)),
+            account_id: None,
+            time: chrono::Utc::now(),
+            region: None,
+            resources: Vec::new(),
+            detail: Some(detail),
+        })
+    }
+
+    // Every matching rule gets the event even when an earlier one failed, the first failure is
+    // returned. Events no rule matches are dropped, as they are by EventBridge
+    pub async fn deliver(&self, event: CloudWatchEvent<Value>) -> Result<(), HexagonalError> {
+        let mut result = Ok(());
+        for rule in &self.rules {
+            let matches = match &event.detail_type {
+                Some(detail_type) => rule.detail_types.contains(detail_type),
+                None => false,
+            };
+            if !matches {
+                continue;
+            }
+            if let Err(err) = rule.dispatcher.dispatch(event.clone()).await {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<'a> EventingPort for InProcessEventBus<'a> {
+    async fn emit<T: SerialisableEvent + Sync + 'static>(
+        &self,
+        event: &T,
+    ) -> Result<(), HexagonalError> {
+        let event = self.cloudwatch_event(event)?;
+        self.deliver(event).await
+    }
+}
+
+// Delivers from a tokio task, so emit returns once the event is accepted as it does with
+// EventBridge. Delivery failures can only be logged
+pub struct SpawningEventBus {
+    bus: Arc<InProcessEventBus<'static>>,
+}
+
+impl SpawningEventBus {
+    pub fn new(bus: InProcessEventBus<'static>) -> SpawningEventBus {
+        SpawningEventBus { bus: Arc::new(bus) }
+    }
+}
+
+#[async_trait]
+impl EventingPort for SpawningEventBus {
+    async fn emit<T: SerialisableEvent + Sync + 'static>(
+        &self,
+        event: &T,
+    ) -> Result<(), HexagonalError> {
+        let event = self.bus.cloudwatch_event(event)?;
+        let bus = self.bus.clone();
+        tokio::spawn(async move {
+            if let Err(err) = bus.deliver(event).await {
+                println!("Error: {}", err);
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use eventing::events::event_registry::EventRegistry;
+    use eventing::events::event_wrapper::TenantEvent;
+    use eventing::events::user::username_updated::EventEmailUpdatedV1;
+    use models::models::outbox::OutboxItem;
+
+    use super::*;
+
+    fn recording_dispatcher(handled: &Mutex<Vec<String>>) -> EventDispatcher<'_> {
+        EventDispatcher::new(EventRegistry::storefront()).on(
+            |event: TenantEvent<EventEmailUpdatedV1>| {
+                handled.lock().unwrap().push(format!(
+                    "{}@{}",
+                    event.event.username,
+                    event.tenant.unwrap_or_default()
+                ));
+                async { Ok(()) }
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_emit_delivers_to_matching_rules() {
+        let handled = Mutex::new(Vec::new());
+        let unmatched = Mutex::new(Vec::new());
+        let bus = InProcessEventBus::new(&AppConfig::default())
+            .with_rule(&["user_email_updated"], recording_dispatcher(&handled))
+            .with_rule(&["user_deleted"], recording_dispatcher(&unmatched));
+
+        let event = EventEmailUpdatedV1::new("jdoe".to_string(), "jdoe@example.com".to_string());
+        bus.emit(&event).await.unwrap();
+        // what the outbox relay publishes, with the tenant the change was made in
+        let item = OutboxItem {
+            tenant: "acme".to_string(),
+            ..event.to_outbox_item()
+        };
+        bus.emit(&item).await.unwrap();
+
+        assert_eq!(*handled.lock().unwrap(), vec!["jdoe@", "jdoe@acme"]);
+        assert!(unmatched.lock().unwrap().is_empty());
+
+        let cloudwatch_event = bus.cloudwatch_event(&event).unwrap();
+        assert_eq!(
+            cloudwatch_event.source,
+            Some(format!("{}.users", app_config::DEFAULT_EVENT_SOURCE))
+        );
+        assert_eq!(cloudwatch_event.id, Some(event.envelope.event_id));
+    }
+
+    #[tokio::test]
+    async fn test_emit_returns_delivery_failures() {
+        let bus = InProcessEventBus::new(&AppConfig::default()).with_rule(
+            &["user_email_updated"],
+            EventDispatcher::new(EventRegistry::storefront()).on(
+                |_: TenantEvent<EventEmailUpdatedV1>| async {
+                    Err(HexagonalError {
+                        error: error::HexagonalErrorCode::AdaptorError,
+                        message: "test".to_string(),
+                        trace: "test".to_string(),
+                    })
+                },
+            ),
+        );
+        let event = EventEmailUpdatedV1::new("jdoe".to_string(), "jdoe@example.com".to_string());
+        assert_eq!(bus.emit(&event).await.unwrap_err().message, "test");
+    }
+}
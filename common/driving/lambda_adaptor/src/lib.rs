@@ -12,3 +12,8 @@ macro_rules! common_lambda_adaptor {
         println!("Starting lambda_driving_adaptor");
     };
 }
+
+pub mod dynamodb_stream;
+pub mod eventbridge;
+pub mod http_with_events;
+pub mod in_process_bus;
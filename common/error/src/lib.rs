@@ -12,6 +12,8 @@ pub enum HexagonalErrorCode {
     BadInput,
     #[serde(rename = "AdaptorError")]
     AdaptorError,
+    #[serde(rename = "PreconditionFailed")]
+    PreconditionFailed,
     #[serde(rename = "Unkown")]
     Unkown,
 }
@@ -41,6 +43,7 @@ impl HexagonalErrorCode {
             HexagonalErrorCode::Conflict => http::StatusCode::CONFLICT,
             HexagonalErrorCode::BadInput => http::StatusCode::BAD_REQUEST,
             HexagonalErrorCode::AdaptorError => http::StatusCode::INTERNAL_SERVER_ERROR,
+            HexagonalErrorCode::PreconditionFailed => http::StatusCode::PRECONDITION_FAILED,
             HexagonalErrorCode::Unkown => http::StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -0,0 +1,36 @@
+module "cart_events" {
+    source = "../lambda_event_common"
+    app_name = var.app_name
+    lambda_name = "CartEventsLambda"
+    additional_policy_arns = [var.dynamo_policy_arn, var.event_bus_policy_arn]
+    bootstrap_folder_name = "cart_events"
+    dynamo_table_name = var.dynamo_table_name
+    cursor_signing_key = var.cursor_signing_key
+    architectures = var.architectures
+    eventbridge_rule_arn = aws_cloudwatch_event_rule.cart_events_rule.arn
+    env_vars = {
+        "EVENT_BUS_NAME" = var.event_bus_arn
+    }
+}
+
+resource "aws_cloudwatch_event_rule" "cart_events_rule" {
+    name        = "${var.app_name}-cart_events_rule"
+    description = "Capture user and product delete events in order to clear and remove from carts"
+
+    event_bus_name = var.event_bus_arn
+
+    # Keep in step with the handlers cart_events registers
+    event_pattern = jsonencode({
+        detail-type = [
+            "user_deleted",
+            "product_deleted"
+        ]
+    })
+}
+
+resource "aws_cloudwatch_event_target" "cart_events_target" {
+    rule      = aws_cloudwatch_event_rule.cart_events_rule.name
+    event_bus_name = var.event_bus_arn
+    arn       = module.cart_events.lambda_arn
+    target_id = "${var.app_name}-cart_events_target"
+}
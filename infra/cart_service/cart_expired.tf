@@ -0,0 +1,31 @@
+module "cart_expired_lambda" {
+    source = "../lambda_stream_common"
+    app_name = var.app_name
+    lambda_name = "CartExpiredLambda"
+    additional_policy_arns = [var.dynamo_policy_arn, var.event_bus_policy_arn]
+    bootstrap_folder_name = "cart_expired"
+    dynamo_table_name = var.dynamo_table_name
+    cursor_signing_key = var.cursor_signing_key
+    architectures = var.architectures
+    stream_arn = var.dynamo_stream_arn
+    # only cart items removed by the TTL, not those removed by users
+    filter_patterns = [
+        jsonencode({
+            eventName = ["REMOVE"]
+            userIdentity = {
+                type = ["Service"]
+                principalId = ["dynamodb.amazonaws.com"]
+            }
+            dynamodb = {
+                Keys = {
+                    Pkey = {
+                        S = [{ prefix = "CART#USER#" }]
+                    }
+                }
+            }
+        })
+    ]
+    env_vars = {
+        "EVENT_BUS_NAME" = var.event_bus_arn
+    }
+}
@@ -5,6 +5,7 @@ module "cart_get_lambda" {
     additional_policy_arns = [var.dynamo_policy_arn, var.event_bus_policy_arn]
     bootstrap_folder_name = "cart_get"
     dynamo_table_name = var.dynamo_table_name
+    cursor_signing_key = var.cursor_signing_key
     architectures = var.architectures
     api_gateway_execution_arn = var.api_gateway_execution_arn
     env_vars = {
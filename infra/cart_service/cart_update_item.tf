@@ -5,9 +5,11 @@ module "cart_update_item_lambda" {
     additional_policy_arns = [var.dynamo_policy_arn, var.event_bus_policy_arn]
     bootstrap_folder_name = "cart_update_item"
     dynamo_table_name = var.dynamo_table_name
+    cursor_signing_key = var.cursor_signing_key
     architectures = var.architectures
     api_gateway_execution_arn = var.api_gateway_execution_arn
     env_vars = {
         "EVENT_BUS_NAME" = var.event_bus_arn
+        "CART_EXPIRY_SECONDS" = var.cart_expiry_seconds
     }
 }
\ No newline at end of file
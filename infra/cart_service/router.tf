@@ -16,6 +16,15 @@ locals {
                 }
             }
         },
+        "/cart/{username}/history" = {
+            "get" = {
+                "x-amazon-apigateway-integration" = {
+                    "httpMethod" = "POST"
+                    "type" = "aws_proxy"
+                    "uri" = "arn:aws:apigateway:ap-southeast-2:lambda:path/2015-03-31/functions/${module.cart_history_lambda.lambda_arn}/invocations"
+                }
+            }
+        }
         "/cart/{username}/item" = {
             "post" = {
                 "x-amazon-apigateway-integration" = {
@@ -3,11 +3,27 @@ variable "dynamo_table_name" {
     nullable = false
 }
 
+variable "cursor_signing_key" {
+    type = string
+    nullable = false
+    sensitive = true
+}
+
 variable "dynamo_policy_arn" {
     type = string
     nullable = false
 }
 
+variable "dynamo_stream_arn" {
+    type = string
+    nullable = false
+}
+
+variable "cart_expiry_seconds" {
+    type = number
+    nullable = false
+}
+
 variable "app_name" {
     type = string
     nullable = false
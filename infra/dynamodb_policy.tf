@@ -46,4 +46,21 @@ data "aws_iam_policy_document" "dynamodb_single_table_access_policy_document" {
             "${aws_dynamodb_table.dynamodb_single_table.arn}/index/*"
         ]
     }
-}
\ No newline at end of file
+
+    statement {
+        sid = "AllowDynamoDBStreamOperations"
+
+        effect = "Allow"
+
+        actions = [
+            "dynamodb:DescribeStream",
+            "dynamodb:GetRecords",
+            "dynamodb:GetShardIterator",
+            "dynamodb:ListStreams"
+        ]
+
+        resources = [
+            "${aws_dynamodb_table.dynamodb_single_table.arn}/stream/*"
+        ]
+    }
+}
@@ -4,6 +4,10 @@ resource "aws_dynamodb_table" "dynamodb_single_table" {
     hash_key       = "Pkey"
     range_key      = "Skey"
 
+    # Outbox items are relayed to the event bus from the stream, expired carts need the old image
+    stream_enabled   = true
+    stream_view_type = "NEW_AND_OLD_IMAGES"
+
     attribute {
         name = "Pkey"
         type = "S"
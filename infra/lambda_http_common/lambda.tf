@@ -40,6 +40,7 @@ resource "aws_lambda_function" "lambda" {
       var.env_vars,
       {
         DYNAMO_TABLE_NAME = var.dynamo_table_name
+        CURSOR_SIGNING_KEY = var.cursor_signing_key
       }
     )
   }
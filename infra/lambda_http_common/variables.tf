@@ -24,6 +24,12 @@ variable "dynamo_table_name" {
     nullable = false
 }
 
+variable "cursor_signing_key" {
+    type = string
+    nullable = false
+    sensitive = true
+}
+
 variable "env_vars" {
     type = map(string)
     default = {}
@@ -0,0 +1,65 @@
+resource "aws_iam_role" "lambda_role" {
+    name               = "${var.app_name}-${var.lambda_name}"
+    assume_role_policy = data.aws_iam_policy_document.lambda_assume_role.json
+}
+
+resource "aws_iam_role_policy_attachment" "lambda_role_policy_attachment" {
+    count       = length(var.additional_policy_arns)
+    role        = aws_iam_role.lambda_role.name
+    policy_arn  = var.additional_policy_arns[count.index]
+}
+
+resource "aws_iam_role_policy_attachment" "basic_execution_role_policy_attachment" {
+    role        = aws_iam_role.lambda_role.name
+    policy_arn  = "arn:aws:iam::aws:policy/service-role/AWSLambdaBasicExecutionRole"
+}
+
+data "archive_file" "lambda_archive" {
+  type        = "zip"
+  source_file = "${path.module}/../../target/lambda/${var.bootstrap_folder_name}/bootstrap"
+  output_path = "${path.module}/../../target/archive/${var.bootstrap_folder_name}.zip"
+}
+
+resource "aws_lambda_function" "lambda" {
+  filename      = data.archive_file.lambda_archive.output_path
+  function_name = "${var.app_name}-${var.lambda_name}"
+  role          = aws_iam_role.lambda_role.arn
+
+  handler = "bootstrap"
+
+  source_code_hash = data.archive_file.lambda_archive.output_base64sha256
+
+  runtime = "provided.al2023"
+
+  architectures = var.architectures
+
+  memory_size = 1769
+
+  environment {
+    variables = merge(
+      var.env_vars,
+      {
+        DYNAMO_TABLE_NAME = var.dynamo_table_name
+        CURSOR_SIGNING_KEY = var.cursor_signing_key
+      }
+    )
+  }
+}
+
+resource "aws_lambda_event_source_mapping" "stream_mapping" {
+  event_source_arn  = var.stream_arn
+  function_name     = aws_lambda_function.lambda.arn
+  starting_position = "TRIM_HORIZON"
+
+  dynamic "filter_criteria" {
+    for_each = length(var.filter_patterns) > 0 ? [1] : []
+    content {
+      dynamic "filter" {
+        for_each = var.filter_patterns
+        content {
+          pattern = filter.value
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,7 @@
+output "lambda_arn" {
+    value = aws_lambda_function.lambda.arn
+}
+
+output "lambda_name" {
+    value = aws_lambda_function.lambda.function_name
+}
\ No newline at end of file
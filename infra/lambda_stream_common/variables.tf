@@ -0,0 +1,53 @@
+variable "app_name" {
+    type = string
+    nullable = false
+}
+
+variable "lambda_name" {
+    type = string
+    nullable = false
+}
+
+variable "additional_policy_arns" {
+    type = list(string)
+    default = []
+    nullable = false
+}
+
+variable "bootstrap_folder_name" {
+    type = string
+    nullable = false
+}
+
+variable "dynamo_table_name" {
+    type = string
+    nullable = false
+}
+
+variable "cursor_signing_key" {
+    type = string
+    nullable = false
+    sensitive = true
+}
+
+variable "env_vars" {
+    type = map(string)
+    default = {}
+    nullable = false
+}
+
+variable "architectures" {
+    type = list(string)
+    nullable = false
+}
+
+variable "stream_arn" {
+    type = string
+    nullable = false
+}
+
+variable "filter_patterns" {
+    type = list(string)
+    default = []
+    nullable = false
+}
@@ -2,6 +2,7 @@ module "user_service" {
     source = "./user_service"
     app_name = local.app_name
     dynamo_table_name = aws_dynamodb_table.dynamodb_single_table.name
+    cursor_signing_key = random_password.cursor_signing_key.result
     dynamo_policy_arn = aws_iam_policy.dynamodb_single_table_access_policy.arn
     architectures = var.architectures
     api_gateway_id = aws_api_gateway_rest_api.main_api.id
@@ -14,6 +15,7 @@ module "product_service" {
     source = "./product_service"
     app_name = local.app_name
     dynamo_table_name = aws_dynamodb_table.dynamodb_single_table.name
+    cursor_signing_key = random_password.cursor_signing_key.result
     dynamo_policy_arn = aws_iam_policy.dynamodb_single_table_access_policy.arn
     architectures = var.architectures
     api_gateway_id = aws_api_gateway_rest_api.main_api.id
@@ -26,10 +28,25 @@ module "cart_service" {
     source = "./cart_service"
     app_name = local.app_name
     dynamo_table_name = aws_dynamodb_table.dynamodb_single_table.name
+    cursor_signing_key = random_password.cursor_signing_key.result
     dynamo_policy_arn = aws_iam_policy.dynamodb_single_table_access_policy.arn
+    dynamo_stream_arn = aws_dynamodb_table.dynamodb_single_table.stream_arn
+    cart_expiry_seconds = var.cart_expiry_seconds
     architectures = var.architectures
     api_gateway_id = aws_api_gateway_rest_api.main_api.id
     api_gateway_execution_arn = "${aws_api_gateway_rest_api.main_api.execution_arn}/*"
     event_bus_arn = aws_cloudwatch_event_bus.core_event_bus.arn
     event_bus_policy_arn = aws_iam_policy.event_bus_policy.arn
-}
\ No newline at end of file
+}
+
+module "outbox_service" {
+    source = "./outbox_service"
+    app_name = local.app_name
+    dynamo_table_name = aws_dynamodb_table.dynamodb_single_table.name
+    cursor_signing_key = random_password.cursor_signing_key.result
+    dynamo_policy_arn = aws_iam_policy.dynamodb_single_table_access_policy.arn
+    dynamo_stream_arn = aws_dynamodb_table.dynamodb_single_table.stream_arn
+    architectures = var.architectures
+    event_bus_arn = aws_cloudwatch_event_bus.core_event_bus.arn
+    event_bus_policy_arn = aws_iam_policy.event_bus_policy.arn
+}
@@ -0,0 +1,27 @@
+module "outbox_relay" {
+    source = "../lambda_stream_common"
+    app_name = var.app_name
+    lambda_name = "OutboxRelayLambda"
+    additional_policy_arns = [var.dynamo_policy_arn, var.event_bus_policy_arn]
+    bootstrap_folder_name = "outbox_relay"
+    dynamo_table_name = var.dynamo_table_name
+    cursor_signing_key = var.cursor_signing_key
+    architectures = var.architectures
+    stream_arn = var.dynamo_stream_arn
+    # only newly written outbox items, marking them sent also shows up on the stream
+    filter_patterns = [
+        jsonencode({
+            eventName = ["INSERT"]
+            dynamodb = {
+                Keys = {
+                    Pkey = {
+                        S = [{ prefix = "OUTBOX#" }]
+                    }
+                }
+            }
+        })
+    ]
+    env_vars = {
+        "EVENT_BUS_NAME" = var.event_bus_arn
+    }
+}
@@ -0,0 +1,40 @@
+variable "dynamo_table_name" {
+    type = string
+    nullable = false
+}
+
+variable "cursor_signing_key" {
+    type = string
+    nullable = false
+    sensitive = true
+}
+
+variable "dynamo_policy_arn" {
+    type = string
+    nullable = false
+}
+
+variable "dynamo_stream_arn" {
+    type = string
+    nullable = false
+}
+
+variable "app_name" {
+    type = string
+    nullable = false
+}
+
+variable "architectures" {
+    type = list(string)
+    nullable = false
+}
+
+variable "event_bus_arn" {
+    type = string
+    nullable = false
+}
+
+variable "event_bus_policy_arn" {
+    type = string
+    nullable = false
+}
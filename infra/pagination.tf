@@ -0,0 +1,5 @@
+# Signs pagination cursors, shared by every function so a cursor from one invocation works in the next
+resource "random_password" "cursor_signing_key" {
+    length  = 64
+    special = false
+}
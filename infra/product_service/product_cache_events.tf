@@ -0,0 +1,46 @@
+resource "aws_cloudwatch_event_rule" "product_cache_rule" {
+    name        = "${var.app_name}-product_cache_rule"
+    description = "Capture product update and delete events in order to evict them from the product read caches"
+
+    event_bus_name = var.event_bus_arn
+
+    # Keep in step with the handlers product_cache_events registers
+    event_pattern = jsonencode({
+        detail-type = [
+            "product_updated",
+            "product_soft_deleted",
+            "product_deleted"
+        ]
+    })
+}
+
+# The caches live in the functions that read through them, so they are the targets
+resource "aws_cloudwatch_event_target" "product_get_cache_target" {
+    rule      = aws_cloudwatch_event_rule.product_cache_rule.name
+    event_bus_name = var.event_bus_arn
+    arn       = module.product_get_lambda.lambda_arn
+    target_id = "${var.app_name}-product_get_cache_target"
+}
+
+resource "aws_cloudwatch_event_target" "product_batch_get_cache_target" {
+    rule      = aws_cloudwatch_event_rule.product_cache_rule.name
+    event_bus_name = var.event_bus_arn
+    arn       = module.product_batch_get_lambda.lambda_arn
+    target_id = "${var.app_name}-product_batch_get_cache_target"
+}
+
+resource "aws_lambda_permission" "product_get_allow_eventbridge" {
+    statement_id  = "AllowExecutionFromEventBridge"
+    action        = "lambda:InvokeFunction"
+    function_name = module.product_get_lambda.lambda_name
+    principal     = "events.amazonaws.com"
+    source_arn    = aws_cloudwatch_event_rule.product_cache_rule.arn
+}
+
+resource "aws_lambda_permission" "product_batch_get_allow_eventbridge" {
+    statement_id  = "AllowExecutionFromEventBridge"
+    action        = "lambda:InvokeFunction"
+    function_name = module.product_batch_get_lambda.lambda_name
+    principal     = "events.amazonaws.com"
+    source_arn    = aws_cloudwatch_event_rule.product_cache_rule.arn
+}
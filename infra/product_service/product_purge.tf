@@ -0,0 +1,28 @@
+module "product_purge_lambda" {
+    source = "../lambda_event_common"
+    app_name = var.app_name
+    lambda_name = "ProductPurgeLambda"
+    additional_policy_arns = [var.dynamo_policy_arn, var.event_bus_policy_arn]
+    bootstrap_folder_name = "product_purge"
+    dynamo_table_name = var.dynamo_table_name
+    cursor_signing_key = var.cursor_signing_key
+    architectures = var.architectures
+    eventbridge_rule_arn = aws_cloudwatch_event_rule.product_purge_schedule_rule.arn
+    env_vars = {
+        "EVENT_BUS_NAME" = var.event_bus_arn
+    }
+}
+
+# Scheduled rules only run on the default bus
+resource "aws_cloudwatch_event_rule" "product_purge_schedule_rule" {
+    name        = "${var.app_name}-product_purge_schedule_rule"
+    description = "Purge deleted products once their retention window has passed"
+
+    schedule_expression = "rate(1 hour)"
+}
+
+resource "aws_cloudwatch_event_target" "product_purge_schedule_target" {
+    rule      = aws_cloudwatch_event_rule.product_purge_schedule_rule.name
+    arn       = module.product_purge_lambda.lambda_arn
+    target_id = "${var.app_name}-product_purge_schedule_target"
+}
\ No newline at end of file
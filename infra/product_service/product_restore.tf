@@ -0,0 +1,14 @@
+module "product_restore_lambda" {
+    source = "../lambda_http_common"
+    app_name = var.app_name
+    lambda_name = "ProductRestoreLambda"
+    additional_policy_arns = [var.dynamo_policy_arn, var.event_bus_policy_arn]
+    bootstrap_folder_name = "product_restore"
+    dynamo_table_name = var.dynamo_table_name
+    cursor_signing_key = var.cursor_signing_key
+    architectures = var.architectures
+    api_gateway_execution_arn = var.api_gateway_execution_arn
+    env_vars = {
+        "EVENT_BUS_NAME" = var.event_bus_arn
+    }
+}
\ No newline at end of file
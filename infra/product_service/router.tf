@@ -48,5 +48,29 @@ locals {
                 }
             }
         }
+        "/product/{id}/restore" = {
+            "post" = {
+                "x-amazon-apigateway-integration" = {
+                    "httpMethod" = "POST"
+                    "type" = "aws_proxy"
+                    "uri" = "arn:aws:apigateway:ap-southeast-2:lambda:path/2015-03-31/functions/${module.product_restore_lambda.lambda_arn}/invocations"
+                    "requestParameters": {
+                        "integration.request.path.id": "method.request.path.id"
+                    }
+                }
+            }
+        }
+        "/product/{id}/history" = {
+            "get" = {
+                "x-amazon-apigateway-integration" = {
+                    "httpMethod" = "POST"
+                    "type" = "aws_proxy"
+                    "uri" = "arn:aws:apigateway:ap-southeast-2:lambda:path/2015-03-31/functions/${module.product_history_lambda.lambda_arn}/invocations"
+                    "requestParameters": {
+                        "integration.request.path.id": "method.request.path.id"
+                    }
+                }
+            }
+        }
     }
 }
\ No newline at end of file
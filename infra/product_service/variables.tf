@@ -3,6 +3,12 @@ variable "dynamo_table_name" {
     nullable = false
 }
 
+variable "cursor_signing_key" {
+    type = string
+    nullable = false
+    sensitive = true
+}
+
 variable "dynamo_policy_arn" {
     type = string
     nullable = false
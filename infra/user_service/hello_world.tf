@@ -5,6 +5,7 @@ module "hello_world_lambda" {
     additional_policy_arns = []
     bootstrap_folder_name = "user_hello_world"
     dynamo_table_name = var.dynamo_table_name
+    cursor_signing_key = var.cursor_signing_key
     architectures = var.architectures
     api_gateway_execution_arn = var.api_gateway_execution_arn
 }
\ No newline at end of file
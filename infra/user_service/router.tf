@@ -51,5 +51,23 @@ locals {
                 }
             }
         }
+        "/user/{username}/restore" = {
+            "post" = {
+                "x-amazon-apigateway-integration" = {
+                    "httpMethod" = "POST"
+                    "type" = "aws_proxy"
+                    "uri" = "arn:aws:apigateway:ap-southeast-2:lambda:path/2015-03-31/functions/${module.user_restore_lambda.lambda_arn}/invocations"
+                }
+            }
+        }
+        "/user/{username}/history" = {
+            "get" = {
+                "x-amazon-apigateway-integration" = {
+                    "httpMethod" = "POST"
+                    "type" = "aws_proxy"
+                    "uri" = "arn:aws:apigateway:ap-southeast-2:lambda:path/2015-03-31/functions/${module.user_history_lambda.lambda_arn}/invocations"
+                }
+            }
+        }
     }
 }
\ No newline at end of file
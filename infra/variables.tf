@@ -1,3 +1,10 @@
+# How long a cart item lives after its last change before the TTL removes it
+variable "cart_expiry_seconds" {
+    type = number
+    default = 2592000
+    nullable = false
+}
+
 variable "architectures" {
     type = list(string)
     default = ["arm64"]
@@ -1,14 +1,13 @@
-use crate::domain::cart_add_item_core;
+use super::domain::cart_add_item_core;
 
 use error::HexagonalError;
-use eventing::EventingPort;
 use http::{Error, Response, StatusCode};
 use http_port_tools::http_payload_decoder;
-use http_port_tools::port_objects::HttpPortRequest;
+use http_port_tools::port_objects::{etag, HttpPortRequest};
 use jsonschema::{Draft, JSONSchema};
 use lazy_static::lazy_static;
 use models::{
-    default_time,
+    default_time, initial_version,
     models::cart::{CartItem, CartRepositoryPort},
 };
 use serde::{Deserialize, Serialize};
@@ -46,9 +45,8 @@ pub struct CartAddItemBody {
     pub quantity: u32,
 }
 
-pub async fn cart_create_post_http_port<T1: CartRepositoryPort, T2: EventingPort>(
+pub async fn cart_create_post_http_port<T1: CartRepositoryPort>(
     cart_repository_port: &T1,
-    eventing_port: &T2,
     http_request: HttpPortRequest,
 ) -> Result<Response<String>, Error> {
     let username = match http_request.path_parameters.first("username") {
@@ -62,18 +60,20 @@ pub async fn cart_create_post_http_port<T1: CartRepositoryPort, T2: EventingPort
             .compile_to_http_response())
         }
     };
+    let principal = http_request.principal();
     let payload = http_request.payload;
     let cart_body = http_payload_decoder!(CartAddItemBody, CART_ADD_ITEM_SCHEMA, payload);
     match cart_add_item_core(
         cart_repository_port,
-        eventing_port,
         CartItem {
             product_id: cart_body.product_id,
             user_id: username.to_string(),
             quantity: cart_body.quantity,
             created_at: default_time(),
             updated_at: default_time(),
+            version: initial_version(),
         },
+        &principal,
     )
     .await
     {
@@ -81,6 +81,7 @@ pub async fn cart_create_post_http_port<T1: CartRepositoryPort, T2: EventingPort
             let resp = Response::builder()
                 .status(StatusCode::CREATED)
                 .header("content-type", "application/json")
+                .header("etag", etag(result.version))
                 .body(serde_json::to_string(&result).unwrap());
             Ok(resp.unwrap())
         }
@@ -1,18 +1,18 @@
-use crate::domain::cart_clear_delete_core;
-use eventing::{events::user::user_deleted::EventUserDeletedV1, EventingPort};
+use super::domain::cart_clear_delete_core;
+use error::HexagonalError;
+use eventing::events::event_emmiter::SerialisableEvent;
+use eventing::events::user::user_deleted::EventUserDeletedV1;
 use models::models::cart::CartRepositoryPort;
 
-pub async fn cart_clear_user_deleted_event_port<T1: CartRepositoryPort, T2: EventingPort>(
+// A failure fails the delivery, so EventBridge retries it
+pub async fn cart_clear_user_deleted_event_port<T1: CartRepositoryPort>(
     cart_repository_port: &T1,
-    eventing_port: &T2,
     event: EventUserDeletedV1,
-) -> Result<(), ()> {
+) -> Result<(), HexagonalError> {
+    // the cart's history names the event as what removed the items
+    let principal = format!("event:{}", event.get_event_type());
     let username = event.user.username;
-    match cart_clear_delete_core(cart_repository_port, eventing_port, username.to_string()).await {
-        Ok(_) => Ok(()),
-        Err(err) => {
-            println!("Error: {}", err);
-            Ok(())
-        }
-    }
+    cart_clear_delete_core(cart_repository_port, username.to_string(), &principal)
+        .await
+        .map(|_| ())
 }
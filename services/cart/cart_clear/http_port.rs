@@ -1,14 +1,12 @@
-use crate::domain::cart_clear_delete_core;
+use super::domain::cart_clear_delete_core;
 
-use eventing::EventingPort;
 use http::{Error, Response, StatusCode};
 use http_port_tools::port_objects::HttpPortRequest;
 use models::models::cart::CartRepositoryPort;
 use serde_json::json;
 
-pub async fn cart_create_post_http_port<T1: CartRepositoryPort, T2: EventingPort>(
+pub async fn cart_create_post_http_port<T1: CartRepositoryPort>(
     cart_repository_port: &T1,
-    eventing_port: &T2,
     http_request: HttpPortRequest,
 ) -> Result<Response<String>, Error> {
     let email = match http_request.path_parameters.first("username") {
@@ -28,7 +26,13 @@ pub async fn cart_create_post_http_port<T1: CartRepositoryPort, T2: EventingPort
             return Ok(resp.unwrap());
         }
     };
-    match cart_clear_delete_core(cart_repository_port, eventing_port, email.to_string()).await {
+    match cart_clear_delete_core(
+        cart_repository_port,
+        email.to_string(),
+        &http_request.principal(),
+    )
+    .await
+    {
         Ok(result) => {
             let resp = Response::builder()
                 .status(StatusCode::OK)
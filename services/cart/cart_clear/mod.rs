@@ -0,0 +1,3 @@
+mod domain;
+pub mod event_port;
+pub mod http_port;
@@ -0,0 +1,112 @@
+use error::HexagonalError;
+use eventing::events::event_envelope::EventEnvelope;
+use eventing::events::event_registry::EventRegistry;
+use eventing::events::event_wrapper::TenantEvent;
+use eventing::events::product::product_deleted::EventProductDeletedV1;
+use eventing::events::user::user_deleted::EventUserDeletedV1;
+use lambda_adaptor::eventbridge::EventDispatcher;
+use models::models::cart::CartRepositoryAdaptor;
+use persistance_repository::{DynamoDBSingleTableRepository, Tenant};
+
+use super::cart_clear_event_port::cart_clear_user_deleted_event_port;
+use super::cart_product_global_delete::event_port::cart_product_deleted_event_port;
+
+// Handled within the tenant the event was raised in, and as part of its chain
+fn event_repository(
+    dynamo_db_repository: &DynamoDBSingleTableRepository,
+    tenant: Option<&str>,
+    envelope: &EventEnvelope,
+) -> Result<DynamoDBSingleTableRepository, HexagonalError> {
+    Ok(dynamo_db_repository
+        .for_tenant(Tenant::from_request(tenant)?)
+        .with_correlation(envelope.caused()))
+}
+
+// Every event the cart service consumes, the cart_events rule in infra matches the same types
+pub fn cart_event_dispatcher(
+    dynamo_db_repository: &DynamoDBSingleTableRepository,
+) -> EventDispatcher<'_> {
+    EventDispatcher::new(EventRegistry::storefront())
+        .on(move |event: TenantEvent<EventUserDeletedV1>| async move {
+            let tenant_repository = event_repository(
+                dynamo_db_repository,
+                event.tenant.as_deref(),
+                &event.event.envelope,
+            )?;
+            let cart_repository = CartRepositoryAdaptor::new(&tenant_repository);
+            cart_clear_user_deleted_event_port(&cart_repository, event.event).await
+        })
+        .on(
+            move |event: TenantEvent<EventProductDeletedV1>| async move {
+                let tenant_repository = event_repository(
+                    dynamo_db_repository,
+                    event.tenant.as_deref(),
+                    &event.event.envelope,
+                )?;
+                let cart_repository = CartRepositoryAdaptor::new(&tenant_repository);
+                cart_product_deleted_event_port(&cart_repository, event.event).await
+            },
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use app_config::AppConfig;
+    use eventing::events::event_emmiter::SerialisableEvent;
+    use eventing::EventingPort;
+    use in_memory_persistance_repository::InMemorySingleTable;
+    use lambda_adaptor::in_process_bus::InProcessEventBus;
+    use models::default_time;
+    use models::models::cart::{CartItem, CartRepositoryPort};
+    use models::models::outbox::OutboxItem;
+    use models::models::user::User;
+    use persistance_repository::PageRequest;
+
+    use super::*;
+
+    fn test_event(_: &CartItem) -> OutboxItem {
+        OutboxItem::new("test".to_string(), 1, "{}".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_user_deleted_clears_their_cart_in_its_tenant() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let tenant_repository = repository.for_tenant(Tenant::new("acme").unwrap());
+        let carts = CartRepositoryAdaptor::new(&tenant_repository);
+        carts
+            .cart_add_item(
+                &CartItem::new("1".to_string(), "jdoe".to_string(), 1),
+                "tester",
+                test_event,
+            )
+            .await
+            .unwrap();
+
+        // as deployed, see cart_events.tf
+        let bus = InProcessEventBus::new(&AppConfig::default()).with_rule(
+            &["user_deleted", "product_deleted"],
+            cart_event_dispatcher(&repository),
+        );
+        let user = User {
+            first: "Jo".to_string(),
+            last: "Doe".to_string(),
+            email: "jdoe@example.com".to_string(),
+            username: "jdoe".to_string(),
+            created_at: default_time(),
+            updated_at: default_time(),
+            version: 1,
+        };
+        // what the outbox relay publishes for a user purged in acme
+        let item = OutboxItem {
+            tenant: "acme".to_string(),
+            ..EventUserDeletedV1::new(user).to_outbox_item()
+        };
+        bus.emit(&item).await.unwrap();
+
+        let cart = carts
+            .cart_get_by_user_id(&"jdoe".to_string(), &PageRequest::default())
+            .await
+            .unwrap();
+        assert!(cart.items.is_empty());
+    }
+}
@@ -0,0 +1,52 @@
+// Every event the cart service consumes, routed to its event port by the dispatcher
+// Only the event side of cart_clear is included, its http port is deployed on its own. The port
+// finds its domain as super::domain
+#[path = "../cart_clear/event_port.rs"]
+mod cart_clear_event_port;
+#[path = "../cart_product_global_delete/mod.rs"]
+mod cart_product_global_delete;
+#[path = "../cart_clear/domain.rs"]
+mod domain;
+mod event_dispatcher;
+
+use crate::event_dispatcher::cart_event_dispatcher;
+
+use aws_lambda_events::cloudwatch_events::CloudWatchEvent;
+use lambda_adaptor::common_lambda_adaptor;
+use lambda_adaptor::eventbridge::EventDispatcher;
+use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+
+async fn eventbridge_lambda_driving_adaptor(
+    event_dispatcher: &EventDispatcher<'_>,
+    event: LambdaEvent<CloudWatchEvent<serde_json::Value>>,
+) -> Result<(), Error> {
+    event_dispatcher
+        .dispatch(event.payload)
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    // Common snippit from all lambda functions
+    common_lambda_adaptor!();
+    {
+        // Provision required repositories once in the main function
+        let config = app_config::AppConfig::load()?;
+        let sdk_credential_meta_repository =
+            sdk_credential_meta_repository::SdkCredentialsMetaRepository::new().await;
+        let dynamo_db_repository = persistance_repository::DynamoDBSingleTableRepository::new(
+            &sdk_credential_meta_repository,
+            &config,
+        )?
+        .with_operation("cart_events");
+
+        let event_dispatcher = cart_event_dispatcher(&dynamo_db_repository);
+
+        run(service_fn(|event| {
+            eventbridge_lambda_driving_adaptor(&event_dispatcher, event)
+        }))
+        .await
+    }
+}
@@ -0,0 +1,5 @@
+// Loaded by the local server, next to cart_clear whose event port it shares
+use crate::cart_clear::event_port as cart_clear_event_port;
+#[path = "../cart_product_global_delete/mod.rs"]
+mod cart_product_global_delete;
+pub mod event_dispatcher;
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+
+use error::HexagonalError;
+use eventing::events::cart::cart_expired::EventCartExpiredV1;
+use eventing::EventingPort;
+use models::models::cart::CartItem;
+use persistance_repository::Tenant;
+
+// One event per user of each tenant, carrying every item of theirs that expired in the batch
+pub async fn cart_expired_core<T1: EventingPort>(
+    eventing_port: &T1,
+    expired_items: Vec<(Tenant, CartItem)>,
+) -> Result<(), HexagonalError> {
+    let mut carts: BTreeMap<(Option<String>, String), (Tenant, Vec<CartItem>)> = BTreeMap::new();
+    for (tenant, item) in expired_items {
+        let cart = (tenant.id().map(|id| id.to_string()), item.user_id.clone());
+        carts
+            .entry(cart)
+            .or_insert_with(|| (tenant, Vec::new()))
+            .1
+            .push(item);
+    }
+    let events: Vec<EventCartExpiredV1> = carts
+        .into_iter()
+        .map(|((_, user_id), (tenant, cart_items))| {
+            EventCartExpiredV1::new(user_id, cart_items).with_tenant(&tenant)
+        })
+        .collect();
+    // the items are already gone from the table, so there is nothing to retry from
+    eventing_port
+        .emit_batch(&events)
+        .await
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use eventing::events::event_emmiter::SerialisableEvent;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cart_expired_core_emits_per_user() {
+        // Arrange
+        let mut eventing_port = eventing::MockEventingPort::new();
+        eventing_port
+            .expect_emit_batch::<EventCartExpiredV1>()
+            .withf(|events| {
+                events.len() == 3
+                    && events[0].user_id == "a"
+                    && events[0].tenant.is_none()
+                    && events[0].cart_items.len() == 2
+                    && events[1].user_id == "b"
+                    && events[1].get_event_type() == "cart_expired"
+                    // the same user id in another tenant is another cart
+                    && events[2].user_id == "a"
+                    && events[2].tenant == Some("acme".to_string())
+                    && events[2].cart_items.len() == 1
+            })
+            .times(1)
+            .returning(|events| events.iter().map(|_| Ok(())).collect());
+
+        // Act
+        let acme = Tenant::new("acme").unwrap();
+        let result = cart_expired_core(
+            &eventing_port,
+            vec![
+                (
+                    Tenant::default(),
+                    CartItem::new("1".to_string(), "a".to_string(), 1),
+                ),
+                (
+                    Tenant::default(),
+                    CartItem::new("1".to_string(), "b".to_string(), 1),
+                ),
+                (
+                    Tenant::default(),
+                    CartItem::new("2".to_string(), "a".to_string(), 3),
+                ),
+                (acme, CartItem::new("2".to_string(), "a".to_string(), 3)),
+            ],
+        )
+        .await;
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_cart_expired_core_reports_failed_events() {
+        // Arrange
+        let mut eventing_port = eventing::MockEventingPort::new();
+        eventing_port
+            .expect_emit_batch::<EventCartExpiredV1>()
+            .times(1)
+            .returning(|_| {
+                vec![
+                    Ok(()),
+                    Err(HexagonalError {
+                        error: error::HexagonalErrorCode::AdaptorError,
+                        message: "Unable to emit event: cart_expired".to_string(),
+                        trace: "ThrottlingException".to_string(),
+                    }),
+                ]
+            });
+
+        // Act
+        let result = cart_expired_core(
+            &eventing_port,
+            vec![
+                (
+                    Tenant::default(),
+                    CartItem::new("1".to_string(), "a".to_string(), 1),
+                ),
+                (
+                    Tenant::default(),
+                    CartItem::new("1".to_string(), "b".to_string(), 1),
+                ),
+            ],
+        )
+        .await;
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err().error,
+            error::HexagonalErrorCode::AdaptorError
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cart_expired_core_nothing_expired() {
+        // Arrange
+        let mut eventing_port = eventing::MockEventingPort::new();
+        eventing_port.expect_emit::<EventCartExpiredV1>().times(0);
+        eventing_port
+            .expect_emit_batch::<EventCartExpiredV1>()
+            .withf(|events| events.is_empty())
+            .returning(|_| Vec::new());
+
+        // Act
+        let result = cart_expired_core(&eventing_port, Vec::new()).await;
+
+        // Assert
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,61 @@
+mod domain;
+mod stream_port;
+
+use crate::stream_port::cart_expired_stream_port;
+
+use aws_lambda_events::dynamodb::{Event, EventRecord};
+use eventing::EventingPort;
+use lambda_adaptor::common_lambda_adaptor;
+use lambda_adaptor::dynamodb_stream::into_attr_map;
+use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+use metrics::{EmfMetricsAdaptor, MetricsPort};
+
+// TTL deletions are made by the DynamoDB service itself, anything else is a user removing an item
+fn is_ttl_removal(record: &EventRecord) -> bool {
+    record.event_name == "REMOVE"
+        && record.user_identity.as_ref().is_some_and(|identity| {
+            identity.type_ == "Service" && identity.principal_id == "dynamodb.amazonaws.com"
+        })
+}
+
+async fn dynamodb_stream_lambda_driving_adaptor<T1: EventingPort, T2: MetricsPort>(
+    eventing_port: &T1,
+    metrics_port: &T2,
+    event: LambdaEvent<Event>,
+) -> Result<(), Error> {
+    let expired_items = event
+        .payload
+        .records
+        .into_iter()
+        .filter(is_ttl_removal)
+        .map(|record| into_attr_map(record.change.old_image))
+        .collect();
+
+    // failing the whole batch makes Lambda retry it, which re-emits events already sent
+    cart_expired_stream_port(eventing_port, metrics_port, expired_items)
+        .await
+        .map_err(|err| {
+            println!("Error: {}", err);
+            Error::from(err.to_string())
+        })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    // Common snippit from all lambda functions
+    common_lambda_adaptor!();
+    {
+        // Provision required repositories once in the main function
+        let config = app_config::AppConfig::load()?;
+        let sdk_credential_meta_repository =
+            sdk_credential_meta_repository::SdkCredentialsMetaRepository::new().await;
+        let eventing_repository =
+            eventing::EventingRepository::new(&sdk_credential_meta_repository, &config)?;
+        let metrics_adaptor = EmfMetricsAdaptor::new(&config);
+
+        run(service_fn(|event| {
+            dynamodb_stream_lambda_driving_adaptor(&eventing_repository, &metrics_adaptor, event)
+        }))
+        .await
+    }
+}
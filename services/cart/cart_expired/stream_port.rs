@@ -0,0 +1,36 @@
+use std::collections::HashMap;
+
+use crate::domain::cart_expired_core;
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use error::HexagonalError;
+use eventing::EventingPort;
+use metrics::MetricsPort;
+use models::models::cart::CartItem;
+
+// Takes the old images of items the TTL removed, anything that is not a cart item is skipped.
+// Cart items that can't be read are logged, counted and dropped, they are already gone from the
+// table.
+pub async fn cart_expired_stream_port<T1: EventingPort, T2: MetricsPort>(
+    eventing_port: &T1,
+    metrics_port: &T2,
+    expired_items: Vec<HashMap<String, AttributeValue>>,
+) -> Result<(), HexagonalError> {
+    let items = expired_items
+        .into_iter()
+        .filter_map(CartItem::from_item)
+        .filter_map(|item| match item {
+            Ok(item) => Some(item),
+            Err(err) => {
+                tracing::warn!(
+                    key = err.key(),
+                    error = %err,
+                    "Skipping unreadable expired cart item"
+                );
+                metrics_port.record_skipped_item("cart_expired.decode");
+                None
+            }
+        })
+        .collect();
+    cart_expired_core(eventing_port, items).await
+}
@@ -1,11 +1,13 @@
 use models::models::cart::{CartItem, CartRepositoryPort};
+use persistance_repository::{Page, PageRequest};
 
 pub async fn cart_get_core<T1: CartRepositoryPort>(
     cart_repository_port: &T1,
     user_id: String,
-) -> Result<Vec<CartItem>, error::HexagonalError> {
+    page: &PageRequest,
+) -> Result<Page<CartItem>, error::HexagonalError> {
     let cart_result = cart_repository_port
-        .cart_get_by_user_id(&user_id.to_ascii_lowercase())
+        .cart_get_by_user_id(&user_id.to_ascii_lowercase(), page)
         .await;
 
     cart_result
@@ -28,16 +30,27 @@ mod tests {
             quantity: 1,
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         let result_cart_item = cart_item.clone();
 
         cart_repository_port
             .expect_cart_get_by_user_id()
-            .returning(move |_| Ok(vec![result_cart_item.clone()]));
+            .returning(move |_, _| {
+                Ok(Page {
+                    items: vec![result_cart_item.clone()],
+                    next_cursor: None,
+                })
+            });
 
         // Act
-        let result = cart_get_core(&cart_repository_port, cart_item.user_id).await;
+        let result = cart_get_core(
+            &cart_repository_port,
+            cart_item.user_id,
+            &PageRequest::default(),
+        )
+        .await;
 
         // Assert
         assert!(result.is_ok());
@@ -54,11 +67,12 @@ mod tests {
             quantity: 1,
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         cart_repository_port
             .expect_cart_get_by_user_id()
-            .returning(move |_| {
+            .returning(move |_, _| {
                 Err(error::HexagonalError {
                     error: error::HexagonalErrorCode::AdaptorError,
                     message: "test".to_string(),
@@ -67,7 +81,12 @@ mod tests {
             });
 
         // Act
-        let result = cart_get_core(&cart_repository_port, cart_item.user_id).await;
+        let result = cart_get_core(
+            &cart_repository_port,
+            cart_item.user_id,
+            &PageRequest::default(),
+        )
+        .await;
 
         // Assert
         assert!(result.is_err());
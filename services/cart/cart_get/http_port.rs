@@ -1,4 +1,4 @@
-use crate::domain::cart_get_core;
+use super::domain::cart_get_core;
 
 use error::HexagonalError;
 use http::{Error, Response, StatusCode};
@@ -20,7 +20,11 @@ pub async fn cart_get_get_http_port<T1: CartRepositoryPort>(
             .compile_to_http_response())
         }
     };
-    match cart_get_core(cart_repository_port, username.to_string()).await {
+    let page = match http_request.page_request() {
+        Ok(page) => page,
+        Err(err) => return Ok(err.compile_to_http_response()),
+    };
+    match cart_get_core(cart_repository_port, username.to_string(), &page).await {
         Ok(result) => {
             let resp = Response::builder()
                 .status(StatusCode::OK)
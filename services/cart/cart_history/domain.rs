@@ -0,0 +1,47 @@
+use error::HexagonalError;
+use models::models::cart::CartRepositoryPort;
+use models::models::history::HistoryRecord;
+use persistance_repository::{Page, PageRequest};
+
+pub async fn cart_history_core<T1: CartRepositoryPort>(
+    cart_repository_port: &T1,
+    user_id: String,
+    page: &PageRequest,
+) -> Result<Page<HistoryRecord>, HexagonalError> {
+    cart_repository_port
+        .cart_history_by_user_id(&user_id.to_ascii_lowercase(), page)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cart_history_core_lowercases_user_id() {
+        // Arrange
+        let mut cart_repository_port = models::models::cart::MockCartRepositoryPort::new();
+
+        cart_repository_port
+            .expect_cart_history_by_user_id()
+            .withf(|user_id, _| user_id == "jdoe")
+            .times(1)
+            .returning(|_, _| {
+                Ok(Page {
+                    items: vec![],
+                    next_cursor: None,
+                })
+            });
+
+        // Act
+        let result = cart_history_core(
+            &cart_repository_port,
+            "JDoe".to_string(),
+            &PageRequest::default(),
+        )
+        .await;
+
+        // Assert
+        assert!(result.unwrap().items.is_empty());
+    }
+}
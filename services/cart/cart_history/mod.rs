@@ -0,0 +1,2 @@
+mod domain;
+pub mod http_port;
@@ -3,9 +3,10 @@ use models::models::cart::CartRepositoryPort;
 pub async fn cart_product_delete_core<T1: CartRepositoryPort>(
     cart_repository_port: &T1,
     product_id: String,
+    principal: &str,
 ) -> Result<(), Vec<error::HexagonalError>> {
     let product_delete_result = cart_repository_port
-        .cart_global_remove_product(&product_id)
+        .cart_global_remove_product(&product_id, principal)
         .await;
 
     product_delete_result
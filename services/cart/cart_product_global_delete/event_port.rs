@@ -1,18 +1,74 @@
-use crate::domain::cart_product_delete_core;
+use super::domain::cart_product_delete_core;
 
+use error::HexagonalError;
+use eventing::events::event_emmiter::SerialisableEvent;
 use eventing::events::product::product_deleted::EventProductDeletedV1;
 use models::models::cart::CartRepositoryPort;
 
+// A failure fails the delivery, so EventBridge retries it. The removal carries on past a cart it
+// can't update, so the failure holds every error it ran into
 pub async fn cart_product_deleted_event_port<T1: CartRepositoryPort>(
     cart_repository_port: &T1,
     event: EventProductDeletedV1,
-) -> Result<(), ()> {
+) -> Result<(), HexagonalError> {
+    // the cart's history names the event as what removed the items
+    let principal = format!("event:{}", event.get_event_type());
     let product_id = event.product.id;
-    match cart_product_delete_core(cart_repository_port, product_id.to_string()).await {
-        Ok(_) => Ok(()),
-        Err(err) => {
-            err.iter().for_each(|e| println!("Error: {}", e));
-            Ok(())
-        }
+    cart_product_delete_core(cart_repository_port, product_id.to_string(), &principal)
+        .await
+        .map_err(|errors| HexagonalError {
+            error: error::HexagonalErrorCode::AdaptorError,
+            message: "Unable to remove product from carts".to_string(),
+            trace: errors
+                .iter()
+                .map(|e| format!("{}: {}", e.message, e.trace))
+                .collect::<Vec<String>>()
+                .join("; "),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use models::models::product::Product;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cart_product_deleted_event_port_keeps_every_error() {
+        // Arrange
+        let mut cart_repository_port = models::models::cart::MockCartRepositoryPort::new();
+        cart_repository_port
+            .expect_cart_global_remove_product()
+            .withf(|_, principal| principal == "event:product_deleted")
+            .times(1)
+            .returning(|_, _| {
+                Err(vec![
+                    HexagonalError {
+                        error: error::HexagonalErrorCode::AdaptorError,
+                        message: "Unable to get cart items".to_string(),
+                        trace: "ThrottlingException".to_string(),
+                    },
+                    HexagonalError {
+                        error: error::HexagonalErrorCode::AdaptorError,
+                        message: "Unable to remove product from carts".to_string(),
+                        trace: "ProvisionedThroughputExceededException".to_string(),
+                    },
+                ])
+            });
+
+        // Act
+        let product = Product::new("Widget".to_string(), 100, "A widget".to_string());
+        let result = cart_product_deleted_event_port(
+            &cart_repository_port,
+            EventProductDeletedV1::new(product),
+        )
+        .await;
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err().trace,
+            "Unable to get cart items: ThrottlingException; \
+             Unable to remove product from carts: ProvisionedThroughputExceededException"
+        );
     }
 }
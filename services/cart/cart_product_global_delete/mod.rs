@@ -0,0 +1,2 @@
+mod domain;
+pub mod event_port;
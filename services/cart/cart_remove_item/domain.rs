@@ -1,28 +1,29 @@
-use eventing::{events::cart::cart_items_removed::EventCartItemsRemovedV1, EventingPort};
+use eventing::events::{
+    cart::cart_items_removed::EventCartItemsRemovedV1, event_emmiter::SerialisableEvent,
+};
 use models::models::cart::{CartItem, CartRepositoryPort};
+use models::models::outbox::OutboxItem;
 
-pub async fn cart_remove_item_core<T1: CartRepositoryPort, T2: EventingPort>(
+fn cart_item_removed_event(cart_item: &CartItem) -> OutboxItem {
+    EventCartItemsRemovedV1::new(vec![cart_item.clone()]).to_outbox_item()
+}
+
+pub async fn cart_remove_item_core<T1: CartRepositoryPort>(
     cart_repository_port: &T1,
-    eventing_port: &T2,
     user_id: String,
     product_id: String,
+    expected_version: Option<u64>,
+    principal: &str,
 ) -> Result<CartItem, error::HexagonalError> {
-    let cart_item_result = cart_repository_port
-        .cart_remove_item(&user_id.to_ascii_lowercase(), &product_id)
-        .await;
-
-    if cart_item_result.is_ok() {
-        let event_result = eventing_port
-            .emit(&EventCartItemsRemovedV1::new(vec![cart_item_result
-                .clone()
-                .unwrap()]))
-            .await;
-        if event_result.is_err() {
-            return Err(event_result.unwrap_err());
-        }
-    }
-
-    cart_item_result
+    cart_repository_port
+        .cart_remove_item(
+            &user_id.to_ascii_lowercase(),
+            &product_id,
+            expected_version,
+            principal,
+            cart_item_removed_event,
+        )
+        .await
 }
 
 #[cfg(test)]
@@ -45,20 +46,23 @@ mod tests {
             quantity: 1,
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         cart_repository_port
             .expect_cart_remove_item()
-            .returning(move |_, _| Ok(result_cart_item.clone()));
-
-        let mut eventing_port = eventing::MockEventingPort::new();
-        eventing_port
-            .expect_emit::<EventCartItemsRemovedV1>()
-            .returning(move |_| Ok(()));
+            .times(1)
+            .returning(move |_, _, _, _, outbox_event| {
+                assert_eq!(
+                    outbox_event(&result_cart_item).event_type,
+                    "cart_items_removed"
+                );
+                Ok(result_cart_item.clone())
+            });
 
         // Act
         let result =
-            cart_remove_item_core(&cart_repository_port, &eventing_port, user_id, product_id).await;
+            cart_remove_item_core(&cart_repository_port, user_id, product_id, None, "tester").await;
 
         // Assert
         assert!(result.is_ok());
@@ -71,53 +75,7 @@ mod tests {
 
         cart_repository_port
             .expect_cart_remove_item()
-            .returning(move |_, _| {
-                Err(error::HexagonalError {
-                    message: "Error".to_string(),
-                    error: error::HexagonalErrorCode::AdaptorError,
-                    trace: "".to_string(),
-                })
-            });
-
-        let mut eventing_port = eventing::MockEventingPort::new();
-        eventing_port
-            .expect_emit::<EventCartItemsRemovedV1>()
-            .returning(move |_| Ok(()));
-
-        // Act
-        let result = cart_remove_item_core(
-            &cart_repository_port,
-            &eventing_port,
-            uuid::Uuid::new_v4().to_string(),
-            uuid::Uuid::new_v4().to_string(),
-        )
-        .await;
-
-        // Assert
-        assert!(result.is_err());
-    }
-
-    #[tokio::test]
-    async fn test_cart_remove_item_core_eventing_error() {
-        // Arrange
-        let mut cart_repository_port = models::models::cart::MockCartRepositoryPort::new();
-
-        let result_cart_item = CartItem {
-            product_id: uuid::Uuid::new_v4().to_string(),
-            user_id: uuid::Uuid::new_v4().to_string(),
-            quantity: 1,
-            created_at: default_time(),
-            updated_at: default_time(),
-        };
-
-        cart_repository_port
-            .expect_cart_remove_item()
-            .returning(move |_, _| Ok(result_cart_item.clone()));
-
-        let mut eventing_port = eventing::MockEventingPort::new();
-        eventing_port
-            .expect_emit::<EventCartItemsRemovedV1>()
-            .returning(move |_| {
+            .returning(move |_, _, _, _, _| {
                 Err(error::HexagonalError {
                     message: "Error".to_string(),
                     error: error::HexagonalErrorCode::AdaptorError,
@@ -128,9 +86,10 @@ mod tests {
         // Act
         let result = cart_remove_item_core(
             &cart_repository_port,
-            &eventing_port,
             uuid::Uuid::new_v4().to_string(),
             uuid::Uuid::new_v4().to_string(),
+            None,
+            "tester",
         )
         .await;
 
@@ -1,14 +1,12 @@
-use crate::domain::cart_remove_item_core;
+use super::domain::cart_remove_item_core;
 
 use error::HexagonalError;
-use eventing::EventingPort;
 use http::{Error, Response, StatusCode};
 use http_port_tools::port_objects::HttpPortRequest;
 use models::models::cart::CartRepositoryPort;
 
-pub async fn cart_remove_item_delete_http_port<T1: CartRepositoryPort, T2: EventingPort>(
+pub async fn cart_remove_item_delete_http_port<T1: CartRepositoryPort>(
     cart_repository_port: &T1,
-    eventing_port: &T2,
     http_request: HttpPortRequest,
 ) -> Result<Response<String>, Error> {
     let username = match http_request.path_parameters.first("username") {
@@ -33,11 +31,16 @@ pub async fn cart_remove_item_delete_http_port<T1: CartRepositoryPort, T2: Event
             return Ok(err.compile_to_http_response());
         }
     };
+    let expected_version = match http_request.if_match() {
+        Ok(expected_version) => expected_version,
+        Err(err) => return Ok(err.compile_to_http_response()),
+    };
     match cart_remove_item_core(
         cart_repository_port,
-        eventing_port,
         username.to_string(),
         product_id.to_string(),
+        expected_version,
+        &http_request.principal(),
     )
     .await
     {
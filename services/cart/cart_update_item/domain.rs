@@ -1,29 +1,29 @@
-use eventing::{events::cart::cart_item_added::EventCartItemAddedV1, EventingPort};
+use eventing::events::{
+    cart::cart_item_added::EventCartItemAddedV1, event_emmiter::SerialisableEvent,
+};
 use models::models::cart::{CartItem, CartRepositoryPort};
+use models::models::outbox::OutboxItem;
 
-pub async fn cart_update_item_core<T1: CartRepositoryPort, T2: EventingPort>(
+fn cart_item_updated_event(cart_item: &CartItem) -> OutboxItem {
+    EventCartItemAddedV1::new(cart_item.clone()).to_outbox_item()
+}
+
+pub async fn cart_update_item_core<T1: CartRepositoryPort>(
     cart_repository_port: &T1,
-    eventing_port: &T2,
     cart_item: CartItem,
+    expected_version: Option<u64>,
+    principal: &str,
 ) -> Result<CartItem, error::HexagonalError> {
-    let cart_item_result = cart_repository_port
+    cart_repository_port
         .cart_update_item(
             &cart_item.user_id.to_ascii_lowercase(),
             &cart_item.product_id,
             cart_item.quantity,
+            expected_version,
+            principal,
+            cart_item_updated_event,
         )
-        .await;
-
-    if cart_item_result.is_ok() {
-        let event_result = eventing_port
-            .emit(&EventCartItemAddedV1::new(cart_item.clone()))
-            .await;
-        if event_result.is_err() {
-            return Err(event_result.unwrap_err());
-        }
-    }
-
-    cart_item_result
+        .await
 }
 
 #[cfg(test)]
@@ -36,7 +36,6 @@ mod tests {
     async fn test_cart_update_item_core() {
         // Arrange
         let mut cart_repository_port = models::models::cart::MockCartRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
 
         let cart_item = CartItem {
             product_id: uuid::Uuid::new_v4().to_string(),
@@ -44,69 +43,33 @@ mod tests {
             quantity: 1,
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         let result_cart_item = cart_item.clone();
 
         cart_repository_port
             .expect_cart_update_item()
-            .returning(move |_, _, _| Ok(result_cart_item.clone()));
-
-        eventing_port
-            .expect_emit::<EventCartItemAddedV1>()
             .times(1)
-            .returning(|_| Ok(()));
-
-        // Act
-        let result = cart_update_item_core(&cart_repository_port, &eventing_port, cart_item).await;
-
-        // Assert
-        assert!(result.is_ok());
-    }
-
-    #[tokio::test]
-    async fn test_cart_add_item_core_eventing_error() {
-        // Arrange
-        let mut cart_repository_port = models::models::cart::MockCartRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
-
-        let cart_item = CartItem {
-            product_id: uuid::Uuid::new_v4().to_string(),
-            user_id: uuid::Uuid::new_v4().to_string(),
-            quantity: 1,
-            created_at: default_time(),
-            updated_at: default_time(),
-        };
-
-        let result_cart_item = cart_item.clone();
-
-        cart_repository_port
-            .expect_cart_update_item()
-            .returning(move |_, _, _| Ok(result_cart_item.clone()));
-
-        eventing_port
-            .expect_emit::<EventCartItemAddedV1>()
-            .times(1)
-            .returning(|_| {
-                Err(error::HexagonalError {
-                    error: error::HexagonalErrorCode::AdaptorError,
-                    message: "test".to_string(),
-                    trace: "".to_string(),
-                })
+            .returning(move |_, _, _, _, _, outbox_event| {
+                assert_eq!(
+                    outbox_event(&result_cart_item).event_type,
+                    "cart_item_added"
+                );
+                Ok(result_cart_item.clone())
             });
 
         // Act
-        let result = cart_update_item_core(&cart_repository_port, &eventing_port, cart_item).await;
+        let result = cart_update_item_core(&cart_repository_port, cart_item, None, "tester").await;
 
         // Assert
-        assert!(result.is_err());
+        assert!(result.is_ok());
     }
 
     #[tokio::test]
     async fn test_cart_add_item_core_repository_error() {
         // Arrange
         let mut cart_repository_port = models::models::cart::MockCartRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
 
         let cart_item = CartItem {
             product_id: uuid::Uuid::new_v4().to_string(),
@@ -114,11 +77,12 @@ mod tests {
             quantity: 1,
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         cart_repository_port
             .expect_cart_update_item()
-            .returning(move |_, _, _| {
+            .returning(move |_, _, _, _, _, _| {
                 Err(error::HexagonalError {
                     error: error::HexagonalErrorCode::AdaptorError,
                     message: "test".to_string(),
@@ -126,13 +90,8 @@ mod tests {
                 })
             });
 
-        eventing_port
-            .expect_emit::<EventCartItemAddedV1>()
-            .times(0)
-            .returning(|_| Ok(()));
-
         // Act
-        let result = cart_update_item_core(&cart_repository_port, &eventing_port, cart_item).await;
+        let result = cart_update_item_core(&cart_repository_port, cart_item, None, "tester").await;
 
         // Assert
         assert!(result.is_err());
@@ -2,23 +2,27 @@ mod domain;
 mod http_port;
 use crate::http_port::cart_update_item_patch_http_port;
 
-use eventing::EventingPort;
 use http_port_tools::port_objects::{HttpPortRequest, HttpPortResponse};
 
 use lambda_adaptor::common_lambda_adaptor;
 use lambda_http::{run, service_fn, Error, IntoResponse};
-use models::models::cart::CartRepositoryPort;
+use models::models::cart::CartRepositoryAdaptor;
+use persistance_repository::DynamoDBSingleTableRepository;
 
-async fn http_lambda_driving_adaptor<T1: CartRepositoryPort, T2: EventingPort>(
-    cart_repository_port: &T1,
-    eventing_port: &T2,
+async fn http_lambda_driving_adaptor(
+    dynamo_db_repository: &DynamoDBSingleTableRepository,
     event: lambda_http::Request,
 ) -> Result<lambda_http::Response<lambda_http::Body>, Error> {
     let http_request = HttpPortRequest::from(event);
-    let generic_http_response =
-        cart_update_item_patch_http_port(cart_repository_port, eventing_port, http_request)
-            .await
-            .unwrap();
+    let generic_http_response = match http_request.scoped_repository(dynamo_db_repository) {
+        Ok(tenant_repository) => {
+            let cart_repository = CartRepositoryAdaptor::new(&tenant_repository);
+            cart_update_item_patch_http_port(&cart_repository, http_request)
+                .await
+                .unwrap()
+        }
+        Err(err) => err.compile_to_http_response(),
+    };
     let lambda_http_response = HttpPortResponse(generic_http_response)
         .into_response()
         .await;
@@ -32,18 +36,17 @@ async fn main() -> Result<(), Error> {
 
     {
         // Provision required repositories once in the main function
+        let config = app_config::AppConfig::load()?;
         let sdk_credential_meta_repository =
             sdk_credential_meta_repository::SdkCredentialsMetaRepository::new().await;
         let dynamo_db_repository = persistance_repository::DynamoDBSingleTableRepository::new(
             &sdk_credential_meta_repository,
-        );
-        let eventing_repository =
-            eventing::EventingRepository::new(&sdk_credential_meta_repository);
-        let cart_repository =
-            models::models::cart::CartRepositoryAdaptor::new(&dynamo_db_repository);
+            &config,
+        )?
+        .with_operation("cart_update_item");
 
         run(service_fn(|event| {
-            http_lambda_driving_adaptor(&cart_repository, &eventing_repository, event)
+            http_lambda_driving_adaptor(&dynamo_db_repository, event)
         }))
         .await
     }
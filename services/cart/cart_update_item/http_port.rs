@@ -1,10 +1,9 @@
-use crate::domain::cart_update_item_core;
+use super::domain::cart_update_item_core;
 
 use error::HexagonalError;
-use eventing::EventingPort;
 use http::{Error, Response, StatusCode};
 use http_port_tools::http_payload_decoder;
-use http_port_tools::port_objects::HttpPortRequest;
+use http_port_tools::port_objects::{etag, HttpPortRequest};
 use jsonschema::{Draft, JSONSchema};
 use lazy_static::lazy_static;
 use models::models::cart::{CartItem, CartRepositoryPort};
@@ -38,9 +37,8 @@ pub struct CartUpdateItemBody {
     pub quantity: u32,
 }
 
-pub async fn cart_update_item_patch_http_port<T1: CartRepositoryPort, T2: EventingPort>(
+pub async fn cart_update_item_patch_http_port<T1: CartRepositoryPort>(
     cart_repository_port: &T1,
-    eventing_port: &T2,
     http_request: HttpPortRequest,
 ) -> Result<Response<String>, Error> {
     let username = match http_request.path_parameters.first("username") {
@@ -65,19 +63,26 @@ pub async fn cart_update_item_patch_http_port<T1: CartRepositoryPort, T2: Eventi
             return Ok(err.compile_to_http_response());
         }
     };
+    let expected_version = match http_request.if_match() {
+        Ok(expected_version) => expected_version,
+        Err(err) => return Ok(err.compile_to_http_response()),
+    };
+    let principal = http_request.principal();
     let payload = http_request.payload;
     let cart_update_item_body =
         http_payload_decoder!(CartUpdateItemBody, CART_ITEM_SCHEMA, payload);
     match cart_update_item_core(
         cart_repository_port,
-        eventing_port,
         CartItem {
             product_id: product_id.to_string(),
             user_id: username.to_string(),
             quantity: cart_update_item_body.quantity,
             created_at: "".to_string(), // Will be excluded in adaptor
             updated_at: "".to_string(), // Will be overwritten in adaptor
+            version: 0,                 // Will be overwritten in adaptor
         },
+        expected_version,
+        &principal,
     )
     .await
     {
@@ -85,6 +90,7 @@ pub async fn cart_update_item_patch_http_port<T1: CartRepositoryPort, T2: Eventi
             let resp = Response::builder()
                 .status(StatusCode::OK)
                 .header("content-type", "application/json")
+                .header("etag", etag(result.version))
                 .body(serde_json::to_string(&result).unwrap());
             Ok(resp.unwrap())
         }
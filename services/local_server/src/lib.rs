@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use error::HexagonalError;
+use eventing::events::event_emmiter::SerialisableEvent;
+use eventing::EventingPort;
+use http::Method;
+use http_port_tools::port_objects::HttpPortRequest;
+use lambda_adaptor::in_process_bus::InProcessEventBus;
+use percent_encoding::percent_decode_str;
+
+// API Gateway serves everything under the stage name, the local server accepts paths with or
+// without it so the integration tests can point straight at it
+const STAGE_PREFIX: &str = "/main";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    HelloWorld,
+    UserCreate,
+    UserGet,
+    UserUpdate,
+    UserDelete,
+    UserEmailUpdate,
+    UserRestore,
+    UserHistory,
+    ProductCreate,
+    ProductBatchGet,
+    ProductGet,
+    ProductUpdate,
+    ProductDelete,
+    ProductRestore,
+    ProductHistory,
+    CartGet,
+    CartClear,
+    CartHistory,
+    CartAddItem,
+    CartRemoveItem,
+    CartUpdateItem,
+}
+
+// Mirrors the router fragments in infra/*/router.tf
+const ROUTES: &[(Method, &str, Route)] = &[
+    (Method::GET, "/hello_world", Route::HelloWorld),
+    (Method::POST, "/user", Route::UserCreate),
+    (Method::GET, "/user/{username}", Route::UserGet),
+    (Method::PUT, "/user/{username}", Route::UserUpdate),
+    (Method::DELETE, "/user/{username}", Route::UserDelete),
+    (
+        Method::PUT,
+        "/user/{username}/email",
+        Route::UserEmailUpdate,
+    ),
+    (Method::POST, "/user/{username}/restore", Route::UserRestore),
+    (Method::GET, "/user/{username}/history", Route::UserHistory),
+    (Method::POST, "/product", Route::ProductCreate),
+    (Method::GET, "/product", Route::ProductBatchGet),
+    (Method::GET, "/product/{id}", Route::ProductGet),
+    (Method::PUT, "/product/{id}", Route::ProductUpdate),
+    (Method::DELETE, "/product/{id}", Route::ProductDelete),
+    (Method::POST, "/product/{id}/restore", Route::ProductRestore),
+    (Method::GET, "/product/{id}/history", Route::ProductHistory),
+    (Method::GET, "/cart/{username}", Route::CartGet),
+    (Method::DELETE, "/cart/{username}", Route::CartClear),
+    (Method::GET, "/cart/{username}/history", Route::CartHistory),
+    (Method::POST, "/cart/{username}/item", Route::CartAddItem),
+    (
+        Method::DELETE,
+        "/cart/{username}/item/{product_id}",
+        Route::CartRemoveItem,
+    ),
+    (
+        Method::PATCH,
+        "/cart/{username}/item/{product_id}",
+        Route::CartUpdateItem,
+    ),
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RouteError {
+    NotFound,
+    // with the methods the path does take
+    MethodNotAllowed(Vec<Method>),
+}
+
+impl RouteError {
+    pub fn into_http_response(self, method: &Method, path: &str) -> http::Response<String> {
+        match self {
+            RouteError::NotFound => HexagonalError {
+                error: error::HexagonalErrorCode::NotFound,
+                message: format!("No route matches {}", path),
+                trace: "".to_string(),
+            }
+            .compile_to_http_response(),
+            // there is no error code for 405, so the status and Allow header are set here
+            RouteError::MethodNotAllowed(allowed) => {
+                let allow = allowed
+                    .iter()
+                    .map(Method::as_str)
+                    .collect::<Vec<&str>>()
+                    .join(", ");
+                let mut response = HexagonalError {
+                    error: error::HexagonalErrorCode::BadInput,
+                    message: format!("Method {} is not allowed on {}", method, path),
+                    trace: format!("allowed are {}", allow),
+                }
+                .compile_to_http_response();
+                *response.status_mut() = http::StatusCode::METHOD_NOT_ALLOWED;
+                if let Ok(allow) = http::HeaderValue::from_str(&allow) {
+                    response.headers_mut().insert(http::header::ALLOW, allow);
+                }
+                response
+            }
+        }
+    }
+}
+
+fn match_template(template: &str, path: &str) -> Option<HashMap<String, String>> {
+    let template_segments: Vec<&str> = template.trim_matches('/').split('/').collect();
+    let path_segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    if template_segments.len() != path_segments.len() {
+        return None;
+    }
+
+    let mut path_parameters = HashMap::new();
+    for (template_segment, path_segment) in template_segments.iter().zip(path_segments) {
+        match template_segment
+            .strip_prefix('{')
+            .and_then(|name| name.strip_suffix('}'))
+        {
+            Some(name) => {
+                if path_segment.is_empty() {
+                    return None;
+                }
+                // API Gateway hands path parameters over already decoded
+                let value = percent_decode_str(path_segment)
+                    .decode_utf8_lossy()
+                    .to_string();
+                path_parameters.insert(name.to_string(), value);
+            }
+            None if *template_segment == path_segment => {}
+            None => return None,
+        }
+    }
+    Some(path_parameters)
+}
+
+pub fn match_route(
+    method: &Method,
+    path: &str,
+) -> Result<(Route, HashMap<String, String>), RouteError> {
+    let path = match path.strip_prefix(STAGE_PREFIX) {
+        Some(stripped) if stripped.is_empty() || stripped.starts_with('/') => stripped,
+        _ => path,
+    };
+
+    let mut allowed = Vec::new();
+    for (route_method, template, route) in ROUTES {
+        if let Some(path_parameters) = match_template(template, path) {
+            if route_method == method {
+                return Ok((*route, path_parameters));
+            }
+            allowed.push(route_method.clone());
+        }
+    }
+    match allowed.is_empty() {
+        true => Err(RouteError::NotFound),
+        false => Err(RouteError::MethodNotAllowed(allowed)),
+    }
+}
+
+fn parse_query_string(query: Option<&str>) -> query_map::QueryMap {
+    let mut query_string_parameters: HashMap<String, Vec<String>> = HashMap::new();
+    for pair in query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+    {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let decode = |part: &str| {
+            percent_decode_str(&part.replace('+', " "))
+                .decode_utf8_lossy()
+                .to_string()
+        };
+        query_string_parameters
+            .entry(decode(key))
+            .or_default()
+            .push(decode(value));
+    }
+    query_string_parameters.into()
+}
+
+// Builds the same request the lambda adaptors hand to the http ports
+pub fn into_http_port_request(
+    parts: &http::request::Parts,
+    path_parameters: HashMap<String, String>,
+    body: String,
+) -> HttpPortRequest {
+    HttpPortRequest {
+        path_parameters: path_parameters.into(),
+        query_string_parameters: parse_query_string(parts.uri.query()),
+        payload: Some(body),
+        headers: parts.headers.clone(),
+        // nothing authenticates requests locally, so every write is recorded as anonymous
+        principal: None,
+        // nor is there an authorizer, the tenant comes from the x-tenant-id header alone
+        tenant_claim: None,
+        // stands in for API Gateway's, so events are correlated as they would be deployed
+        request_id: Some(models::new_uuid()),
+    }
+}
+
+// Relayed outbox events are logged, then delivered in process to the consumers the rules in infra
+// would deliver them to
+pub struct LocalEventingAdaptor<'a> {
+    event_bus: InProcessEventBus<'a>,
+}
+
+impl<'a> LocalEventingAdaptor<'a> {
+    pub fn new(event_bus: InProcessEventBus<'a>) -> LocalEventingAdaptor<'a> {
+        LocalEventingAdaptor { event_bus }
+    }
+}
+
+#[async_trait]
+impl<'a> EventingPort for LocalEventingAdaptor<'a> {
+    async fn emit<T: SerialisableEvent + Sync + 'static>(
+        &self,
+        event: &T,
+    ) -> Result<(), HexagonalError> {
+        tracing::info!(
+            "Event {} v{}: {}",
+            event.get_event_type(),
+            event.get_version(),
+            event.serialise()
+        );
+        self.event_bus.emit(event).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_route_with_path_parameters() {
+        let (route, path_parameters) =
+            match_route(&Method::PATCH, "/cart/some%20user/item/123").unwrap();
+        assert_eq!(route, Route::CartUpdateItem);
+        assert_eq!(path_parameters["username"], "some user");
+        assert_eq!(path_parameters["product_id"], "123");
+    }
+
+    #[test]
+    fn test_match_route_with_stage_prefix() {
+        assert_eq!(
+            match_route(&Method::POST, "/main/user").unwrap().0,
+            Route::UserCreate
+        );
+        assert_eq!(
+            match_route(&Method::GET, "/main/product/abc").unwrap().0,
+            Route::ProductGet
+        );
+    }
+
+    #[test]
+    fn test_match_route_errors() {
+        assert_eq!(
+            match_route(&Method::GET, "/nowhere").unwrap_err(),
+            RouteError::NotFound
+        );
+        assert_eq!(
+            match_route(&Method::GET, "/cart").unwrap_err(),
+            RouteError::NotFound
+        );
+        assert_eq!(
+            match_route(&Method::POST, "/product/abc").unwrap_err(),
+            RouteError::MethodNotAllowed(vec![Method::GET, Method::PUT, Method::DELETE])
+        );
+    }
+
+    #[test]
+    fn test_method_not_allowed_response() {
+        let response = match_route(&Method::POST, "/cart/jdoe")
+            .unwrap_err()
+            .into_http_response(&Method::POST, "/cart/jdoe");
+        assert_eq!(response.status(), http::StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[http::header::ALLOW], "GET, DELETE");
+    }
+
+    #[test]
+    fn test_into_http_port_request_parses_query_string() {
+        let (parts, _) = http::Request::builder()
+            .uri("/product?id=1&id=2&who=a+b")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let request = into_http_port_request(&parts, HashMap::new(), "".to_string());
+        assert_eq!(
+            request.query_string_parameters.all("id"),
+            Some(vec!["1", "2"])
+        );
+        assert_eq!(request.query_string_parameters.first("who"), Some("a b"));
+        assert_eq!(request.payload, Some("".to_string()));
+    }
+}
@@ -0,0 +1,338 @@
+#[path = "../../users/hello_world/mod.rs"]
+mod hello_world;
+#[path = "../../users/user_create/mod.rs"]
+mod user_create;
+#[path = "../../users/user_delete/mod.rs"]
+mod user_delete;
+#[path = "../../users/user_email_update/mod.rs"]
+mod user_email_update;
+#[path = "../../users/user_get/mod.rs"]
+mod user_get;
+#[path = "../../users/user_history/mod.rs"]
+mod user_history;
+#[path = "../../users/user_purge/mod.rs"]
+mod user_purge;
+#[path = "../../users/user_restore/mod.rs"]
+mod user_restore;
+#[path = "../../users/user_update/mod.rs"]
+mod user_update;
+
+#[path = "../../product/product_batch_get/mod.rs"]
+mod product_batch_get;
+#[path = "../../product/product_cache_events/mod.rs"]
+mod product_cache_events;
+#[path = "../../product/product_create/mod.rs"]
+mod product_create;
+#[path = "../../product/product_delete/mod.rs"]
+mod product_delete;
+#[path = "../../product/product_get/mod.rs"]
+mod product_get;
+#[path = "../../product/product_history/mod.rs"]
+mod product_history;
+#[path = "../../product/product_purge/mod.rs"]
+mod product_purge;
+#[path = "../../product/product_restore/mod.rs"]
+mod product_restore;
+#[path = "../../product/product_update/mod.rs"]
+mod product_update;
+
+#[path = "../../cart/cart_add_item/mod.rs"]
+mod cart_add_item;
+#[path = "../../cart/cart_clear/mod.rs"]
+mod cart_clear;
+#[path = "../../cart/cart_events/mod.rs"]
+mod cart_events;
+#[path = "../../cart/cart_get/mod.rs"]
+mod cart_get;
+#[path = "../../cart/cart_history/mod.rs"]
+mod cart_history;
+#[path = "../../cart/cart_remove_item/mod.rs"]
+mod cart_remove_item;
+#[path = "../../cart/cart_update_item/mod.rs"]
+mod cart_update_item;
+
+#[path = "../../outbox/outbox_relay/mod.rs"]
+mod outbox_relay;
+
+use std::convert::Infallible;
+use std::time::Duration;
+
+use app_config::{AppConfig, ConfigError};
+use bytes::Bytes;
+use error::HexagonalError;
+use http_body_util::{BodyExt, Full};
+use http_port_tools::port_objects::HttpPortRequest;
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use in_memory_persistance_repository::InMemorySingleTable;
+use lambda_adaptor::in_process_bus::InProcessEventBus;
+use local_server::{into_http_port_request, match_route, LocalEventingAdaptor, Route};
+use models::models::cart::CartRepositoryAdaptor;
+use models::models::outbox::OutboxRepositoryAdaptor;
+use models::models::product::ProductRepositoryAdaptor;
+use models::models::product_cache::{CachedProductRepositoryAdaptor, ProductCache};
+use models::models::user::UserRepositoryAdaptor;
+use persistance_repository::{DynamoDBSingleTableRepository, Tenant, AWS_DYNAMO_DB_REPOSITORY};
+use tokio::net::TcpListener;
+
+struct Ports<'a> {
+    user_repository: UserRepositoryAdaptor<'a>,
+    product_repository: CachedProductRepositoryAdaptor<'a, ProductRepositoryAdaptor<'a>>,
+    cart_repository: CartRepositoryAdaptor<'a>,
+}
+
+impl<'a> Ports<'a> {
+    fn new(
+        dynamo_db_repository: &'a DynamoDBSingleTableRepository,
+        product_cache: &'a ProductCache,
+    ) -> Ports<'a> {
+        Ports {
+            user_repository: UserRepositoryAdaptor::new(dynamo_db_repository),
+            product_repository: CachedProductRepositoryAdaptor::new(
+                ProductRepositoryAdaptor::new(dynamo_db_repository),
+                product_cache,
+            ),
+            cart_repository: CartRepositoryAdaptor::new(dynamo_db_repository),
+        }
+    }
+}
+
+const OUTBOX_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const OUTBOX_POLL_LIMIT: i32 = 25;
+
+// There is no table stream locally, so the outbox is drained by polling its pending index
+async fn outbox_relay_poller(
+    outbox_repository: OutboxRepositoryAdaptor<'static>,
+    eventing_repository: LocalEventingAdaptor<'static>,
+) {
+    loop {
+        match outbox_relay::poll_port::outbox_relay_poll_port(
+            &outbox_repository,
+            &eventing_repository,
+            OUTBOX_POLL_LIMIT,
+        )
+        .await
+        {
+            // a full page means there is probably more waiting
+            Ok(relayed) if relayed as i32 == OUTBOX_POLL_LIMIT => continue,
+            Ok(_) => {}
+            Err(err) => tracing::error!("Error relaying outbox: {}", err),
+        }
+        tokio::time::sleep(OUTBOX_POLL_INTERVAL).await;
+    }
+}
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+// Stands in for the scheduled purge functions
+async fn purge_poller(
+    dynamo_db_repository: &'static DynamoDBSingleTableRepository,
+    product_cache: &'static ProductCache,
+    tenants: Vec<Tenant>,
+) {
+    loop {
+        for tenant in &tenants {
+            let tenant_repository = dynamo_db_repository.for_tenant(tenant.clone());
+            let ports = Ports::new(&tenant_repository, product_cache);
+            if let Err(err) =
+                user_purge::schedule_port::user_purge_schedule_port(&ports.user_repository).await
+            {
+                tracing::error!("Error purging users: {}", err);
+            }
+            if let Err(err) =
+                product_purge::schedule_port::product_purge_schedule_port(&ports.product_repository)
+                    .await
+            {
+                tracing::error!("Error purging products: {}", err);
+            }
+        }
+        tokio::time::sleep(PURGE_INTERVAL).await;
+    }
+}
+
+async fn dispatch(
+    ports: &Ports<'_>,
+    route: Route,
+    request: HttpPortRequest,
+) -> Result<http::Response<String>, http::Error> {
+    let users = &ports.user_repository;
+    let products = &ports.product_repository;
+    let carts = &ports.cart_repository;
+    match route {
+        Route::HelloWorld => hello_world::http_port::hello_world_get_http_port(request).await,
+        Route::UserCreate => {
+            user_create::http_port::user_create_post_http_port(users, request).await
+        }
+        Route::UserGet => user_get::http_port::user_get_get_http_port(users, request).await,
+        Route::UserUpdate => {
+            user_update::http_port::user_update_put_http_port(users, request).await
+        }
+        Route::UserDelete => {
+            user_delete::http_port::user_delete_delete_http_port(users, request).await
+        }
+        Route::UserEmailUpdate => {
+            user_email_update::http_port::user_username_update_put_http_port(users, request).await
+        }
+        Route::UserRestore => {
+            user_restore::http_port::user_restore_post_http_port(users, request).await
+        }
+        Route::UserHistory => {
+            user_history::http_port::user_history_get_http_port(users, request).await
+        }
+        Route::ProductCreate => {
+            product_create::http_port::product_create_post_http_port(products, request).await
+        }
+        Route::ProductBatchGet => {
+            product_batch_get::http_port::product_get_batch_post_http_port(products, request).await
+        }
+        Route::ProductGet => {
+            product_get::http_port::product_get_get_http_port(products, request).await
+        }
+        Route::ProductUpdate => {
+            product_update::http_port::product_update_put_http_port(products, request).await
+        }
+        Route::ProductDelete => {
+            product_delete::http_port::product_delete_delete_http_port(products, request).await
+        }
+        Route::ProductRestore => {
+            product_restore::http_port::product_restore_post_http_port(products, request).await
+        }
+        Route::ProductHistory => {
+            product_history::http_port::product_history_get_http_port(products, request).await
+        }
+        Route::CartGet => cart_get::http_port::cart_get_get_http_port(carts, request).await,
+        Route::CartClear => cart_clear::http_port::cart_create_post_http_port(carts, request).await,
+        Route::CartHistory => {
+            cart_history::http_port::cart_history_get_http_port(carts, request).await
+        }
+        Route::CartAddItem => {
+            cart_add_item::http_port::cart_create_post_http_port(carts, request).await
+        }
+        Route::CartRemoveItem => {
+            cart_remove_item::http_port::cart_remove_item_delete_http_port(carts, request).await
+        }
+        Route::CartUpdateItem => {
+            cart_update_item::http_port::cart_update_item_patch_http_port(carts, request).await
+        }
+    }
+}
+
+async fn http_local_driving_adaptor(
+    dynamo_db_repository: &'static DynamoDBSingleTableRepository,
+    product_cache: &'static ProductCache,
+    request: hyper::Request<Incoming>,
+) -> Result<hyper::Response<Full<Bytes>>, Infallible> {
+    let (parts, body) = request.into_parts();
+    let body = match body.collect().await {
+        Ok(collected) => String::from_utf8_lossy(&collected.to_bytes()).to_string(),
+        Err(_) => String::new(),
+    };
+
+    let response = match match_route(&parts.method, parts.uri.path()) {
+        Ok((route, path_parameters)) => {
+            let http_request = into_http_port_request(&parts, path_parameters, body);
+            match http_request.scoped_repository(dynamo_db_repository) {
+                Ok(tenant_repository) => {
+                    let ports = Ports::new(&tenant_repository, product_cache);
+                    match dispatch(&ports, route, http_request).await {
+                        Ok(response) => response,
+                        Err(err) => HexagonalError {
+                            error: error::HexagonalErrorCode::AdaptorError,
+                            message: "Unable to build response".to_string(),
+                            trace: err.to_string(),
+                        }
+                        .compile_to_http_response(),
+                    }
+                }
+                Err(err) => err.compile_to_http_response(),
+            }
+        }
+        Err(err) => err.into_http_response(&parts.method, parts.uri.path()),
+    };
+    tracing::info!("{} {} {}", parts.method, parts.uri, response.status());
+
+    let (parts, body) = response.into_parts();
+    Ok(hyper::Response::from_parts(
+        parts,
+        Full::new(Bytes::from(body)),
+    ))
+}
+
+// Uses DynamoDB when a table is configured, e.g. to point at DynamoDB Local through
+// AWS_ENDPOINT_URL, and the in memory table otherwise
+async fn persistance_repository(
+    config: AppConfig,
+) -> Result<DynamoDBSingleTableRepository, ConfigError> {
+    match config.table.name {
+        Some(_) => {
+            let sdk_credential_meta_repository =
+                sdk_credential_meta_repository::SdkCredentialsMetaRepository::new().await;
+            DynamoDBSingleTableRepository::new(&sdk_credential_meta_repository, &config)
+                .map(|repository| repository.with_operation("local_server"))
+        }
+        // the in memory table only has the default index names
+        None => Ok(InMemorySingleTable::new()
+            .repository("local")
+            .with_config(AppConfig {
+                table: AppConfig::default().table,
+                ..config
+            })),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_target(false)
+        .init();
+
+    let config = AppConfig::load()?;
+    let tenants = Tenant::configured(&config)?;
+    let event_config = config.clone();
+    // One cache for the life of the server, shared by every request like a warm function's
+    let product_cache: &'static ProductCache = Box::leak(Box::new(ProductCache::new(
+        config.product_cache.ttl,
+        config.product_cache.capacity,
+    )));
+    let dynamo_db_repository = AWS_DYNAMO_DB_REPOSITORY
+        .get_or_try_init(|| persistance_repository(config))
+        .await?;
+    // Events go where the rules in infra send them, see cart_events.tf and product_cache_events.tf
+    let event_bus = InProcessEventBus::new(&event_config)
+        .with_rule(
+            &["user_deleted", "product_deleted"],
+            cart_events::event_dispatcher::cart_event_dispatcher(dynamo_db_repository),
+        )
+        .with_rule(
+            &["product_updated", "product_soft_deleted", "product_deleted"],
+            product_cache_events::event_dispatcher::product_cache_event_dispatcher(product_cache),
+        );
+    tokio::spawn(outbox_relay_poller(
+        OutboxRepositoryAdaptor::new(dynamo_db_repository),
+        LocalEventingAdaptor::new(event_bus),
+    ));
+    tokio::spawn(purge_poller(dynamo_db_repository, product_cache, tenants));
+
+    let address =
+        std::env::var("LOCAL_SERVER_ADDRESS").unwrap_or_else(|_| "127.0.0.1:3000".to_string());
+    let listener = TcpListener::bind(&address).await?;
+    tracing::info!("Listening on http://{}", address);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            let service = service_fn(|request| {
+                http_local_driving_adaptor(dynamo_db_repository, product_cache, request)
+            });
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::error!("Error serving connection: {}", err);
+            }
+        });
+    }
+}
@@ -0,0 +1,112 @@
+use error::HexagonalError;
+use eventing::EventingPort;
+use models::models::outbox::{OutboxItem, OutboxRepositoryPort};
+
+// Publishes the items in batches before marking each one that went through sent, so a failure in
+// between only ever re-publishes. Consumers have to tolerate the duplicates this delivery
+// guarantee brings. Items that failed stay pending for the next run and the first failure is
+// returned.
+pub async fn outbox_relay_core<T1: OutboxRepositoryPort, T2: EventingPort>(
+    outbox_repository_port: &T1,
+    eventing_port: &T2,
+    items: Vec<OutboxItem>,
+) -> Result<(), HexagonalError> {
+    let results = eventing_port.emit_batch(&items).await;
+    let mut first_failure = None;
+    for (item, result) in items.iter().zip(results) {
+        match result {
+            Ok(()) => outbox_repository_port.outbox_mark_sent(item).await?,
+            Err(err) => {
+                first_failure.get_or_insert(err);
+            }
+        }
+    }
+    match first_failure {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::Sequence;
+    use models::models::outbox::MockOutboxRepositoryPort;
+
+    use super::*;
+
+    fn outbox_item(event_type: &str) -> OutboxItem {
+        OutboxItem::new(event_type.to_string(), 1, "{}".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_outbox_relay_core_emits_then_marks_sent() {
+        // Arrange
+        let mut outbox_repository_port = MockOutboxRepositoryPort::new();
+        let mut eventing_port = eventing::MockEventingPort::new();
+        let mut sequence = Sequence::new();
+
+        eventing_port
+            .expect_emit_batch::<OutboxItem>()
+            .withf(|items| items.len() == 2)
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|items| items.iter().map(|_| Ok(())).collect());
+        outbox_repository_port
+            .expect_outbox_mark_sent()
+            .times(2)
+            .in_sequence(&mut sequence)
+            .returning(|_| Ok(()));
+
+        // Act
+        let result = outbox_relay_core(
+            &outbox_repository_port,
+            &eventing_port,
+            vec![outbox_item("user_created"), outbox_item("user_deleted")],
+        )
+        .await;
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_outbox_relay_core_does_not_mark_unsent() {
+        // Arrange
+        let mut outbox_repository_port = MockOutboxRepositoryPort::new();
+        let mut eventing_port = eventing::MockEventingPort::new();
+
+        // the first was throttled, the second went through
+        eventing_port
+            .expect_emit_batch::<OutboxItem>()
+            .times(1)
+            .returning(|_| {
+                vec![
+                    Err(HexagonalError {
+                        error: error::HexagonalErrorCode::AdaptorError,
+                        message: "test".to_string(),
+                        trace: "ThrottlingException".to_string(),
+                    }),
+                    Ok(()),
+                ]
+            });
+        outbox_repository_port
+            .expect_outbox_mark_sent()
+            .withf(|item| item.event_type == "user_deleted")
+            .times(1)
+            .returning(|_| Ok(()));
+
+        // Act
+        let result = outbox_relay_core(
+            &outbox_repository_port,
+            &eventing_port,
+            vec![outbox_item("user_created"), outbox_item("user_deleted")],
+        )
+        .await;
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err().error,
+            error::HexagonalErrorCode::AdaptorError
+        );
+    }
+}
@@ -0,0 +1,59 @@
+mod domain;
+mod stream_port;
+
+use crate::stream_port::outbox_relay_stream_port;
+
+use aws_lambda_events::dynamodb::Event;
+use eventing::EventingPort;
+use lambda_adaptor::common_lambda_adaptor;
+use lambda_adaptor::dynamodb_stream::into_attr_map;
+use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+use models::models::outbox::OutboxRepositoryPort;
+
+async fn dynamodb_stream_lambda_driving_adaptor<T1: OutboxRepositoryPort, T2: EventingPort>(
+    outbox_repository_port: &T1,
+    eventing_port: &T2,
+    event: LambdaEvent<Event>,
+) -> Result<(), Error> {
+    let inserted_items = event
+        .payload
+        .records
+        .into_iter()
+        .filter(|record| record.event_name == "INSERT")
+        .map(|record| into_attr_map(record.change.new_image))
+        .collect();
+
+    // failing the whole batch makes Lambda retry it, which re-publishes anything already sent
+    outbox_relay_stream_port(outbox_repository_port, eventing_port, inserted_items)
+        .await
+        .map_err(|err| {
+            println!("Error: {}", err);
+            Error::from(err.to_string())
+        })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    // Common snippit from all lambda functions
+    common_lambda_adaptor!();
+    {
+        // Provision required repositories once in the main function
+        let config = app_config::AppConfig::load()?;
+        let sdk_credential_meta_repository =
+            sdk_credential_meta_repository::SdkCredentialsMetaRepository::new().await;
+        let dynamo_db_repository = persistance_repository::DynamoDBSingleTableRepository::new(
+            &sdk_credential_meta_repository,
+            &config,
+        )?
+        .with_operation("outbox_relay");
+        let eventing_repository =
+            eventing::EventingRepository::new(&sdk_credential_meta_repository, &config)?;
+        let outbox_repository =
+            models::models::outbox::OutboxRepositoryAdaptor::new(&dynamo_db_repository);
+
+        run(service_fn(|event| {
+            dynamodb_stream_lambda_driving_adaptor(&outbox_repository, &eventing_repository, event)
+        }))
+        .await
+    }
+}
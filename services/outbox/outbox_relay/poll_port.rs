@@ -0,0 +1,17 @@
+use super::domain::outbox_relay_core;
+
+use error::HexagonalError;
+use eventing::EventingPort;
+use models::models::outbox::OutboxRepositoryPort;
+
+// For runs without a table stream, e.g. the local server, pending items are polled instead
+pub async fn outbox_relay_poll_port<T1: OutboxRepositoryPort, T2: EventingPort>(
+    outbox_repository_port: &T1,
+    eventing_port: &T2,
+    limit: i32,
+) -> Result<usize, HexagonalError> {
+    let items = outbox_repository_port.outbox_get_pending(limit).await?;
+    let relayed = items.len();
+    outbox_relay_core(outbox_repository_port, eventing_port, items).await?;
+    Ok(relayed)
+}
@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+
+use crate::domain::outbox_relay_core;
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use error::HexagonalError;
+use eventing::EventingPort;
+use models::models::outbox::{OutboxItem, OutboxRepositoryPort};
+
+// Takes the new images of inserted items, anything that is not an outbox item is skipped.
+// Outbox items that can't be read are logged and left pending rather than blocking the stream.
+pub async fn outbox_relay_stream_port<T1: OutboxRepositoryPort, T2: EventingPort>(
+    outbox_repository_port: &T1,
+    eventing_port: &T2,
+    inserted_items: Vec<HashMap<String, AttributeValue>>,
+) -> Result<(), HexagonalError> {
+    let items = inserted_items
+        .into_iter()
+        .filter_map(OutboxItem::from_item)
+        .filter_map(|item| match item {
+            Ok(item) => Some(item),
+            Err(err) => {
+                println!("Skipping unreadable outbox item: {}", err);
+                None
+            }
+        })
+        .collect();
+    outbox_relay_core(outbox_repository_port, eventing_port, items).await
+}
@@ -25,6 +25,7 @@ mod tests {
             price_cents: 10000,
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         let product2 = Product {
@@ -34,6 +35,7 @@ mod tests {
             price_cents: 10000,
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         let product_ids = vec![product1.id.clone(), product2.id.clone()];
@@ -1,4 +1,4 @@
-use crate::domain::product_get_batch_core;
+use super::domain::product_get_batch_core;
 
 use error::HexagonalError;
 use http::{Error, Response, StatusCode};
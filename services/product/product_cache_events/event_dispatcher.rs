@@ -0,0 +1,127 @@
+use error::HexagonalError;
+use eventing::events::event_registry::EventRegistry;
+use eventing::events::event_wrapper::TenantEvent;
+use eventing::events::product::product_deleted::EventProductDeletedV1;
+use eventing::events::product::product_soft_deleted::EventProductSoftDeletedV1;
+use eventing::events::product::product_updated::EventProductUpdatedV1;
+use lambda_adaptor::eventbridge::EventDispatcher;
+use models::models::product_cache::ProductCache;
+use persistance_repository::Tenant;
+
+fn invalidate(
+    product_cache: &ProductCache,
+    tenant: Option<&str>,
+    id: &str,
+) -> Result<(), HexagonalError> {
+    product_cache.invalidate(&Tenant::from_request(tenant)?, id);
+    Ok(())
+}
+
+// Every write that changes what a product read returns, the product_cache rule in infra matches
+// the same types
+pub fn product_cache_event_dispatcher(product_cache: &ProductCache) -> EventDispatcher<'_> {
+    EventDispatcher::new(EventRegistry::storefront())
+        .on(
+            move |event: TenantEvent<EventProductUpdatedV1>| async move {
+                invalidate(
+                    product_cache,
+                    event.tenant.as_deref(),
+                    &event.event.product.id,
+                )
+            },
+        )
+        .on(
+            move |event: TenantEvent<EventProductSoftDeletedV1>| async move {
+                invalidate(
+                    product_cache,
+                    event.tenant.as_deref(),
+                    &event.event.product.id,
+                )
+            },
+        )
+        .on(
+            move |event: TenantEvent<EventProductDeletedV1>| async move {
+                invalidate(
+                    product_cache,
+                    event.tenant.as_deref(),
+                    &event.event.product.id,
+                )
+            },
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use app_config::AppConfig;
+    use eventing::events::event_emmiter::SerialisableEvent;
+    use eventing::EventingPort;
+    use in_memory_persistance_repository::InMemorySingleTable;
+    use lambda_adaptor::in_process_bus::InProcessEventBus;
+    use models::models::outbox::OutboxItem;
+    use models::models::product::{
+        MutableProduct, Product, ProductRepositoryAdaptor, ProductRepositoryPort,
+    };
+    use models::models::product_cache::CachedProductRepositoryAdaptor;
+
+    use super::*;
+
+    fn test_event(_: &Product) -> OutboxItem {
+        OutboxItem::new("test".to_string(), 1, "{}".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_product_updated_in_its_tenant_is_refetched() {
+        let repository = InMemorySingleTable::new().repository("table");
+        let tenant_repository = repository.for_tenant(Tenant::new("acme").unwrap());
+        let cache = ProductCache::new(Duration::from_secs(60), 10);
+        let products = ProductRepositoryAdaptor::new(&tenant_repository);
+        let product = Product::new("Lamp".to_string(), 1000, "A lamp".to_string());
+        products
+            .product_create(&product, "tester", test_event)
+            .await
+            .unwrap();
+        let cached = CachedProductRepositoryAdaptor::new(
+            ProductRepositoryAdaptor::new(&tenant_repository),
+            &cache,
+        );
+        cached.product_get_by_id(&product.id).await.unwrap();
+
+        // written by another function, so this cache only learns of it from the event
+        let update = MutableProduct {
+            product_name: None,
+            price_cents: Some(2000),
+            description: None,
+        };
+        let updated = products
+            .product_update_by_id(&product.id, &update, None, "tester", test_event)
+            .await
+            .unwrap();
+        let read = cached
+            .product_get_by_id(&product.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.price_cents, 1000);
+
+        // as deployed, see product_cache_events.tf
+        let bus = InProcessEventBus::new(&AppConfig::default()).with_rule(
+            &["product_updated", "product_soft_deleted", "product_deleted"],
+            product_cache_event_dispatcher(&cache),
+        );
+        // what the outbox relay publishes for a product updated in acme
+        let item = OutboxItem {
+            tenant: "acme".to_string(),
+            ..EventProductUpdatedV1::new(updated).to_outbox_item()
+        };
+        bus.emit(&item).await.unwrap();
+
+        let read = cached
+            .product_get_by_id(&product.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.price_cents, 2000);
+    }
+}
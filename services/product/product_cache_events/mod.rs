@@ -0,0 +1 @@
+pub mod event_dispatcher;
@@ -1,9 +1,8 @@
-use crate::domain::product_create_core;
+use super::domain::product_create_core;
 
-use eventing::EventingPort;
 use http::{Error, Response, StatusCode};
 use http_port_tools::http_payload_decoder;
-use http_port_tools::port_objects::HttpPortRequest;
+use http_port_tools::port_objects::{etag, HttpPortRequest};
 use jsonschema::{Draft, JSONSchema};
 use lazy_static::lazy_static;
 use models::models::product::{Product, ProductRepositoryPort};
@@ -38,18 +37,19 @@ lazy_static! {
     };
 }
 
-pub async fn product_create_post_http_port<T1: ProductRepositoryPort, T2: EventingPort>(
+pub async fn product_create_post_http_port<T1: ProductRepositoryPort>(
     product_repository_port: &T1,
-    eventing_port: &T2,
     http_request: HttpPortRequest,
 ) -> Result<Response<String>, Error> {
+    let principal = http_request.principal();
     let payload = http_request.payload;
     let product = http_payload_decoder!(Product, PRODUCT_SCHEMA, payload);
-    match product_create_core(product_repository_port, eventing_port, product).await {
+    match product_create_core(product_repository_port, product, &principal).await {
         Ok(result) => {
             let resp = Response::builder()
                 .status(StatusCode::CREATED)
                 .header("content-type", "application/json")
+                .header("etag", etag(result.version))
                 .body(serde_json::to_string(&result).unwrap());
             Ok(resp.unwrap())
         }
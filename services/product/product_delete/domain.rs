@@ -1,24 +1,24 @@
 use error::HexagonalError;
-use eventing::{events::product::product_deleted::EventProductDeletedV1, EventingPort};
+use eventing::events::{
+    event_emmiter::SerialisableEvent, product::product_soft_deleted::EventProductSoftDeletedV1,
+};
+use models::models::outbox::OutboxItem;
 use models::models::product::{Product, ProductRepositoryPort};
 
-pub async fn product_delete_core<T1: ProductRepositoryPort, T2: EventingPort>(
+// product_deleted follows once product_purge removes it
+fn product_soft_deleted_event(product: &Product) -> OutboxItem {
+    EventProductSoftDeletedV1::new(product.clone()).to_outbox_item()
+}
+
+pub async fn product_delete_core<T1: ProductRepositoryPort>(
     product_repository_port: &T1,
-    eventing_port: &T2,
     id: &String,
+    expected_version: Option<u64>,
+    principal: &str,
 ) -> Result<Product, HexagonalError> {
-    let product = product_repository_port.product_delete_by_id(id).await;
-
-    if product.is_ok() {
-        let event_result = eventing_port
-            .emit(&EventProductDeletedV1::new(product.clone().unwrap()))
-            .await;
-        if event_result.is_err() {
-            return Err(event_result.unwrap_err());
-        }
-    }
-
-    product
+    product_repository_port
+        .product_delete_by_id(id, expected_version, principal, product_soft_deleted_event)
+        .await
 }
 
 #[cfg(test)]
@@ -30,38 +30,6 @@ mod tests {
     #[tokio::test]
     async fn test_product_delete_core() {
         let mut product_repository_port = models::models::product::MockProductRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
-
-        let product = Product {
-            id: uuid::Uuid::new_v4().to_string(),
-            product_name: "test".to_string(),
-            description: "test".to_string(),
-            price_cents: 10000,
-            created_at: default_time(),
-            updated_at: default_time(),
-        };
-
-        let result_product = product.clone();
-
-        product_repository_port
-            .expect_product_delete_by_id()
-            .returning(move |_| Ok(result_product.clone()));
-
-        eventing_port
-            .expect_emit::<EventProductDeletedV1>()
-            .times(1)
-            .returning(|_| Ok(()));
-
-        let result =
-            product_delete_core(&product_repository_port, &eventing_port, &product.id).await;
-
-        assert!(result.is_ok());
-    }
-
-    #[tokio::test]
-    async fn test_product_delete_core_eventing_error() {
-        let mut product_repository_port = models::models::product::MockProductRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
 
         let product = Product {
             id: uuid::Uuid::new_v4().to_string(),
@@ -70,35 +38,31 @@ mod tests {
             price_cents: 10000,
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         let result_product = product.clone();
 
         product_repository_port
             .expect_product_delete_by_id()
-            .returning(move |_| Ok(result_product.clone()));
-
-        eventing_port
-            .expect_emit::<EventProductDeletedV1>()
             .times(1)
-            .returning(|_| {
-                Err(HexagonalError {
-                    error: error::HexagonalErrorCode::AdaptorError,
-                    message: "test".to_string(),
-                    trace: "".to_string(),
-                })
+            .returning(move |_, _, _, outbox_event| {
+                assert_eq!(
+                    outbox_event(&result_product).event_type,
+                    "product_soft_deleted"
+                );
+                Ok(result_product.clone())
             });
 
         let result =
-            product_delete_core(&product_repository_port, &eventing_port, &product.id).await;
+            product_delete_core(&product_repository_port, &product.id, None, "tester").await;
 
-        assert!(result.is_err());
+        assert!(result.is_ok());
     }
 
     #[tokio::test]
     async fn test_product_delete_core_product_error() {
         let mut product_repository_port = models::models::product::MockProductRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
 
         let product = Product {
             id: uuid::Uuid::new_v4().to_string(),
@@ -107,11 +71,12 @@ mod tests {
             price_cents: 10000,
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         product_repository_port
             .expect_product_delete_by_id()
-            .returning(move |_| {
+            .returning(move |_, _, _, _| {
                 Err(HexagonalError {
                     error: error::HexagonalErrorCode::AdaptorError,
                     message: "test".to_string(),
@@ -119,13 +84,8 @@ mod tests {
                 })
             });
 
-        eventing_port
-            .expect_emit::<EventProductDeletedV1>()
-            .times(0)
-            .returning(|_| Ok(()));
-
         let result =
-            product_delete_core(&product_repository_port, &eventing_port, &product.id).await;
+            product_delete_core(&product_repository_port, &product.id, None, "tester").await;
 
         assert!(result.is_err());
     }
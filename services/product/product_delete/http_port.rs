@@ -1,14 +1,12 @@
-use crate::domain::product_delete_core;
+use super::domain::product_delete_core;
 
 use error::HexagonalError;
-use eventing::EventingPort;
 use http::{Error, Response, StatusCode};
 use http_port_tools::port_objects::HttpPortRequest;
 use models::models::product::ProductRepositoryPort;
 
-pub async fn product_delete_delete_http_port<T1: ProductRepositoryPort, T2: EventingPort>(
+pub async fn product_delete_delete_http_port<T1: ProductRepositoryPort>(
     product_repository_port: &T1,
-    eventing_port: &T2,
     http_request: HttpPortRequest,
 ) -> Result<Response<String>, Error> {
     let id = match http_request.path_parameters.first("id") {
@@ -22,7 +20,18 @@ pub async fn product_delete_delete_http_port<T1: ProductRepositoryPort, T2: Even
             return Ok(err.compile_to_http_response());
         }
     };
-    match product_delete_core(product_repository_port, eventing_port, &id.to_string()).await {
+    let expected_version = match http_request.if_match() {
+        Ok(expected_version) => expected_version,
+        Err(err) => return Ok(err.compile_to_http_response()),
+    };
+    match product_delete_core(
+        product_repository_port,
+        &id.to_string(),
+        expected_version,
+        &http_request.principal(),
+    )
+    .await
+    {
         Ok(product) => {
             let resp = Response::builder()
                 .status(StatusCode::OK)
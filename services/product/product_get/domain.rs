@@ -25,6 +25,7 @@ mod tests {
             price_cents: 10000,
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         let result_product = product.clone();
@@ -49,6 +50,7 @@ mod tests {
             price_cents: 10000,
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         product_repository_port
@@ -1,21 +1,37 @@
 mod domain;
 mod http_port;
+#[path = "../product_cache_events/mod.rs"]
+mod product_cache_events;
 use crate::http_port::product_get_get_http_port;
+use crate::product_cache_events::event_dispatcher::product_cache_event_dispatcher;
 
 use http_port_tools::port_objects::{HttpPortRequest, HttpPortResponse};
 
 use lambda_adaptor::common_lambda_adaptor;
-use lambda_http::{run, service_fn, Error, IntoResponse};
-use models::models::product::ProductRepositoryPort;
+use lambda_adaptor::http_with_events::run_http_with_events;
+use lambda_http::{Error, IntoResponse};
+use models::models::product::ProductRepositoryAdaptor;
+use models::models::product_cache::{CachedProductRepositoryAdaptor, ProductCache};
+use persistance_repository::DynamoDBSingleTableRepository;
 
-async fn http_lambda_driving_adaptor<T1: ProductRepositoryPort>(
-    product_repository_port: &T1,
+async fn http_lambda_driving_adaptor(
+    dynamo_db_repository: &DynamoDBSingleTableRepository,
+    product_cache: &ProductCache,
     event: lambda_http::Request,
 ) -> Result<lambda_http::Response<lambda_http::Body>, Error> {
     let http_request = HttpPortRequest::from(event);
-    let generic_http_response = product_get_get_http_port(product_repository_port, http_request)
-        .await
-        .unwrap();
+    let generic_http_response = match http_request.scoped_repository(dynamo_db_repository) {
+        Ok(tenant_repository) => {
+            let product_repository = CachedProductRepositoryAdaptor::new(
+                ProductRepositoryAdaptor::new(&tenant_repository),
+                product_cache,
+            );
+            product_get_get_http_port(&product_repository, http_request)
+                .await
+                .unwrap()
+        }
+        Err(err) => err.compile_to_http_response(),
+    };
     let lambda_http_response = HttpPortResponse(generic_http_response)
         .into_response()
         .await;
@@ -28,15 +44,22 @@ async fn main() -> Result<(), Error> {
     common_lambda_adaptor!();
 
     // Provision required repositories once in the main function
+    let config = app_config::AppConfig::load()?;
     let sdk_credential_meta_repository =
         sdk_credential_meta_repository::SdkCredentialsMetaRepository::new().await;
-    let dynamo_db_repository =
-        persistance_repository::DynamoDBSingleTableRepository::new(&sdk_credential_meta_repository);
-    let product_repository =
-        models::models::product::ProductRepositoryAdaptor::new(&dynamo_db_repository);
+    let dynamo_db_repository = persistance_repository::DynamoDBSingleTableRepository::new(
+        &sdk_credential_meta_repository,
+        &config,
+    )?
+    .with_operation("product_get");
+    // Kept across warm invocations
+    let product_cache = ProductCache::new(config.product_cache.ttl, config.product_cache.capacity);
 
-    run(service_fn(|event| {
-        http_lambda_driving_adaptor(&product_repository, event)
-    }))
+    // Also the target of the product_cache rule, see product_cache_events.tf
+    let event_dispatcher = product_cache_event_dispatcher(&product_cache);
+    run_http_with_events(
+        |event| http_lambda_driving_adaptor(&dynamo_db_repository, &product_cache, event),
+        &event_dispatcher,
+    )
     .await
 }
@@ -1,8 +1,8 @@
-use crate::domain::product_get_core;
+use super::domain::product_get_core;
 
 use error::HexagonalError;
 use http::{Error, Response, StatusCode};
-use http_port_tools::port_objects::HttpPortRequest;
+use http_port_tools::port_objects::{etag, HttpPortRequest};
 use models::models::product::ProductRepositoryPort;
 
 pub async fn product_get_get_http_port<T1: ProductRepositoryPort>(
@@ -26,6 +26,7 @@ pub async fn product_get_get_http_port<T1: ProductRepositoryPort>(
                 let resp = Response::builder()
                     .status(StatusCode::OK)
                     .header("content-type", "application/json")
+                    .header("etag", etag(result.version))
                     .body(serde_json::to_string(&result).unwrap());
                 Ok(resp.unwrap())
             }
@@ -0,0 +1,50 @@
+use error::HexagonalError;
+use models::models::history::HistoryRecord;
+use models::models::product::ProductRepositoryPort;
+use persistance_repository::{Page, PageRequest};
+
+pub async fn product_history_core<T1: ProductRepositoryPort>(
+    product_repository_port: &T1,
+    id: &String,
+    page: &PageRequest,
+) -> Result<Page<HistoryRecord>, HexagonalError> {
+    product_repository_port
+        .product_history_by_id(id, page)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_product_history_core_not_found() {
+        // Arrange
+        let mut product_repository_port = models::models::product::MockProductRepositoryPort::new();
+
+        product_repository_port
+            .expect_product_history_by_id()
+            .times(1)
+            .returning(move |_, _| {
+                Err(HexagonalError {
+                    error: error::HexagonalErrorCode::NotFound,
+                    message: "test".to_string(),
+                    trace: "".to_string(),
+                })
+            });
+
+        // Act
+        let result = product_history_core(
+            &product_repository_port,
+            &"id".to_string(),
+            &PageRequest::default(),
+        )
+        .await;
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err().error,
+            error::HexagonalErrorCode::NotFound
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use error::HexagonalError;
+use eventing::events::{
+    event_emmiter::SerialisableEvent, product::product_deleted::EventProductDeletedV1,
+};
+use models::models::outbox::OutboxItem;
+use models::models::product::{Product, ProductRepositoryPort};
+
+// The final deletion, carts drop the product off the back of it
+fn product_deleted_event(product: &Product) -> OutboxItem {
+    EventProductDeletedV1::new(product.clone()).to_outbox_item()
+}
+
+// Purges a page at a time until a page comes back short, returning how many were purged
+pub async fn product_purge_core<T1: ProductRepositoryPort>(
+    product_repository_port: &T1,
+    page_size: i32,
+) -> Result<usize, HexagonalError> {
+    let mut purged = 0;
+    loop {
+        let page = product_repository_port
+            .product_purge_deleted(page_size, product_deleted_event)
+            .await?;
+        purged += page.len();
+        if (page.len() as i32) < page_size {
+            return Ok(purged);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::Sequence;
+    use models::default_time;
+
+    use super::*;
+
+    fn product(id: &str) -> Product {
+        Product {
+            id: id.to_string(),
+            product_name: "test".to_string(),
+            description: "test".to_string(),
+            price_cents: 10000,
+            created_at: default_time(),
+            updated_at: default_time(),
+            version: 2,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_product_purge_core_stops_on_short_page() {
+        // Arrange
+        let mut product_repository_port = models::models::product::MockProductRepositoryPort::new();
+        let mut sequence = Sequence::new();
+
+        product_repository_port
+            .expect_product_purge_deleted()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|limit, outbox_event| {
+                assert_eq!(limit, 2);
+                assert_eq!(outbox_event(&product("a")).event_type, "product_deleted");
+                Ok(vec![product("a"), product("b")])
+            });
+        product_repository_port
+            .expect_product_purge_deleted()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(vec![product("c")]));
+
+        // Act
+        let result = product_purge_core(&product_repository_port, 2).await;
+
+        // Assert
+        assert_eq!(result.unwrap(), 3);
+    }
+}
@@ -0,0 +1,51 @@
+mod domain;
+mod schedule_port;
+
+use crate::schedule_port::product_purge_schedule_port;
+
+use aws_lambda_events::cloudwatch_events::CloudWatchEvent;
+use lambda_adaptor::common_lambda_adaptor;
+use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+
+use models::models::product::ProductRepositoryAdaptor;
+use persistance_repository::{DynamoDBSingleTableRepository, Tenant};
+
+async fn eventbridge_lambda_driving_adaptor(
+    dynamo_db_repository: &DynamoDBSingleTableRepository,
+    tenants: &[Tenant],
+    _event: LambdaEvent<CloudWatchEvent>,
+) -> Result<(), Error> {
+    // Each tenant's deleted partition is its own, so they are swept one at a time
+    let mut purged = 0;
+    for tenant in tenants {
+        let tenant_repository = dynamo_db_repository.for_tenant(tenant.clone());
+        let product_repository = ProductRepositoryAdaptor::new(&tenant_repository);
+        purged += product_purge_schedule_port(&product_repository)
+            .await
+            .map_err(|err| err.to_string())?;
+    }
+    tracing::info!("Purged {} deleted products", purged);
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    // Common snippit from all lambda functions
+    common_lambda_adaptor!();
+
+    // Provision required repositories once in the main function
+    let config = app_config::AppConfig::load()?;
+    let sdk_credential_meta_repository =
+        sdk_credential_meta_repository::SdkCredentialsMetaRepository::new().await;
+    let dynamo_db_repository = persistance_repository::DynamoDBSingleTableRepository::new(
+        &sdk_credential_meta_repository,
+        &config,
+    )?
+    .with_operation("product_purge");
+    let tenants = Tenant::configured(&config)?;
+
+    run(service_fn(|event| {
+        eventbridge_lambda_driving_adaptor(&dynamo_db_repository, &tenants, event)
+    }))
+    .await
+}
@@ -0,0 +1,13 @@
+use super::domain::product_purge_core;
+
+use error::HexagonalError;
+use models::models::product::ProductRepositoryPort;
+
+const PURGE_PAGE_SIZE: i32 = 25;
+
+// Runs on a schedule, each run purges every product whose retention window has passed
+pub async fn product_purge_schedule_port<T1: ProductRepositoryPort>(
+    product_repository_port: &T1,
+) -> Result<usize, HexagonalError> {
+    product_purge_core(product_repository_port, PURGE_PAGE_SIZE).await
+}
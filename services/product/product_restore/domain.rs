@@ -0,0 +1,56 @@
+use error::HexagonalError;
+use eventing::events::{
+    event_emmiter::SerialisableEvent, product::product_restored::EventProductRestoredV1,
+};
+use models::models::outbox::OutboxItem;
+use models::models::product::{Product, ProductRepositoryPort};
+
+fn product_restored_event(product: &Product) -> OutboxItem {
+    EventProductRestoredV1::new(product.clone()).to_outbox_item()
+}
+
+pub async fn product_restore_core<T1: ProductRepositoryPort>(
+    product_repository_port: &T1,
+    id: &String,
+    principal: &str,
+) -> Result<Product, HexagonalError> {
+    product_repository_port
+        .product_restore_by_id(id, principal, product_restored_event)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use models::default_time;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_product_restore_core() {
+        let mut product_repository_port = models::models::product::MockProductRepositoryPort::new();
+
+        let product = Product {
+            id: uuid::Uuid::new_v4().to_string(),
+            product_name: "test".to_string(),
+            description: "test".to_string(),
+            price_cents: 10000,
+            created_at: default_time(),
+            updated_at: default_time(),
+            version: 3,
+        };
+
+        let result_product = product.clone();
+
+        product_repository_port
+            .expect_product_restore_by_id()
+            .times(1)
+            .returning(move |_, _, outbox_event| {
+                assert_eq!(outbox_event(&result_product).event_type, "product_restored");
+                Ok(result_product.clone())
+            });
+
+        let result = product_restore_core(&product_repository_port, &product.id, "tester").await;
+
+        assert_eq!(result.unwrap(), product);
+    }
+}
@@ -0,0 +1,40 @@
+use super::domain::product_restore_core;
+
+use error::HexagonalError;
+use http::{Error, Response, StatusCode};
+use http_port_tools::port_objects::{etag, HttpPortRequest};
+use models::models::product::ProductRepositoryPort;
+
+pub async fn product_restore_post_http_port<T1: ProductRepositoryPort>(
+    product_repository_port: &T1,
+    http_request: HttpPortRequest,
+) -> Result<Response<String>, Error> {
+    let id = match http_request.path_parameters.first("id") {
+        Some(value) => value,
+        None => {
+            let err = HexagonalError {
+                error: error::HexagonalErrorCode::BadInput,
+                message: "id is required".to_string(),
+                trace: "".to_string(),
+            };
+            return Ok(err.compile_to_http_response());
+        }
+    };
+    match product_restore_core(
+        product_repository_port,
+        &id.to_string(),
+        &http_request.principal(),
+    )
+    .await
+    {
+        Ok(product) => {
+            let resp = Response::builder()
+                .status(StatusCode::OK)
+                .header("content-type", "application/json")
+                .header("etag", etag(product.version))
+                .body(serde_json::to_string(&product).unwrap());
+            Ok(resp.unwrap())
+        }
+        Err(err) => Ok(err.compile_to_http_response()),
+    }
+}
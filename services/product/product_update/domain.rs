@@ -1,12 +1,20 @@
 use error::HexagonalError;
-use eventing::{events::product::product_updated::EventProductUpdatedV1, EventingPort};
+use eventing::events::{
+    event_emmiter::SerialisableEvent, product::product_updated::EventProductUpdatedV1,
+};
+use models::models::outbox::OutboxItem;
 use models::models::product::{MutableProduct, Product, ProductRepositoryPort};
 
-pub async fn product_update_core<T1: ProductRepositoryPort, T2: EventingPort>(
+fn product_updated_event(product: &Product) -> OutboxItem {
+    EventProductUpdatedV1::new(product.clone()).to_outbox_item()
+}
+
+pub async fn product_update_core<T1: ProductRepositoryPort>(
     product_repository_port: &T1,
-    eventing_port: &T2,
     id: &String,
     product_updates: MutableProduct,
+    expected_version: Option<u64>,
+    principal: &str,
 ) -> Result<Product, HexagonalError> {
     if product_updates.price_cents.is_none()
         && product_updates.product_name.is_none()
@@ -19,20 +27,15 @@ pub async fn product_update_core<T1: ProductRepositoryPort, T2: EventingPort>(
         });
     }
 
-    let product = product_repository_port
-        .product_update_by_id(id, &product_updates)
-        .await;
-
-    if product.is_ok() {
-        let event_result = eventing_port
-            .emit(&EventProductUpdatedV1::new(product.clone().unwrap()))
-            .await;
-        if event_result.is_err() {
-            return Err(event_result.unwrap_err());
-        }
-    }
-
-    product
+    product_repository_port
+        .product_update_by_id(
+            id,
+            &product_updates,
+            expected_version,
+            principal,
+            product_updated_event,
+        )
+        .await
 }
 
 #[cfg(test)]
@@ -45,7 +48,6 @@ mod tests {
     async fn test_product_update_core() {
         // Arrange
         let mut product_repository_port = models::models::product::MockProductRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
 
         let product = Product {
             id: uuid::Uuid::new_v4().to_string(),
@@ -54,6 +56,7 @@ mod tests {
             price_cents: 10000,
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         let mutable_product = MutableProduct {
@@ -66,82 +69,30 @@ mod tests {
 
         product_repository_port
             .expect_product_update_by_id()
-            .returning(move |_, _| Ok(return_product.clone()));
-
-        eventing_port
-            .expect_emit::<EventProductUpdatedV1>()
             .times(1)
-            .returning(|_| Ok(()));
-
-        // Act
-        let result = product_update_core(
-            &product_repository_port,
-            &eventing_port,
-            &product.id,
-            mutable_product,
-        )
-        .await;
-
-        // Assert
-        assert!(result.is_ok());
-    }
-
-    #[tokio::test]
-    async fn test_product_update_core_eventing_error() {
-        // Arrange
-        let mut product_repository_port = models::models::product::MockProductRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
-
-        let product = Product {
-            id: uuid::Uuid::new_v4().to_string(),
-            product_name: "test".to_string(),
-            description: "test".to_string(),
-            price_cents: 10000,
-            created_at: default_time(),
-            updated_at: default_time(),
-        };
-
-        let mutable_product = MutableProduct {
-            product_name: Some("test".to_string()),
-            description: Some("test".to_string()),
-            price_cents: Some(10000),
-        };
-
-        let return_product = product.clone();
-
-        product_repository_port
-            .expect_product_update_by_id()
-            .returning(move |_, _| Ok(return_product.clone()));
-
-        eventing_port
-            .expect_emit::<EventProductUpdatedV1>()
-            .times(1)
-            .returning(|_| {
-                Err(HexagonalError {
-                    error: error::HexagonalErrorCode::AdaptorError,
-                    message: "".to_string(),
-                    trace: "".to_string(),
-                })
+            .returning(move |_, _, _, _, outbox_event| {
+                assert_eq!(outbox_event(&return_product).event_type, "product_updated");
+                Ok(return_product.clone())
             });
 
         // Act
         let result = product_update_core(
             &product_repository_port,
-            &eventing_port,
             &product.id,
             mutable_product,
+            None,
+            "tester",
         )
         .await;
 
         // Assert
-        assert!(result.is_err());
+        assert!(result.is_ok());
     }
 
     #[tokio::test]
     async fn test_product_update_core_error_from_repository() {
         // Arrange
         let mut product_repository_port = models::models::product::MockProductRepositoryPort::new();
-        let eventing_port = eventing::MockEventingPort::new();
 
         let product = Product {
             id: uuid::Uuid::new_v4().to_string(),
@@ -150,6 +101,7 @@ mod tests {
             price_cents: 10000,
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         let mutable_product = MutableProduct {
@@ -160,7 +112,7 @@ mod tests {
 
         product_repository_port
             .expect_product_update_by_id()
-            .returning(move |_, _| {
+            .returning(move |_, _, _, _, _| {
                 Err(HexagonalError {
                     error: error::HexagonalErrorCode::AdaptorError,
                     message: "".to_string(),
@@ -171,9 +123,10 @@ mod tests {
         // Act
         let result = product_update_core(
             &product_repository_port,
-            &eventing_port,
             &product.id,
             mutable_product,
+            None,
+            "tester",
         )
         .await;
 
@@ -2,23 +2,27 @@ mod domain;
 mod http_port;
 use crate::http_port::product_update_put_http_port;
 
-use eventing::EventingPort;
 use http_port_tools::port_objects::{HttpPortRequest, HttpPortResponse};
 
 use lambda_adaptor::common_lambda_adaptor;
 use lambda_http::{run, service_fn, Error, IntoResponse};
-use models::models::product::ProductRepositoryPort;
+use models::models::product::ProductRepositoryAdaptor;
+use persistance_repository::DynamoDBSingleTableRepository;
 
-async fn http_lambda_driving_adaptor<T1: ProductRepositoryPort, T2: EventingPort>(
-    product_repository_port: &T1,
-    eventing_port: &T2,
+async fn http_lambda_driving_adaptor(
+    dynamo_db_repository: &DynamoDBSingleTableRepository,
     event: lambda_http::Request,
 ) -> Result<lambda_http::Response<lambda_http::Body>, Error> {
     let http_request = HttpPortRequest::from(event);
-    let generic_http_response =
-        product_update_put_http_port(product_repository_port, eventing_port, http_request)
-            .await
-            .unwrap();
+    let generic_http_response = match http_request.scoped_repository(dynamo_db_repository) {
+        Ok(tenant_repository) => {
+            let product_repository = ProductRepositoryAdaptor::new(&tenant_repository);
+            product_update_put_http_port(&product_repository, http_request)
+                .await
+                .unwrap()
+        }
+        Err(err) => err.compile_to_http_response(),
+    };
     let lambda_http_response = HttpPortResponse(generic_http_response)
         .into_response()
         .await;
@@ -31,16 +35,17 @@ async fn main() -> Result<(), Error> {
     common_lambda_adaptor!();
 
     // Provision required repositories once in the main function
+    let config = app_config::AppConfig::load()?;
     let sdk_credential_meta_repository =
         sdk_credential_meta_repository::SdkCredentialsMetaRepository::new().await;
-    let dynamo_db_repository =
-        persistance_repository::DynamoDBSingleTableRepository::new(&sdk_credential_meta_repository);
-    let eventing_repository = eventing::EventingRepository::new(&sdk_credential_meta_repository);
-    let product_repository =
-        models::models::product::ProductRepositoryAdaptor::new(&dynamo_db_repository);
+    let dynamo_db_repository = persistance_repository::DynamoDBSingleTableRepository::new(
+        &sdk_credential_meta_repository,
+        &config,
+    )?
+    .with_operation("product_update");
 
     run(service_fn(|event| {
-        http_lambda_driving_adaptor(&product_repository, &eventing_repository, event)
+        http_lambda_driving_adaptor(&dynamo_db_repository, event)
     }))
     .await
 }
@@ -1,10 +1,9 @@
-use crate::domain::product_update_core;
+use super::domain::product_update_core;
 
 use error::HexagonalError;
-use eventing::EventingPort;
 use http::{Error, Response, StatusCode};
 use http_port_tools::http_payload_decoder;
-use http_port_tools::port_objects::HttpPortRequest;
+use http_port_tools::port_objects::{etag, HttpPortRequest};
 use jsonschema::{Draft, JSONSchema};
 use lazy_static::lazy_static;
 use models::models::product::{MutableProduct, ProductRepositoryPort};
@@ -35,9 +34,8 @@ lazy_static! {
     };
 }
 
-pub async fn product_update_put_http_port<T1: ProductRepositoryPort, T2: EventingPort>(
+pub async fn product_update_put_http_port<T1: ProductRepositoryPort>(
     product_repository_port: &T1,
-    eventing_port: &T2,
     http_request: HttpPortRequest,
 ) -> Result<Response<String>, Error> {
     let id = match http_request.path_parameters.first("id") {
@@ -51,13 +49,19 @@ pub async fn product_update_put_http_port<T1: ProductRepositoryPort, T2: Eventin
             return Ok(err.compile_to_http_response());
         }
     };
+    let expected_version = match http_request.if_match() {
+        Ok(expected_version) => expected_version,
+        Err(err) => return Ok(err.compile_to_http_response()),
+    };
+    let principal = http_request.principal();
     let payload = http_request.payload;
     let product_updates = http_payload_decoder!(MutableProduct, PRODUCT_SCHEMA, payload);
     match product_update_core(
         product_repository_port,
-        eventing_port,
         &id.to_string(),
         product_updates,
+        expected_version,
+        &principal,
     )
     .await
     {
@@ -65,6 +69,7 @@ pub async fn product_update_put_http_port<T1: ProductRepositoryPort, T2: Eventin
             let resp = Response::builder()
                 .status(StatusCode::OK)
                 .header("content-type", "application/json")
+                .header("etag", etag(product.version))
                 .body(serde_json::to_string(&product).unwrap());
             Ok(resp.unwrap())
         }
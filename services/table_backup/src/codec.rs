@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::primitives::Blob;
+use aws_sdk_dynamodb::types::AttributeValue;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Map, Value};
+
+// Items are written in DynamoDB's own JSON shape, {"Pkey": {"S": "USER#a"}, ...}, so the type of
+// every attribute survives the round trip and the files read the same as the AWS CLI's output
+pub fn item_to_json(item: &HashMap<String, AttributeValue>) -> Value {
+    Value::Object(
+        item.iter()
+            .map(|(name, value)| (name.clone(), attribute_to_json(value)))
+            .collect(),
+    )
+}
+
+pub fn item_from_json(value: &Value) -> Result<HashMap<String, AttributeValue>, String> {
+    value
+        .as_object()
+        .ok_or("item must be an object".to_string())?
+        .iter()
+        .map(|(name, value)| {
+            attribute_from_json(value)
+                .map(|value| (name.clone(), value))
+                .map_err(|err| format!("{}: {}", name, err))
+        })
+        .collect()
+}
+
+fn attribute_to_json(value: &AttributeValue) -> Value {
+    let encode = |blob: &Blob| Value::String(STANDARD.encode(blob.as_ref()));
+    match value {
+        AttributeValue::S(s) => json!({ "S": s }),
+        AttributeValue::N(n) => json!({ "N": n }),
+        AttributeValue::B(b) => json!({ "B": encode(b) }),
+        AttributeValue::Bool(b) => json!({ "BOOL": b }),
+        AttributeValue::Null(is_null) => json!({ "NULL": is_null }),
+        AttributeValue::Ss(ss) => json!({ "SS": ss }),
+        AttributeValue::Ns(ns) => json!({ "NS": ns }),
+        AttributeValue::Bs(bs) => json!({ "BS": bs.iter().map(encode).collect::<Vec<Value>>() }),
+        AttributeValue::L(l) => {
+            json!({ "L": l.iter().map(attribute_to_json).collect::<Vec<Value>>() })
+        }
+        AttributeValue::M(m) => json!({ "M": item_to_json(m) }),
+        // only reachable with an SDK newer than this tool
+        _ => json!({ "NULL": true }),
+    }
+}
+
+fn attribute_from_json(value: &Value) -> Result<AttributeValue, String> {
+    let (attribute_type, inner) = match value
+        .as_object()
+        .map(Map::iter)
+        .map(|mut i| (i.next(), i.next()))
+    {
+        Some((Some(typed), None)) => typed,
+        _ => return Err("attribute must be an object with a single type".to_string()),
+    };
+    let string = |value: &Value| {
+        value
+            .as_str()
+            .map(str::to_string)
+            .ok_or(format!("{} must be a string", attribute_type))
+    };
+    let strings = |value: &Value| {
+        value
+            .as_array()
+            .ok_or(format!("{} must be a list", attribute_type))?
+            .iter()
+            .map(string)
+            .collect::<Result<Vec<String>, String>>()
+    };
+    let decode = |value: String| {
+        STANDARD
+            .decode(value)
+            .map(Blob::new)
+            .map_err(|err| format!("{} is not base64: {}", attribute_type, err))
+    };
+    match attribute_type.as_str() {
+        "S" => Ok(AttributeValue::S(string(inner)?)),
+        "N" => Ok(AttributeValue::N(string(inner)?)),
+        "B" => Ok(AttributeValue::B(decode(string(inner)?)?)),
+        "BOOL" => inner
+            .as_bool()
+            .map(AttributeValue::Bool)
+            .ok_or("BOOL must be a boolean".to_string()),
+        "NULL" => inner
+            .as_bool()
+            .map(AttributeValue::Null)
+            .ok_or("NULL must be a boolean".to_string()),
+        "SS" => Ok(AttributeValue::Ss(strings(inner)?)),
+        "NS" => Ok(AttributeValue::Ns(strings(inner)?)),
+        "BS" => Ok(AttributeValue::Bs(
+            strings(inner)?
+                .into_iter()
+                .map(decode)
+                .collect::<Result<Vec<Blob>, String>>()?,
+        )),
+        "L" => Ok(AttributeValue::L(
+            inner
+                .as_array()
+                .ok_or("L must be a list".to_string())?
+                .iter()
+                .map(attribute_from_json)
+                .collect::<Result<Vec<AttributeValue>, String>>()?,
+        )),
+        "M" => Ok(AttributeValue::M(item_from_json(inner)?)),
+        other => Err(format!("unknown attribute type {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_round_trip() {
+        let item = HashMap::from([
+            ("Pkey".to_string(), AttributeValue::S("USER#a".to_string())),
+            ("count".to_string(), AttributeValue::N("3".to_string())),
+            (
+                "avatar".to_string(),
+                AttributeValue::B(Blob::new(vec![0, 1, 255])),
+            ),
+            ("active".to_string(), AttributeValue::Bool(true)),
+            (
+                "tags".to_string(),
+                AttributeValue::Ss(vec!["a".to_string()]),
+            ),
+            (
+                "history".to_string(),
+                AttributeValue::L(vec![AttributeValue::M(HashMap::from([(
+                    "at".to_string(),
+                    AttributeValue::Null(true),
+                )]))]),
+            ),
+        ]);
+
+        let encoded = item_to_json(&item);
+        assert_eq!(encoded["avatar"], json!({ "B": "AAH/" }));
+        assert_eq!(item_from_json(&encoded).unwrap(), item);
+    }
+
+    #[test]
+    fn test_item_from_json_rejects_untyped_values() {
+        assert!(item_from_json(&json!({ "Pkey": "USER#a" })).is_err());
+        assert!(item_from_json(&json!({ "Pkey": { "S": "a", "N": "1" } })).is_err());
+        assert!(item_from_json(&json!({ "Pkey": { "X": "a" } })).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+pub mod codec;
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use aws_sdk_dynamodb::types::{AttributeValue, PutRequest, WriteRequest};
+use error::HexagonalError;
+use futures::future::join_all;
+use persistance_repository::{DynamoDBSingleTableRepository, MeasuredSend, Tenant};
+use tokio::sync::mpsc;
+
+// Lines are restored a batch at a time rather than reading the whole file in first
+const IMPORT_BATCH_SIZE: usize = 500;
+
+// Which items to take, by the prefix of their Pkey, e.g. USER#, PRODUCT# or CART#. Prefixes match
+// the key the models built whatever tenant it's in, or the stored key, so TENANT#acme# takes every
+// item of that tenant. No prefixes means every item, outbox items included
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntityFilter {
+    pub prefixes: Vec<String>,
+}
+
+impl EntityFilter {
+    pub fn matches(&self, item: &HashMap<String, AttributeValue>) -> bool {
+        if self.prefixes.is_empty() {
+            return true;
+        }
+        match item.get("Pkey").and_then(|p_key| p_key.as_s().ok()) {
+            Some(p_key) => {
+                let (_, model_key) = Tenant::of_key(p_key);
+                self.prefixes
+                    .iter()
+                    .any(|prefix| model_key.starts_with(prefix) || p_key.starts_with(prefix))
+            }
+            None => false,
+        }
+    }
+}
+
+fn adaptor_error(message: &str, trace: String) -> HexagonalError {
+    HexagonalError {
+        error: error::HexagonalErrorCode::AdaptorError,
+        message: message.to_string(),
+        trace,
+    }
+}
+
+// Scans the table in `segments` parallel segments and writes one item per line, returning how
+// many were written. Lines come out in no particular order.
+pub async fn export_table<W: Write>(
+    repository: &DynamoDBSingleTableRepository,
+    segments: i32,
+    filter: &EntityFilter,
+    writer: &mut W,
+) -> Result<usize, HexagonalError> {
+    let (sender, mut receiver) = mpsc::channel::<HashMap<String, AttributeValue>>(1000);
+    let scans = join_all(
+        (0..segments).map(|segment| scan_segment(repository, segment, segments, sender.clone())),
+    );
+    // the channel closes once every segment has dropped its sender
+    drop(sender);
+
+    let write_lines = async {
+        let mut written = 0;
+        while let Some(item) = receiver.recv().await {
+            if !filter.matches(&item) {
+                continue;
+            }
+            writeln!(writer, "{}", codec::item_to_json(&item))
+                .map_err(|e| adaptor_error("Unable to write export", e.to_string()))?;
+            written += 1;
+        }
+        Ok::<usize, HexagonalError>(written)
+    };
+
+    let (scanned, written) = tokio::join!(scans, write_lines);
+    scanned
+        .into_iter()
+        .collect::<Result<Vec<()>, HexagonalError>>()?;
+    writer
+        .flush()
+        .map_err(|e| adaptor_error("Unable to write export", e.to_string()))?;
+    written
+}
+
+async fn scan_segment(
+    repository: &DynamoDBSingleTableRepository,
+    segment: i32,
+    total_segments: i32,
+    sender: mpsc::Sender<HashMap<String, AttributeValue>>,
+) -> Result<(), HexagonalError> {
+    let mut exclusive_start_key = None;
+    loop {
+        let output = repository
+            .scan()
+            .segment(segment)
+            .total_segments(total_segments)
+            .set_exclusive_start_key(exclusive_start_key)
+            .send_measured(repository)
+            .await
+            .map_err(|e| {
+                adaptor_error("Unable to scan table", e.into_service_error().to_string())
+            })?;
+        for item in output.items.unwrap_or_default() {
+            // the writer only goes away if it failed, which it reports itself
+            if sender.send(item).await.is_err() {
+                return Ok(());
+            }
+        }
+        match output.last_evaluated_key {
+            Some(key) => exclusive_start_key = Some(key),
+            None => return Ok(()),
+        }
+    }
+}
+
+// Restores an export, overwriting any item with the same key. Returns how many items were
+// written. Blank lines are skipped, a line that isn't an item stops the import with its number.
+pub async fn import_table<R: BufRead>(
+    repository: &DynamoDBSingleTableRepository,
+    filter: &EntityFilter,
+    reader: R,
+) -> Result<usize, HexagonalError> {
+    let mut written = 0;
+    let mut batch = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| adaptor_error("Unable to read import", e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let item = serde_json::from_str(&line)
+            .map_err(|e| e.to_string())
+            .and_then(|value| codec::item_from_json(&value))
+            .map_err(|e| HexagonalError {
+                error: error::HexagonalErrorCode::BadInput,
+                message: format!("Line {} is not an item", index + 1),
+                trace: e,
+            })?;
+        if filter.matches(&item) {
+            batch.push(item);
+        }
+        if batch.len() == IMPORT_BATCH_SIZE {
+            written += write_items(repository, std::mem::take(&mut batch)).await?;
+        }
+    }
+    written += write_items(repository, batch).await?;
+    Ok(written)
+}
+
+async fn write_items(
+    repository: &DynamoDBSingleTableRepository,
+    items: Vec<HashMap<String, AttributeValue>>,
+) -> Result<usize, HexagonalError> {
+    let count = items.len();
+    let puts = items
+        .into_iter()
+        .map(|item| {
+            // Item is always set so unwrap is safe
+            let put = PutRequest::builder().set_item(Some(item)).build().unwrap();
+            WriteRequest::builder().put_request(put).build()
+        })
+        .collect();
+    repository
+        .batch_write(puts)
+        .await
+        .map_err(|e| adaptor_error("Unable to write items", e.to_string()))?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use in_memory_persistance_repository::InMemorySingleTable;
+
+    use super::*;
+
+    fn item(p_key: &str, s_key: &str) -> HashMap<String, AttributeValue> {
+        HashMap::from([
+            ("Pkey".to_string(), AttributeValue::S(p_key.to_string())),
+            ("Skey".to_string(), AttributeValue::S(s_key.to_string())),
+            ("quantity".to_string(), AttributeValue::N("2".to_string())),
+        ])
+    }
+
+    async fn seeded_table() -> DynamoDBSingleTableRepository {
+        let repository = InMemorySingleTable::new().repository("table");
+        let mut items = vec![
+            item("USER#a", "USER#a"),
+            item("USER#EMAIL#a@example.com", "USER#EMAIL#a@example.com"),
+            item("OUTBOX#1", "OUTBOX#1"),
+            item("TENANT#acme#USER#b", "USER#b"),
+            item("TENANT#acme#CART#USER#b", "CART#PRODUCT#1"),
+        ];
+        for i in 0..40 {
+            items.push(item(&format!("PRODUCT#{}", i), "PRODUCT#"));
+            items.push(item("CART#USER#a", &format!("CART#PRODUCT#{}", i)));
+        }
+        write_items(&repository, items).await.unwrap();
+        repository
+    }
+
+    async fn all_items(repository: &DynamoDBSingleTableRepository) -> Vec<String> {
+        let mut file = Vec::new();
+        export_table(repository, 1, &EntityFilter::default(), &mut file)
+            .await
+            .unwrap();
+        let mut lines: Vec<String> = String::from_utf8(file)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        lines.sort();
+        lines
+    }
+
+    #[tokio::test]
+    async fn test_export_then_import_round_trips() {
+        let source = seeded_table().await;
+        let mut file = Vec::new();
+        let exported = export_table(&source, 4, &EntityFilter::default(), &mut file)
+            .await
+            .unwrap();
+        assert_eq!(exported, 85);
+
+        let target = InMemorySingleTable::new().repository("table");
+        let imported = import_table(&target, &EntityFilter::default(), file.as_slice())
+            .await
+            .unwrap();
+        assert_eq!(imported, 85);
+        assert_eq!(all_items(&target).await, all_items(&source).await);
+    }
+
+    #[tokio::test]
+    async fn test_prefix_filter_on_export_and_import() {
+        let source = seeded_table().await;
+        let users = EntityFilter {
+            prefixes: vec!["USER#".to_string()],
+        };
+        let mut file = Vec::new();
+        assert_eq!(
+            export_table(&source, 3, &users, &mut file).await.unwrap(),
+            3
+        );
+        let acme = EntityFilter {
+            prefixes: vec!["TENANT#acme#".to_string()],
+        };
+        let mut file = Vec::new();
+        assert_eq!(export_table(&source, 3, &acme, &mut file).await.unwrap(), 2);
+
+        let mut file = Vec::new();
+        export_table(&source, 3, &EntityFilter::default(), &mut file)
+            .await
+            .unwrap();
+        let target = InMemorySingleTable::new().repository("table");
+        let carts_and_products = EntityFilter {
+            prefixes: vec!["CART#".to_string(), "PRODUCT#".to_string()],
+        };
+        assert_eq!(
+            import_table(&target, &carts_and_products, file.as_slice())
+                .await
+                .unwrap(),
+            81
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_reports_bad_line() {
+        let target = InMemorySingleTable::new().repository("table");
+        let file = "{\"Pkey\":{\"S\":\"USER#a\"},\"Skey\":{\"S\":\"USER#a\"}}\n\nnot json\n";
+        let err = import_table(&target, &EntityFilter::default(), file.as_bytes())
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, error::HexagonalErrorCode::BadInput);
+        assert_eq!(err.message, "Line 3 is not an item");
+    }
+}
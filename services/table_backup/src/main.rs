@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+use app_config::{AppConfig, DYNAMO_TABLE_NAME};
+use aws_sdk_dynamodb::Client;
+use persistance_repository::DynamoDBSingleTableRepository;
+use table_backup::{export_table, import_table, EntityFilter};
+
+const USAGE: &str = "Usage:
+  table_backup export <file> [--segments <n>] [--prefix <prefix>]...
+  table_backup import <file> [--prefix <prefix>]...
+
+Reads the table from DYNAMO_TABLE_NAME, set AWS_ENDPOINT_URL to use DynamoDB Local.
+--prefix limits the items to those whose Pkey starts with it, e.g. USER#, PRODUCT# or CART# in
+any tenant, or TENANT#<id># for one tenant's";
+
+struct Args {
+    command: String,
+    file: String,
+    segments: i32,
+    filter: EntityFilter,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let command = args.next().ok_or("missing command")?;
+    let file = args.next().ok_or("missing file")?;
+    let mut segments = 4;
+    let mut filter = EntityFilter::default();
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(format!("missing value for {}", flag))?;
+        match flag.as_str() {
+            "--segments" => {
+                segments = value
+                    .parse()
+                    .ok()
+                    .filter(|segments| *segments > 0)
+                    .ok_or("--segments must be a positive number")?
+            }
+            "--prefix" => filter.prefixes.push(value),
+            other => return Err(format!("unknown option {}", other)),
+        }
+    }
+    Ok(Args {
+        command,
+        file,
+        segments,
+        filter,
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let config = AppConfig::load()?;
+    let [table_name] = config.require([DYNAMO_TABLE_NAME])?;
+    let sdk_credential_meta_repository =
+        sdk_credential_meta_repository::SdkCredentialsMetaRepository::new().await;
+    // built from the client directly as cursors are never handed out here
+    let repository = DynamoDBSingleTableRepository::new_with_client(
+        Client::new(&sdk_credential_meta_repository.sdk_config),
+        table_name,
+    )
+    .with_config(config);
+
+    match args.command.as_str() {
+        "export" => {
+            let mut writer = BufWriter::new(File::create(&args.file)?);
+            let written = export_table(&repository, args.segments, &args.filter, &mut writer)
+                .await
+                .map_err(|err| err.to_string())?;
+            println!("Exported {} items to {}", written, args.file);
+        }
+        "import" => {
+            let reader = BufReader::new(File::open(&args.file)?);
+            let written = import_table(&repository, &args.filter, reader)
+                .await
+                .map_err(|err| err.to_string())?;
+            println!("Imported {} items from {}", written, args.file);
+        }
+        other => {
+            eprintln!("unknown command {}\n\n{}", other, USAGE);
+            std::process::exit(2);
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::domain::hello_world_core;
+use super::domain::hello_world_core;
 use http::{Error, Response, StatusCode};
 use http_port_tools::port_objects::HttpPortRequest;
 
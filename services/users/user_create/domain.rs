@@ -1,5 +1,6 @@
-use eventing::{events::user::user_created::EventUserCreatedV1, EventingPort};
+use eventing::events::{event_emmiter::SerialisableEvent, user::user_created::EventUserCreatedV1};
 use lib_user_regexes::{create_email_regex, create_username_regex};
+use models::models::outbox::OutboxItem;
 use models::models::user::{User, UserRepositoryPort};
 use regex::Regex;
 use tokio::sync::OnceCell;
@@ -7,10 +8,14 @@ use tokio::sync::OnceCell;
 pub static EMAIL_REGEX: OnceCell<Regex> = OnceCell::const_new();
 pub static USERNMAME_REGEX: OnceCell<Regex> = OnceCell::const_new();
 
-pub async fn user_create_core<T1: UserRepositoryPort, T2: EventingPort>(
+fn user_created_event(user: &User) -> OutboxItem {
+    EventUserCreatedV1::new(user.clone()).to_outbox_item()
+}
+
+pub async fn user_create_core<T1: UserRepositoryPort>(
     user_repository_port: &T1,
-    eventing_port: &T2,
     mut who: User,
+    principal: &str,
 ) -> Result<User, error::HexagonalError> {
     let email_regex = EMAIL_REGEX.get_or_init(create_email_regex);
     let username_regex = USERNMAME_REGEX.get_or_init(create_username_regex);
@@ -43,18 +48,9 @@ pub async fn user_create_core<T1: UserRepositoryPort, T2: EventingPort>(
         });
     }
 
-    let user = user_repository_port.user_create(&who).await;
-
-    if user.is_ok() {
-        let event_result = eventing_port
-            .emit(&EventUserCreatedV1::new(user.clone().unwrap()))
-            .await;
-        if event_result.is_err() {
-            return Err(event_result.unwrap_err());
-        }
-    }
-
-    user
+    user_repository_port
+        .user_create(&who, principal, user_created_event)
+        .await
 }
 
 #[cfg(test)]
@@ -79,7 +75,6 @@ mod tests {
     async fn test_user_create_core() {
         // Arrange
         let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
 
         let user = User {
             email: "testemail@email.com".to_string(),
@@ -88,21 +83,21 @@ mod tests {
             username: "username".to_string(),
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         let returned_user = user.clone();
 
-        eventing_port
-            .expect_emit::<EventUserCreatedV1>()
-            .times(1)
-            .returning(move |_| Ok(()));
         user_repository_port
             .expect_user_create()
             .times(1)
-            .returning(move |_| Ok(returned_user.clone()));
+            .returning(move |user, _, outbox_event| {
+                assert_eq!(outbox_event(user).event_type, "user_created");
+                Ok(returned_user.clone())
+            });
 
         // Act
-        let result = user_create_core(&user_repository_port, &eventing_port, user.clone()).await;
+        let result = user_create_core(&user_repository_port, user.clone(), "tester").await;
 
         // Assert
         assert!(result.is_ok());
@@ -113,7 +108,6 @@ mod tests {
     async fn test_user_create_core_invalid_email() {
         // Arrange
         let user_repository_port = models::models::user::MockUserRepositoryPort::new();
-        let eventing_port = eventing::MockEventingPort::new();
 
         let user = User {
             email: "notanemail".to_string(),
@@ -122,10 +116,11 @@ mod tests {
             username: "username".to_string(),
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         // Act
-        let result = user_create_core(&user_repository_port, &eventing_port, user.clone()).await;
+        let result = user_create_core(&user_repository_port, user.clone(), "tester").await;
 
         // Assert
         assert!(result.is_err());
@@ -139,7 +134,6 @@ mod tests {
     async fn test_user_create_error_from_dynamo() {
         // Arrange
         let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
-        let eventing_port = eventing::MockEventingPort::new();
 
         let user = User {
             email: "averygoodemail@email.com".to_string(),
@@ -148,12 +142,13 @@ mod tests {
             username: "username".to_string(),
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         user_repository_port
             .expect_user_create()
             .times(1)
-            .returning(move |_| {
+            .returning(move |_, _, _| {
                 Err(error::HexagonalError {
                     error: error::HexagonalErrorCode::Conflict,
                     message: "Error in Dynamo".to_string(),
@@ -162,7 +157,7 @@ mod tests {
             });
 
         // Act
-        let result = user_create_core(&user_repository_port, &eventing_port, user.clone()).await;
+        let result = user_create_core(&user_repository_port, user.clone(), "tester").await;
 
         // Assert
         assert!(result.is_err());
@@ -171,47 +166,4 @@ mod tests {
             error::HexagonalErrorCode::Conflict
         );
     }
-
-    #[tokio::test]
-    async fn test_user_create_error_from_eventing() {
-        // Arrange
-        let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
-
-        let user = User {
-            email: "averygoodemail@email.com".to_string(),
-            first: "first".to_string(),
-            last: "last".to_string(),
-            username: "username".to_string(),
-            created_at: default_time(),
-            updated_at: default_time(),
-        };
-
-        let returned_user = user.clone();
-
-        eventing_port
-            .expect_emit::<EventUserCreatedV1>()
-            .times(1)
-            .returning(move |_| {
-                Err(error::HexagonalError {
-                    error: error::HexagonalErrorCode::AdaptorError,
-                    message: "".to_string(),
-                    trace: "".to_string(),
-                })
-            });
-        user_repository_port
-            .expect_user_create()
-            .times(1)
-            .returning(move |_| Ok(returned_user.clone()));
-
-        // Act
-        let result = user_create_core(&user_repository_port, &eventing_port, user.clone()).await;
-
-        // Assert
-        assert!(result.is_err());
-        assert_eq!(
-            result.unwrap_err().error,
-            error::HexagonalErrorCode::AdaptorError
-        );
-    }
 }
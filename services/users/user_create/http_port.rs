@@ -1,9 +1,8 @@
-use crate::domain::user_create_core;
+use super::domain::user_create_core;
 
-use eventing::EventingPort;
 use http::{Error, Response, StatusCode};
 use http_port_tools::http_payload_decoder;
-use http_port_tools::port_objects::HttpPortRequest;
+use http_port_tools::port_objects::{etag, HttpPortRequest};
 use jsonschema::{Draft, JSONSchema};
 use lazy_static::lazy_static;
 use models::models::user::{User, UserRepositoryPort};
@@ -42,18 +41,19 @@ lazy_static! {
     };
 }
 
-pub async fn user_create_post_http_port<T1: UserRepositoryPort, T2: EventingPort>(
+pub async fn user_create_post_http_port<T1: UserRepositoryPort>(
     user_repository_port: &T1,
-    eventing_port: &T2,
     http_request: HttpPortRequest,
 ) -> Result<Response<String>, Error> {
+    let principal = http_request.principal();
     let payload = http_request.payload;
     let user = http_payload_decoder!(User, USER_SCHEMA, payload);
-    match user_create_core(user_repository_port, eventing_port, user).await {
+    match user_create_core(user_repository_port, user, &principal).await {
         Ok(result) => {
             let resp = Response::builder()
                 .status(StatusCode::CREATED)
                 .header("content-type", "application/json")
+                .header("etag", etag(result.version))
                 .body(serde_json::to_string(&result).unwrap());
             Ok(resp.unwrap())
         }
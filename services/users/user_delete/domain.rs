@@ -1,26 +1,29 @@
 use error::HexagonalError;
-use eventing::{events::user::user_deleted::EventUserDeletedV1, EventingPort};
+use eventing::events::{
+    event_emmiter::SerialisableEvent, user::user_soft_deleted::EventUserSoftDeletedV1,
+};
+use models::models::outbox::OutboxItem;
 use models::models::user::{User, UserRepositoryPort};
 
-pub async fn user_delete_core<T1: UserRepositoryPort, T2: EventingPort>(
+// The user is only soft deleted here, user_deleted follows once user_purge removes it
+fn user_soft_deleted_event(user: &User) -> OutboxItem {
+    EventUserSoftDeletedV1::new(user.clone()).to_outbox_item()
+}
+
+pub async fn user_delete_core<T1: UserRepositoryPort>(
     user_repository_port: &T1,
-    eventing_port: &T2,
     username: &String,
+    expected_version: Option<u64>,
+    principal: &str,
 ) -> Result<User, HexagonalError> {
-    let user = user_repository_port
-        .user_delete_by_username(&username)
-        .await;
-
-    if user.is_ok() {
-        let event_result = eventing_port
-            .emit(&EventUserDeletedV1::new(user.clone().unwrap()))
-            .await;
-        if event_result.is_err() {
-            return Err(event_result.unwrap_err());
-        }
-    }
-
-    user
+    user_repository_port
+        .user_delete_by_username(
+            username,
+            expected_version,
+            principal,
+            user_soft_deleted_event,
+        )
+        .await
 }
 
 #[cfg(test)]
@@ -33,7 +36,6 @@ mod tests {
     async fn test_user_delete_core() {
         // Arrange
         let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
 
         let username = "mycoolusername".to_string();
 
@@ -44,6 +46,7 @@ mod tests {
             username: "username".to_string(),
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         let return_user = user.clone();
@@ -51,15 +54,13 @@ mod tests {
         user_repository_port
             .expect_user_delete_by_username()
             .times(1)
-            .returning(move |_| Ok(return_user.clone()));
-
-        eventing_port
-            .expect_emit::<EventUserDeletedV1>()
-            .times(1)
-            .returning(move |_| Ok(()));
+            .returning(move |_, _, _, outbox_event| {
+                assert_eq!(outbox_event(&return_user).event_type, "user_soft_deleted");
+                Ok(return_user.clone())
+            });
 
         // Act
-        let result = user_delete_core(&user_repository_port, &eventing_port, &username).await;
+        let result = user_delete_core(&user_repository_port, &username, None, "tester").await;
 
         // Assert
         assert!(result.is_ok());
@@ -70,14 +71,13 @@ mod tests {
     async fn test_user_delete_not_found() {
         // Arrange
         let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
-        let eventing_port = eventing::MockEventingPort::new();
 
         let username = "mycoolusername".to_string();
 
         user_repository_port
             .expect_user_delete_by_username()
             .times(1)
-            .returning(move |_| {
+            .returning(move |_, _, _, _| {
                 Err(HexagonalError {
                     error: error::HexagonalErrorCode::NotFound,
                     message: "User not found".to_string(),
@@ -86,7 +86,7 @@ mod tests {
             });
 
         // Act
-        let result = user_delete_core(&user_repository_port, &eventing_port, &username).await;
+        let result = user_delete_core(&user_repository_port, &username, None, "tester").await;
 
         // Assert
         assert!(result.is_err());
@@ -95,48 +95,4 @@ mod tests {
             error::HexagonalErrorCode::NotFound
         );
     }
-
-    #[tokio::test]
-    async fn test_user_delete_eventing_failure() {
-        // Arrange
-        let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
-
-        let username = "mycoolusername".to_string();
-
-        let user = User {
-            email: "test@test.com".to_string(),
-            first: "first".to_string(),
-            last: "last".to_string(),
-            username: "username".to_string(),
-            created_at: default_time(),
-            updated_at: default_time(),
-        };
-
-        user_repository_port
-            .expect_user_delete_by_username()
-            .times(1)
-            .returning(move |_| Ok(user.clone()));
-
-        eventing_port
-            .expect_emit::<EventUserDeletedV1>()
-            .times(1)
-            .returning(move |_| {
-                Err(HexagonalError {
-                    error: error::HexagonalErrorCode::AdaptorError,
-                    message: "Error in Eventing".to_string(),
-                    trace: "".to_string(),
-                })
-            });
-
-        // Act
-        let result = user_delete_core(&user_repository_port, &eventing_port, &username).await;
-
-        // Assert
-        assert!(result.is_err());
-        assert_eq!(
-            result.unwrap_err().error,
-            error::HexagonalErrorCode::AdaptorError
-        );
-    }
 }
@@ -1,14 +1,12 @@
-use crate::domain::user_delete_core;
+use super::domain::user_delete_core;
 
 use error::HexagonalError;
-use eventing::EventingPort;
 use http::{Error, Response, StatusCode};
 use http_port_tools::port_objects::HttpPortRequest;
 use models::models::user::UserRepositoryPort;
 
-pub async fn user_delete_delete_http_port<T1: UserRepositoryPort, T2: EventingPort>(
+pub async fn user_delete_delete_http_port<T1: UserRepositoryPort>(
     user_repository_port: &T1,
-    eventing_port: &T2,
     http_request: HttpPortRequest,
 ) -> Result<Response<String>, Error> {
     let username = match http_request.path_parameters.first("username") {
@@ -22,7 +20,18 @@ pub async fn user_delete_delete_http_port<T1: UserRepositoryPort, T2: EventingPo
             .compile_to_http_response())
         }
     };
-    match user_delete_core(user_repository_port, eventing_port, &username.to_string()).await {
+    let expected_version = match http_request.if_match() {
+        Ok(expected_version) => expected_version,
+        Err(err) => return Ok(err.compile_to_http_response()),
+    };
+    match user_delete_core(
+        user_repository_port,
+        &username.to_string(),
+        expected_version,
+        &http_request.principal(),
+    )
+    .await
+    {
         Ok(user) => {
             let resp = Response::builder()
                 .status(StatusCode::OK)
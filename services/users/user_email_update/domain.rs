@@ -1,18 +1,26 @@
 use error::HexagonalError;
-use eventing::{events::user::username_updated::EventEmailUpdatedV1, EventingPort};
+use eventing::events::{
+    event_emmiter::SerialisableEvent, user::username_updated::EventEmailUpdatedV1,
+};
 use lib_user_regexes::create_email_regex;
-use models::models::user::UserRepositoryPort;
+use models::models::outbox::OutboxItem;
+use models::models::user::{User, UserRepositoryPort};
 use regex::Regex;
 use tokio::sync::OnceCell;
 
 pub static EMAIL_REGEX: OnceCell<Regex> = OnceCell::const_new();
 
-pub async fn user_email_update_core<T1: UserRepositoryPort, T2: EventingPort>(
+fn email_updated_event(user: &User) -> OutboxItem {
+    EventEmailUpdatedV1::new(user.username.clone(), user.email.clone()).to_outbox_item()
+}
+
+pub async fn user_email_update_core<T1: UserRepositoryPort>(
     user_repository_port: &T1,
-    eventing_port: &T2,
     username: &String,
     new_email: &String,
-) -> Result<(), HexagonalError> {
+    expected_version: Option<u64>,
+    principal: &str,
+) -> Result<User, HexagonalError> {
     let email_regex = EMAIL_REGEX.get_or_init(create_email_regex);
 
     let lower_email = new_email.to_ascii_lowercase();
@@ -25,24 +33,15 @@ pub async fn user_email_update_core<T1: UserRepositoryPort, T2: EventingPort>(
         });
     }
 
-    let result = user_repository_port
-        .user_update_email_by_username(username, &lower_email)
-        .await;
-
-    if result.is_ok() {
-        let event_result = eventing_port
-            .emit(&EventEmailUpdatedV1::new(
-                username.clone(),
-                lower_email.clone(),
-            ))
-            .await;
-
-        if event_result.is_err() {
-            return Err(event_result.unwrap_err());
-        }
-    }
-
-    result
+    user_repository_port
+        .user_update_email_by_username(
+            username,
+            &lower_email,
+            expected_version,
+            principal,
+            email_updated_event,
+        )
+        .await
 }
 
 #[cfg(test)]
@@ -53,7 +52,6 @@ mod tests {
     async fn test_user_username_update_core() {
         // Arrange
         let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
 
         let email = "thisEmailisValidated@test.com".to_string();
         let username = "thisUsernameIsNotValidated".to_string();
@@ -61,16 +59,23 @@ mod tests {
         user_repository_port
             .expect_user_update_email_by_username()
             .times(1)
-            .returning(move |_, _| Ok(()));
-
-        eventing_port
-            .expect_emit::<EventEmailUpdatedV1>()
-            .times(1)
-            .returning(move |_| Ok(()));
+            .returning(move |username, email, _, _, outbox_event| {
+                let user = User {
+                    email: email.clone(),
+                    first: "first".to_string(),
+                    last: "last".to_string(),
+                    username: username.clone(),
+                    created_at: "0".to_string(),
+                    updated_at: "0".to_string(),
+                    version: 2,
+                };
+                assert_eq!(outbox_event(&user).event_type, "user_email_updated");
+                Ok(user)
+            });
 
         // Act
         let result =
-            user_email_update_core(&user_repository_port, &eventing_port, &username, &email).await;
+            user_email_update_core(&user_repository_port, &username, &email, None, "tester").await;
 
         // Assert
         assert!(result.is_ok());
@@ -80,7 +85,6 @@ mod tests {
     async fn test_user_username_update_core_error() {
         // Arrange
         let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
-        let eventing_port = eventing::MockEventingPort::new();
 
         let email = "thisEmailisValidated@test.com".to_string();
         let username = "thisUsernameIsNotValidated".to_string();
@@ -88,7 +92,7 @@ mod tests {
         user_repository_port
             .expect_user_update_email_by_username()
             .times(1)
-            .returning(move |_, _| {
+            .returning(move |_, _, _, _, _| {
                 Err(HexagonalError {
                     error: error::HexagonalErrorCode::NotFound,
                     message: "test".to_string(),
@@ -98,40 +102,7 @@ mod tests {
 
         // Act
         let result =
-            user_email_update_core(&user_repository_port, &eventing_port, &username, &email).await;
-
-        // Assert
-        assert!(result.is_err());
-    }
-
-    #[tokio::test]
-    async fn test_user_username_update_core_error_from_eventing() {
-        // Arrange
-        let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
-
-        let email = "thisEmailisValidated@test.com".to_string();
-        let username = "thisUsernameIsNotValidated".to_string();
-
-        user_repository_port
-            .expect_user_update_email_by_username()
-            .times(1)
-            .returning(move |_, _| Ok(()));
-
-        eventing_port
-            .expect_emit::<EventEmailUpdatedV1>()
-            .times(1)
-            .returning(move |_| {
-                Err(HexagonalError {
-                    error: error::HexagonalErrorCode::AdaptorError,
-                    message: "test".to_string(),
-                    trace: "".to_string(),
-                })
-            });
-
-        // Act
-        let result =
-            user_email_update_core(&user_repository_port, &eventing_port, &username, &email).await;
+            user_email_update_core(&user_repository_port, &username, &email, None, "tester").await;
 
         // Assert
         assert!(result.is_err());
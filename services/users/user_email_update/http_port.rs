@@ -1,10 +1,9 @@
-use crate::domain::user_email_update_core;
+use super::domain::user_email_update_core;
 
 use error::HexagonalError;
-use eventing::EventingPort;
 use http::{Error, Response, StatusCode};
 use http_port_tools::http_payload_decoder;
-use http_port_tools::port_objects::HttpPortRequest;
+use http_port_tools::port_objects::{etag, HttpPortRequest};
 use jsonschema::{Draft, JSONSchema};
 use lazy_static::lazy_static;
 use models::models::user::UserRepositoryPort;
@@ -37,9 +36,8 @@ struct UserEmailUpdate {
     email: String,
 }
 
-pub async fn user_username_update_put_http_port<T1: UserRepositoryPort, T2: EventingPort>(
+pub async fn user_username_update_put_http_port<T1: UserRepositoryPort>(
     user_repository_port: &T1,
-    eventing_port: &T2,
     http_request: HttpPortRequest,
 ) -> Result<Response<String>, Error> {
     let username = match http_request.path_parameters.first("username") {
@@ -53,13 +51,19 @@ pub async fn user_username_update_put_http_port<T1: UserRepositoryPort, T2: Even
             .compile_to_http_response())
         }
     };
+    let expected_version = match http_request.if_match() {
+        Ok(expected_version) => expected_version,
+        Err(err) => return Ok(err.compile_to_http_response()),
+    };
+    let principal = http_request.principal();
     let payload = http_request.payload;
     let user_updates = http_payload_decoder!(UserEmailUpdate, USER_SCHEMA, payload);
     match user_email_update_core(
         user_repository_port,
-        eventing_port,
         &username.to_string(),
         &user_updates.email,
+        expected_version,
+        &principal,
     )
     .await
     {
@@ -67,15 +71,10 @@ pub async fn user_username_update_put_http_port<T1: UserRepositoryPort, T2: Even
             let resp = Response::builder()
                 .status(StatusCode::OK)
                 .header("content-type", "application/json")
+                .header("etag", etag(user.version))
                 .body(serde_json::to_string(&user).unwrap());
             Ok(resp.unwrap())
         }
-        Err(_) => {
-            let resp = Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .header("content-type", "application/json")
-                .body("".to_string());
-            Ok(resp.unwrap())
-        }
+        Err(err) => Ok(err.compile_to_http_response()),
     }
 }
@@ -1,2 +1,2 @@
-mod http_port;
-mod domain;
\ No newline at end of file
+mod domain;
+pub mod http_port;
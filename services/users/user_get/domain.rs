@@ -28,6 +28,7 @@ mod tests {
             username: "username".to_string(),
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         let return_user = user.clone();
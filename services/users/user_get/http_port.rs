@@ -1,8 +1,8 @@
-use crate::domain::user_get_core;
+use super::domain::user_get_core;
 
 use error::HexagonalError;
 use http::{Error, Response, StatusCode};
-use http_port_tools::port_objects::HttpPortRequest;
+use http_port_tools::port_objects::{etag, HttpPortRequest};
 use models::models::user::UserRepositoryPort;
 
 pub async fn user_get_get_http_port<T1: UserRepositoryPort>(
@@ -26,6 +26,7 @@ pub async fn user_get_get_http_port<T1: UserRepositoryPort>(
                 let resp = Response::builder()
                     .status(StatusCode::OK)
                     .header("content-type", "application/json")
+                    .header("etag", etag(result.version))
                     .body(serde_json::to_string(&result).unwrap());
                 Ok(resp.unwrap())
             }
@@ -0,0 +1,58 @@
+use error::HexagonalError;
+use models::models::history::HistoryRecord;
+use models::models::user::UserRepositoryPort;
+use persistance_repository::{Page, PageRequest};
+
+pub async fn user_history_core<T1: UserRepositoryPort>(
+    user_repository_port: &T1,
+    username: &String,
+    page: &PageRequest,
+) -> Result<Page<HistoryRecord>, HexagonalError> {
+    user_repository_port
+        .user_history_by_username(username, page)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use models::models::history::HistoryAction;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_user_history_core() {
+        // Arrange
+        let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
+
+        let record = HistoryRecord {
+            action: HistoryAction::Created,
+            principal: "tester".to_string(),
+            changed_at: models::default_time(),
+            version: 1,
+            item: None,
+            changes: vec![],
+        };
+        let result_record = record.clone();
+
+        user_repository_port
+            .expect_user_history_by_username()
+            .times(1)
+            .returning(move |_, _| {
+                Ok(Page {
+                    items: vec![result_record.clone()],
+                    next_cursor: None,
+                })
+            });
+
+        // Act
+        let result = user_history_core(
+            &user_repository_port,
+            &"username".to_string(),
+            &PageRequest::default(),
+        )
+        .await;
+
+        // Assert
+        assert_eq!(result.unwrap().items, vec![record]);
+    }
+}
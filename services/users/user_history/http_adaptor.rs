@@ -0,0 +1,51 @@
+mod domain;
+mod http_port;
+use crate::http_port::user_history_get_http_port;
+
+use http_port_tools::port_objects::{HttpPortRequest, HttpPortResponse};
+
+use lambda_adaptor::common_lambda_adaptor;
+use lambda_http::{run, service_fn, Error, IntoResponse};
+use models::models::user::UserRepositoryAdaptor;
+use persistance_repository::DynamoDBSingleTableRepository;
+
+async fn http_lambda_driving_adaptor(
+    dynamo_db_repository: &DynamoDBSingleTableRepository,
+    event: lambda_http::Request,
+) -> Result<lambda_http::Response<lambda_http::Body>, Error> {
+    let http_request = HttpPortRequest::from(event);
+    let generic_http_response = match http_request.scoped_repository(dynamo_db_repository) {
+        Ok(tenant_repository) => {
+            let user_repository = UserRepositoryAdaptor::new(&tenant_repository);
+            user_history_get_http_port(&user_repository, http_request)
+                .await
+                .unwrap()
+        }
+        Err(err) => err.compile_to_http_response(),
+    };
+    let lambda_http_response = HttpPortResponse(generic_http_response)
+        .into_response()
+        .await;
+    Ok(lambda_http_response)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    // Common snippit from all lambda functions
+    common_lambda_adaptor!();
+
+    // Provision required repositories once in the main function
+    let config = app_config::AppConfig::load()?;
+    let sdk_credential_meta_repository =
+        sdk_credential_meta_repository::SdkCredentialsMetaRepository::new().await;
+    let dynamo_db_repository = persistance_repository::DynamoDBSingleTableRepository::new(
+        &sdk_credential_meta_repository,
+        &config,
+    )?
+    .with_operation("user_history");
+
+    run(service_fn(|event| {
+        http_lambda_driving_adaptor(&dynamo_db_repository, event)
+    }))
+    .await
+}
@@ -0,0 +1,37 @@
+use super::domain::user_history_core;
+
+use error::HexagonalError;
+use http::{Error, Response, StatusCode};
+use http_port_tools::port_objects::HttpPortRequest;
+use models::models::user::UserRepositoryPort;
+
+pub async fn user_history_get_http_port<T1: UserRepositoryPort>(
+    user_repository_port: &T1,
+    http_request: HttpPortRequest,
+) -> Result<Response<String>, Error> {
+    let username = match http_request.path_parameters.first("username") {
+        Some(username) => username,
+        None => {
+            return Ok(HexagonalError {
+                error: error::HexagonalErrorCode::BadInput,
+                message: "username is required".to_string(),
+                trace: "".to_string(),
+            }
+            .compile_to_http_response())
+        }
+    };
+    let page = match http_request.page_request() {
+        Ok(page) => page,
+        Err(err) => return Ok(err.compile_to_http_response()),
+    };
+    match user_history_core(user_repository_port, &username.to_string(), &page).await {
+        Ok(result) => {
+            let resp = Response::builder()
+                .status(StatusCode::OK)
+                .header("content-type", "application/json")
+                .body(serde_json::to_string(&result).unwrap());
+            Ok(resp.unwrap())
+        }
+        Err(err) => Ok(err.compile_to_http_response()),
+    }
+}
@@ -0,0 +1,74 @@
+use error::HexagonalError;
+use eventing::events::{event_emmiter::SerialisableEvent, user::user_deleted::EventUserDeletedV1};
+use models::models::outbox::OutboxItem;
+use models::models::user::{User, UserRepositoryPort};
+
+// The final deletion, carts are cleared off the back of it
+fn user_deleted_event(user: &User) -> OutboxItem {
+    EventUserDeletedV1::new(user.clone()).to_outbox_item()
+}
+
+// Purges a page at a time until a page comes back short, returning how many were purged
+pub async fn user_purge_core<T1: UserRepositoryPort>(
+    user_repository_port: &T1,
+    page_size: i32,
+) -> Result<usize, HexagonalError> {
+    let mut purged = 0;
+    loop {
+        let page = user_repository_port
+            .user_purge_deleted(page_size, user_deleted_event)
+            .await?;
+        purged += page.len();
+        if (page.len() as i32) < page_size {
+            return Ok(purged);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::Sequence;
+    use models::default_time;
+
+    use super::*;
+
+    fn user(username: &str) -> User {
+        User {
+            email: format!("{}@test.com", username),
+            first: "first".to_string(),
+            last: "last".to_string(),
+            username: username.to_string(),
+            created_at: default_time(),
+            updated_at: default_time(),
+            version: 2,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_user_purge_core_stops_on_short_page() {
+        // Arrange
+        let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
+        let mut sequence = Sequence::new();
+
+        user_repository_port
+            .expect_user_purge_deleted()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|limit, outbox_event| {
+                assert_eq!(limit, 2);
+                assert_eq!(outbox_event(&user("a")).event_type, "user_deleted");
+                Ok(vec![user("a"), user("b")])
+            });
+        user_repository_port
+            .expect_user_purge_deleted()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(vec![user("c")]));
+
+        // Act
+        let result = user_purge_core(&user_repository_port, 2).await;
+
+        // Assert
+        assert_eq!(result.unwrap(), 3);
+    }
+}
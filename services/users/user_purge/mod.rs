@@ -0,0 +1,2 @@
+mod domain;
+pub mod schedule_port;
@@ -0,0 +1,13 @@
+use super::domain::user_purge_core;
+
+use error::HexagonalError;
+use models::models::user::UserRepositoryPort;
+
+const PURGE_PAGE_SIZE: i32 = 25;
+
+// Runs on a schedule, each run purges every user whose retention window has passed
+pub async fn user_purge_schedule_port<T1: UserRepositoryPort>(
+    user_repository_port: &T1,
+) -> Result<usize, HexagonalError> {
+    user_purge_core(user_repository_port, PURGE_PAGE_SIZE).await
+}
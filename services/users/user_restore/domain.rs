@@ -0,0 +1,60 @@
+use error::HexagonalError;
+use eventing::events::{
+    event_emmiter::SerialisableEvent, user::user_restored::EventUserRestoredV1,
+};
+use models::models::outbox::OutboxItem;
+use models::models::user::{User, UserRepositoryPort};
+
+fn user_restored_event(user: &User) -> OutboxItem {
+    EventUserRestoredV1::new(user.clone()).to_outbox_item()
+}
+
+pub async fn user_restore_core<T1: UserRepositoryPort>(
+    user_repository_port: &T1,
+    username: &String,
+    principal: &str,
+) -> Result<User, HexagonalError> {
+    user_repository_port
+        .user_restore_by_username(username, principal, user_restored_event)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use models::default_time;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_user_restore_core() {
+        // Arrange
+        let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
+
+        let user = User {
+            email: "test@test.com".to_string(),
+            first: "first".to_string(),
+            last: "last".to_string(),
+            username: "username".to_string(),
+            created_at: default_time(),
+            updated_at: default_time(),
+            version: 3,
+        };
+
+        let return_user = user.clone();
+
+        user_repository_port
+            .expect_user_restore_by_username()
+            .times(1)
+            .returning(move |_, _, outbox_event| {
+                assert_eq!(outbox_event(&return_user).event_type, "user_restored");
+                Ok(return_user.clone())
+            });
+
+        // Act
+        let result =
+            user_restore_core(&user_repository_port, &"username".to_string(), "tester").await;
+
+        // Assert
+        assert_eq!(result.unwrap(), user);
+    }
+}
@@ -1,12 +1,18 @@
 use error::HexagonalError;
-use eventing::{events::user::user_updated::EventUserUpdatedV1, EventingPort};
+use eventing::events::{event_emmiter::SerialisableEvent, user::user_updated::EventUserUpdatedV1};
+use models::models::outbox::OutboxItem;
 use models::models::user::{MutableUser, User, UserRepositoryPort};
 
-pub async fn user_update_core<T1: UserRepositoryPort, T2: EventingPort>(
+fn user_updated_event(user: &User) -> OutboxItem {
+    EventUserUpdatedV1::new(user.clone()).to_outbox_item()
+}
+
+pub async fn user_update_core<T1: UserRepositoryPort>(
     user_repository_port: &T1,
-    eventing_port: &T2,
     username: &String,
     update: MutableUser,
+    expected_version: Option<u64>,
+    principal: &str,
 ) -> Result<User, HexagonalError> {
     if update.first.is_none() && update.last.is_none() {
         return Err(HexagonalError {
@@ -16,20 +22,15 @@ pub async fn user_update_core<T1: UserRepositoryPort, T2: EventingPort>(
         });
     }
 
-    let user = user_repository_port
-        .user_update_by_username(username, update)
-        .await;
-
-    if user.is_ok() {
-        let event_result = eventing_port
-            .emit(&EventUserUpdatedV1::new(user.clone().unwrap()))
-            .await;
-        if event_result.is_err() {
-            return Err(event_result.unwrap_err());
-        }
-    }
-
-    user
+    user_repository_port
+        .user_update_by_username(
+            username,
+            update,
+            expected_version,
+            principal,
+            user_updated_event,
+        )
+        .await
 }
 
 #[cfg(test)]
@@ -42,7 +43,6 @@ mod tests {
     async fn test_user_update_core() {
         // Arrange
         let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
 
         let user = User {
             email: "testemail".to_string(),
@@ -51,6 +51,7 @@ mod tests {
             username: "username".to_string(),
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         let mutable_user = MutableUser {
@@ -63,19 +64,18 @@ mod tests {
         user_repository_port
             .expect_user_update_by_username()
             .times(1)
-            .returning(move |_, _| Ok(return_user.clone()));
-
-        eventing_port
-            .expect_emit::<EventUserUpdatedV1>()
-            .times(1)
-            .returning(move |_| Ok(()));
+            .returning(move |_, _, _, _, outbox_event| {
+                assert_eq!(outbox_event(&return_user).event_type, "user_updated");
+                Ok(return_user.clone())
+            });
 
         // Act
         let result = user_update_core(
             &user_repository_port,
-            &eventing_port,
             &user.username,
             mutable_user.clone(),
+            None,
+            "tester",
         )
         .await;
 
@@ -88,7 +88,6 @@ mod tests {
     async fn test_user_update_core_no_updates() {
         // Arrange
         let user_repository_port = models::models::user::MockUserRepositoryPort::new();
-        let eventing_port = eventing::MockEventingPort::new();
 
         let user = User {
             email: "testemail".to_string(),
@@ -97,6 +96,7 @@ mod tests {
             username: "username".to_string(),
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         let mutable_user = MutableUser {
@@ -107,9 +107,10 @@ mod tests {
         // Act
         let result = user_update_core(
             &user_repository_port,
-            &eventing_port,
             &user.username,
             mutable_user.clone(),
+            None,
+            "tester",
         )
         .await;
 
@@ -125,55 +126,6 @@ mod tests {
     async fn test_user_update_core_error_from_dynamo() {
         // Arrange
         let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
-        let eventing_port = eventing::MockEventingPort::new();
-
-        let user = User {
-            email: "testemail".to_string(),
-            first: "first".to_string(),
-            last: "last".to_string(),
-            username: "username".to_string(),
-            created_at: default_time(),
-            updated_at: default_time(),
-        };
-
-        let mutable_user = MutableUser {
-            first: Some("first".to_string()),
-            last: Some("last".to_string()),
-        };
-
-        user_repository_port
-            .expect_user_update_by_username()
-            .times(1)
-            .returning(move |_, _| {
-                Err(HexagonalError {
-                    error: error::HexagonalErrorCode::AdaptorError,
-                    message: "test".to_string(),
-                    trace: "".to_string(),
-                })
-            });
-
-        // Act
-        let result = user_update_core(
-            &user_repository_port,
-            &eventing_port,
-            &user.username,
-            mutable_user.clone(),
-        )
-        .await;
-
-        // Assert
-        assert!(result.is_err());
-        assert_eq!(
-            result.unwrap_err().error,
-            error::HexagonalErrorCode::AdaptorError
-        );
-    }
-
-    #[tokio::test]
-    async fn test_user_update_core_error_from_eventing() {
-        // Arrange
-        let mut user_repository_port = models::models::user::MockUserRepositoryPort::new();
-        let mut eventing_port = eventing::MockEventingPort::new();
 
         let user = User {
             email: "testemail".to_string(),
@@ -182,6 +134,7 @@ mod tests {
             username: "username".to_string(),
             created_at: default_time(),
             updated_at: default_time(),
+            version: 1,
         };
 
         let mutable_user = MutableUser {
@@ -189,17 +142,10 @@ mod tests {
             last: Some("last".to_string()),
         };
 
-        let return_user = user.clone();
-
         user_repository_port
             .expect_user_update_by_username()
             .times(1)
-            .returning(move |_, _| Ok(return_user.clone()));
-
-        eventing_port
-            .expect_emit::<EventUserUpdatedV1>()
-            .times(1)
-            .returning(move |_| {
+            .returning(move |_, _, _, _, _| {
                 Err(HexagonalError {
                     error: error::HexagonalErrorCode::AdaptorError,
                     message: "test".to_string(),
@@ -210,9 +156,10 @@ mod tests {
         // Act
         let result = user_update_core(
             &user_repository_port,
-            &eventing_port,
             &user.username,
             mutable_user.clone(),
+            None,
+            "tester",
         )
         .await;
 
@@ -1,10 +1,9 @@
-use crate::domain::user_update_core;
+use super::domain::user_update_core;
 
 use error::HexagonalError;
-use eventing::EventingPort;
 use http::{Error, Response, StatusCode};
 use http_port_tools::http_payload_decoder;
-use http_port_tools::port_objects::HttpPortRequest;
+use http_port_tools::port_objects::{etag, HttpPortRequest};
 use jsonschema::{Draft, JSONSchema};
 use lazy_static::lazy_static;
 use models::models::user::{MutableUser, UserRepositoryPort};
@@ -32,9 +31,8 @@ lazy_static! {
     };
 }
 
-pub async fn user_update_put_http_port<T1: UserRepositoryPort, T2: EventingPort>(
+pub async fn user_update_put_http_port<T1: UserRepositoryPort>(
     user_repository_port: &T1,
-    eventing_port: &T2,
     http_request: HttpPortRequest,
 ) -> Result<Response<String>, Error> {
     let username = match http_request.path_parameters.first("username") {
@@ -48,13 +46,19 @@ pub async fn user_update_put_http_port<T1: UserRepositoryPort, T2: EventingPort>
             .compile_to_http_response())
         }
     };
+    let expected_version = match http_request.if_match() {
+        Ok(expected_version) => expected_version,
+        Err(err) => return Ok(err.compile_to_http_response()),
+    };
+    let principal = http_request.principal();
     let payload = http_request.payload;
     let user_updates = http_payload_decoder!(MutableUser, USER_SCHEMA, payload);
     match user_update_core(
         user_repository_port,
-        eventing_port,
         &username.to_string(),
         user_updates,
+        expected_version,
+        &principal,
     )
     .await
     {
@@ -62,6 +66,7 @@ pub async fn user_update_put_http_port<T1: UserRepositoryPort, T2: EventingPort>
             let resp = Response::builder()
                 .status(StatusCode::OK)
                 .header("content-type", "application/json")
+                .header("etag", etag(user.version))
                 .body(serde_json::to_string(&user).unwrap());
             Ok(resp.unwrap())
         }
@@ -32,7 +32,7 @@ describe('Clear Cart', function () {
 
         //assert
         assert.equal(res_delete.status, 200);
-        assert.equal(res_items_pre_delete.data.length, 10);
-        assert.equal(res_items_post_delete.data.length, 0);
+        assert.equal(res_items_pre_delete.data.items.length, 10);
+        assert.equal(res_items_post_delete.data.items.length, 0);
     })
 });
\ No newline at end of file
@@ -22,7 +22,8 @@ describe('Get Cart', function () {
 
         //assert
         assert.equal(res.status, 200)
-        expect(res.data[0]).to.include(cart_item)
+        expect(res.data.items[0]).to.include(cart_item)
+        assert.equal(res.data.next_cursor, null)
     })
 
     it('should add multiple products to a users cart then retrieve the cart', async function () {
@@ -49,7 +50,47 @@ describe('Get Cart', function () {
 
         //assert
         assert.equal(res.status, 200);
-        assert.equal(res.data.length, 10);
+        assert.equal(res.data.items.length, 10);
+    })
+
+    it('should page through a users cart with limit and cursor', async function () {
+        //arrange
+        let user_id = faker.internet.userName();
+
+        let res_post_promises = Array(5).fill().map(() => axios.post(
+            `${process.env.INF_API_ENDPOINT}main/cart/${user_id}/item`,
+            { product_id: faker.string.uuid(), quantity: 1 }
+        ))
+        await Promise.all(res_post_promises);
+
+        //act
+        let product_ids = [];
+        let cursor = null;
+        do {
+            let params = cursor ? { limit: 2, cursor } : { limit: 2 };
+            let res = await axios.get(`${process.env.INF_API_ENDPOINT}main/cart/${user_id}`, { params })
+            assert.equal(res.status, 200);
+            assert.isAtMost(res.data.items.length, 2);
+            product_ids.push(...res.data.items.map(item => item.product_id));
+            cursor = res.data.next_cursor;
+        } while (cursor)
+
+        //assert
+        assert.equal(new Set(product_ids).size, 5);
+    })
+
+    it('should reject a tampered cursor', async function () {
+        //arrange
+        let user_id = faker.internet.userName();
+
+        //act
+        let res = await axios.get(
+            `${process.env.INF_API_ENDPOINT}main/cart/${user_id}`,
+            { params: { cursor: 'e30.bm90IGEgc2lnbmF0dXJl' }, validateStatus: () => true }
+        )
+
+        //assert
+        assert.equal(res.status, 400);
     })
 
     it('should return an empty array for no cart', async function () {
@@ -61,6 +102,6 @@ describe('Get Cart', function () {
 
         //assert
         assert.equal(res.status, 200);
-        assert.equal(res.data.length, 0);
+        assert.equal(res.data.items.length, 0);
     })
 });
\ No newline at end of file
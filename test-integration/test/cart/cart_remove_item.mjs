@@ -27,6 +27,6 @@ describe('Remove Product from Cart', function () {
         expect(res_add.data).to.include(cart_item)
         assert.equal(res_delete.status, 200)
         assert.equal(res_get.status, 200)
-        assert.equal(res_get.data.length, 0)
+        assert.equal(res_get.data.items.length, 0)
     })
 });
\ No newline at end of file
@@ -26,6 +26,6 @@ describe('Update Product in Cart', function () {
         expect(res.data).to.include(cart_item)
         assert.equal(res_update.status, 200)
         assert.equal(res_get.status, 200)
-        assert.equal(res_get.data[0].quantity, 2)
+        assert.equal(res_get.data.items[0].quantity, 2)
     })
 });
\ No newline at end of file
@@ -3,11 +3,9 @@ import { assert, expect } from 'chai';
 import { faker } from '@faker-js/faker';
 
 let completion_NFR = 5000;
-let sleep = 500;
-let iterations = completion_NFR / sleep;
 
-describe('Clear Cart on User Deletion', function () {
-    it('should add multiple products to a users cart then clear the cart when the user is deleted', async function () {
+describe('Keep Cart on User Soft Deletion', function () {
+    it('should add multiple products to a users cart then keep the cart while the user can still be restored', async function () {
         //arrange
         this.timeout(completion_NFR);
         let user_id = faker.internet.userName();
@@ -36,21 +34,15 @@ describe('Clear Cart on User Deletion', function () {
         await Promise.all(res_post_promises);
 
         let res_user_delete = await axios.delete(`${process.env.INF_API_ENDPOINT}main/user/${user_id}`)
-        let cart_items_len = 10;
-        let calls = 0;
-
-        while (cart_items_len > 0 && calls < iterations) {
-            let res_items_post_delete = await axios.get(`${process.env.INF_API_ENDPOINT}main/cart/${user_id}`)
-            cart_items_len = res_items_post_delete.data.length;
-            if (cart_items_len > 0) {
-                calls++;
-                await new Promise(resolve => setTimeout(resolve, sleep));
-            }
-        }
+        // the cart is only cleared once the purge job removes the user for good
+        await new Promise(resolve => setTimeout(resolve, completion_NFR / 2));
+        let res_items_post_delete = await axios.get(`${process.env.INF_API_ENDPOINT}main/cart/${user_id}`)
+        let res_user_restore = await axios.post(`${process.env.INF_API_ENDPOINT}main/user/${user_id}/restore`)
 
         //assert
-        assert.equal(cart_items_len, 0);
+        assert.equal(res_items_post_delete.data.items.length, 10);
         assert.equal(res_user_create.status, 201);
         assert.equal(res_user_delete.status, 200);
+        assert.equal(res_user_restore.status, 200);
     })
 });
\ No newline at end of file
@@ -3,11 +3,9 @@ import { assert, expect } from 'chai';
 import { faker } from '@faker-js/faker';
 
 let completion_NFR = 5000;
-let sleep = 500;
-let iterations = completion_NFR / sleep;
 
-describe('Remove Product from Cart on Product Deletion', function () {
-    it('should add a product to multiple users cart then clear the cart when the product is deleted', async function () {
+describe('Keep Product in Carts on Product Soft Deletion', function () {
+    it('should add a product to multiple users cart then keep it in their carts while the product can still be restored', async function () {
         //arrange
         this.timeout(completion_NFR);
         let user_id_1 = faker.internet.userName();
@@ -36,22 +34,15 @@ describe('Remove Product from Cart on Product Deletion', function () {
         ])
 
         let res_product_delete = await axios.delete(`${process.env.INF_API_ENDPOINT}main/product/${res_product_create.data.id}`)
-
-        let cart_items_len = 2;
-        let calls = 0;
-
-        while (cart_items_len > 0 && calls < iterations) {
-            let res_items_post_delete_u1 = await axios.get(`${process.env.INF_API_ENDPOINT}main/cart/${user_id_1}`)
-            let res_items_post_delete_u2 = await axios.get(`${process.env.INF_API_ENDPOINT}main/cart/${user_id_2}`)
-            cart_items_len = res_items_post_delete_u1.data.length + res_items_post_delete_u2.data.length;
-            if (cart_items_len > 0) {
-                calls++;
-                await new Promise(resolve => setTimeout(resolve, sleep));
-            }
-        }
+        // carts only drop the product once the purge job removes it for good
+        await new Promise(resolve => setTimeout(resolve, completion_NFR / 2));
+        let res_items_post_delete_u1 = await axios.get(`${process.env.INF_API_ENDPOINT}main/cart/${user_id_1}`)
+        let res_items_post_delete_u2 = await axios.get(`${process.env.INF_API_ENDPOINT}main/cart/${user_id_2}`)
+        let res_product_restore = await axios.post(`${process.env.INF_API_ENDPOINT}main/product/${res_product_create.data.id}/restore`)
 
         //assert
-        assert.equal(cart_items_len, 0);
+        assert.equal(res_items_post_delete_u1.data.items.length + res_items_post_delete_u2.data.items.length, 2);
         assert.equal(res_product_delete.status, 200);
+        assert.equal(res_product_restore.status, 200);
     })
 });
\ No newline at end of file
@@ -0,0 +1,38 @@
+import axios from 'axios';
+import { assert } from 'chai';
+import { faker } from '@faker-js/faker';
+
+describe('Product History', function () {
+    it('should keep a deleted product\'s history', async function () {
+        //arrange
+        let product = {
+            product_name: faker.commerce.productName(),
+            description: faker.commerce.productDescription(),
+            price_cents: Number(faker.commerce.price({
+                dec: 0
+            }))
+        }
+
+        //act
+        let res_post = await axios.post(`${process.env.INF_API_ENDPOINT}main/product`, product)
+        await axios.delete(`${process.env.INF_API_ENDPOINT}main/product/${res_post.data.id}`)
+        let res = await axios.get(`${process.env.INF_API_ENDPOINT}main/product/${res_post.data.id}/history`)
+
+        //assert
+        assert.equal(res.status, 200)
+        assert.deepEqual(res.data.items.map(record => record.action), ["deleted", "created"])
+        assert.equal(res.data.items[1].version, 1)
+    })
+
+    it('should fail to get the history of a nonexistant product', async function () {
+        //act
+        let res = await axios.get(`${process.env.INF_API_ENDPOINT}main/product/${faker.string.uuid()}/history`,
+            {
+                validateStatus: () => true
+            }
+        )
+
+        //assert
+        assert.equal(res.status, 404)
+    })
+});
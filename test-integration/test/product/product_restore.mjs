@@ -0,0 +1,47 @@
+import axios from 'axios';
+import { assert, expect } from 'chai';
+import { faker } from '@faker-js/faker';
+
+describe('Restore Product', function () {
+    it('should restore a deleted product', async function () {
+        //arrange
+        let product = {
+            product_name: faker.commerce.productName(),
+            description: faker.commerce.productDescription(),
+            price_cents: Number(faker.commerce.price({
+                dec: 0
+            }))
+        }
+
+        //act
+        let res_post = await axios.post(`${process.env.INF_API_ENDPOINT}main/product`, product)
+        await axios.delete(`${process.env.INF_API_ENDPOINT}main/product/${res_post.data.id}`)
+
+        let res_get_deleted = await axios.get(`${process.env.INF_API_ENDPOINT}main/product/${res_post.data.id}`,
+            {
+                validateStatus: () => true
+            }
+        )
+        let res_restore = await axios.post(`${process.env.INF_API_ENDPOINT}main/product/${res_post.data.id}/restore`)
+
+        //assert
+        assert.equal(res_get_deleted.status, 404)
+        assert.equal(res_restore.status, 200)
+        expect(res_restore.data).to.include(product)
+    })
+
+    it('should fail to restore a nonexistant product', async function () {
+        //arrange
+        let id = faker.string.uuid()
+
+        //act
+        let res_restore = await axios.post(`${process.env.INF_API_ENDPOINT}main/product/${id}/restore`, {},
+            {
+                validateStatus: () => true
+            }
+        )
+
+        //assert
+        assert.equal(res_restore.status, 404)
+    })
+});
\ No newline at end of file
@@ -0,0 +1,40 @@
+import axios from 'axios';
+import { assert } from 'chai';
+import { faker } from '@faker-js/faker';
+
+describe('User History', function () {
+    it('should list every change to a user, newest first', async function () {
+        //arrange
+        let user = {
+            first: faker.person.firstName(),
+            last: faker.person.lastName(),
+            email: faker.internet.email().toLowerCase(),
+            username: faker.internet.userName(),
+        }
+        let first = faker.person.firstName()
+
+        //act
+        await axios.post(`${process.env.INF_API_ENDPOINT}main/user`, user)
+        await axios.put(`${process.env.INF_API_ENDPOINT}main/user/${user.username}`, { first })
+        let res_first_page = await axios.get(`${process.env.INF_API_ENDPOINT}main/user/${user.username}/history?limit=1`)
+        let res_second_page = await axios.get(`${process.env.INF_API_ENDPOINT}main/user/${user.username}/history?limit=1&cursor=${encodeURIComponent(res_first_page.data.next_cursor)}`)
+
+        //assert
+        assert.equal(res_first_page.status, 200)
+        assert.equal(res_first_page.data.items[0].action, "updated")
+        assert.deepEqual(res_first_page.data.items[0].changes, [{ field: "first", old: user.first, new: first }])
+        assert.equal(res_second_page.data.items[0].action, "created")
+    })
+
+    it('should fail to get the history of a nonexistant user', async function () {
+        //act
+        let res = await axios.get(`${process.env.INF_API_ENDPOINT}main/user/${faker.internet.userName()}/history`,
+            {
+                validateStatus: () => true
+            }
+        )
+
+        //assert
+        assert.equal(res.status, 404)
+    })
+});
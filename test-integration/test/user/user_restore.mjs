@@ -0,0 +1,69 @@
+import axios from 'axios';
+import { assert, expect } from 'chai';
+import { faker } from '@faker-js/faker';
+
+describe('Restore User', function () {
+    it('should restore a user thats just been deleted', async function () {
+        //arrange
+        let user = {
+            first: faker.person.firstName(),
+            last: faker.person.lastName(),
+            email: faker.internet.email().toLowerCase(),
+            username: faker.internet.userName(),
+        }
+
+        //act
+        await axios.post(`${process.env.INF_API_ENDPOINT}main/user`, user)
+        await axios.delete(`${process.env.INF_API_ENDPOINT}main/user/${user.username}`)
+
+        let res_get_deleted = await axios.get(`${process.env.INF_API_ENDPOINT}main/user/${user.username}`,
+            {
+                validateStatus: () => true,
+            }
+        )
+        let res = await axios.post(`${process.env.INF_API_ENDPOINT}main/user/${user.username}/restore`)
+        let res_get = await axios.get(`${process.env.INF_API_ENDPOINT}main/user/${user.username}`)
+
+        //assert
+        assert.equal(res_get_deleted.status, 404)
+        assert.equal(res.status, 200)
+        expect(res.data).to.include(user)
+        assert.equal(res_get.status, 200)
+    })
+
+    it('should fail to restore a user that hasn\'t been deleted', async function () {
+        //arrange
+        let user = {
+            first: faker.person.firstName(),
+            last: faker.person.lastName(),
+            email: faker.internet.email().toLowerCase(),
+            username: faker.internet.userName(),
+        }
+
+        //act
+        await axios.post(`${process.env.INF_API_ENDPOINT}main/user`, user)
+
+        let res = await axios.post(`${process.env.INF_API_ENDPOINT}main/user/${user.username}/restore`, {},
+            {
+                validateStatus: () => true,
+            }
+        )
+
+        //assert
+        assert.equal(res.status, 409)
+    })
+
+    it('should fail to restore a user that doesn\'t exist', async function () {
+        //arrange
+
+        //act
+        let res = await axios.post(`${process.env.INF_API_ENDPOINT}main/user/${faker.internet.userName()}/restore`, {},
+            {
+                validateStatus: () => true,
+            }
+        )
+
+        //assert
+        assert.equal(res.status, 404)
+    })
+});
\ No newline at end of file
@@ -34,6 +34,39 @@ describe('Update User', function () {
         expect(res.data).to.include(patched_user)
     })
 
+    it('should only update a user at the version given in If-Match', async function () {
+        //arrange
+        let user = {
+            first: faker.person.firstName(),
+            last: faker.person.lastName(),
+            email: faker.internet.email().toLowerCase(),
+            username: faker.internet.userName(),
+        }
+
+        //act
+        let create_res = await axios.post(`${process.env.INF_API_ENDPOINT}main/user`, user)
+        let etag = create_res.headers['etag']
+
+        let res = await axios.put(`${process.env.INF_API_ENDPOINT}main/user/${user.username}`, 
+            { last: faker.person.lastName() },
+            { headers: { 'If-Match': etag } }
+        )
+
+        let stale_res = await axios.put(`${process.env.INF_API_ENDPOINT}main/user/${user.username}`, 
+            { last: faker.person.lastName() },
+            { 
+                headers: { 'If-Match': etag },
+                validateStatus: () => true,
+            }
+        )
+
+        //assert
+        assert.equal(res.status, 200)
+        assert.notEqual(res.headers['etag'], etag)
+        assert.equal(res.data.version, create_res.data.version + 1)
+        assert.equal(stale_res.status, 412)
+    })
+
     it('should fail to update a user with no fields', async function () {
         //arrange
         let user = {